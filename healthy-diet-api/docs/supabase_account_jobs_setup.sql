@@ -0,0 +1,32 @@
+-- healthy-diet account data export and deletion
+-- Background jobs requested through GET /api/user/export and DELETE /api/user.
+-- user_id deliberately has no foreign key: the deletion job (and its audit entries) must
+-- survive the user row it removes.
+-- Deletion jobs wait until run_after (the grace period, ACCOUNT_DELETION_GRACE_DAYS) and can
+-- be cancelled while still pending. Completed exports keep file_path until expires_at.
+
+create table if not exists public.account_jobs (
+    id uuid primary key,
+    user_id uuid not null,
+    kind text not null check (kind in ('export', 'deletion')),
+    status text not null check (status in ('pending', 'processing', 'completed', 'failed', 'cancelled', 'expired')),
+    run_after timestamptz not null default now(),
+    started_at timestamptz,
+    completed_at timestamptz,
+    expires_at timestamptz,
+    file_path text,
+    error_message text,
+    created_at timestamptz not null default now()
+);
+
+create index if not exists idx_account_jobs_due
+    on public.account_jobs (run_after)
+    where status in ('pending', 'processing');
+
+create index if not exists idx_account_jobs_user_kind
+    on public.account_jobs (user_id, kind, created_at desc);
+
+-- At most one deletion waiting per user.
+create unique index if not exists idx_account_jobs_one_pending_deletion
+    on public.account_jobs (user_id)
+    where kind = 'deletion' and status in ('pending', 'processing');
@@ -0,0 +1,20 @@
+-- healthy-diet scoped API tokens
+-- Long-lived tokens for services such as the Node agent. Only a SHA-256 hash of the token is
+-- stored; token_prefix keeps the first characters so admins can tell tokens apart. A token
+-- acts as created_by but may only call admin routes covered by its scopes.
+
+create table if not exists public.api_tokens (
+    id uuid primary key,
+    name text not null,
+    token_hash text not null unique,
+    token_prefix text not null,
+    scopes text[] not null,
+    created_by uuid not null references public.users(id) on delete cascade,
+    created_at timestamptz not null default now(),
+    expires_at timestamptz,
+    last_used_at timestamptz,
+    revoked_at timestamptz
+);
+
+create index if not exists api_tokens_created_at_idx
+    on public.api_tokens (created_at desc);
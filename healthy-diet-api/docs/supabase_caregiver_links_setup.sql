@@ -0,0 +1,27 @@
+-- healthy-diet caregiver links
+-- A user invites a family member or caregiver with an emailed or hand-delivered code. Only the
+-- SHA-256 hash of the code is stored and it is cleared once the invite is accepted or revoked.
+-- scopes limits what the caregiver sees: meals, chat_summaries and/or alerts.
+
+create table if not exists public.caregiver_links (
+    id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references public.users(id) on delete cascade,
+    caregiver_id uuid references public.users(id) on delete cascade,
+    invite_email text,
+    invite_code_hash text unique,
+    scopes text[] not null default '{}',
+    status text not null default 'pending'
+        check (status in ('pending', 'active', 'revoked')),
+    created_at timestamptz not null default now(),
+    invite_expires_at timestamptz not null,
+    accepted_at timestamptz,
+    revoked_at timestamptz,
+    revoked_by uuid
+);
+
+create index if not exists caregiver_links_user_id_idx
+    on public.caregiver_links (user_id, status);
+
+create index if not exists caregiver_links_caregiver_id_idx
+    on public.caregiver_links (caregiver_id, status)
+    where caregiver_id is not null;
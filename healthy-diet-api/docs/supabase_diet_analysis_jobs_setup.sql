@@ -0,0 +1,30 @@
+-- healthy-diet asynchronous meal analysis jobs
+-- POST /api/diet/jobs stores the upload and inserts a pending job; the in-process worker claims
+-- jobs with FOR UPDATE SKIP LOCKED and records each stage (stored, detecting, estimating,
+-- scoring, done) as it starts. result accumulates the partial analysis; record_id points at the
+-- diet record written when the job completes.
+-- Jobs stuck in processing (e.g. after a crash) are claimed again; attempts caps the retries.
+
+create table if not exists public.diet_analysis_jobs (
+    id uuid primary key,
+    user_id uuid not null references public.users(id) on delete cascade,
+    status text not null check (status in ('pending', 'processing', 'completed', 'failed')),
+    stage text not null default 'stored'
+        check (stage in ('stored', 'detecting', 'estimating', 'scoring', 'done')),
+    image_path text not null,
+    result jsonb not null default '{}'::jsonb,
+    record_id uuid references public.diet_records(id) on delete set null,
+    error_message text,
+    attempts int not null default 0,
+    created_at timestamptz not null default now(),
+    started_at timestamptz,
+    updated_at timestamptz not null default now(),
+    completed_at timestamptz
+);
+
+create index if not exists idx_diet_analysis_jobs_queue
+    on public.diet_analysis_jobs (created_at)
+    where status in ('pending', 'processing');
+
+create index if not exists idx_diet_analysis_jobs_user
+    on public.diet_analysis_jobs (user_id, created_at desc);
@@ -0,0 +1,29 @@
+-- healthy-diet diet record annotations
+-- Assigned nutritionists annotate a client's diet record with a comment, a corrected calorie
+-- value and/or tags (parent_id is null); the record owner replies in a thread under it
+-- (parent_id is the nutritionist annotation). read_at is set when the other party reads it:
+-- the owner for nutritionist annotations, the nutritionist for replies to their annotation.
+
+create table if not exists public.diet_record_annotations (
+    id uuid primary key default gen_random_uuid(),
+    record_id uuid not null references public.diet_records(id) on delete cascade,
+    parent_id uuid references public.diet_record_annotations(id) on delete cascade,
+    author_id uuid not null references public.users(id) on delete cascade,
+    author_role text not null check (author_role in ('nutritionist', 'user')),
+    comment text,
+    corrected_calories double precision check (corrected_calories >= 0),
+    tags text[] not null default '{}',
+    created_at timestamptz not null default now(),
+    read_at timestamptz
+);
+
+create index if not exists diet_record_annotations_record_id_idx
+    on public.diet_record_annotations (record_id, created_at);
+
+create index if not exists diet_record_annotations_parent_id_idx
+    on public.diet_record_annotations (parent_id)
+    where parent_id is not null;
+
+create index if not exists diet_record_annotations_unread_idx
+    on public.diet_record_annotations (record_id)
+    where read_at is null;
@@ -0,0 +1,14 @@
+-- healthy-diet per-item detections
+-- Photo meals now store one diet_record_items row per detector box, with the box, the
+-- detector confidence and the share of the reference plate it covers, next to the weight
+-- and nutrients the nutrition engine estimated for it. Manual items leave these null.
+-- Records from before this change have no items; their category columns are unchanged.
+-- Requires supabase_manual_meals_setup.sql.
+
+alter table public.diet_record_items
+    add column if not exists bbox double precision[]
+        check (bbox is null or array_length(bbox, 1) = 4),
+    add column if not exists confidence double precision
+        check (confidence is null or confidence between 0 and 1),
+    add column if not exists area_ratio double precision
+        check (area_ratio is null or area_ratio between 0 and 1);
@@ -0,0 +1,72 @@
+-- healthy-diet squashed photo items
+-- The nutrition engine now applies its meal squash to the area-estimated items themselves,
+-- so a record's items, category columns and totals add up. Photo records saved before this
+-- change stored unsquashed items and category calories next to a squashed total, and
+-- correcting such a record raised its total. This scales their detected items down to the
+-- stored total and recalculates the category and nutrient columns from the items. Records
+-- whose items already add up are left alone, so running it again changes nothing.
+-- Requires supabase_diet_record_detections_setup.sql.
+
+begin;
+
+create temp table squash_fix on commit drop as
+select r.id as record_id,
+       (r.total_calories - coalesce(sum(i.calories) filter (where i.bbox is null), 0))
+           / sum(i.calories) filter (where i.bbox is not null) as factor
+from public.diet_records r
+join public.diet_record_items i on i.record_id = r.id
+where r.entry_source = 'photo'
+group by r.id, r.total_calories
+having sum(i.calories) > r.total_calories + 0.5
+   and sum(i.calories) filter (where i.bbox is not null) > 0;
+
+delete from squash_fix where factor <= 0 or factor >= 1;
+
+update public.diet_record_items i
+set weight_g = round((i.weight_g * f.factor)::numeric, 1),
+    calories = round((i.calories * f.factor)::numeric, 1),
+    carbohydrate_g = round((i.carbohydrate_g * f.factor)::numeric, 1),
+    protein_g = round((i.protein_g * f.factor)::numeric, 1),
+    fat_g = round((i.fat_g * f.factor)::numeric, 1),
+    fiber_g = round((i.fiber_g * f.factor)::numeric, 1),
+    sodium_mg = round((i.sodium_mg * f.factor)::numeric, 1)
+from squash_fix f
+where i.record_id = f.record_id
+  and i.bbox is not null;
+
+update public.diet_records r
+set total_calories = s.total_calories,
+    grain_calories = s.grain_calories,
+    protein_meat_calories = s.protein_meat_calories,
+    protein_bean_calories = s.protein_bean_calories,
+    vegetable_calories = s.vegetable_calories,
+    fruit_calories = s.fruit_calories,
+    dairy_calories = s.dairy_calories,
+    nuts_calories = s.nuts_calories,
+    carbohydrate_g = s.carbohydrate_g,
+    protein_g = s.protein_g,
+    fat_g = s.fat_g,
+    fiber_g = s.fiber_g,
+    sodium_mg = s.sodium_mg
+from (
+    select i.record_id,
+           sum(i.calories) as total_calories,
+           coalesce(sum(i.calories) filter (where i.class = 'grain'), 0) as grain_calories,
+           coalesce(sum(i.calories) filter (where i.class = 'protein_meat'), 0) as protein_meat_calories,
+           coalesce(sum(i.calories) filter (where i.class = 'protein_bean'), 0) as protein_bean_calories,
+           coalesce(sum(i.calories) filter (where i.class = 'vegetable'), 0) as vegetable_calories,
+           coalesce(sum(i.calories) filter (where i.class = 'fruit'), 0) as fruit_calories,
+           coalesce(sum(i.calories) filter (where i.class = 'dairy'), 0) as dairy_calories,
+           coalesce(sum(i.calories) filter (where i.class = 'nuts'), 0) as nuts_calories,
+           sum(i.carbohydrate_g) as carbohydrate_g,
+           sum(i.protein_g) as protein_g,
+           sum(i.fat_g) as fat_g,
+           sum(i.fiber_g) as fiber_g,
+           sum(i.sodium_mg) as sodium_mg
+    from public.diet_record_items i
+    join squash_fix f on f.record_id = i.record_id
+    group by i.record_id
+) s
+where r.id = s.record_id;
+
+commit;
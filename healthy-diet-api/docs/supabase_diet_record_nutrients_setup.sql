@@ -0,0 +1,11 @@
+-- healthy-diet diet record nutrient totals
+-- The nutrition engine (NutritionEngine.json, nutrients_per_100g per food) estimates
+-- carbohydrate, protein, fat, fiber and sodium for every detected item; each record stores the
+-- meal totals. Records from before this change keep nulls and are returned without nutrients.
+
+alter table public.diet_records
+    add column if not exists carbohydrate_g double precision,
+    add column if not exists protein_g double precision,
+    add column if not exists fat_g double precision,
+    add column if not exists fiber_g double precision,
+    add column if not exists sodium_mg double precision;
@@ -0,0 +1,23 @@
+-- healthy-diet email verification and password reset
+-- Each emailed link carries a signed JWT whose jti is a row here; consuming it sets used_at,
+-- so a link works once and only until expires_at. Issuing a new token of the same purpose
+-- marks older unused ones as used.
+
+alter table public.users
+    add column if not exists email_verified_at timestamptz;
+
+create table if not exists public.email_tokens (
+    jti uuid primary key,
+    user_id uuid not null references public.users(id) on delete cascade,
+    purpose text not null check (purpose in ('email_verification', 'password_reset')),
+    expires_at timestamptz not null,
+    used_at timestamptz,
+    created_at timestamptz not null default now()
+);
+
+create index if not exists email_tokens_user_id_purpose_idx
+    on public.email_tokens (user_id, purpose)
+    where used_at is null;
+
+-- Optional housekeeping.
+-- delete from public.email_tokens where expires_at < now() - interval '30 days';
@@ -0,0 +1,48 @@
+-- healthy-diet food composition database
+-- Foods are imported through POST /admin/foods/import (permission 'foods.write') from the
+-- Taiwan FDA food nutrient composition CSV (format=tfda) or a one-row-per-food table
+-- (format=wide). Values are per 100 g edible portion. Re-importing a source updates foods by
+-- (source, source_code) and replaces their aliases. engine_class is the nutrition engine
+-- class the food group maps to. GET /api/foods?q= matches names and aliases by substring and
+-- pg_trgm similarity; Chinese trigrams need a UTF-8 database with a non-C collation.
+
+create extension if not exists pg_trgm;
+
+create table if not exists public.foods (
+    id uuid primary key default gen_random_uuid(),
+    source text not null,
+    source_code text not null,
+    name_zh text not null,
+    name_en text,
+    food_group text,
+    engine_class text not null default 'other',
+    kcal_per_100g double precision not null,
+    carbohydrate_g double precision not null default 0,
+    protein_g double precision not null default 0,
+    fat_g double precision not null default 0,
+    fiber_g double precision not null default 0,
+    sodium_mg double precision not null default 0,
+    created_at timestamptz not null default now(),
+    updated_at timestamptz not null default now(),
+    unique (source, source_code)
+);
+
+create table if not exists public.food_aliases (
+    food_id uuid not null references public.foods (id) on delete cascade,
+    alias text not null,
+    lang text not null check (lang in ('zh', 'en')),
+    primary key (food_id, alias)
+);
+
+create index if not exists idx_foods_name_zh_trgm
+    on public.foods using gin (name_zh gin_trgm_ops);
+create index if not exists idx_foods_name_en_trgm
+    on public.foods using gin (name_en gin_trgm_ops);
+create index if not exists idx_food_aliases_alias_trgm
+    on public.food_aliases using gin (alias gin_trgm_ops);
+
+-- Grant the new permission on databases seeded before it existed.
+insert into public.role_permissions (role, permission) values
+    ('super_admin', 'foods.write'),
+    ('operator', 'foods.write')
+on conflict do nothing;
@@ -0,0 +1,25 @@
+-- healthy-diet login brute-force protection
+-- One row per (scope, key): scope 'account' is keyed by the lowercased email, 'ip' and
+-- 'admin_ip' by the client IP for /auth/login and /auth/admin/login respectively.
+-- failures counts recent misses; reaching the policy threshold sets locked_until and bumps
+-- lockout_count, which doubles the next lockout. A successful login or an admin unlock
+-- deletes the account row.
+
+create table if not exists public.login_failures (
+    scope text not null check (scope in ('account', 'ip', 'admin_ip')),
+    key text not null,
+    failures integer not null default 0,
+    lockout_count integer not null default 0,
+    locked_until timestamptz,
+    last_failure_at timestamptz not null default now(),
+    primary key (scope, key)
+);
+
+-- Lockouts are written to the audit log by the system itself, without an acting admin.
+alter table public.admin_audit_logs
+    alter column admin_id drop not null;
+
+-- Optional housekeeping.
+-- delete from public.login_failures
+-- where last_failure_at < now() - interval '1 day'
+--   and (locked_until is null or locked_until < now());
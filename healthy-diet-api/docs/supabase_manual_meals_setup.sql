@@ -0,0 +1,35 @@
+-- healthy-diet manual meal logging
+-- POST /api/diet_record creates a diet record without a photo, from foods picked in
+-- GET /api/foods and from typed text ("一碗白飯 + 滷雞腿") matched against food names and
+-- aliases. entry_source tells photo and manual records apart; manual records have no image
+-- paths and no engine_version. diet_record_items keeps each food of a manual meal with the
+-- weight and nutrients it was logged with. Requires supabase_foods_setup.sql.
+
+alter table public.diet_records
+    add column if not exists entry_source text not null default 'photo'
+        check (entry_source in ('photo', 'manual'));
+
+alter table public.diet_records
+    alter column result_image_path drop not null,
+    alter column original_image_path drop not null;
+
+create table if not exists public.diet_record_items (
+    id uuid primary key default gen_random_uuid(),
+    record_id uuid not null references public.diet_records (id) on delete cascade,
+    position integer not null,
+    class text not null,
+    food_id uuid references public.foods (id) on delete set null,
+    name text,
+    source_text text,
+    weight_g double precision not null,
+    calories double precision not null,
+    carbohydrate_g double precision not null default 0,
+    protein_g double precision not null default 0,
+    fat_g double precision not null default 0,
+    fiber_g double precision not null default 0,
+    sodium_mg double precision not null default 0,
+    created_at timestamptz not null default now()
+);
+
+create index if not exists idx_diet_record_items_record
+    on public.diet_record_items (record_id, position);
@@ -0,0 +1,11 @@
+-- healthy-diet nutrition engine versioning
+-- Calorie estimates for image analysis and POST /api/calculator come from NutritionEngine.json
+-- (NUTRITION_ENGINE_PATH). Each diet record stores the engine version that produced it, so
+-- records estimated under older food attributes can be told apart. Records created before
+-- the engine existed keep engine_version null.
+
+alter table public.diet_records
+    add column if not exists engine_version text;
+
+create index if not exists idx_diet_records_engine_version
+    on public.diet_records (engine_version);
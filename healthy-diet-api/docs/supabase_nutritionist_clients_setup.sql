@@ -0,0 +1,24 @@
+-- healthy-diet nutritionist clients
+-- Holders of 'clients.assign' assign users to nutritionists through
+-- POST /admin/nutritionists/{nutritionist_id}/clients. A nutritionist is any account whose
+-- role grants 'clients.workspace'; the /admin/workspace endpoints only ever show the
+-- caller's own assigned clients. assigned_by is the acting admin and is informational.
+
+create table if not exists public.nutritionist_clients (
+    nutritionist_id uuid not null references public.users(id) on delete cascade,
+    user_id uuid not null references public.users(id) on delete cascade,
+    assigned_by uuid,
+    assigned_at timestamptz not null default now(),
+    primary key (nutritionist_id, user_id),
+    check (nutritionist_id <> user_id)
+);
+
+create index if not exists nutritionist_clients_user_id_idx
+    on public.nutritionist_clients (user_id);
+
+-- Grant the new permissions on databases seeded before they existed.
+insert into public.role_permissions (role, permission) values
+    ('super_admin', 'clients.assign'),
+    ('super_admin', 'clients.workspace'),
+    ('operator', 'clients.workspace')
+on conflict do nothing;
@@ -0,0 +1,8 @@
+-- healthy-diet OAuth link attempts
+-- Counts wrong passwords on a pending "link this provider to your existing account"
+-- confirmation. The API deletes the request after a few of them, so a single link token
+-- cannot be used for unlimited password guesses.
+-- Requires supabase_oauth_setup.sql.
+
+alter table public.oauth_link_requests
+    add column if not exists failed_attempts integer not null default 0;
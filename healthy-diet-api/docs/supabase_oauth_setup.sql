@@ -0,0 +1,32 @@
+-- healthy-diet OAuth login (Discord)
+-- oauth_states keeps the CSRF state + PKCE verifier of an in-flight authorization request;
+-- a row is deleted as soon as the callback uses it.
+-- oauth_link_requests holds "this provider email matches an existing account" confirmations
+-- until the owner proves the password via POST /auth/discord/link.
+
+alter table public.users
+    add column if not exists discord_id text;
+
+create unique index if not exists users_discord_id_key
+    on public.users (discord_id)
+    where discord_id is not null;
+
+create table if not exists public.oauth_states (
+    state text primary key,
+    provider text not null,
+    pkce_verifier text not null,
+    expires_at timestamptz not null,
+    created_at timestamptz not null default now()
+);
+
+create index if not exists oauth_states_expires_at_idx
+    on public.oauth_states (expires_at);
+
+create table if not exists public.oauth_link_requests (
+    token uuid primary key,
+    user_id uuid not null references public.users(id) on delete cascade,
+    provider text not null,
+    provider_user_id text not null,
+    expires_at timestamptz not null,
+    created_at timestamptz not null default now()
+);
@@ -0,0 +1,11 @@
+-- healthy-diet password reset rate limit
+-- POST /auth/password/forgot counts every request in login_failures, keyed by normalized
+-- email ('password_reset_email') and by client IP ('password_reset_ip').
+-- Requires supabase_login_lockout_setup.sql and supabase_two_factor_setup.sql.
+
+alter table public.login_failures
+    drop constraint if exists login_failures_scope_check;
+alter table public.login_failures
+    add constraint login_failures_scope_check
+    check (scope in ('account', 'ip', 'admin_ip', 'two_factor',
+                     'password_reset_email', 'password_reset_ip'));
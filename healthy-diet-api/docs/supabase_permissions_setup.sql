@@ -0,0 +1,53 @@
+-- healthy-diet role permissions
+-- Each role grants a set of permissions; admin routes check them through
+-- require_admin_middleware, and users.role names one of these roles. agent_role is the role
+-- name forwarded to the agent service (e.g. super_admin -> admin).
+-- Roles and their permissions are edited by holders of 'roles.manage' through
+-- PUT /admin/roles/{role}. Instances cache this table for up to 30 seconds.
+-- While public.roles is empty the server falls back to the same built-in defaults seeded
+-- below.
+
+create table if not exists public.roles (
+    name text primary key check (name ~ '^[a-z0-9_]{1,32}$'),
+    description text,
+    agent_role text,
+    created_at timestamptz not null default now(),
+    updated_at timestamptz not null default now()
+);
+
+create table if not exists public.role_permissions (
+    role text not null references public.roles (name) on delete cascade,
+    permission text not null,
+    primary key (role, permission)
+);
+
+insert into public.roles (name, description, agent_role) values
+    ('super_admin', 'Full access, including roles and API tokens', 'admin'),
+    ('operator', 'Day-to-day administration', 'nutritionist'),
+    ('user', 'Regular app user', null)
+on conflict (name) do nothing;
+
+insert into public.role_permissions (role, permission)
+select 'super_admin', permission
+from unnest(array[
+    'admin.access', 'users.read', 'users.write', 'users.manage_admins', 'roles.manage',
+    'routes.read', 'routes.write', 'announcements.read', 'announcements.write',
+    'rag.read', 'rag.write', 'rag.delete', 'kg.read', 'kg.write',
+    'api_tokens.manage', 'agent_tokens.create', 'clients.assign', 'clients.workspace',
+    'foods.write', 'datasets.export'
+]) as permission
+on conflict do nothing;
+
+insert into public.role_permissions (role, permission)
+select 'operator', permission
+from unnest(array[
+    'admin.access', 'users.read', 'users.write',
+    'routes.read', 'routes.write', 'announcements.read', 'announcements.write',
+    'rag.read', 'rag.write', 'rag.delete', 'kg.read', 'kg.write', 'clients.workspace',
+    'foods.write'
+]) as permission
+on conflict do nothing;
+
+-- Optional: keep users.role pointing at a defined role.
+-- alter table public.users
+--     add constraint users_role_fkey foreign key (role) references public.roles (name);
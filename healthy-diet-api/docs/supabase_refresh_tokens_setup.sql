@@ -0,0 +1,24 @@
+-- healthy-diet refresh token store
+-- Every refresh JWT carries a jti (this row) and a fam (the login it descends from).
+-- Rotation marks the presented row revoked + replaced_by; presenting a replaced row again
+-- revokes the whole family.
+
+create table if not exists public.refresh_tokens (
+    jti uuid primary key,
+    user_id uuid not null references public.users(id) on delete cascade,
+    family_id uuid not null,
+    device_id text,
+    expires_at timestamptz not null,
+    revoked_at timestamptz,
+    replaced_by uuid,
+    created_at timestamptz not null default now()
+);
+
+create index if not exists refresh_tokens_family_id_idx
+    on public.refresh_tokens (family_id);
+
+create index if not exists refresh_tokens_user_id_created_at_idx
+    on public.refresh_tokens (user_id, created_at desc);
+
+-- Optional housekeeping: expired rows are never accepted, so they can be pruned periodically.
+-- delete from public.refresh_tokens where expires_at < now() - interval '30 days';
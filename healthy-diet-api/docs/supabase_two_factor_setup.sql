@@ -0,0 +1,34 @@
+-- healthy-diet TOTP second factor
+-- Operators and super admins must pass a TOTP (or recovery code) check after their password.
+-- user_totp holds one secret per user; confirmed_at stays null until the first valid code is
+-- entered. last_used_step is the last accepted 30-second step so a code cannot be replayed.
+-- The TOTP secret has to be readable to verify codes, so it is stored as-is: restrict access
+-- to this table like any other credential store.
+
+create table if not exists public.user_totp (
+    user_id uuid primary key references public.users(id) on delete cascade,
+    secret text not null,
+    confirmed_at timestamptz,
+    last_used_step bigint,
+    created_at timestamptz not null default now()
+);
+
+-- Argon2 hashes of single-use recovery codes; regenerating replaces the whole set.
+create table if not exists public.user_recovery_codes (
+    id uuid primary key,
+    user_id uuid not null references public.users(id) on delete cascade,
+    code_hash text not null,
+    used_at timestamptz,
+    created_at timestamptz not null default now()
+);
+
+create index if not exists idx_user_recovery_codes_user_id
+    on public.user_recovery_codes (user_id)
+    where used_at is null;
+
+-- Wrong codes are counted per user id under the 'two_factor' lockout scope.
+alter table public.login_failures
+    drop constraint if exists login_failures_scope_check;
+alter table public.login_failures
+    add constraint login_failures_scope_check
+    check (scope in ('account', 'ip', 'admin_ip', 'two_factor'));
@@ -0,0 +1,40 @@
+-- healthy-diet third-party login identities
+-- Maps (provider, subject) from Google / LINE / Apple / Discord / any OIDC provider to users.id.
+-- Run after supabase_oauth_setup.sql; replaces users.discord_id.
+
+create table if not exists public.user_identities (
+    id uuid primary key default gen_random_uuid(),
+    user_id uuid not null references public.users(id) on delete cascade,
+    provider text not null,
+    subject text not null,
+    email text,
+    created_at timestamptz not null default now(),
+    last_login_at timestamptz,
+    unique (provider, subject),
+    unique (user_id, provider)
+);
+
+create index if not exists user_identities_user_id_idx
+    on public.user_identities (user_id);
+
+-- OIDC nonce of the pending authorization request.
+alter table public.oauth_states
+    add column if not exists nonce text;
+
+-- Move Discord links created before user_identities existed.
+do $$
+begin
+    if exists (
+        select 1 from information_schema.columns
+        where table_schema = 'public' and table_name = 'users' and column_name = 'discord_id'
+    ) then
+        insert into public.user_identities (user_id, provider, subject, email, created_at)
+        select id, 'discord', discord_id, email, now()
+        from public.users
+        where discord_id is not null
+        on conflict do nothing;
+
+        drop index if exists public.users_discord_id_key;
+        alter table public.users drop column discord_id;
+    end if;
+end $$;
@@ -0,0 +1,39 @@
+-- healthy-diet login sessions
+-- One row per login. The session id equals the refresh token family_id and is carried in
+-- access tokens as the `sid` claim, so revoking a session also invalidates its access tokens.
+-- Run after supabase_refresh_tokens_setup.sql.
+
+create table if not exists public.user_sessions (
+    id uuid primary key,
+    user_id uuid not null references public.users(id) on delete cascade,
+    device_id text,
+    user_agent text,
+    ip_address text,
+    created_at timestamptz not null default now(),
+    last_used_at timestamptz not null default now(),
+    revoked_at timestamptz
+);
+
+create index if not exists user_sessions_user_id_last_used_at_idx
+    on public.user_sessions (user_id, last_used_at desc)
+    where revoked_at is null;
+
+-- Backfill sessions for refresh token families issued before this table existed.
+insert into public.user_sessions (id, user_id, device_id, created_at, last_used_at, revoked_at)
+select
+    family_id,
+    (array_agg(user_id))[1],
+    (array_agg(device_id order by created_at))[1],
+    min(created_at),
+    max(created_at),
+    case when bool_and(revoked_at is not null) then max(revoked_at) end
+from public.refresh_tokens
+group by family_id
+on conflict (id) do nothing;
+
+alter table public.refresh_tokens
+    drop constraint if exists refresh_tokens_family_id_fkey;
+
+alter table public.refresh_tokens
+    add constraint refresh_tokens_family_id_fkey
+    foreign key (family_id) references public.user_sessions(id) on delete cascade;
@@ -0,0 +1,13 @@
+-- healthy-diet admin account suspension
+-- A non-null suspended_at blocks the account: access tokens and API tokens owned by it are
+-- rejected and no new tokens are issued until an admin unsuspends it. suspended_by is the
+-- acting admin; it is informational, so there is no foreign key.
+
+alter table public.users
+    add column if not exists suspended_at timestamptz,
+    add column if not exists suspended_reason text,
+    add column if not exists suspended_by uuid;
+
+create index if not exists users_suspended_at_idx
+    on public.users (suspended_at)
+    where suspended_at is not null;
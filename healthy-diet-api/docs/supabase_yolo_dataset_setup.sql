@@ -0,0 +1,21 @@
+-- healthy-diet retraining dataset
+-- When a user moves, relabels, draws or removes a box in PATCH /api/diet_record/{record_id},
+-- the items keep the corrected labels (label_corrected marks boxes the user changed) and
+-- diet_records.labels_corrected_at dates the correction. GET /admin/datasets/yolo
+-- (permission 'datasets.export') exports those photos with their labels as a YOLO dataset.
+-- Requires supabase_diet_record_detections_setup.sql.
+
+alter table public.diet_record_items
+    add column if not exists label_corrected boolean not null default false;
+
+alter table public.diet_records
+    add column if not exists labels_corrected_at timestamptz;
+
+create index if not exists idx_diet_records_labels_corrected
+    on public.diet_records (labels_corrected_at desc)
+    where labels_corrected_at is not null;
+
+-- The export contains users' meal photos, so only super_admin gets it by default.
+insert into public.role_permissions (role, permission) values
+    ('super_admin', 'datasets.export')
+on conflict do nothing;
@@ -0,0 +1,220 @@
+use crate::{
+    api::model::ErrorResponse,
+    model::AppState,
+    utils::{
+        account_jobs::{
+            AccountJob, STATUS_COMPLETED, cancel_deletion, current_export, enqueue_export,
+            pending_deletion, schedule_deletion,
+        },
+        audit::write_audit_log,
+        jwt::AuthUser,
+    },
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+/// Schedules the account for deletion after the grace period. Calling it again returns the
+/// deletion that is already scheduled.
+pub async fn request_account_deletion_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<AccountJob>), (StatusCode, Json<ErrorResponse>)> {
+    let existing = pending_deletion(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Account Deletion): {:?}", e);
+            internal_error()
+        })?;
+    if let Some(job) = existing {
+        return Ok((StatusCode::ACCEPTED, Json(job)));
+    }
+
+    let job = schedule_deletion(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Account Deletion): {:?}", e);
+            internal_error()
+        })?;
+
+    // Account audit entries carry no actor so they outlive the deleted user row.
+    write_audit_log(
+        &state.db,
+        None,
+        "ACCOUNT_DELETION_REQUESTED",
+        "user",
+        &auth_user.user_id.to_string(),
+        json!({ "jobId": job.id, "runAfter": job.run_after }),
+    )
+    .await;
+
+    info!(
+        "User {} scheduled account deletion for {}",
+        auth_user.user_id, job.run_after
+    );
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+pub async fn cancel_account_deletion_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<AccountJob>, (StatusCode, Json<ErrorResponse>)> {
+    let job = cancel_deletion(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Cancel Account Deletion): {:?}", e);
+            internal_error()
+        })?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "No pending account deletion".to_string(),
+            }),
+        ))?;
+
+    write_audit_log(
+        &state.db,
+        None,
+        "ACCOUNT_DELETION_CANCELLED",
+        "user",
+        &auth_user.user_id.to_string(),
+        json!({ "jobId": job.id }),
+    )
+    .await;
+
+    info!("User {} cancelled account deletion", auth_user.user_id);
+    Ok(Json(job))
+}
+
+/// Downloads the finished export ZIP. While none is ready this starts (or reports) the
+/// background export and answers 202 so the client can poll.
+pub async fn export_account_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let current = current_export(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Account Export): {:?}", e);
+            internal_error()
+        })?;
+
+    let job = match current {
+        Some(job) if job.status == STATUS_COMPLETED => {
+            let path = job.file_path.clone().unwrap_or_default();
+            let bytes = tokio::fs::read(&path).await.map_err(|e| {
+                error!("Account export file {} unreadable: {}", path, e);
+                internal_error()
+            })?;
+            let filename = format!(
+                "healthy-diet-export-{}.zip",
+                job.created_at.format("%Y%m%d")
+            );
+            return Ok((
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{}\"", filename),
+                    ),
+                ],
+                bytes,
+            )
+                .into_response());
+        }
+        Some(job) => job,
+        None => {
+            let job = enqueue_export(&state.db, auth_user.user_id)
+                .await
+                .map_err(|e| {
+                    error!("DB Error (Account Export): {:?}", e);
+                    internal_error()
+                })?;
+            write_audit_log(
+                &state.db,
+                None,
+                "ACCOUNT_EXPORT_REQUESTED",
+                "user",
+                &auth_user.user_id.to_string(),
+                json!({ "jobId": job.id }),
+            )
+            .await;
+            info!("User {} requested a data export", auth_user.user_id);
+            job
+        }
+    };
+
+    Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::{register_test_account, setup_db};
+
+    #[tokio::test]
+    async fn test_deletion_request_is_idempotent_and_cancellable() {
+        let state = setup_db().await;
+        let (email, _) = register_test_account(state.clone(), "account_delete".to_string()).await;
+        let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        let auth_user = AuthUser {
+            user_id,
+            email: email.clone(),
+            role: "user".to_string(),
+            session_id: None,
+            api_token_id: None,
+        };
+
+        let (status, Json(first)) =
+            request_account_deletion_handler(auth_user.clone(), State(state.clone()))
+                .await
+                .unwrap();
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(first.run_after > first.created_at);
+
+        let (_, Json(second)) =
+            request_account_deletion_handler(auth_user.clone(), State(state.clone()))
+                .await
+                .unwrap();
+        assert_eq!(first.id, second.id);
+
+        let Json(cancelled) =
+            cancel_account_deletion_handler(auth_user.clone(), State(state.clone()))
+                .await
+                .unwrap();
+        assert_eq!(cancelled.status, "cancelled");
+        assert!(
+            cancel_account_deletion_handler(auth_user, State(state.clone()))
+                .await
+                .is_err()
+        );
+
+        let _ = sqlx::query("DELETE FROM account_jobs WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&state.db)
+            .await;
+        let _ = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&state.db)
+            .await;
+    }
+}
@@ -1,9 +1,22 @@
 use crate::{
-    api::model::{AuthResponse, ErrorResponse, UserDetailResponse, UserProfile, is_super_admin},
+    api::{
+        model::{AuthResponse, ErrorResponse, UserDetailResponse, UserProfile},
+        password_reset::send_password_reset_email,
+    },
     model::AppState,
     utils::{
-        jwt::{AuthUser, sign_jwt_with_access_ttl},
+        audit::write_audit_log,
+        client_info::ClientInfo,
+        hash::hash_password,
+        jwt::AuthUser,
+        login_guard::{SCOPE_ACCOUNT, account_key, clear_failures},
+        permission::{
+            AGENT_TOKENS_CREATE, ROLES_MANAGE, USERS_MANAGE_ADMINS, ensure_permission, is_admin,
+            role_definition,
+        },
+        refresh_token::issue_token_pair,
         route_control::{MANAGED_ROUTE_KEYS, is_protected_route_key},
+        session::revoke_all_sessions,
     },
 };
 use axum::{
@@ -14,12 +27,30 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use sqlx::Row;
 use std::{collections::HashMap, sync::Arc};
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Deserialize)]
 pub struct AdminUsersQuery {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    /// Case-insensitive substring of the email.
+    pub email: Option<String>,
+    /// Case-insensitive substring of the nickname.
+    pub nickname: Option<String>,
+    pub role: Option<String>,
+    /// Exact entry of the user's `disease` list.
+    pub disease: Option<String>,
+    pub suspended: Option<bool>,
+}
+
+/// Turns free text into an `ILIKE` substring pattern, escaping the wildcards it may contain.
+fn contains_pattern(value: Option<&str>) -> Option<String> {
+    let value = value.map(str::trim).filter(|value| !value.is_empty())?;
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    Some(format!("%{}%", escaped))
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
@@ -30,6 +61,17 @@ pub struct AdminUserListItem {
     pub nickname: Option<String>,
     pub avatar_url: Option<String>,
     pub role: String,
+    pub suspended_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserRolePayload {
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuspendUserPayload {
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
@@ -109,16 +151,37 @@ pub async fn admin_users_handler(
     let limit = params.limit.unwrap_or(50).clamp(1, 200);
     let offset = params.offset.unwrap_or(0).max(0);
 
+    let role = params
+        .role
+        .as_deref()
+        .map(str::trim)
+        .filter(|role| !role.is_empty());
+    let disease = params
+        .disease
+        .as_deref()
+        .map(str::trim)
+        .filter(|disease| !disease.is_empty());
+
     let users = sqlx::query_as::<_, AdminUserListItem>(
         r#"
-        SELECT id, email, nickname, avatar_url, role
+        SELECT id, email, nickname, avatar_url, role, suspended_at
         FROM users
-        ORDER BY id DESC
+        WHERE ($3::text IS NULL OR email ILIKE $3)
+          AND ($4::text IS NULL OR nickname ILIKE $4)
+          AND ($5::text IS NULL OR role = $5)
+          AND ($6::text IS NULL OR $6 = ANY(disease))
+          AND ($7::boolean IS NULL OR (suspended_at IS NOT NULL) = $7)
+        ORDER BY email ASC, id ASC
         LIMIT $1 OFFSET $2
         "#,
     )
     .bind(limit)
     .bind(offset)
+    .bind(contains_pattern(params.email.as_deref()))
+    .bind(contains_pattern(params.nickname.as_deref()))
+    .bind(role)
+    .bind(disease)
+    .bind(params.suspended)
     .fetch_all(&state.db)
     .await
     .map_err(|e| {
@@ -237,28 +300,22 @@ pub async fn update_route_control_handler(
         )
     })?;
 
-    if let Err(e) = sqlx::query(
-        r#"
-        INSERT INTO admin_audit_logs (admin_id, action, target_type, target_id, metadata, created_at)
-        VALUES ($1, $2, 'route', $3, $4, now())
-        "#,
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        if payload.is_enabled {
+            "ROUTE_ENABLED"
+        } else {
+            "ROUTE_DISABLED"
+        },
+        "route",
+        &route_key,
+        serde_json::json!({
+            "isEnabled": payload.is_enabled,
+            "reason": reason,
+        }),
     )
-    .bind(admin_user.user_id)
-    .bind(if payload.is_enabled {
-        "ROUTE_ENABLED"
-    } else {
-        "ROUTE_DISABLED"
-    })
-    .bind(&route_key)
-    .bind(serde_json::json!({
-        "isEnabled": payload.is_enabled,
-        "reason": reason,
-    }))
-    .execute(&state.db)
-    .await
-    {
-        warn!("Audit log write failed for route control {}: {:?}", route_key, e);
-    }
+    .await;
 
     Ok(Json(RouteControlItem {
         is_protected: is_protected_route_key(&control.route_key),
@@ -587,19 +644,19 @@ pub async fn admin_me_handler(
     }))
 }
 
+/// Deprecated: hands out a full super-admin session. Agents should use a scoped API token
+/// from `/admin/api-tokens` instead.
 pub async fn create_agent_admin_token_handler(
     auth_user: AuthUser,
     State(state): State<Arc<AppState>>,
     Json(payload): Json<CreateAgentTokenPayload>,
 ) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
-    if !is_super_admin(&auth_user.role) {
-        return Err((
-            StatusCode::FORBIDDEN,
-            Json(ErrorResponse {
-                error: "Super admin required".to_string(),
-            }),
-        ));
-    }
+    warn!(
+        "Deprecated agent token requested by {}; use a scoped API token instead",
+        auth_user.user_id
+    );
+
+    ensure_permission(&state.db, &auth_user.role, AGENT_TOKENS_CREATE).await?;
 
     let access_ttl = payload
         .expires_in_seconds
@@ -628,26 +685,30 @@ pub async fn create_agent_admin_token_handler(
         }),
     ))?;
 
-    if !is_super_admin(&user.role) {
-        return Err((
-            StatusCode::FORBIDDEN,
+    // The token claims may predate a role change; re-check the role stored now.
+    ensure_permission(&state.db, &user.role, AGENT_TOKENS_CREATE).await?;
+
+    let (token, refresh_token, expires_in) = issue_token_pair(
+        &state.db,
+        user.id,
+        &user.email,
+        &user.role,
+        access_ttl,
+        &ClientInfo {
+            device_id: Some("agent".to_string()),
+            ..ClientInfo::default()
+        },
+    )
+    .await
+    .map_err(|e| {
+        error!("Token issue error (agent token): {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
-                error: "Super admin required".to_string(),
+                error: "Failed to generate token".to_string(),
             }),
-        ));
-    }
-
-    let (token, refresh_token, expires_in) =
-        sign_jwt_with_access_ttl(&user.id.to_string(), &user.email, &user.role, access_ttl)
-            .map_err(|e| {
-                error!("JWT generation error (agent token): {:?}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse {
-                        error: "Failed to generate token".to_string(),
-                    }),
-                )
-            })?;
+        )
+    })?;
 
     Ok(Json(AuthResponse {
         token,
@@ -707,3 +768,428 @@ pub async fn admin_user_detail_handler(
         disease: user_row.get("disease"),
     }))
 }
+
+/// Lifts a login lockout on the account and resets its failure counter.
+pub async fn unlock_user_handler(
+    admin_user: AuthUser,
+    Path(user_id): Path<uuid::Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let email = sqlx::query_scalar::<_, String>("SELECT email FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Admin Unlock User): {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Internal server error".to_string(),
+                }),
+            )
+        })?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "User not found".to_string(),
+            }),
+        ))?;
+
+    let had_failures = clear_failures(&state.db, SCOPE_ACCOUNT, &account_key(&email))
+        .await
+        .map_err(|e| {
+            error!("DB Error (Admin Unlock User): {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Internal server error".to_string(),
+                }),
+            )
+        })?;
+
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "ACCOUNT_UNLOCKED",
+        "user",
+        &user_id.to_string(),
+        serde_json::json!({ "hadFailures": had_failures }),
+    )
+    .await;
+
+    info!("Admin {} unlocked user {}", admin_user.user_id, user_id);
+    Ok(Json(serde_json::json!({
+        "message": "Account unlocked",
+        "hadFailures": had_failures,
+    })))
+}
+
+fn admin_db_error(context: &str, e: sqlx::Error) -> (StatusCode, Json<ErrorResponse>) {
+    error!("DB Error ({}): {:?}", context, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+fn admin_forbidden(message: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::FORBIDDEN,
+        Json(ErrorResponse {
+            error: message.to_string(),
+        }),
+    )
+}
+
+/// Loads the target's email and role for an account action.
+async fn find_target_user(
+    state: &AppState,
+    user_id: uuid::Uuid,
+    context: &str,
+) -> Result<(String, String), (StatusCode, Json<ErrorResponse>)> {
+    sqlx::query_as::<_, (String, String)>("SELECT email, role FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| admin_db_error(context, e))?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "User not found".to_string(),
+            }),
+        ))
+}
+
+/// Accounts whose role grants admin access can only be acted on with
+/// `users.manage_admins`, and nobody may act on their own account.
+async fn ensure_can_manage(
+    state: &AppState,
+    admin_user: &AuthUser,
+    target_id: uuid::Uuid,
+    target_role: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if admin_user.user_id == target_id {
+        return Err(admin_forbidden(
+            "You cannot perform this action on your own account",
+        ));
+    }
+    let target_is_admin = is_admin(&state.db, target_role)
+        .await
+        .map_err(|e| admin_db_error("Admin Permission Check", e))?;
+    if target_is_admin {
+        ensure_permission(&state.db, &admin_user.role, USERS_MANAGE_ADMINS).await?;
+    }
+    Ok(())
+}
+
+/// Changes a user's role (`roles.manage`). The user's sessions are revoked so tokens
+/// carrying the old role stop working.
+pub async fn update_user_role_handler(
+    admin_user: AuthUser,
+    Path(user_id): Path<uuid::Uuid>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<UpdateUserRolePayload>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    ensure_permission(&state.db, &admin_user.role, ROLES_MANAGE).await?;
+
+    let role = payload.role.trim().to_lowercase();
+    let known_role = role_definition(&state.db, &role)
+        .await
+        .map_err(|e| admin_db_error("Admin Change Role", e))?
+        .is_some();
+    if !known_role {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("Unknown role: {}", payload.role),
+            }),
+        ));
+    }
+
+    let (_, previous_role) = find_target_user(&state, user_id, "Admin Change Role").await?;
+    ensure_can_manage(&state, &admin_user, user_id, &previous_role).await?;
+    if previous_role == role {
+        return Ok(Json(serde_json::json!({
+            "message": "Role unchanged",
+            "role": role,
+        })));
+    }
+
+    sqlx::query("UPDATE users SET role = $1 WHERE id = $2")
+        .bind(&role)
+        .bind(user_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| admin_db_error("Admin Change Role", e))?;
+
+    let revoked = revoke_all_sessions(&state.db, user_id)
+        .await
+        .map_err(|e| admin_db_error("Admin Change Role", e))?;
+
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "USER_ROLE_CHANGED",
+        "user",
+        &user_id.to_string(),
+        serde_json::json!({
+            "from": previous_role,
+            "to": role,
+            "revokedSessions": revoked,
+        }),
+    )
+    .await;
+
+    info!(
+        "Admin {} changed role of user {} from {} to {}",
+        admin_user.user_id, user_id, previous_role, role
+    );
+    Ok(Json(serde_json::json!({
+        "message": "Role updated",
+        "role": role,
+    })))
+}
+
+/// Suspends the account: existing sessions are revoked, the access token check rejects it
+/// and it cannot sign in again until unsuspended.
+pub async fn suspend_user_handler(
+    admin_user: AuthUser,
+    Path(user_id): Path<uuid::Uuid>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SuspendUserPayload>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let (_, target_role) = find_target_user(&state, user_id, "Admin Suspend User").await?;
+    ensure_can_manage(&state, &admin_user, user_id, &target_role).await?;
+
+    let reason = payload
+        .reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+
+    let suspended = sqlx::query(
+        r#"
+        UPDATE users
+        SET suspended_at = now(), suspended_reason = $2, suspended_by = $3
+        WHERE id = $1 AND suspended_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(&reason)
+    .bind(admin_user.user_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| admin_db_error("Admin Suspend User", e))?
+    .rows_affected();
+    if suspended == 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "User is already suspended".to_string(),
+            }),
+        ));
+    }
+
+    let revoked = revoke_all_sessions(&state.db, user_id)
+        .await
+        .map_err(|e| admin_db_error("Admin Suspend User", e))?;
+
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "USER_SUSPENDED",
+        "user",
+        &user_id.to_string(),
+        serde_json::json!({ "reason": reason, "revokedSessions": revoked }),
+    )
+    .await;
+
+    warn!("Admin {} suspended user {}", admin_user.user_id, user_id);
+    Ok(Json(serde_json::json!({
+        "message": "User suspended",
+        "revokedSessions": revoked,
+    })))
+}
+
+pub async fn unsuspend_user_handler(
+    admin_user: AuthUser,
+    Path(user_id): Path<uuid::Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let (_, target_role) = find_target_user(&state, user_id, "Admin Unsuspend User").await?;
+    ensure_can_manage(&state, &admin_user, user_id, &target_role).await?;
+
+    let unsuspended = sqlx::query(
+        r#"
+        UPDATE users
+        SET suspended_at = NULL, suspended_reason = NULL, suspended_by = NULL
+        WHERE id = $1 AND suspended_at IS NOT NULL
+        "#,
+    )
+    .bind(user_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| admin_db_error("Admin Unsuspend User", e))?
+    .rows_affected();
+    if unsuspended == 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "User is not suspended".to_string(),
+            }),
+        ));
+    }
+
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "USER_UNSUSPENDED",
+        "user",
+        &user_id.to_string(),
+        serde_json::json!({}),
+    )
+    .await;
+
+    info!("Admin {} unsuspended user {}", admin_user.user_id, user_id);
+    Ok(Json(serde_json::json!({ "message": "User unsuspended" })))
+}
+
+/// Invalidates the current password, signs the user out everywhere and emails a reset link.
+pub async fn force_password_reset_handler(
+    admin_user: AuthUser,
+    Path(user_id): Path<uuid::Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let (email, target_role) =
+        find_target_user(&state, user_id, "Admin Force Password Reset").await?;
+    ensure_can_manage(&state, &admin_user, user_id, &target_role).await?;
+
+    // Same unusable random hash as OAuth-only accounts get, so the old password stops working.
+    let password_hash = hash_password(&uuid::Uuid::new_v4().to_string()).map_err(|e| {
+        error!("Hash Error (Admin Force Password Reset): {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            }),
+        )
+    })?;
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+        .bind(&password_hash)
+        .bind(user_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| admin_db_error("Admin Force Password Reset", e))?;
+
+    let revoked = revoke_all_sessions(&state.db, user_id)
+        .await
+        .map_err(|e| admin_db_error("Admin Force Password Reset", e))?;
+
+    send_password_reset_email(&state, user_id, &email).await?;
+
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "USER_PASSWORD_RESET_FORCED",
+        "user",
+        &user_id.to_string(),
+        serde_json::json!({ "revokedSessions": revoked }),
+    )
+    .await;
+
+    warn!(
+        "Admin {} forced a password reset for user {}",
+        admin_user.user_id, user_id
+    );
+    Ok(Json(serde_json::json!({
+        "message": "Password reset email sent",
+        "revokedSessions": revoked,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::model::ROLE_OPERATOR,
+        utils::{
+            session::{AccountAccess, check_account_access},
+            test::{register_test_account, setup_db},
+        },
+    };
+
+    #[test]
+    fn test_contains_pattern_escapes_wildcards() {
+        assert_eq!(contains_pattern(Some("  ")), None);
+        assert_eq!(
+            contains_pattern(Some("50%_off")),
+            Some("%50\\%\\_off%".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suspended_user_is_rejected_until_unsuspended() {
+        let state = setup_db().await;
+        let (email, _) = register_test_account(state.clone(), "admin_suspend".to_string()).await;
+        let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        let operator = AuthUser {
+            user_id: uuid::Uuid::new_v4(),
+            email: "operator@example.com".to_string(),
+            role: ROLE_OPERATOR.to_string(),
+            session_id: None,
+            api_token_id: None,
+        };
+
+        let Json(suspended) = suspend_user_handler(
+            operator.clone(),
+            Path(user_id),
+            State(state.clone()),
+            Json(SuspendUserPayload {
+                reason: Some("spam".to_string()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(suspended["message"], "User suspended");
+        assert_eq!(
+            check_account_access(&state.db, user_id, None)
+                .await
+                .unwrap(),
+            AccountAccess::Suspended
+        );
+        assert!(
+            update_user_role_handler(
+                operator.clone(),
+                Path(user_id),
+                State(state.clone()),
+                Json(UpdateUserRolePayload {
+                    role: ROLE_OPERATOR.to_string(),
+                }),
+            )
+            .await
+            .is_err()
+        );
+
+        let Json(unsuspended) =
+            unsuspend_user_handler(operator, Path(user_id), State(state.clone()))
+                .await
+                .unwrap();
+        assert_eq!(unsuspended["message"], "User unsuspended");
+        assert_eq!(
+            check_account_access(&state.db, user_id, None)
+                .await
+                .unwrap(),
+            AccountAccess::Active
+        );
+
+        let _ = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&state.db)
+            .await;
+    }
+}
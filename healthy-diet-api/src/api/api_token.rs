@@ -0,0 +1,268 @@
+use crate::{
+    api::model::ErrorResponse,
+    model::AppState,
+    utils::{
+        api_token::{ApiTokenItem, generate_api_token, is_known_scope},
+        audit::write_audit_log,
+        jwt::AuthUser,
+        permission::{API_TOKENS_MANAGE, ensure_permission},
+    },
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, info};
+
+const MAX_TOKEN_NAME_LEN: usize = 100;
+const MAX_EXPIRES_IN_DAYS: i64 = 365;
+/// Characters of the plaintext token kept for display, e.g. `hdk_3f9a1c2b`.
+const TOKEN_PREFIX_LEN: usize = 12;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenPayload {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenResponse {
+    /// Only returned here; the server keeps a hash.
+    pub token: String,
+    #[serde(flatten)]
+    pub item: ApiTokenItem,
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+fn bad_request(message: String) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse { error: message }),
+    )
+}
+
+pub async fn list_api_tokens_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ApiTokenItem>>, (StatusCode, Json<ErrorResponse>)> {
+    ensure_permission(&state.db, &auth_user.role, API_TOKENS_MANAGE).await?;
+
+    let tokens = sqlx::query_as::<_, ApiTokenItem>(
+        r#"
+        SELECT id, name, token_prefix, scopes, created_by, created_at, expires_at, last_used_at, revoked_at
+        FROM api_tokens
+        ORDER BY created_at DESC
+        "#,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        error!("DB Error (List API Tokens): {:?}", e);
+        internal_error()
+    })?;
+
+    Ok(Json(tokens))
+}
+
+pub async fn create_api_token_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<CreateApiTokenPayload>,
+) -> Result<(StatusCode, Json<CreateApiTokenResponse>), (StatusCode, Json<ErrorResponse>)> {
+    ensure_permission(&state.db, &auth_user.role, API_TOKENS_MANAGE).await?;
+
+    let name = payload.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_LEN {
+        return Err(bad_request(format!(
+            "name must be 1-{} characters",
+            MAX_TOKEN_NAME_LEN
+        )));
+    }
+
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in payload.scopes {
+        let scope = scope.trim().to_string();
+        if !is_known_scope(&scope) {
+            return Err(bad_request(format!("Unknown scope {}", scope)));
+        }
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    if scopes.is_empty() {
+        return Err(bad_request("At least one scope is required".to_string()));
+    }
+
+    if let Some(days) = payload.expires_in_days
+        && !(1..=MAX_EXPIRES_IN_DAYS).contains(&days)
+    {
+        return Err(bad_request(format!(
+            "expiresInDays must be between 1 and {}",
+            MAX_EXPIRES_IN_DAYS
+        )));
+    }
+
+    let (token, token_hash) = generate_api_token();
+    let token_prefix: String = token.chars().take(TOKEN_PREFIX_LEN).collect();
+
+    let item = sqlx::query_as::<_, ApiTokenItem>(
+        r#"
+        INSERT INTO api_tokens (id, name, token_hash, token_prefix, scopes, created_by, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, now(), now() + make_interval(days => $7::int))
+        RETURNING id, name, token_prefix, scopes, created_by, created_at, expires_at, last_used_at, revoked_at
+        "#,
+    )
+    .bind(uuid::Uuid::new_v4())
+    .bind(&name)
+    .bind(&token_hash)
+    .bind(&token_prefix)
+    .bind(&scopes)
+    .bind(auth_user.user_id)
+    .bind(payload.expires_in_days.map(|days| days as i32))
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| {
+        error!("DB Error (Create API Token): {:?}", e);
+        internal_error()
+    })?;
+
+    write_audit_log(
+        &state.db,
+        Some(auth_user.user_id),
+        "API_TOKEN_CREATED",
+        "api_token",
+        &item.id.to_string(),
+        serde_json::json!({
+            "name": item.name,
+            "scopes": item.scopes,
+            "expiresAt": item.expires_at,
+        }),
+    )
+    .await;
+
+    info!("Admin {} created API token {}", auth_user.user_id, item.id);
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateApiTokenResponse { token, item }),
+    ))
+}
+
+pub async fn revoke_api_token_handler(
+    auth_user: AuthUser,
+    Path(token_id): Path<uuid::Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    ensure_permission(&state.db, &auth_user.role, API_TOKENS_MANAGE).await?;
+
+    let revoked = sqlx::query_scalar::<_, uuid::Uuid>(
+        "UPDATE api_tokens SET revoked_at = now() WHERE id = $1 AND revoked_at IS NULL RETURNING id",
+    )
+    .bind(token_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        error!("DB Error (Revoke API Token): {:?}", e);
+        internal_error()
+    })?;
+
+    if revoked.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "API token not found".to_string(),
+            }),
+        ));
+    }
+
+    write_audit_log(
+        &state.db,
+        Some(auth_user.user_id),
+        "API_TOKEN_REVOKED",
+        "api_token",
+        &token_id.to_string(),
+        serde_json::json!({}),
+    )
+    .await;
+
+    info!("Admin {} revoked API token {}", auth_user.user_id, token_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{
+        api_token::{SCOPE_RAG_READ, authenticate_api_token},
+        test::{register_test_account, setup_db},
+    };
+
+    #[tokio::test]
+    async fn test_api_token_lifecycle() {
+        let state = setup_db().await;
+        let (email, _) = register_test_account(state.clone(), "api_token".to_string()).await;
+        let user_id: uuid::Uuid = sqlx::query_scalar(
+            "UPDATE users SET role = 'super_admin' WHERE email = $1 RETURNING id",
+        )
+        .bind(&email)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        let admin = AuthUser {
+            user_id,
+            email: email.clone(),
+            role: "super_admin".to_string(),
+            session_id: None,
+            api_token_id: None,
+        };
+
+        let (status, Json(created)) = create_api_token_handler(
+            admin.clone(),
+            State(state.clone()),
+            Json(CreateApiTokenPayload {
+                name: "agent".to_string(),
+                scopes: vec![SCOPE_RAG_READ.to_string()],
+                expires_in_days: Some(30),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+
+        let principal = authenticate_api_token(&state.db, &created.token)
+            .await
+            .unwrap()
+            .expect("fresh token should authenticate");
+        assert_eq!(principal.user_id, user_id);
+        assert!(principal.has_scope(SCOPE_RAG_READ));
+
+        let revoked =
+            revoke_api_token_handler(admin.clone(), Path(created.item.id), State(state.clone()))
+                .await;
+        assert_eq!(revoked.unwrap(), StatusCode::NO_CONTENT);
+        assert!(
+            authenticate_api_token(&state.db, &created.token)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let _ = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&state.db)
+            .await;
+    }
+}
@@ -1,10 +1,124 @@
-use crate::utils::calculator::{BasicCalorieResponse, ExtractedFoodItem, calculate_basic_calories};
-use axum::Json;
+use crate::{
+    api::model::ErrorResponse,
+    model::AppState,
+    utils::{
+        jwt::AuthUser,
+        nutrition_engine::{FoodPortion, NutrientVector},
+    },
+};
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Serialize;
+use std::sync::Arc;
 
+const MAX_ITEMS: usize = 50;
+const MAX_MANUAL_WEIGHT_G: f64 = 5000.0;
+
+#[derive(Serialize, Debug)]
+pub struct CalculatedItem {
+    pub class: String,
+    pub estimated_weight_g: f64,
+    pub calories: f64,
+    pub default_cooking_method: String,
+    pub nutrients: NutrientVector,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BasicCalorieResponse {
+    pub source: String,
+    pub engine_version: String,
+    pub total_calories: f64,
+    pub nutrients: NutrientVector,
+    pub items: Vec<CalculatedItem>,
+}
+
+fn bad_request(message: String) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse { error: message }),
+    )
+}
+
+/// Each item needs an image share (`area_ratio`, 0-1) or a weighed portion (`weight_g`).
+fn validate_portions(portions: &[FoodPortion]) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if portions.is_empty() || portions.len() > MAX_ITEMS {
+        return Err(bad_request(format!("Send 1-{} food items", MAX_ITEMS)));
+    }
+    for portion in portions {
+        if portion.class.trim().is_empty() {
+            return Err(bad_request("Every item needs a class".to_string()));
+        }
+        match (portion.area_ratio, portion.weight_g) {
+            (_, Some(weight_g)) if !(weight_g > 0.0 && weight_g <= MAX_MANUAL_WEIGHT_G) => {
+                return Err(bad_request(format!(
+                    "weight_g must be between 0 and {}",
+                    MAX_MANUAL_WEIGHT_G
+                )));
+            }
+            (Some(area_ratio), None) if !(0.0..=1.0).contains(&area_ratio) => {
+                return Err(bad_request(
+                    "area_ratio must be between 0 and 1".to_string(),
+                ));
+            }
+            (None, None) => {
+                return Err(bad_request(format!(
+                    "Item `{}` needs area_ratio or weight_g",
+                    portion.class
+                )));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Manual mode: the same estimate the image analysis uses, for foods the user describes.
 pub async fn basic_calculate_handler(
-    Json(payload): Json<Vec<ExtractedFoodItem>>,
-) -> Json<BasicCalorieResponse> {
-    let result = calculate_basic_calories(payload);
+    _auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Vec<FoodPortion>>,
+) -> Result<Json<BasicCalorieResponse>, (StatusCode, Json<ErrorResponse>)> {
+    validate_portions(&payload)?;
+    let estimate = state.nutrition_engine.estimate(&payload);
+
+    Ok(Json(BasicCalorieResponse {
+        source: "nutrition_engine".to_string(),
+        engine_version: estimate.engine_version,
+        total_calories: (estimate.total_calories * 10.0).round() / 10.0,
+        nutrients: estimate.nutrients.rounded(),
+        items: estimate
+            .items
+            .into_iter()
+            .map(|item| CalculatedItem {
+                class: item.class,
+                estimated_weight_g: item.estimated_weight_g,
+                calories: item.calories,
+                default_cooking_method: item.cooking_method,
+                nutrients: item.nutrients,
+            })
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portion(area_ratio: Option<f64>, weight_g: Option<f64>) -> FoodPortion {
+        FoodPortion {
+            class: "grain".to_string(),
+            confidence: None,
+            area_ratio,
+            weight_g,
+        }
+    }
 
-    Json(result)
+    #[test]
+    fn test_validate_portions_needs_area_or_weight() {
+        assert!(validate_portions(&[portion(Some(0.3), None)]).is_ok());
+        assert!(validate_portions(&[portion(None, Some(180.0))]).is_ok());
+        assert!(validate_portions(&[portion(None, None)]).is_err());
+        assert!(validate_portions(&[portion(Some(1.5), None)]).is_err());
+        assert!(validate_portions(&[portion(None, Some(0.0))]).is_err());
+        assert!(validate_portions(&[]).is_err());
+    }
 }
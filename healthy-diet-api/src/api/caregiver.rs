@@ -0,0 +1,448 @@
+use crate::{
+    api::{
+        chat_room::{ChatRoomTitleItem, fetch_chat_room_titles},
+        diet_record::{DailyDietStats, fetch_daily_diet_stats, fetch_diet_records},
+        email_verification::frontend_link,
+        model::{DietRecordResponse, ErrorResponse},
+    },
+    model::AppState,
+    utils::{
+        audit::write_audit_log,
+        caregiver::{
+            CaregiverLink, INVITE_TTL_DAYS, SCOPE_CHAT_SUMMARIES, SCOPE_MEALS, accept_invite,
+            active_link_for_caregiver, create_invite, generate_invite_code, hash_invite_code,
+            is_known_scope, links_of_caregiver, links_of_user, revoke_link,
+        },
+        jwt::AuthUser,
+        mailer::EmailMessage,
+    },
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+use validator::Validate;
+
+const DEFAULT_STATS_DAYS: i32 = 30;
+const MAX_STATS_DAYS: i32 = 365;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct InviteCaregiverPayload {
+    /// Optional: the code is also returned so it can be shared in person.
+    #[validate(email(message = "Email formate not correct"))]
+    pub email: Option<String>,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptCaregiverInvitePayload {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DietStatsQuery {
+    pub days: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaregiverInviteResponse {
+    pub link: CaregiverLink,
+    /// Shown once; only its hash is stored.
+    pub invite_code: String,
+    pub email_sent: bool,
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+fn error_response(status: StatusCode, message: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        status,
+        Json(ErrorResponse {
+            error: message.to_string(),
+        }),
+    )
+}
+
+/// The caller's active link `link_id`, provided it grants `scope`.
+async fn caregiver_link_with_scope(
+    state: &AppState,
+    auth_user: &AuthUser,
+    link_id: Uuid,
+    scope: &str,
+) -> Result<CaregiverLink, (StatusCode, Json<ErrorResponse>)> {
+    let link = active_link_for_caregiver(&state.db, link_id, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Caregiver Link): {:?}", e);
+            internal_error()
+        })?
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Caregiver link not found"))?;
+
+    if !link.has_scope(scope) {
+        return Err(error_response(
+            StatusCode::FORBIDDEN,
+            &format!("This link does not share {}", scope),
+        ));
+    }
+    Ok(link)
+}
+
+/// Invites a caregiver with the chosen scopes. The invite code is emailed when an address
+/// is given and always returned so it can be handed over directly.
+pub async fn invite_caregiver_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<InviteCaregiverPayload>,
+) -> Result<(StatusCode, Json<CaregiverInviteResponse>), (StatusCode, Json<ErrorResponse>)> {
+    payload.validate().map_err(|e| {
+        error_response(
+            StatusCode::BAD_REQUEST,
+            &format!("Validation failed: {}", e),
+        )
+    })?;
+
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in payload.scopes {
+        let scope = scope.trim().to_string();
+        if !is_known_scope(&scope) {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                &format!("Unknown scope: {}", scope),
+            ));
+        }
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    if scopes.is_empty() {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "At least one scope is required",
+        ));
+    }
+
+    let email = payload
+        .email
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty());
+    if email
+        .as_deref()
+        .is_some_and(|email| email == auth_user.email.to_lowercase())
+    {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "You cannot invite yourself",
+        ));
+    }
+
+    let (code, code_hash) = generate_invite_code();
+    let link = create_invite(
+        &state.db,
+        auth_user.user_id,
+        email.as_deref(),
+        &scopes,
+        &code_hash,
+    )
+    .await
+    .map_err(|e| {
+        error!("DB Error (Invite Caregiver): {:?}", e);
+        internal_error()
+    })?;
+
+    let mut email_sent = false;
+    if let Some(to) = &email {
+        let sent = state
+            .mailer
+            .send(&EmailMessage {
+                to: to.clone(),
+                subject: "You have been invited as a Healthy Diet caregiver".to_string(),
+                body: format!(
+                    "{} invited you to follow their meals on Healthy Diet.\n\nSign in and enter the code {} or open:\n\n{}\n\nThe invitation expires in {} days.\n",
+                    auth_user.email,
+                    code,
+                    frontend_link("/caregiver/accept", &code),
+                    INVITE_TTL_DAYS
+                ),
+            })
+            .await;
+        match sent {
+            Ok(()) => email_sent = true,
+            Err(e) => error!("Failed to send caregiver invite email: {}", e),
+        }
+    }
+
+    write_audit_log(
+        &state.db,
+        None,
+        "CAREGIVER_INVITED",
+        "caregiver_link",
+        &link.id.to_string(),
+        json!({ "userId": auth_user.user_id, "scopes": link.scopes }),
+    )
+    .await;
+
+    info!(
+        "User {} created caregiver invite {}",
+        auth_user.user_id, link.id
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(CaregiverInviteResponse {
+            link,
+            invite_code: code,
+            email_sent,
+        }),
+    ))
+}
+
+/// Pending invites and active caregivers of the caller.
+pub async fn list_caregivers_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<CaregiverLink>>, (StatusCode, Json<ErrorResponse>)> {
+    let links = links_of_user(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (List Caregivers): {:?}", e);
+            internal_error()
+        })?;
+    Ok(Json(links))
+}
+
+pub async fn accept_caregiver_invite_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AcceptCaregiverInvitePayload>,
+) -> Result<Json<CaregiverLink>, (StatusCode, Json<ErrorResponse>)> {
+    let link = accept_invite(
+        &state.db,
+        auth_user.user_id,
+        &hash_invite_code(&payload.code),
+    )
+    .await
+    .map_err(|e| {
+        error!("DB Error (Accept Caregiver Invite): {:?}", e);
+        internal_error()
+    })?
+    .ok_or_else(|| {
+        error_response(
+            StatusCode::NOT_FOUND,
+            "Invite code is invalid or has expired",
+        )
+    })?;
+
+    write_audit_log(
+        &state.db,
+        None,
+        "CAREGIVER_LINKED",
+        "caregiver_link",
+        &link.id.to_string(),
+        json!({ "userId": link.user_id, "caregiverId": auth_user.user_id }),
+    )
+    .await;
+
+    info!(
+        "User {} became caregiver of {} via link {}",
+        auth_user.user_id, link.user_id, link.id
+    );
+    Ok(Json(link))
+}
+
+/// Either the user or the caregiver ends the link; a pending invite is withdrawn.
+pub async fn revoke_caregiver_link_handler(
+    auth_user: AuthUser,
+    Path(link_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let link = revoke_link(&state.db, link_id, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Revoke Caregiver Link): {:?}", e);
+            internal_error()
+        })?
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Caregiver link not found"))?;
+
+    write_audit_log(
+        &state.db,
+        None,
+        "CAREGIVER_REVOKED",
+        "caregiver_link",
+        &link.id.to_string(),
+        json!({ "revokedBy": auth_user.user_id }),
+    )
+    .await;
+
+    info!(
+        "User {} revoked caregiver link {}",
+        auth_user.user_id, link.id
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Users the caller looks after.
+pub async fn list_caregiving_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<CaregiverLink>>, (StatusCode, Json<ErrorResponse>)> {
+    let links = links_of_caregiver(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (List Caregiving): {:?}", e);
+            internal_error()
+        })?;
+    Ok(Json(links))
+}
+
+pub async fn caregiving_diet_records_handler(
+    auth_user: AuthUser,
+    Path(link_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DietRecordResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let link = caregiver_link_with_scope(&state, &auth_user, link_id, SCOPE_MEALS).await?;
+    let records = fetch_diet_records(&state.db, link.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Caregiving Diet Records): {:?}", e);
+            internal_error()
+        })?;
+    Ok(Json(records))
+}
+
+pub async fn caregiving_diet_stats_handler(
+    auth_user: AuthUser,
+    Path(link_id): Path<Uuid>,
+    Query(params): Query<DietStatsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DailyDietStats>>, (StatusCode, Json<ErrorResponse>)> {
+    let link = caregiver_link_with_scope(&state, &auth_user, link_id, SCOPE_MEALS).await?;
+    let days = params
+        .days
+        .unwrap_or(DEFAULT_STATS_DAYS)
+        .clamp(1, MAX_STATS_DAYS);
+    let stats = fetch_daily_diet_stats(&state.db, link.user_id, days)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Caregiving Diet Stats): {:?}", e);
+            internal_error()
+        })?;
+    Ok(Json(stats))
+}
+
+/// Room titles and summaries only; message contents stay private.
+pub async fn caregiving_chat_summaries_handler(
+    auth_user: AuthUser,
+    Path(link_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ChatRoomTitleItem>>, (StatusCode, Json<ErrorResponse>)> {
+    let link = caregiver_link_with_scope(&state, &auth_user, link_id, SCOPE_CHAT_SUMMARIES).await?;
+    let rooms = fetch_chat_room_titles(&state.db, link.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Caregiving Chat Summaries): {:?}", e);
+            internal_error()
+        })?;
+    Ok(Json(rooms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::{register_test_account, setup_db};
+
+    async fn test_user(state: Arc<AppState>, prefix: &str) -> AuthUser {
+        let (email, _) = register_test_account(state.clone(), prefix.to_string()).await;
+        let user_id: Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        AuthUser {
+            user_id,
+            email,
+            role: "user".to_string(),
+            session_id: None,
+            api_token_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caregiver_invite_accept_scope_and_revoke() {
+        let state = setup_db().await;
+        let elder = test_user(state.clone(), "caregiver_elder").await;
+        let caregiver = test_user(state.clone(), "caregiver_family").await;
+
+        let (status, Json(invite)) = invite_caregiver_handler(
+            elder.clone(),
+            State(state.clone()),
+            Json(InviteCaregiverPayload {
+                email: None,
+                scopes: vec![SCOPE_MEALS.to_string()],
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(!invite.email_sent);
+
+        let Json(link) = accept_caregiver_invite_handler(
+            caregiver.clone(),
+            State(state.clone()),
+            Json(AcceptCaregiverInvitePayload {
+                code: invite.invite_code.to_lowercase(),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(link.caregiver_id, Some(caregiver.user_id));
+
+        assert!(
+            caregiving_diet_records_handler(caregiver.clone(), Path(link.id), State(state.clone()))
+                .await
+                .is_ok()
+        );
+        let denied = caregiving_chat_summaries_handler(
+            caregiver.clone(),
+            Path(link.id),
+            State(state.clone()),
+        )
+        .await;
+        assert!(matches!(denied, Err((StatusCode::FORBIDDEN, _))));
+
+        assert_eq!(
+            revoke_caregiver_link_handler(elder.clone(), Path(link.id), State(state.clone()))
+                .await
+                .unwrap(),
+            StatusCode::NO_CONTENT
+        );
+        assert!(
+            caregiving_diet_records_handler(caregiver.clone(), Path(link.id), State(state.clone()))
+                .await
+                .is_err()
+        );
+
+        for user in [&elder, &caregiver] {
+            let _ = sqlx::query("DELETE FROM caregiver_links WHERE user_id = $1")
+                .bind(user.user_id)
+                .execute(&state.db)
+                .await;
+            let _ = sqlx::query("DELETE FROM users WHERE id = $1")
+                .bind(user.user_id)
+                .execute(&state.db)
+                .await;
+        }
+    }
+}
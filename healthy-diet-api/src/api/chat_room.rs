@@ -8,10 +8,11 @@ use base64::{Engine as _, engine::general_purpose};
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 use serde_json::json;
-use sqlx::Row;
+use sqlx::{PgPool, Row};
 use std::sync::Arc;
 use tokio::fs;
 use tracing::error;
+use uuid::Uuid;
 
 use crate::{api::model::ErrorResponse, model::AppState, utils::jwt::AuthUser};
 
@@ -114,10 +115,11 @@ pub async fn get_chat_rooms_handler(
     Ok((StatusCode::OK, Json(json!({ "rooms": room_responses }))))
 }
 
-pub async fn get_chat_room_titles_handler(
-    State(state): State<Arc<AppState>>,
-    auth_user: AuthUser,
-) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+/// Room titles and summaries of the user, newest first. Shared with the caregiver view.
+pub(crate) async fn fetch_chat_room_titles(
+    db: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<ChatRoomTitleItem>, sqlx::Error> {
     let rows = sqlx::query(
         r#"
         SELECT room_id, title, summary, last_message_at
@@ -126,20 +128,11 @@ pub async fn get_chat_room_titles_handler(
         ORDER BY last_message_at DESC NULLS LAST, created_at DESC
         "#,
     )
-    .bind(auth_user.user_id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| {
-        error!("failed to get chat room titles: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: "Failed to fetch chat room titles".into(),
-            }),
-        )
-    })?;
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
 
-    let rooms: Vec<ChatRoomTitleItem> = rows
+    Ok(rows
         .into_iter()
         .map(|row| ChatRoomTitleItem {
             room_id: row.try_get::<String, _>("room_id").unwrap_or_default(),
@@ -152,16 +145,35 @@ pub async fn get_chat_room_titles_handler(
                 .ok()
                 .flatten(),
         })
-        .collect();
-
-    Ok((StatusCode::OK, Json(json!({ "rooms": rooms }))))
+        .collect())
 }
 
-pub async fn get_room_history_handler(
+pub async fn get_chat_room_titles_handler(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
-    Path(room_id): Path<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let rooms = fetch_chat_room_titles(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("failed to get chat room titles: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to fetch chat room titles".into(),
+                }),
+            )
+        })?;
+
+    Ok((StatusCode::OK, Json(json!({ "rooms": rooms }))))
+}
+
+/// Messages of one room in order, with attached images inlined. Shared with the
+/// nutritionist workspace.
+pub(crate) async fn fetch_room_history(
+    db: &PgPool,
+    user_id: Uuid,
+    room_id: &str,
+) -> Result<Vec<ChatMessage>, sqlx::Error> {
     let records = sqlx::query(
         r#"
         SELECT user_message, ai_analysis_report, image_path
@@ -170,19 +182,10 @@ pub async fn get_room_history_handler(
         ORDER BY created_at ASC
         "#,
     )
-    .bind(&room_id)
-    .bind(auth_user.user_id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| {
-        error!("查詢歷史紀錄失敗: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: "資料庫查詢失敗".into(),
-            }),
-        )
-    })?;
+    .bind(room_id)
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
 
     let mut history: Vec<ChatMessage> = Vec::new();
 
@@ -212,6 +215,26 @@ pub async fn get_room_history_handler(
         }
     }
 
+    Ok(history)
+}
+
+pub async fn get_room_history_handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(room_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let history = fetch_room_history(&state.db, auth_user.user_id, &room_id)
+        .await
+        .map_err(|e| {
+            error!("查詢歷史紀錄失敗: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "資料庫查詢失敗".into(),
+                }),
+            )
+        })?;
+
     Ok((StatusCode::OK, Json(json!({ "history": history }))))
 }
 
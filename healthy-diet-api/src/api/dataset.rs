@@ -0,0 +1,207 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::{
+    api::model::ErrorResponse,
+    model::AppState,
+    utils::{
+        audit::write_audit_log,
+        jwt::AuthUser,
+        nutrition_engine::CATEGORIES,
+        yolo_dataset::{
+            DEFAULT_EXPORT_LIMIT, DEFAULT_VAL_RATIO, DatasetFilter, MAX_EXPORT_LIMIT,
+            MAX_VAL_RATIO, load_labeled_images, write_dataset_zip,
+        },
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct YoloDatasetQuery {
+    /// First correction date, `YYYY-MM-DD`.
+    pub from: Option<String>,
+    /// Last correction date, inclusive.
+    pub to: Option<String>,
+    pub min_confidence: Option<f64>,
+    pub val_ratio: Option<f64>,
+    pub limit: Option<i64>,
+    /// Comma-separated class names in the model's class id order.
+    pub classes: Option<String>,
+}
+
+fn bad_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            error: message.into(),
+        }),
+    )
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".into(),
+        }),
+    )
+}
+
+fn parse_date(value: Option<&str>, name: &str) -> Result<Option<NaiveDate>, String> {
+    value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| format!("Invalid {} date. Use YYYY-MM-DD", name))
+        })
+        .transpose()
+}
+
+fn parse_filter(query: &YoloDatasetQuery) -> Result<DatasetFilter, String> {
+    let from = parse_date(query.from.as_deref(), "from")?;
+    let to = parse_date(query.to.as_deref(), "to")?;
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        return Err("from must not be after to".to_string());
+    }
+
+    let min_confidence = query.min_confidence.unwrap_or(0.0);
+    if !(0.0..=1.0).contains(&min_confidence) {
+        return Err("min_confidence must be between 0 and 1".to_string());
+    }
+    let val_ratio = query.val_ratio.unwrap_or(DEFAULT_VAL_RATIO);
+    if !(0.0..=MAX_VAL_RATIO).contains(&val_ratio) {
+        return Err(format!("val_ratio must be between 0 and {}", MAX_VAL_RATIO));
+    }
+
+    let classes: Vec<String> = match query.classes.as_deref() {
+        Some(classes) => classes
+            .split(',')
+            .map(str::trim)
+            .filter(|class| !class.is_empty())
+            .map(str::to_string)
+            .collect(),
+        None => CATEGORIES.iter().map(|class| class.to_string()).collect(),
+    };
+    if classes.is_empty() {
+        return Err("classes must name at least one class".to_string());
+    }
+    if (1..classes.len()).any(|i| classes[..i].contains(&classes[i])) {
+        return Err("classes must not repeat".to_string());
+    }
+
+    Ok(DatasetFilter {
+        from,
+        to,
+        min_confidence,
+        val_ratio,
+        limit: query
+            .limit
+            .unwrap_or(DEFAULT_EXPORT_LIMIT)
+            .clamp(1, MAX_EXPORT_LIMIT),
+        classes,
+    })
+}
+
+/// Downloads the photos whose labels users corrected as a YOLO dataset ZIP for retraining.
+pub async fn admin_export_yolo_dataset_handler(
+    admin_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<YoloDatasetQuery>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let filter = parse_filter(&query).map_err(bad_request)?;
+
+    let (images, summary) = load_labeled_images(&state.db, &filter).await.map_err(|e| {
+        error!("DB Error (YOLO Dataset Export): {:?}", e);
+        internal_error()
+    })?;
+    let zip_filter = filter.clone();
+    let (bytes, summary) =
+        tokio::task::spawn_blocking(move || write_dataset_zip(&images, &zip_filter, summary))
+            .await
+            .map_err(|e| {
+                error!("YOLO dataset export task failed: {:?}", e);
+                internal_error()
+            })?
+            .map_err(|e| {
+                error!("YOLO dataset export failed: {}", e);
+                internal_error()
+            })?;
+
+    info!(
+        "Exported YOLO dataset: {} train, {} val, {} boxes",
+        summary.train_images, summary.val_images, summary.boxes
+    );
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "YOLO_DATASET_EXPORTED",
+        "dataset",
+        "yolo",
+        json!({ "filter": filter, "summary": summary }),
+    )
+    .await;
+
+    let filename = format!("healthy-diet-yolo-{}.zip", Utc::now().format("%Y%m%d"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(classes: Option<&str>) -> YoloDatasetQuery {
+        YoloDatasetQuery {
+            from: Some("2026-01-01".to_string()),
+            to: Some("2026-03-31".to_string()),
+            min_confidence: None,
+            val_ratio: None,
+            limit: Some(100_000),
+            classes: classes.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_parse_filter_defaults_and_validation() {
+        let filter = parse_filter(&query(None)).unwrap();
+        assert_eq!(filter.classes.len(), CATEGORIES.len());
+        assert_eq!(filter.limit, MAX_EXPORT_LIMIT);
+        assert_eq!(filter.val_ratio, DEFAULT_VAL_RATIO);
+
+        let filter = parse_filter(&query(Some("grain, vegetable"))).unwrap();
+        assert_eq!(filter.classes, ["grain", "vegetable"]);
+
+        assert!(parse_filter(&query(Some("grain,grain"))).is_err());
+        assert!(parse_filter(&query(Some(" , "))).is_err());
+        let reversed = YoloDatasetQuery {
+            from: Some("2026-04-01".to_string()),
+            ..query(None)
+        };
+        assert!(parse_filter(&reversed).is_err());
+        let bad_ratio = YoloDatasetQuery {
+            val_ratio: Some(0.9),
+            ..query(None)
+        };
+        assert!(parse_filter(&bad_ratio).is_err());
+    }
+}
@@ -3,48 +3,46 @@ use axum_extra::extract::Multipart;
 use base64::{Engine as _, engine::general_purpose};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::{env, fs, process::Command, sync::Arc};
+use std::{collections::HashMap, env, path::Path, sync::Arc, time::Duration};
 use tracing::error;
 use uuid::Uuid;
 
 use crate::{
     api::model::ErrorResponse,
     model::{AppState, ENVKey, OutSideURL},
-    utils::jwt::AuthUser,
+    utils::{
+        caregiver::{LOW_SCORE_ALERT_THRESHOLD, send_low_score_alerts},
+        diet_items::{ENTRY_SOURCE_PHOTO, ItemDetection, MealItem, insert_items},
+        food_detector::{Detection, DetectionResult, DetectorError},
+        jwt::AuthUser,
+        nutrition_engine::{
+            CATEGORIES, FALLBACK_CLASS, FoodPortion, NutrientVector, NutritionEngine,
+        },
+    },
 };
 
-#[derive(Deserialize, Debug)]
-pub struct YoloScriptOutput {
-    pub status: String,
-    pub image_path: String,
-    pub json_path: String,
-    pub detections: Vec<Detection>,
-}
-
-#[derive(Deserialize, Debug)]
-pub struct Detection {
-    #[serde(rename = "class")]
-    pub class_name: String,
-    pub confidence: f64,
-    pub bbox: [f64; 4], // [x_min, y_min, x_max, y_max]
-}
+const UPLOAD_DIR: &str = "/app/uploads";
+/// Bounds each Gemini call, well inside the time a background job may hold its claim.
+pub(crate) const GEMINI_TIMEOUT_SECS: u64 = 60;
 
 #[derive(Serialize)]
 pub struct CalorieResponse {
     pub message: String,
     pub total_calories: f64,
     pub detected_items: Vec<FoodItem>,
+    pub nutrients: NutrientVector,
     pub image_base64: Option<String>,
     pub ai_score: i32,
     pub ai_comment: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct FoodItem {
     pub class: String,
     pub confidence: f64,
     pub estimated_weight_g: f64,
     pub calories: f64,
+    pub nutrients: NutrientVector,
 }
 
 #[derive(Deserialize, Debug)]
@@ -53,14 +51,40 @@ struct AiEvaluationFormat {
     comment: String,
 }
 
-pub async fn yolo_handler(
-    auth_user: AuthUser,
-    State(state): State<Arc<AppState>>,
-    mut multipart: Multipart,
-) -> Result<Json<CalorieResponse>, (StatusCode, Json<ErrorResponse>)> {
+/// What the AI evaluation and caregiver alerts need to know about the user.
+pub(crate) struct MealProfile {
+    pub nickname: Option<String>,
+    pub age: Option<f64>,
+    pub gender: Option<String>,
+    pub taboo: Vec<String>,
+    pub disease: Vec<String>,
+}
+
+/// The nutrition engine's estimate for the detected boxes, with calories and area share per
+/// category.
+pub(crate) struct MealEstimate {
+    pub total_calories: f64,
+    pub detected_items: Vec<FoodItem>,
+    pub nutrients: NutrientVector,
+    pub stats: HashMap<&'static str, (f64, f64)>,
+    /// `None` when the figures come from the foods table rather than the engine.
+    pub engine_version: Option<String>,
+    /// What is stored in `diet_record_items`, one per detection or logged food.
+    pub items: Vec<MealItem>,
+}
+
+pub(crate) struct MealEvaluation {
+    pub score: i32,
+    pub comment: String,
+}
+
+pub(crate) async fn load_meal_profile(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<MealProfile, (StatusCode, Json<ErrorResponse>)> {
     let user_profile = sqlx::query!(
         "SELECT nickname, height, weight, age, gender, taboo, disease FROM users WHERE id = $1",
-        auth_user.user_id
+        user_id
     )
     .fetch_optional(&state.db)
     .await
@@ -80,6 +104,19 @@ pub async fn yolo_handler(
         }),
     ))?;
 
+    Ok(MealProfile {
+        nickname: user_profile.nickname,
+        age: user_profile.age,
+        gender: user_profile.gender,
+        taboo: user_profile.taboo.unwrap_or_default(),
+        disease: user_profile.disease.unwrap_or_default(),
+    })
+}
+
+/// Reads the `image` field of the upload.
+pub(crate) async fn read_image_field(
+    multipart: &mut Multipart,
+) -> Result<Vec<u8>, (StatusCode, Json<ErrorResponse>)> {
     let mut image_data = None;
     while let Some(field) = multipart.next_field().await.map_err(|e| {
         (
@@ -108,11 +145,15 @@ pub async fn yolo_handler(
             error: "請上傳圖片".into(),
         }),
     ))?;
-    let file_id = Uuid::new_v4().to_string();
-    let input_path = format!("/app/uploads/{}.jpg", file_id);
+    Ok(data.to_vec())
+}
+
+/// Saves the upload under a fresh name and returns its path.
+pub(crate) async fn store_upload(data: &[u8]) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
+    let input_path = format!("{}/{}.jpg", UPLOAD_DIR, Uuid::new_v4());
 
-    fs::create_dir_all("/app/uploads").ok();
-    fs::write(&input_path, data).map_err(|_| {
+    tokio::fs::create_dir_all(UPLOAD_DIR).await.ok();
+    tokio::fs::write(&input_path, data).await.map_err(|_| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
@@ -120,138 +161,145 @@ pub async fn yolo_handler(
             }),
         )
     })?;
+    Ok(input_path)
+}
 
-    let yolo_script = env::var("YOLO_SCRIPT_PATH")
-        .unwrap_or_else(|_| "../healthy-diet-yolo/predict.py".to_string());
-    let output = Command::new("python3")
-        .arg(&yolo_script)
-        .arg("--input")
-        .arg(&input_path)
-        .output()
+pub(crate) async fn detect_food(
+    state: &AppState,
+    input_path: &str,
+) -> Result<DetectionResult, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .food_detector
+        .detect(Path::new(input_path))
+        .await
         .map_err(|e| {
-            error!("YOLO CLI 執行錯誤: {:?}", e);
+            error!("YOLO 辨識失敗 ({}): {}", state.food_detector.name(), e);
+            let (status, message) = match e {
+                DetectorError::Unavailable(_) => {
+                    (StatusCode::INTERNAL_SERVER_ERROR, "辨識引擎啟動失敗")
+                }
+                DetectorError::Failed(_) => {
+                    (StatusCode::INTERNAL_SERVER_ERROR, "YOLO 辨識過程出現錯誤")
+                }
+                DetectorError::InvalidOutput(_) => {
+                    (StatusCode::INTERNAL_SERVER_ERROR, "解析辨識結果失敗")
+                }
+                DetectorError::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, "辨識逾時，請稍後再試"),
+            };
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
+                status,
                 Json(ErrorResponse {
-                    error: "辨識引擎啟動失敗".into(),
+                    error: message.into(),
                 }),
             )
-        })?;
+        })
+}
 
-    if !output.status.success() {
-        let stderror = String::from_utf8_lossy(&output.stderr);
-        error!("!辨識過程出錯: {:?}", stderror);
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: "YOLO 辨識過程出現錯誤".into(),
-            }),
-        ));
+pub(crate) fn estimate_meal(engine: &NutritionEngine, detections: Vec<Detection>) -> MealEstimate {
+    let boxes: Vec<ItemDetection> = detections
+        .iter()
+        .map(|det| ItemDetection {
+            bbox: det.bbox,
+            confidence: det.confidence,
+            area_ratio: engine.area_ratio_of_bbox(&det.bbox),
+            corrected: false,
+        })
+        .collect();
+    let portions: Vec<FoodPortion> = detections
+        .into_iter()
+        .zip(&boxes)
+        .map(|(det, detection)| FoodPortion {
+            area_ratio: Some(detection.area_ratio),
+            class: det.class_name,
+            confidence: Some(det.confidence),
+            weight_g: None,
+        })
+        .collect();
+    let estimate = engine.estimate(&portions);
+    let items: Vec<MealItem> = estimate
+        .items
+        .iter()
+        .zip(boxes)
+        .map(|(item, detection)| MealItem {
+            class: item.class.clone(),
+            food_id: None,
+            name: None,
+            source_text: None,
+            weight_g: item.estimated_weight_g,
+            calories: item.calories,
+            nutrients: item.nutrients,
+            detection: Some(detection),
+        })
+        .collect();
+
+    // Totals are summed from the stored items, so a record recalculated from its items
+    // after a correction keeps the same figures.
+    let mut meal = estimate_from_items(&items);
+    for (category, (_, area)) in estimate.categories {
+        meal.stats.entry(category).or_default().1 = area;
     }
+    meal.detected_items = estimate
+        .items
+        .into_iter()
+        .map(|item| FoodItem {
+            class: item.class,
+            confidence: (item.confidence.unwrap_or_default() * 100.0).round() / 100.0,
+            estimated_weight_g: item.estimated_weight_g,
+            calories: item.calories,
+            nutrients: item.nutrients,
+        })
+        .collect();
+    meal.engine_version = Some(estimate.engine_version);
+    meal
+}
 
-    let stdout_str = String::from_utf8_lossy(&output.stdout);
-    let json_start = stdout_str.find('{').unwrap_or(0);
-    let json_end = stdout_str
-        .rfind('}')
-        .unwrap_or(stdout_str.len().saturating_sub(1))
-        + 1;
-    let clean_json = if json_start < json_end {
-        &stdout_str[json_start..json_end]
-    } else {
-        "{}"
-    };
-
-    let yolo_result: YoloScriptOutput = serde_json::from_str(clean_json).map_err(|e| {
-        error!("解析 YOLO 失敗: {}, 原始輸出: {}", e, stdout_str);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: "解析辨識結果失敗".into(),
-            }),
-        )
-    })?;
-
+/// Totals of items whose figures are final: foods logged by hand, or the stored items of a
+/// record being corrected. Nothing is capped or squashed again, and no plate area is
+/// recorded.
+pub(crate) fn estimate_from_items(items: &[MealItem]) -> MealEstimate {
+    let mut stats: HashMap<&'static str, (f64, f64)> = CATEGORIES
+        .iter()
+        .map(|category| (*category, (0.0, 0.0)))
+        .collect();
     let mut total_calories = 0.0;
-    let mut detected_items = Vec::new();
-    let mut stats = std::collections::HashMap::new();
-
-    for cat in [
-        "grain",
-        "protein_meat",
-        "protein_bean",
-        "vegetable",
-        "fruit",
-        "dairy",
-        "nuts",
-        "other",
-    ] {
-        stats.insert(cat, (0.0_f64, 0.0_f64));
-    }
-
-    let total_image_area = 800.0 * 800.0;
-    let reference_full_screen_weight_g = 550.0;
-
-    let mut meat_count = 0;
-    let mut grain_count = 0;
-
-    for det in yolo_result.detections {
-        let x_min = det.bbox[0];
-        let y_min = det.bbox[1];
-        let x_max = det.bbox[2];
-        let y_max = det.bbox[3];
-
-        let bbox_area_pixels = (x_max - x_min) * (y_max - y_min);
-        let area_ratio = (bbox_area_pixels / total_image_area).clamp(0.0, 1.0);
-
-        let adjusted_ratio = if area_ratio > 0.15 {
-            0.15 + (area_ratio - 0.15) * 0.5
-        } else {
-            area_ratio
-        };
-
-        let (density_modifier, cal_per_gram, max_weight, category_key) =
-            match det.class_name.as_str() {
-                "grain" => {
-                    grain_count += 1;
-                    let m = if grain_count > 1 { 0.4 } else { 1.1 };
-                    (m, 1.3, 220.0, "grain")
-                }
-                "protein_meat" => {
-                    meat_count += 1;
-                    let m = if meat_count > 1 { 0.5 } else { 1.3 };
-                    (m, 2.3, 160.0, "protein_meat")
-                }
-                "protein_bean" => (1.2, 1.4, 150.0, "protein_bean"),
-                "vegetable" => (0.5, 0.25, 120.0, "vegetable"),
-                "fruit" => (1.0, 0.5, 150.0, "fruit"),
-                "dairy" => (1.0, 0.6, 250.0, "dairy"),
-                "nuts" => (0.8, 6.0, 30.0, "nuts"),
-                _ => (1.0, 1.0, 150.0, "other"),
-            };
-
-        let weight_g = (adjusted_ratio * reference_full_screen_weight_g * density_modifier)
-            .clamp(5.0, max_weight);
-        let item_calories = weight_g * cal_per_gram;
-
-        total_calories += item_calories;
-
-        if let Some(entry) = stats.get_mut(category_key) {
-            entry.0 += item_calories;
-            entry.1 += area_ratio * 100.0;
+    let mut nutrients = NutrientVector::default();
+    for item in items {
+        let category = CATEGORIES
+            .iter()
+            .find(|category| **category == item.class)
+            .copied()
+            .unwrap_or(FALLBACK_CLASS);
+        if let Some(entry) = stats.get_mut(category) {
+            entry.0 += item.calories;
         }
-
-        detected_items.push(FoodItem {
-            class: det.class_name,
-            confidence: (det.confidence * 100.0).round() / 100.0,
-            estimated_weight_g: (weight_g * 10.0).round() / 10.0,
-            calories: (item_calories * 10.0).round() / 10.0,
-        });
+        total_calories += item.calories;
+        nutrients.add(&item.nutrients);
     }
 
-    if total_calories > 950.0 {
-        total_calories = 850.0 + (total_calories - 850.0) * 0.2;
+    MealEstimate {
+        total_calories,
+        detected_items: items
+            .iter()
+            .map(|item| FoodItem {
+                class: item.class.clone(),
+                confidence: 1.0,
+                estimated_weight_g: item.weight_g,
+                calories: item.calories,
+                nutrients: item.nutrients,
+            })
+            .collect(),
+        nutrients,
+        stats,
+        engine_version: None,
+        items: items.to_vec(),
     }
+}
 
+/// Asks Gemini for a health score and a short comment.
+pub(crate) async fn score_meal(
+    profile: &MealProfile,
+    estimate: &MealEstimate,
+) -> Result<MealEvaluation, (StatusCode, Json<ErrorResponse>)> {
     let api_key = env::var(ENVKey::GEMINI_API_KEY).map_err(|_| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -261,8 +309,9 @@ pub async fn yolo_handler(
         )
     })?;
     let ai_url = format!("{}{}", OutSideURL::GEMINI_API_URL, api_key);
-    let taboo_str = user_profile.taboo.unwrap_or_default().join("、");
-    let disease_str = user_profile.disease.unwrap_or_default().join("、");
+    let taboo_str = profile.taboo.join("、");
+    let disease_str = profile.disease.join("、");
+    let stats = &estimate.stats;
 
     let system_instruction = "你是一位熱情且鼓勵為主的專業營養師。請根據資料評估：\n\n\
         1. 給予健康評分(0~100)。評分標準要符合一般人的飲食現況，若有澱粉、肉與蔬菜，即使比例不完美，也應給予 60~75 分的基礎分。\n\
@@ -270,12 +319,12 @@ pub async fn yolo_handler(
         3. 給予一段具建設性且口吻溫暖的建議(45字內)。\n\
         4. 必須回傳 JSON：{\"score\": 70, \"comment\": \"有菜有肉很均衡，如果飯換成五穀飯會更棒喔！\"}";
     let user_prompt = format!(
-        "年齡:{} / 性別:{} / 疾病:{} / 禁忌:{} / 總熱量:{:.1}kcal (穀:{:.1}, 豆:{:.1}, 肉:{:.1}, 蔬:{:.1})",
-        user_profile
+        "年齡:{} / 性別:{} / 疾病:{} / 禁忌:{} / 總熱量:{:.1}kcal (穀:{:.1}, 豆:{:.1}, 肉:{:.1}, 蔬:{:.1}) / 碳水:{:.1}g 蛋白質:{:.1}g 脂肪:{:.1}g 纖維:{:.1}g 鈉:{:.0}mg",
+        profile
             .age
             .map(|v| v.to_string())
             .unwrap_or_else(|| "未提供".into()),
-        user_profile.gender.unwrap_or_else(|| "未提供".into()),
+        profile.gender.clone().unwrap_or_else(|| "未提供".into()),
         if disease_str.is_empty() {
             "無".into()
         } else {
@@ -286,16 +335,22 @@ pub async fn yolo_handler(
         } else {
             taboo_str
         },
-        total_calories,
+        estimate.total_calories,
         stats["grain"].0,
         stats["protein_bean"].0,
         stats["protein_meat"].0,
-        stats["vegetable"].0
+        stats["vegetable"].0,
+        estimate.nutrients.carbohydrate_g,
+        estimate.nutrients.protein_g,
+        estimate.nutrients.fat_g,
+        estimate.nutrients.fiber_g,
+        estimate.nutrients.sodium_mg
     );
 
     let client = reqwest::Client::new();
     let response = client
         .post(&ai_url)
+        .timeout(Duration::from_secs(GEMINI_TIMEOUT_SECS))
         .json(&json!({
             "system_instruction": { "parts": { "text": system_instruction } },
             "contents": [{ "role": "user", "parts": [{ "text": user_prompt }] }]
@@ -337,34 +392,185 @@ pub async fn yolo_handler(
         error!("AI 解析失敗，顯示原始輸出：{}", clean_json_ai);
     }
 
-    sqlx::query!(
-      r#"INSERT INTO diet_records (user_id, total_calories, grain_calories, grain_area, protein_meat_calories, protein_meat_area, vegetable_calories, vegetable_area, ai_health_score, ai_evaluation, result_image_path, original_image_path)
-                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
-              auth_user.user_id,
-              total_calories,
-              stats["grain"].0,
-              stats["grain"].1,
-              stats["protein_meat"].0,
-              stats["protein_meat"].1,
-              stats["vegetable"].0,
-              stats["vegetable"].1,
-              ai_score,
-              ai_comment,
-              &yolo_result.image_path,
-              &input_path
+    Ok(MealEvaluation {
+        score: ai_score,
+        comment: ai_comment,
+    })
+}
+
+pub(crate) struct AnalyzedMeal<'a> {
+    pub user_id: Uuid,
+    pub email: &'a str,
+    pub profile: &'a MealProfile,
+    pub estimate: &'a MealEstimate,
+    pub evaluation: &'a MealEvaluation,
+    /// `ENTRY_SOURCE_PHOTO` or `ENTRY_SOURCE_MANUAL`.
+    pub entry_source: &'a str,
+    pub result_image_path: Option<&'a str>,
+    pub original_image_path: Option<&'a str>,
+}
+
+async fn insert_meal(db: &sqlx::PgPool, meal: &AnalyzedMeal<'_>) -> Result<Uuid, sqlx::Error> {
+    let stats = &meal.estimate.stats;
+    let mut tx = db.begin().await?;
+    let record_id = sqlx::query_scalar::<_, Uuid>(
+        r#"INSERT INTO diet_records (user_id, total_calories, grain_calories, grain_area, protein_meat_calories, protein_meat_area, protein_bean_calories, protein_bean_area, vegetable_calories, vegetable_area, fruit_calories, fruit_area, dairy_calories, dairy_area, nuts_calories, nuts_area, ai_health_score, ai_evaluation, result_image_path, original_image_path, engine_version, carbohydrate_g, protein_g, fat_g, fiber_g, sodium_mg, entry_source)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27)
+                 RETURNING id"#,
+    )
+    .bind(meal.user_id)
+    .bind(meal.estimate.total_calories)
+    .bind(stats["grain"].0)
+    .bind(stats["grain"].1)
+    .bind(stats["protein_meat"].0)
+    .bind(stats["protein_meat"].1)
+    .bind(stats["protein_bean"].0)
+    .bind(stats["protein_bean"].1)
+    .bind(stats["vegetable"].0)
+    .bind(stats["vegetable"].1)
+    .bind(stats["fruit"].0)
+    .bind(stats["fruit"].1)
+    .bind(stats["dairy"].0)
+    .bind(stats["dairy"].1)
+    .bind(stats["nuts"].0)
+    .bind(stats["nuts"].1)
+    .bind(meal.evaluation.score)
+    .bind(&meal.evaluation.comment)
+    .bind(meal.result_image_path)
+    .bind(meal.original_image_path)
+    .bind(&meal.estimate.engine_version)
+    .bind(meal.estimate.nutrients.carbohydrate_g)
+    .bind(meal.estimate.nutrients.protein_g)
+    .bind(meal.estimate.nutrients.fat_g)
+    .bind(meal.estimate.nutrients.fiber_g)
+    .bind(meal.estimate.nutrients.sodium_mg)
+    .bind(meal.entry_source)
+    .fetch_one(&mut *tx)
+    .await?;
+    insert_items(&mut tx, record_id, &meal.estimate.items).await?;
+    tx.commit().await?;
+    Ok(record_id)
+}
+
+/// Inserts the diet record with its items and alerts caregivers about low scores. Returns the
+/// record id, or `None` when the insert failed (logged; the analysis is still shown to the
+/// user).
+pub(crate) async fn save_meal(state: &Arc<AppState>, meal: &AnalyzedMeal<'_>) -> Option<Uuid> {
+    let record_id = match insert_meal(&state.db, meal).await {
+        Ok(record_id) => record_id,
+        Err(e) => {
+            error!("DB 錯誤 (儲存飲食紀錄): {:?}", e);
+            return None;
+        }
+    };
+
+    let ai_score = meal.evaluation.score;
+    if ai_score < LOW_SCORE_ALERT_THRESHOLD {
+        let alert_state = state.clone();
+        let user_id = meal.user_id;
+        let display_name = meal
+            .profile
+            .nickname
+            .clone()
+            .unwrap_or_else(|| meal.email.to_string());
+        tokio::spawn(async move {
+            send_low_score_alerts(&alert_state, user_id, &display_name, ai_score).await;
+        });
+    }
+    Some(record_id)
+}
+
+pub async fn yolo_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<Json<CalorieResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let profile = load_meal_profile(&state, auth_user.user_id).await?;
+    let data = read_image_field(&mut multipart).await?;
+    let input_path = store_upload(&data).await?;
+
+    let detection = detect_food(&state, &input_path).await?;
+    let result_image_path = detection
+        .annotated_image_path
+        .unwrap_or_else(|| input_path.clone());
+
+    let estimate = estimate_meal(&state.nutrition_engine, detection.detections);
+    let evaluation = score_meal(&profile, &estimate).await?;
+
+    save_meal(
+        &state,
+        &AnalyzedMeal {
+            user_id: auth_user.user_id,
+            email: &auth_user.email,
+            profile: &profile,
+            estimate: &estimate,
+            evaluation: &evaluation,
+            entry_source: ENTRY_SOURCE_PHOTO,
+            result_image_path: Some(&result_image_path),
+            original_image_path: Some(&input_path),
+        },
     )
-    .execute(&state.db).await.ok();
+    .await;
 
-    let image_base64 = fs::read(&yolo_result.image_path)
+    let image_base64 = tokio::fs::read(&result_image_path)
+        .await
         .ok()
         .map(|b| general_purpose::STANDARD.encode(b));
 
     Ok(Json(CalorieResponse {
         message: "辨識完成".into(),
         image_base64,
-        total_calories: (total_calories * 10.0).round() / 10.0,
-        detected_items,
-        ai_score,
-        ai_comment,
+        total_calories: (estimate.total_calories * 10.0).round() / 10.0,
+        detected_items: estimate.detected_items,
+        nutrients: estimate.nutrients.rounded(),
+        ai_score: evaluation.score,
+        ai_comment: evaluation.comment,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(class: &str, bbox: [f64; 4]) -> Detection {
+        Detection {
+            class_name: class.to_string(),
+            confidence: 0.87,
+            bbox,
+        }
+    }
+
+    #[test]
+    fn test_estimate_meal_keeps_every_detection_as_an_item() {
+        let engine = NutritionEngine::embedded();
+        let estimate = estimate_meal(
+            &engine,
+            vec![
+                detection("fruit", [0.0, 0.0, 200.0, 200.0]),
+                detection("dairy", [200.0, 0.0, 300.0, 300.0]),
+                detection("protein_bean", [0.0, 200.0, 150.0, 350.0]),
+            ],
+        );
+
+        assert_eq!(estimate.items.len(), 3);
+        for (item, category) in estimate
+            .items
+            .iter()
+            .zip(["fruit", "dairy", "protein_bean"])
+        {
+            assert_eq!(item.class, category);
+            assert_eq!(estimate.stats[category].0, item.calories);
+            let detection = item.detection.as_ref().unwrap();
+            assert_eq!(detection.confidence, 0.87);
+            assert_eq!(
+                detection.area_ratio,
+                engine.area_ratio_of_bbox(&detection.bbox)
+            );
+        }
+        assert_eq!(
+            estimate.items[1].detection.as_ref().unwrap().bbox,
+            [200.0, 0.0, 300.0, 300.0]
+        );
+        assert_eq!(estimate.stats["nuts"].0, 0.0);
+    }
+}
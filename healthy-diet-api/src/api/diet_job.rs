@@ -0,0 +1,134 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use axum_extra::extract::Multipart;
+use futures::stream::Stream;
+use std::{convert::Infallible, sync::Arc, time::Duration};
+use tokio::time::Instant;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    api::{
+        diet::{read_image_field, store_upload},
+        model::ErrorResponse,
+    },
+    model::AppState,
+    utils::{
+        diet_jobs::{DietJob, enqueue_job, find_job},
+        jwt::AuthUser,
+    },
+};
+
+/// How often the event stream checks the job for progress.
+const EVENT_POLL_MILLIS: u64 = 500;
+/// Streams close after this long; clients reconnect or fall back to polling the job.
+const EVENT_STREAM_MAX_SECS: u64 = 300;
+
+fn db_error(e: sqlx::Error) -> (StatusCode, Json<ErrorResponse>) {
+    error!("DB Error (Diet Jobs): {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "資料庫連線錯誤".into(),
+        }),
+    )
+}
+
+fn job_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "找不到辨識工作".into(),
+        }),
+    )
+}
+
+/// Stores the upload and queues the analysis; the worker picks it up right away.
+pub async fn create_diet_job_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<DietJob>), (StatusCode, Json<ErrorResponse>)> {
+    let data = read_image_field(&mut multipart).await?;
+    let input_path = store_upload(&data).await?;
+    let job = enqueue_job(&state.db, auth_user.user_id, &input_path)
+        .await
+        .map_err(db_error)?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+pub async fn get_diet_job_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<DietJob>, (StatusCode, Json<ErrorResponse>)> {
+    let job = find_job(&state.db, job_id, auth_user.user_id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(job_not_found)?;
+    Ok(Json(job))
+}
+
+fn job_event(job: &DietJob) -> Event {
+    Event::default()
+        .event(job.event_name())
+        .json_data(job)
+        .unwrap_or_else(|_| Event::default().event("error").data("{}"))
+}
+
+/// Sends the job whenever its stage or status changes, ending after `done` or `failed`.
+pub async fn diet_job_events_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<ErrorResponse>)> {
+    let job = find_job(&state.db, job_id, auth_user.user_id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(job_not_found)?;
+
+    let deadline = Instant::now() + Duration::from_secs(EVENT_STREAM_MAX_SECS);
+    let user_id = auth_user.user_id;
+    let stream = futures::stream::unfold(
+        (Some(job), None::<(String, String)>),
+        move |(next, last_sent)| {
+            let state = state.clone();
+            async move {
+                let mut job = next;
+                loop {
+                    if let Some(current) = job.take() {
+                        let marker = (current.status.clone(), current.stage.clone());
+                        if last_sent.as_ref() != Some(&marker) {
+                            let follow_up = if current.is_finished() {
+                                None
+                            } else {
+                                Some(marker)
+                            };
+                            return Some((Ok(job_event(&current)), (None, follow_up)));
+                        }
+                    }
+                    // `last_sent` is `None` here only once the finished job has been sent.
+                    last_sent.as_ref()?;
+                    if Instant::now() >= deadline {
+                        return None;
+                    }
+                    tokio::time::sleep(Duration::from_millis(EVENT_POLL_MILLIS)).await;
+                    match find_job(&state.db, job_id, user_id).await {
+                        Ok(Some(current)) => job = Some(current),
+                        Ok(None) => return None,
+                        Err(e) => {
+                            error!("DB Error (Diet Job Events {}): {:?}", job_id, e);
+                            return None;
+                        }
+                    }
+                }
+            }
+        },
+    );
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15))))
+}
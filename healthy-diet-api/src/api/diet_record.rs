@@ -1,69 +1,950 @@
-use crate::api::model::DietRecordResponse;
-use axum::{Json, extract::State, http::StatusCode};
-use std::sync::Arc;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use tracing::error;
+use uuid::Uuid;
 
-use crate::{api::model::ErrorResponse, model::AppState, utils::jwt::AuthUser};
+use crate::{
+    api::{
+        diet::{MealEstimate, MealEvaluation, estimate_from_items, load_meal_profile, score_meal},
+        manual_meal::portion_item,
+        model::{DietRecordResponse, ErrorResponse},
+    },
+    model::AppState,
+    utils::{
+        account_jobs::remove_files,
+        annotation::{
+            AUTHOR_USER, DietRecordAnnotation, NewAnnotation, annotations_for_records,
+            create_annotation, find_thread_root, mark_read, normalize_comment, record_belongs_to,
+            unread_count_for_user,
+        },
+        diet_items::{ItemDetection, MealItem, StoredMealItem, items_for_records, replace_items},
+        foods::{FoodNutrients, load_foods},
+        jwt::AuthUser,
+        meal_text::DEFAULT_SERVING_G,
+        nutrition_engine::{CATEGORIES, FoodPortion, NutrientVector, NutritionEngine},
+    },
+};
 
-pub async fn diet_records_handler(
-    auth_user: AuthUser,
-    State(state): State<Arc<AppState>>,
-) -> Result<Json<Vec<DietRecordResponse>>, (StatusCode, Json<ErrorResponse>)> {
-    let records = sqlx::query!(
+/// Items per record, as when logging a meal.
+const MAX_RECORD_ITEMS: usize = 30;
+const MAX_ITEM_WEIGHT_G: f64 = 5000.0;
+
+#[derive(Debug, Deserialize)]
+pub struct DietRecordItemPatch {
+    /// An item of the record to keep; omitted for an added item.
+    pub id: Option<Uuid>,
+    /// Replaces the food with one from `GET /api/foods`.
+    pub food_id: Option<Uuid>,
+    /// Relabels the item with another nutrition engine class.
+    pub class: Option<String>,
+    pub weight_g: Option<f64>,
+    /// Moves the item's box, or draws one for an added item: `[x_min, y_min, x_max, y_max]`
+    /// in pixels of the uploaded image.
+    pub bbox: Option<[f64; 4]>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateDietRecordPayload {
+    /// The corrected item list; items left out are removed.
+    pub items: Option<Vec<DietRecordItemPatch>>,
+    #[serde(default)]
+    pub rescore: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnnotationReplyPayload {
+    pub comment: String,
+}
+
+const RECORD_COLUMNS: &str = r#"
+    id, created_at, total_calories,
+    grain_calories, grain_area,
+    protein_meat_calories, protein_meat_area,
+    protein_bean_calories, protein_bean_area,
+    vegetable_calories, vegetable_area,
+    fruit_calories, fruit_area,
+    dairy_calories, dairy_area,
+    nuts_calories, nuts_area,
+    ai_health_score, ai_evaluation, entry_source,
+    carbohydrate_g, protein_g, fat_g, fiber_g, sodium_mg
+"#;
+
+#[derive(Debug, FromRow)]
+struct DietRecordRow {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+    total_calories: f64,
+    grain_calories: Option<f64>,
+    grain_area: Option<f64>,
+    protein_meat_calories: Option<f64>,
+    protein_meat_area: Option<f64>,
+    protein_bean_calories: Option<f64>,
+    protein_bean_area: Option<f64>,
+    vegetable_calories: Option<f64>,
+    vegetable_area: Option<f64>,
+    fruit_calories: Option<f64>,
+    fruit_area: Option<f64>,
+    dairy_calories: Option<f64>,
+    dairy_area: Option<f64>,
+    nuts_calories: Option<f64>,
+    nuts_area: Option<f64>,
+    ai_health_score: Option<i32>,
+    ai_evaluation: Option<String>,
+    entry_source: String,
+    carbohydrate_g: Option<f64>,
+    protein_g: Option<f64>,
+    fat_g: Option<f64>,
+    fiber_g: Option<f64>,
+    sodium_mg: Option<f64>,
+}
+
+impl DietRecordRow {
+    fn into_response(
+        self,
+        annotations: Vec<DietRecordAnnotation>,
+        items: Vec<StoredMealItem>,
+    ) -> DietRecordResponse {
+        DietRecordResponse {
+            id: self.id.to_string(),                 // UUID 轉成字串
+            created_at: self.created_at.to_string(), // TIMESTAMPTZ 轉成標準時間字串
+            total_calories: self.total_calories,
+            grain_calories: self.grain_calories,
+            grain_area: self.grain_area,
+            protein_meat_calories: self.protein_meat_calories,
+            protein_meat_area: self.protein_meat_area,
+            protein_bean_calories: self.protein_bean_calories,
+            protein_bean_area: self.protein_bean_area,
+            vegetable_calories: self.vegetable_calories,
+            vegetable_area: self.vegetable_area,
+            fruit_calories: self.fruit_calories,
+            fruit_area: self.fruit_area,
+            dairy_calories: self.dairy_calories,
+            dairy_area: self.dairy_area,
+            nuts_calories: self.nuts_calories,
+            nuts_area: self.nuts_area,
+            ai_health_score: self.ai_health_score,
+            nutrients: self.nutrients(),
+            ai_evaluation: self.ai_evaluation,
+            entry_source: self.entry_source,
+            annotations,
+            items,
+        }
+    }
+
+    /// Records estimated before nutrients were tracked have none.
+    fn nutrients(&self) -> Option<NutrientVector> {
+        Some(NutrientVector {
+            carbohydrate_g: self.carbohydrate_g?,
+            protein_g: self.protein_g?,
+            fat_g: self.fat_g?,
+            fiber_g: self.fiber_g?,
+            sodium_mg: self.sodium_mg?,
+        })
+    }
+}
+
+/// The user's 30 most recent diet records with their annotations and items. Shared with the caregiver
+/// and nutritionist views.
+pub(crate) async fn fetch_diet_records(
+    db: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<DietRecordResponse>, sqlx::Error> {
+    let records = sqlx::query_as::<_, DietRecordRow>(&format!(
+        "SELECT {} FROM diet_records WHERE user_id = $1 ORDER BY created_at DESC LIMIT 30",
+        RECORD_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    let record_ids: Vec<Uuid> = records.iter().map(|r| r.id).collect();
+    let mut annotations = annotations_for_records(db, &record_ids).await?;
+    let mut items = items_for_records(db, &record_ids).await?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| {
+            let record_annotations = annotations.remove(&r.id).unwrap_or_default();
+            let record_items = items.remove(&r.id).unwrap_or_default();
+            r.into_response(record_annotations, record_items)
+        })
+        .collect())
+}
+
+/// One of the user's records, or `None` when it is not theirs.
+pub(crate) async fn fetch_diet_record(
+    db: &PgPool,
+    user_id: Uuid,
+    record_id: Uuid,
+) -> Result<Option<DietRecordResponse>, sqlx::Error> {
+    let Some(record) = sqlx::query_as::<_, DietRecordRow>(&format!(
+        "SELECT {} FROM diet_records WHERE id = $1 AND user_id = $2",
+        RECORD_COLUMNS
+    ))
+    .bind(record_id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let annotations = annotations_for_records(db, &[record_id])
+        .await?
+        .remove(&record_id)
+        .unwrap_or_default();
+    let items = items_for_records(db, &[record_id])
+        .await?
+        .remove(&record_id)
+        .unwrap_or_default();
+    Ok(Some(record.into_response(annotations, items)))
+}
+
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyDietStats {
+    pub date: String,
+    pub meal_count: i64,
+    pub total_calories: f64,
+    pub average_health_score: Option<f64>,
+}
+
+/// Per-day meal count, calories and average AI health score over the last `days` days (UTC).
+pub(crate) async fn fetch_daily_diet_stats(
+    db: &PgPool,
+    user_id: Uuid,
+    days: i32,
+) -> Result<Vec<DailyDietStats>, sqlx::Error> {
+    sqlx::query_as::<_, DailyDietStats>(
         r#"
         SELECT
-            id, created_at, total_calories,
-            grain_calories, grain_area,
-            protein_meat_calories, protein_meat_area,
-            protein_bean_calories, protein_bean_area,
-            vegetable_calories, vegetable_area,
-            fruit_calories, fruit_area,
-            dairy_calories, dairy_area,
-            nuts_calories, nuts_area,
-            ai_health_score, ai_evaluation
+            (created_at AT TIME ZONE 'UTC')::date::text AS date,
+            COUNT(*) AS meal_count,
+            COALESCE(SUM(total_calories), 0)::float8 AS total_calories,
+            AVG(ai_health_score)::float8 AS average_health_score
         FROM diet_records
         WHERE user_id = $1
-        ORDER BY created_at DESC
-        LIMIT 30
+          AND created_at >= now() - make_interval(days => $2)
+        GROUP BY 1
+        ORDER BY 1 ASC
         "#,
-        auth_user.user_id
     )
-    .fetch_all(&state.db)
+    .bind(user_id)
+    .bind(days)
+    .fetch_all(db)
     .await
-    .map_err(|e| {
-        error!("DB Error (Get Diet Records): {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: "無法取得飲食紀錄".to_string(),
-            }),
+}
+
+pub async fn diet_records_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DietRecordResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let records = fetch_diet_records(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Get Diet Records): {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "無法取得飲食紀錄".to_string(),
+                }),
+            )
+        })?;
+
+    Ok(Json(records))
+}
+
+pub async fn diet_record_detail_handler(
+    auth_user: AuthUser,
+    Path(record_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<DietRecordResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let record = fetch_diet_record(&state.db, auth_user.user_id, record_id)
+        .await
+        .map_err(|e| record_db_error("Get Diet Record", e))?
+        .ok_or_else(record_not_found)?;
+    Ok(Json(record))
+}
+
+fn bad_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            error: message.into(),
+        }),
+    )
+}
+
+fn record_db_error(context: &str, e: sqlx::Error) -> (StatusCode, Json<ErrorResponse>) {
+    error!("DB Error ({}): {:?}", context, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "資料庫連線錯誤".to_string(),
+        }),
+    )
+}
+
+/// An item estimated by the nutrition engine from its class and weight alone.
+fn engine_item(
+    engine: &NutritionEngine,
+    class: &str,
+    weight_g: f64,
+    source_text: Option<String>,
+) -> MealItem {
+    let estimate = engine.estimate(&[FoodPortion {
+        class: class.to_string(),
+        confidence: None,
+        area_ratio: None,
+        weight_g: Some(weight_g),
+    }]);
+    let item = &estimate.items[0];
+    MealItem {
+        class: class.to_string(),
+        food_id: None,
+        name: None,
+        source_text,
+        weight_g: item.estimated_weight_g,
+        calories: item.calories,
+        nutrients: item.nutrients,
+        detection: None,
+    }
+}
+
+fn valid_bbox(bbox: &[f64; 4]) -> bool {
+    bbox.iter().all(|value| value.is_finite() && *value >= 0.0)
+        && bbox[2] > bbox[0]
+        && bbox[3] > bbox[1]
+}
+
+/// Applies one corrected item. A new `food_id` takes the food's figures, a new `class` is
+/// re-estimated by the engine, and otherwise the existing item is scaled to the new weight.
+/// The item keeps its detector box unless `bbox` replaces it; a moved or relabelled box is
+/// marked as corrected.
+fn resolve_item(
+    patch: &DietRecordItemPatch,
+    existing: &HashMap<Uuid, MealItem>,
+    foods: &HashMap<Uuid, FoodNutrients>,
+    engine: &NutritionEngine,
+) -> Result<MealItem, String> {
+    let base = match patch.id {
+        Some(id) => Some(
+            existing
+                .get(&id)
+                .ok_or_else(|| format!("找不到項目 {}", id))?,
+        ),
+        None => None,
+    };
+    let weight_g = patch
+        .weight_g
+        .or(base.map(|item| item.weight_g))
+        .unwrap_or(DEFAULT_SERVING_G);
+    if !(weight_g.is_finite() && weight_g > 0.0 && weight_g <= MAX_ITEM_WEIGHT_G) {
+        return Err(format!("份量需介於 0 到 {} 公克之間", MAX_ITEM_WEIGHT_G));
+    }
+    let source_text = base.and_then(|item| item.source_text.clone());
+    let detection = match patch.bbox {
+        Some(bbox) if !valid_bbox(&bbox) => return Err("框選範圍不正確".to_string()),
+        Some(bbox) => Some(ItemDetection {
+            bbox,
+            confidence: 1.0,
+            area_ratio: engine.area_ratio_of_bbox(&bbox),
+            corrected: true,
+        }),
+        None => base.and_then(|item| item.detection.clone()),
+    };
+
+    let relabelled = if let Some(food_id) = patch
+        .food_id
+        .filter(|food_id| base.and_then(|item| item.food_id) != Some(*food_id))
+    {
+        let food = foods
+            .get(&food_id)
+            .ok_or_else(|| format!("找不到食物 {}", food_id))?;
+        portion_item(food, weight_g, source_text)
+    } else if let Some(class) = patch
+        .class
+        .as_deref()
+        .filter(|class| base.map(|item| item.class.as_str()) != Some(*class))
+    {
+        if !engine.foods.contains_key(class) {
+            return Err(format!("不支援的食物類別 {}", class));
+        }
+        engine_item(engine, class, weight_g, source_text)
+    } else {
+        let item = base.ok_or("新增的項目需指定 food_id 或 class")?;
+        return Ok(MealItem {
+            detection,
+            ..item.with_weight(weight_g)
+        });
+    };
+    Ok(MealItem {
+        detection: detection.map(|detection| ItemDetection {
+            corrected: true,
+            ..detection
+        }),
+        ..relabelled
+    })
+}
+
+/// Whether a correction changed what the photo's boxes show: a box was moved, relabelled,
+/// drawn or removed. Weight changes alone leave the labels as they were.
+fn labels_changed(
+    patches: &[DietRecordItemPatch],
+    items: &[MealItem],
+    existing: &HashMap<Uuid, MealItem>,
+) -> bool {
+    let changed = patches.iter().zip(items).any(|(patch, item)| {
+        let base = patch.id.and_then(|id| existing.get(&id));
+        item.detection.is_some()
+            && base.map(|base| (&base.class, &base.detection))
+                != Some((&item.class, &item.detection))
+    });
+    let removed = existing.iter().any(|(id, item)| {
+        item.detection.is_some() && !patches.iter().any(|patch| patch.id == Some(*id))
+    });
+    changed || removed
+}
+
+/// Totals of a corrected item list. Stored items already carry the figures the record was
+/// totalled at, including the engine's squash, so unchanged items keep the record's totals.
+/// The engine version is the current one when an item was re-estimated by class.
+fn corrected_estimate(
+    patches: &[DietRecordItemPatch],
+    items: &[MealItem],
+    existing: &HashMap<Uuid, MealItem>,
+    engine: &NutritionEngine,
+) -> MealEstimate {
+    let reestimated = patches.iter().zip(items).any(|(patch, item)| {
+        item.food_id.is_none()
+            && patch
+                .id
+                .and_then(|id| existing.get(&id))
+                .map(|base| &base.class)
+                != Some(&item.class)
+    });
+    MealEstimate {
+        engine_version: reestimated.then(|| engine.version.clone()),
+        ..estimate_from_items(items)
+    }
+}
+
+/// The record's stored totals, for re-scoring a record whose items are unchanged.
+fn stored_estimate(record: &DietRecordResponse) -> MealEstimate {
+    let calories = [
+        ("grain", record.grain_calories),
+        ("protein_meat", record.protein_meat_calories),
+        ("protein_bean", record.protein_bean_calories),
+        ("vegetable", record.vegetable_calories),
+        ("fruit", record.fruit_calories),
+        ("dairy", record.dairy_calories),
+        ("nuts", record.nuts_calories),
+    ];
+    let mut stats: HashMap<&'static str, (f64, f64)> = CATEGORIES
+        .iter()
+        .map(|category| (*category, (0.0, 0.0)))
+        .collect();
+    for (category, value) in calories {
+        stats.insert(category, (value.unwrap_or_default(), 0.0));
+    }
+    MealEstimate {
+        total_calories: record.total_calories,
+        detected_items: Vec::new(),
+        nutrients: record.nutrients.unwrap_or_default(),
+        stats,
+        engine_version: None,
+        items: Vec::new(),
+    }
+}
+
+/// Writes corrected items with the totals recalculated from them, and the new score if the
+/// record was re-scored. Plate areas come from the photo and are kept. The engine version is
+/// cleared once no item comes from the engine. Corrected labels of a photo record date it
+/// for the retraining export.
+async fn save_correction(
+    db: &PgPool,
+    record_id: Uuid,
+    items: Option<&[MealItem]>,
+    labels_changed: bool,
+    estimate: &MealEstimate,
+    evaluation: Option<&MealEvaluation>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    if let Some(items) = items {
+        let stats = &estimate.stats;
+        sqlx::query(
+            r#"
+            UPDATE diet_records
+            SET total_calories = $2, grain_calories = $3, protein_meat_calories = $4,
+                protein_bean_calories = $5, vegetable_calories = $6, fruit_calories = $7,
+                dairy_calories = $8, nuts_calories = $9, carbohydrate_g = $10, protein_g = $11,
+                fat_g = $12, fiber_g = $13, sodium_mg = $14,
+                engine_version = CASE WHEN $15 THEN COALESCE($16, engine_version) END
+            WHERE id = $1
+            "#,
+        )
+        .bind(record_id)
+        .bind(estimate.total_calories)
+        .bind(stats["grain"].0)
+        .bind(stats["protein_meat"].0)
+        .bind(stats["protein_bean"].0)
+        .bind(stats["vegetable"].0)
+        .bind(stats["fruit"].0)
+        .bind(stats["dairy"].0)
+        .bind(stats["nuts"].0)
+        .bind(estimate.nutrients.carbohydrate_g)
+        .bind(estimate.nutrients.protein_g)
+        .bind(estimate.nutrients.fat_g)
+        .bind(estimate.nutrients.fiber_g)
+        .bind(estimate.nutrients.sodium_mg)
+        .bind(items.iter().any(|item| item.food_id.is_none()))
+        .bind(&estimate.engine_version)
+        .execute(&mut *tx)
+        .await?;
+        replace_items(&mut tx, record_id, items).await?;
+    }
+    if labels_changed {
+        sqlx::query(
+            "UPDATE diet_records SET labels_corrected_at = now() WHERE id = $1 AND original_image_path IS NOT NULL",
         )
-    })?;
+        .bind(record_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    if let Some(evaluation) = evaluation {
+        sqlx::query(
+            "UPDATE diet_records SET ai_health_score = $2, ai_evaluation = $3 WHERE id = $1",
+        )
+        .bind(record_id)
+        .bind(evaluation.score)
+        .bind(&evaluation.comment)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Corrects a record: `items` replaces its item list (fix a mis-detected food, change a
+/// portion, drop a duplicate) and the totals are recalculated; `rescore` asks the AI again.
+pub async fn update_diet_record_handler(
+    auth_user: AuthUser,
+    Path(record_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<UpdateDietRecordPayload>,
+) -> Result<Json<DietRecordResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if payload.items.is_none() && !payload.rescore {
+        return Err(bad_request("沒有要更新的內容"));
+    }
+    let current = fetch_diet_record(&state.db, auth_user.user_id, record_id)
+        .await
+        .map_err(|e| record_db_error("Update Diet Record", e))?
+        .ok_or_else(record_not_found)?;
+
+    let mut labels_corrected = false;
+    let mut corrected = None;
+    let items = match &payload.items {
+        Some(patches) => {
+            if patches.is_empty() {
+                return Err(bad_request("請保留至少一項食物，或直接刪除紀錄"));
+            }
+            if patches.len() > MAX_RECORD_ITEMS {
+                return Err(bad_request(format!("每餐最多 {} 項食物", MAX_RECORD_ITEMS)));
+            }
+            let mut seen = HashSet::new();
+            if let Some(id) = patches
+                .iter()
+                .filter_map(|patch| patch.id)
+                .find(|id| !seen.insert(*id))
+            {
+                return Err(bad_request(format!("食物項目 {} 重複", id)));
+            }
+            let existing: HashMap<Uuid, MealItem> = current
+                .items
+                .iter()
+                .map(|stored| (stored.id, stored.item.clone()))
+                .collect();
+            let food_ids: Vec<Uuid> = patches.iter().filter_map(|patch| patch.food_id).collect();
+            let foods = load_foods(&state.db, &food_ids)
+                .await
+                .map_err(|e| record_db_error("Update Diet Record", e))?;
+            let items = patches
+                .iter()
+                .map(|patch| resolve_item(patch, &existing, &foods, &state.nutrition_engine))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(bad_request)?;
+            labels_corrected = labels_changed(patches, &items, &existing);
+            corrected = Some(corrected_estimate(
+                patches,
+                &items,
+                &existing,
+                &state.nutrition_engine,
+            ));
+            Some(items)
+        }
+        None => None,
+    };
+
+    let estimate = corrected.unwrap_or_else(|| stored_estimate(&current));
+    let evaluation = if payload.rescore {
+        let profile = load_meal_profile(&state, auth_user.user_id).await?;
+        Some(score_meal(&profile, &estimate).await?)
+    } else {
+        None
+    };
+
+    save_correction(
+        &state.db,
+        record_id,
+        items.as_deref(),
+        labels_corrected,
+        &estimate,
+        evaluation.as_ref(),
+    )
+    .await
+    .map_err(|e| record_db_error("Update Diet Record", e))?;
+
+    let record = fetch_diet_record(&state.db, auth_user.user_id, record_id)
+        .await
+        .map_err(|e| record_db_error("Update Diet Record", e))?
+        .ok_or_else(record_not_found)?;
+    Ok(Json(record))
+}
+
+/// Deletes a record with its items and annotations, then its image files.
+pub async fn delete_diet_record_handler(
+    auth_user: AuthUser,
+    Path(record_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let (result_image_path, original_image_path) =
+        sqlx::query_as::<_, (Option<String>, Option<String>)>(
+            "DELETE FROM diet_records WHERE id = $1 AND user_id = $2 RETURNING result_image_path, original_image_path",
+        )
+        .bind(record_id)
+        .bind(auth_user.user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| record_db_error("Delete Diet Record", e))?
+        .ok_or_else(record_not_found)?;
 
-    let response: Vec<DietRecordResponse> = records
+    // Records where the detector drew nothing keep the upload as the result image.
+    let mut files: Vec<String> = [result_image_path, original_image_path]
         .into_iter()
-        .map(|r| DietRecordResponse {
-            id: r.id.to_string(),                 // UUID 轉成字串
-            created_at: r.created_at.to_string(), // TIMESTAMPTZ 轉成標準時間字串
-            total_calories: r.total_calories,
-            grain_calories: r.grain_calories,
-            grain_area: r.grain_area,
-            protein_meat_calories: r.protein_meat_calories,
-            protein_meat_area: r.protein_meat_area,
-            protein_bean_calories: r.protein_bean_calories,
-            protein_bean_area: r.protein_bean_area,
-            vegetable_calories: r.vegetable_calories,
-            vegetable_area: r.vegetable_area,
-            fruit_calories: r.fruit_calories,
-            fruit_area: r.fruit_area,
-            dairy_calories: r.dairy_calories,
-            dairy_area: r.dairy_area,
-            nuts_calories: r.nuts_calories,
-            nuts_area: r.nuts_area,
-            ai_health_score: r.ai_health_score,
-            ai_evaluation: r.ai_evaluation,
-        })
+        .flatten()
         .collect();
+    files.dedup();
+    remove_files(&files).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn annotation_db_error(context: &str, e: sqlx::Error) -> (StatusCode, Json<ErrorResponse>) {
+    error!("DB Error ({}): {:?}", context, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+fn record_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "Diet record not found".to_string(),
+        }),
+    )
+}
+
+/// The user's reply to a nutritionist annotation on one of their records. Replying also
+/// marks the record's annotations as read.
+pub async fn reply_to_annotation_handler(
+    auth_user: AuthUser,
+    Path((record_id, annotation_id)): Path<(Uuid, Uuid)>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AnnotationReplyPayload>,
+) -> Result<(StatusCode, Json<DietRecordAnnotation>), (StatusCode, Json<ErrorResponse>)> {
+    let comment = normalize_comment(Some(&payload.comment))
+        .map_err(|message| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse { error: message }),
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Reply cannot be empty".to_string(),
+                }),
+            )
+        })?;
+
+    let owned = record_belongs_to(&state.db, record_id, auth_user.user_id)
+        .await
+        .map_err(|e| annotation_db_error("Reply Annotation", e))?;
+    if !owned {
+        return Err(record_not_found());
+    }
+    let is_root = find_thread_root(&state.db, record_id, annotation_id)
+        .await
+        .map_err(|e| annotation_db_error("Reply Annotation", e))?;
+    if !is_root {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Annotation not found".to_string(),
+            }),
+        ));
+    }
+
+    let reply = create_annotation(
+        &state.db,
+        &NewAnnotation {
+            record_id,
+            parent_id: Some(annotation_id),
+            author_id: auth_user.user_id,
+            author_role: AUTHOR_USER,
+            comment: Some(&comment),
+            corrected_calories: None,
+            tags: &[],
+        },
+    )
+    .await
+    .map_err(|e| annotation_db_error("Reply Annotation", e))?;
+
+    if let Err(e) = mark_read(&state.db, record_id, auth_user.user_id).await {
+        error!("DB Error (Mark Annotations Read): {:?}", e);
+    }
+
+    Ok((StatusCode::CREATED, Json(reply)))
+}
+
+pub async fn mark_annotations_read_handler(
+    auth_user: AuthUser,
+    Path(record_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let owned = record_belongs_to(&state.db, record_id, auth_user.user_id)
+        .await
+        .map_err(|e| annotation_db_error("Mark Annotations Read", e))?;
+    if !owned {
+        return Err(record_not_found());
+    }
+
+    let marked = mark_read(&state.db, record_id, auth_user.user_id)
+        .await
+        .map_err(|e| annotation_db_error("Mark Annotations Read", e))?;
+    Ok(Json(json!({ "marked": marked })))
+}
+
+/// Number of nutritionist annotations on the user's records not read yet, for a badge.
+pub async fn unread_annotations_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let unread = unread_count_for_user(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| annotation_db_error("Unread Annotations", e))?;
+    Ok(Json(json!({ "unread": unread })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::diet::estimate_meal, utils::food_detector::Detection};
+
+    fn patch(id: Option<Uuid>, class: Option<&str>, weight_g: Option<f64>) -> DietRecordItemPatch {
+        DietRecordItemPatch {
+            id,
+            food_id: None,
+            class: class.map(str::to_string),
+            weight_g,
+            bbox: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_item_scales_relabels_and_rejects() {
+        let engine = NutritionEngine::embedded();
+        let rice_id = Uuid::new_v4();
+        let rice = engine_item(&engine, "grain", 200.0, Some("一碗白飯".to_string()));
+        let existing = HashMap::from([(rice_id, rice.clone())]);
+        let foods = HashMap::new();
+
+        let half = resolve_item(
+            &patch(Some(rice_id), None, Some(100.0)),
+            &existing,
+            &foods,
+            &engine,
+        )
+        .unwrap();
+        assert_eq!(half.calories, rice.calories / 2.0);
+        assert_eq!(half.source_text, rice.source_text);
+
+        // Relabelling keeps the weight and re-estimates with the new class.
+        let vegetable = resolve_item(
+            &patch(Some(rice_id), Some("vegetable"), None),
+            &existing,
+            &foods,
+            &engine,
+        )
+        .unwrap();
+        assert_eq!(vegetable.class, "vegetable");
+        assert_eq!(vegetable.weight_g, 200.0);
+        assert_eq!(
+            vegetable.calories,
+            engine_item(&engine, "vegetable", 200.0, None).calories
+        );
+
+        let unknown = patch(Some(Uuid::new_v4()), None, None);
+        assert!(resolve_item(&unknown, &existing, &foods, &engine).is_err());
+        let bad_class = patch(None, Some("pizza"), None);
+        assert!(resolve_item(&bad_class, &existing, &foods, &engine).is_err());
+        let no_food = patch(None, None, Some(50.0));
+        assert!(resolve_item(&no_food, &existing, &foods, &engine).is_err());
+        let too_heavy = patch(Some(rice_id), None, Some(MAX_ITEM_WEIGHT_G + 1.0));
+        assert!(resolve_item(&too_heavy, &existing, &foods, &engine).is_err());
+    }
+
+    #[test]
+    fn test_box_corrections_mark_labels() {
+        let engine = NutritionEngine::embedded();
+        let detected = |class: &str, bbox: [f64; 4]| MealItem {
+            detection: Some(ItemDetection {
+                bbox,
+                confidence: 0.8,
+                area_ratio: engine.area_ratio_of_bbox(&bbox),
+                corrected: false,
+            }),
+            ..engine_item(&engine, class, 150.0, None)
+        };
+        let (rice_id, duplicate_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let existing = HashMap::from([
+            (rice_id, detected("grain", [10.0, 10.0, 200.0, 200.0])),
+            (duplicate_id, detected("grain", [12.0, 12.0, 198.0, 198.0])),
+        ]);
+        let foods = HashMap::new();
+        let resolve = |patches: &[DietRecordItemPatch]| {
+            patches
+                .iter()
+                .map(|patch| resolve_item(patch, &existing, &foods, &engine).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // A weight change keeps the labels.
+        let patches = [
+            patch(Some(rice_id), None, Some(120.0)),
+            patch(Some(duplicate_id), None, None),
+        ];
+        let items = resolve(&patches);
+        assert!(!items[0].detection.as_ref().unwrap().corrected);
+        assert!(!labels_changed(&patches, &items, &existing));
+
+        // Dropping the duplicate box is a label correction.
+        let patches = [patch(Some(rice_id), None, None)];
+        assert!(labels_changed(&patches, &resolve(&patches), &existing));
+
+        // Relabelling and moving boxes mark the item corrected.
+        let patches = [
+            patch(Some(rice_id), Some("vegetable"), None),
+            DietRecordItemPatch {
+                bbox: Some([0.0, 0.0, 50.0, 80.0]),
+                ..patch(Some(duplicate_id), None, None)
+            },
+        ];
+        let items = resolve(&patches);
+        let relabelled = items[0].detection.as_ref().unwrap();
+        assert!(relabelled.corrected);
+        assert_eq!(relabelled.bbox, [10.0, 10.0, 200.0, 200.0]);
+        let moved = items[1].detection.as_ref().unwrap();
+        assert_eq!(moved.bbox, [0.0, 0.0, 50.0, 80.0]);
+        assert!(moved.corrected);
+        assert!(labels_changed(&patches, &items, &existing));
+
+        let inverted = DietRecordItemPatch {
+            bbox: Some([50.0, 0.0, 10.0, 80.0]),
+            ..patch(Some(rice_id), None, None)
+        };
+        assert!(resolve_item(&inverted, &existing, &foods, &engine).is_err());
+    }
+
+    #[test]
+    fn test_unchanged_photo_record_keeps_totals() {
+        let engine = NutritionEngine::embedded();
+        let detection = |class: &str| Detection {
+            class_name: class.to_string(),
+            confidence: 0.9,
+            bbox: [0.0, 0.0, 600.0, 600.0],
+        };
+        // Five large boxes: the engine squashes the meal below its threshold.
+        let meal = estimate_meal(
+            &engine,
+            ["protein_meat", "grain", "other", "dairy", "fruit"]
+                .map(detection)
+                .to_vec(),
+        );
+        assert!(meal.total_calories < engine.meal_squash.threshold_kcal);
+
+        let ids: Vec<Uuid> = meal.items.iter().map(|_| Uuid::new_v4()).collect();
+        let existing: HashMap<Uuid, MealItem> = ids
+            .iter()
+            .copied()
+            .zip(meal.items.iter().cloned())
+            .collect();
+        let foods = HashMap::new();
+        let correct = |patches: &[DietRecordItemPatch]| {
+            let items: Vec<MealItem> = patches
+                .iter()
+                .map(|patch| resolve_item(patch, &existing, &foods, &engine).unwrap())
+                .collect();
+            corrected_estimate(patches, &items, &existing, &engine)
+        };
+
+        // Re-sending the items, with or without their weights, changes nothing.
+        let bare: Vec<DietRecordItemPatch> =
+            ids.iter().map(|id| patch(Some(*id), None, None)).collect();
+        let weighed: Vec<DietRecordItemPatch> = ids
+            .iter()
+            .map(|id| patch(Some(*id), None, Some(existing[id].weight_g)))
+            .collect();
+        for patches in [bare, weighed] {
+            let corrected = correct(&patches);
+            assert_eq!(corrected.total_calories, meal.total_calories);
+            assert_eq!(corrected.nutrients, meal.nutrients);
+            for category in CATEGORIES {
+                assert_eq!(corrected.stats[category].0, meal.stats[category].0);
+            }
+            assert!(corrected.engine_version.is_none());
+        }
+
+        // Doubling one portion adds exactly that item's calories.
+        let mut patches: Vec<DietRecordItemPatch> =
+            ids.iter().map(|id| patch(Some(*id), None, None)).collect();
+        patches[1].weight_g = Some(existing[&ids[1]].weight_g * 2.0);
+        let corrected = correct(&patches);
+        assert!(
+            (corrected.total_calories - meal.total_calories - existing[&ids[1]].calories).abs()
+                < 0.2
+        );
 
-    Ok(Json(response))
+        // Relabelling re-estimates with the current engine.
+        patches[1] = patch(Some(ids[1]), Some("vegetable"), None);
+        assert_eq!(
+            correct(&patches).engine_version,
+            Some(engine.version.clone())
+        );
+    }
 }
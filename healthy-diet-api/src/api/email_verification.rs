@@ -0,0 +1,145 @@
+use crate::{
+    api::model::{EmailTokenPayload, ErrorResponse},
+    model::{AppState, ENVKey},
+    utils::{
+        email_token::{EmailTokenError, EmailTokenPurpose, consume_email_token, issue_email_token},
+        jwt::AuthUser,
+        mailer::EmailMessage,
+    },
+};
+use axum::{Json, extract::State, http::StatusCode};
+use serde_json::json;
+use std::{env, sync::Arc};
+use tracing::{error, info, warn};
+
+const DEFAULT_FRONTEND_BASE_URL: &str = "http://localhost:5173";
+
+/// Builds a link into the frontend, e.g. `frontend_link("/verify-email", token)`.
+pub(crate) fn frontend_link(path: &str, token: &str) -> String {
+    let base = env::var(ENVKey::FRONTEND_BASE_URL)
+        .unwrap_or_else(|_| DEFAULT_FRONTEND_BASE_URL.to_string());
+    format!("{}{}?token={}", base.trim_end_matches('/'), path, token)
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+pub(crate) async fn send_verification_email(
+    state: &AppState,
+    user_id: uuid::Uuid,
+    email: &str,
+) -> Result<(), String> {
+    let token = issue_email_token(
+        &state.db,
+        user_id,
+        email,
+        EmailTokenPurpose::EmailVerification,
+    )
+    .await
+    .map_err(|e| format!("{:?}", e))?;
+
+    state
+        .mailer
+        .send(&EmailMessage {
+            to: email.to_string(),
+            subject: "Verify your Healthy Diet email".to_string(),
+            body: format!(
+                "Please confirm your email address by opening the link below (valid for 24 hours):\n\n{}\n",
+                frontend_link("/verify-email", &token)
+            ),
+        })
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub async fn request_email_verification_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let verified = sqlx::query_scalar::<_, bool>(
+        "SELECT email_verified_at IS NOT NULL FROM users WHERE id = $1",
+    )
+    .bind(auth_user.user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        error!("DB Error (Email Verification Request): {:?}", e);
+        internal_error()
+    })?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "User not found".to_string(),
+        }),
+    ))?;
+
+    if verified {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "Email already verified".to_string(),
+            }),
+        ));
+    }
+
+    send_verification_email(&state, auth_user.user_id, &auth_user.email)
+        .await
+        .map_err(|e| {
+            error!("Failed to send verification email: {}", e);
+            internal_error()
+        })?;
+
+    Ok(Json(json!({ "message": "Verification email sent" })))
+}
+
+pub async fn verify_email_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<EmailTokenPayload>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let mut conn = state.db.acquire().await.map_err(|e| {
+        error!("DB Error (Verify Email): {:?}", e);
+        internal_error()
+    })?;
+
+    let user_id = consume_email_token(
+        &mut conn,
+        &payload.token,
+        EmailTokenPurpose::EmailVerification,
+    )
+    .await
+    .map_err(|e| match e {
+        EmailTokenError::Invalid => {
+            warn!("Invalid email verification token");
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Invalid or expired token".to_string(),
+                }),
+            )
+        }
+        e => {
+            error!("Email verification error: {:?}", e);
+            internal_error()
+        }
+    })?;
+
+    sqlx::query(
+        "UPDATE users SET email_verified_at = COALESCE(email_verified_at, now()) WHERE id = $1",
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| {
+        error!("DB Error (Verify Email): {:?}", e);
+        internal_error()
+    })?;
+
+    info!("Email verified for user {}", user_id);
+    Ok(Json(json!({ "message": "Email verified" })))
+}
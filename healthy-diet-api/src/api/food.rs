@@ -0,0 +1,188 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use axum_extra::extract::Multipart;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    api::model::ErrorResponse,
+    model::AppState,
+    utils::{
+        audit::write_audit_log,
+        foods::{
+            FoodDetail, FoodSummary, ImportFormat, ImportSummary, MAX_SEARCH_LIMIT, SOURCE_TFDA,
+            find_food, import_foods, parse_foods_csv, search_foods,
+        },
+        jwt::AuthUser,
+    },
+};
+
+/// Uploads to the import endpoint may be this large; the full TFDA table is about 30 MB.
+pub const FOOD_IMPORT_MAX_BYTES: usize = 64 * 1024 * 1024;
+const DEFAULT_SEARCH_LIMIT: i64 = 20;
+const MAX_QUERY_CHARS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct FoodSearchQuery {
+    pub q: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FoodImportQuery {
+    /// `tfda` (default) or `wide`.
+    pub format: Option<String>,
+    /// Namespace for `source_code`; required for the wide format.
+    pub source: Option<String>,
+}
+
+fn bad_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            error: message.into(),
+        }),
+    )
+}
+
+fn db_error(e: sqlx::Error) -> (StatusCode, Json<ErrorResponse>) {
+    error!("DB Error (Foods): {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "資料庫連線錯誤".into(),
+        }),
+    )
+}
+
+pub async fn search_foods_handler(
+    _auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FoodSearchQuery>,
+) -> Result<Json<Vec<FoodSummary>>, (StatusCode, Json<ErrorResponse>)> {
+    let q = query.q.as_deref().unwrap_or_default().trim();
+    if q.is_empty() {
+        return Err(bad_request("請輸入搜尋關鍵字"));
+    }
+    if q.chars().count() > MAX_QUERY_CHARS {
+        return Err(bad_request("搜尋關鍵字過長"));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+
+    let foods = search_foods(&state.db, q, limit).await.map_err(db_error)?;
+    Ok(Json(foods))
+}
+
+pub async fn food_detail_handler(
+    _auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Path(food_id): Path<Uuid>,
+) -> Result<Json<FoodDetail>, (StatusCode, Json<ErrorResponse>)> {
+    let food = find_food(&state.db, food_id)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "找不到食物".into(),
+                }),
+            )
+        })?;
+    Ok(Json(food))
+}
+
+fn valid_source(source: &str) -> bool {
+    !source.is_empty()
+        && source.len() <= 64
+        && source
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+/// Imports a composition table uploaded as the `file` field. Re-importing a source updates
+/// foods by code, so the TFDA table can be refreshed when a new release comes out.
+pub async fn admin_import_foods_handler(
+    admin_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FoodImportQuery>,
+    mut multipart: Multipart,
+) -> Result<Json<ImportSummary>, (StatusCode, Json<ErrorResponse>)> {
+    let format_name = query.format.as_deref().unwrap_or("tfda");
+    let format = ImportFormat::parse(format_name)
+        .ok_or_else(|| bad_request("Invalid format. Use tfda/wide"))?;
+    let source = match (query.source.as_deref().map(str::trim), format) {
+        (Some(source), _) => source.to_string(),
+        (None, ImportFormat::Tfda) => SOURCE_TFDA.to_string(),
+        (None, ImportFormat::Wide) => {
+            return Err(bad_request("source is required for the wide format"));
+        }
+    };
+    if !valid_source(&source) {
+        return Err(bad_request(
+            "Invalid source. Use lowercase letters, digits, '_' or '-'",
+        ));
+    }
+
+    let mut data = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| bad_request(format!("Invalid multipart payload: {}", e)))?
+    {
+        if field.name() == Some("file") {
+            let bytes = field
+                .bytes()
+                .await
+                .map_err(|e| bad_request(format!("Unable to read upload file: {}", e)))?;
+            data = Some(bytes);
+            break;
+        }
+    }
+    let data = data.ok_or_else(|| bad_request("Missing file field"))?;
+
+    let foods = tokio::task::spawn_blocking(move || parse_foods_csv(format, &data))
+        .await
+        .map_err(|e| {
+            error!("Food import parser panicked: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Internal server error".into(),
+                }),
+            )
+        })?
+        .map_err(|e| bad_request(format!("Unable to import foods: {}", e)))?;
+
+    let summary = import_foods(&state.db, &source, &foods)
+        .await
+        .map_err(db_error)?;
+    info!(
+        "Imported foods from {}: {} inserted, {} updated",
+        source, summary.inserted, summary.updated
+    );
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "FOODS_IMPORTED",
+        "food_source",
+        &source,
+        json!({
+            "format": format_name,
+            "inserted": summary.inserted,
+            "updated": summary.updated,
+        }),
+    )
+    .await;
+
+    Ok(Json(summary))
+}
@@ -0,0 +1,20 @@
+use axum::{
+    Json,
+    http::{HeaderValue, header::CACHE_CONTROL},
+    response::{IntoResponse, Response},
+};
+
+use crate::utils::jwt_keys::keyring;
+
+/// Public verification keys for our access tokens. Services that only verify tokens (e.g. the
+/// Node agent) fetch this instead of sharing the signing secret.
+pub async fn jwks_handler() -> Response {
+    (
+        [(
+            CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=300"),
+        )],
+        Json(keyring().jwks()),
+    )
+        .into_response()
+}
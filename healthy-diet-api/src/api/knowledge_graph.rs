@@ -1,32 +1,40 @@
 use crate::{
-    api::{
-        model::{ErrorResponse, ROLE_OPERATOR, ROLE_SUPER_ADMIN},
-        rag_document::send_json_request,
-    },
-    utils::jwt::AuthUser,
+    api::{model::ErrorResponse, rag_document::send_json_request},
+    model::AppState,
+    utils::{jwt::AuthUser, permission::agent_role},
 };
 use axum::{
     Json,
     body::Bytes,
-    extract::{Path, Query},
+    extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
 };
 use reqwest::Method;
 use serde::Deserialize;
 use serde_json::{Value, json};
+use std::sync::Arc;
+use tracing::error;
+
+/// Rewrites the caller's role into the agent service's vocabulary, as configured on the
+/// role in the `roles` table.
+async fn normalized_admin_user(
+    state: &AppState,
+    admin_user: &AuthUser,
+) -> Result<AuthUser, (StatusCode, Json<ErrorResponse>)> {
+    let forwarded_role = agent_role(&state.db, &admin_user.role).await.map_err(|e| {
+        error!("DB Error (Agent Role Lookup): {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "Internal server error".to_string(),
+            }),
+        )
+    })?;
 
-fn normalized_admin_user(admin_user: &AuthUser) -> AuthUser {
-    let forwarded_role = match admin_user.role.as_str() {
-        ROLE_SUPER_ADMIN => "admin",
-        ROLE_OPERATOR => "nutritionist",
-        other => other,
-    };
-
-    AuthUser {
-        user_id: admin_user.user_id,
-        email: admin_user.email.clone(),
-        role: forwarded_role.to_string(),
-    }
+    Ok(AuthUser {
+        role: forwarded_role,
+        ..admin_user.clone()
+    })
 }
 
 #[derive(Debug, Deserialize)]
@@ -154,10 +162,11 @@ pub async fn knowledge_graph_relation_evidence_handler(
 
 pub async fn admin_knowledge_graph_rebuild_handler(
     admin_user: AuthUser,
+    State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     body: Bytes,
 ) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<ErrorResponse>)> {
-    let admin_user = normalized_admin_user(&admin_user);
+    let admin_user = normalized_admin_user(&state, &admin_user).await?;
     let payload = parse_graph_admin_payload(&body)?;
     let (status, response) = send_json_request(
         &headers,
@@ -174,11 +183,12 @@ pub async fn admin_knowledge_graph_rebuild_handler(
 
 pub async fn admin_knowledge_graph_extract_handler(
     admin_user: AuthUser,
+    State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(document_id): Path<String>,
     body: Bytes,
 ) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<ErrorResponse>)> {
-    let admin_user = normalized_admin_user(&admin_user);
+    let admin_user = normalized_admin_user(&state, &admin_user).await?;
     let payload = parse_graph_admin_payload(&body)?;
     let (status, response) = send_json_request(
         &headers,
@@ -234,10 +244,11 @@ mod tests {
 
 pub async fn admin_knowledge_graph_document_detail_handler(
     admin_user: AuthUser,
+    State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(document_id): Path<String>,
 ) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<ErrorResponse>)> {
-    let admin_user = normalized_admin_user(&admin_user);
+    let admin_user = normalized_admin_user(&state, &admin_user).await?;
     let (status, response) = send_json_request(
         &headers,
         Some(&admin_user),
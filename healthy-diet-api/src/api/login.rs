@@ -1,16 +1,99 @@
 use crate::{
-    api::model::{AuthResponse, ErrorResponse, LoginPayload, User, UserProfile, is_admin_role},
+    api::model::{
+        AuthResponse, ErrorResponse, LoginPayload, LoginResponse, TwoFactorChallenge, User,
+        UserProfile,
+    },
     model::AppState,
-    utils::{hash::verify_password, jwt::sign_jwt},
+    utils::{
+        client_info::ClientInfo,
+        hash::{hash_password, needs_rehash, verify_password},
+        login_guard::{
+            ADMIN_ACCOUNT_POLICY, ADMIN_IP_POLICY, LockoutPolicy, SCOPE_ACCOUNT, SCOPE_ADMIN_IP,
+            SCOPE_IP, USER_ACCOUNT_POLICY, USER_IP_POLICY, account_key, clear_failures,
+            locked_until, record_failure,
+        },
+        permission::is_admin,
+        refresh_token::issue_token_pair,
+        totp::{CHALLENGE_TTL_SECS, is_two_factor_enabled, issue_challenge_token},
+    },
 };
 use axum::{Json, extract::State, http::StatusCode};
+use chrono::{DateTime, Utc};
 use std::sync::Arc;
 use tracing::{error, info, instrument, warn};
 
+fn login_internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+pub(crate) fn locked_error(until: DateTime<Utc>) -> (StatusCode, Json<ErrorResponse>) {
+    let retry_after = (until - Utc::now()).num_seconds().max(1);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(ErrorResponse {
+            error: format!(
+                "Too many failed login attempts. Try again in {} seconds",
+                retry_after
+            ),
+        }),
+    )
+}
+
+/// Counters consulted for a login attempt: the account (admin accounts use the stricter
+/// policy whichever endpoint is used) and the caller's IP (stricter on the admin endpoint).
+fn guard_keys(
+    email: &str,
+    ip_address: Option<&str>,
+    admin_login: bool,
+    admin_account: bool,
+) -> Vec<(&'static str, String, LockoutPolicy)> {
+    let mut keys = vec![(
+        SCOPE_ACCOUNT,
+        account_key(email),
+        if admin_login || admin_account {
+            ADMIN_ACCOUNT_POLICY
+        } else {
+            USER_ACCOUNT_POLICY
+        },
+    )];
+    if let Some(ip) = ip_address {
+        keys.push(if admin_login {
+            (SCOPE_ADMIN_IP, ip.to_string(), ADMIN_IP_POLICY)
+        } else {
+            (SCOPE_IP, ip.to_string(), USER_IP_POLICY)
+        });
+    }
+    keys
+}
+
 async fn authenticate_user(
     state: &AppState,
     payload: &LoginPayload,
+    client: &ClientInfo,
+    admin_login: bool,
 ) -> Result<User, (StatusCode, Json<ErrorResponse>)> {
+    let probe_keys = guard_keys(
+        &payload.email,
+        client.ip_address.as_deref(),
+        admin_login,
+        false,
+    );
+    for (scope, key, _) in &probe_keys {
+        let locked = locked_until(&state.db, scope, key).await.map_err(|e| {
+            error!("Database error during login lockout check: {:?}", e);
+            login_internal_error()
+        })?;
+        if let Some(until) = locked {
+            warn!("Login rejected: {} is locked until {}", scope, until);
+            return Err(locked_error(until));
+        }
+    }
+
     let user = sqlx::query_as::<_, User>(
         "SELECT id, email, password_hash, nickname, avatar_url, role FROM users WHERE email = $1",
     )
@@ -19,18 +102,51 @@ async fn authenticate_user(
     .await
     .map_err(|e| {
         error!("Database error during login: {:?}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: "Internal server error".to_string(),
-            }),
-        )
+        login_internal_error()
     })?;
 
+    let is_valid = match &user {
+        Some(u) => verify_password(&payload.password, &u.password_hash).unwrap_or(false),
+        None => false,
+    };
+
     let user = match user {
-        Some(u) => u,
-        None => {
-            warn!("Login failed: User not found");
+        Some(u) if is_valid => u,
+        user => {
+            match &user {
+                Some(u) => warn!("Login failed: Invalid password for user {}", u.id),
+                None => warn!("Login failed: User not found"),
+            }
+
+            let admin_account = match &user {
+                Some(u) => is_admin(&state.db, &u.role).await.unwrap_or_else(|e| {
+                    // Fall back to the stricter admin policy when the role cannot be resolved.
+                    error!("DB Error (Permission Check): {:?}", e);
+                    true
+                }),
+                None => false,
+            };
+            let keys = guard_keys(
+                &payload.email,
+                client.ip_address.as_deref(),
+                admin_login,
+                admin_account,
+            );
+            let mut lockout = None;
+            for (scope, key, policy) in &keys {
+                match record_failure(&state.db, scope, key, policy).await {
+                    Ok(Some(until)) => {
+                        warn!("Login lockout on {} until {}", scope, until);
+                        lockout = lockout.max(Some(until));
+                    }
+                    Ok(None) => {}
+                    Err(e) => error!("Failed to record login failure: {:?}", e),
+                }
+            }
+            if let Some(until) = lockout {
+                return Err(locked_error(until));
+            }
+
             return Err((
                 StatusCode::UNAUTHORIZED,
                 Json(ErrorResponse {
@@ -40,31 +156,92 @@ async fn authenticate_user(
         }
     };
 
-    let is_valid = verify_password(&payload.password, &user.password_hash).unwrap_or(false);
-    if !is_valid {
-        warn!("Login failed: Invalid password for user {}", user.id);
+    // The IP counter is left alone: one valid account must not reset it for a whole botnet.
+    if let Err(e) = clear_failures(&state.db, SCOPE_ACCOUNT, &account_key(&payload.email)).await {
+        warn!(
+            "Failed to reset login failures for user {}: {:?}",
+            user.id, e
+        );
+    }
+
+    upgrade_password_hash(state, &user, &payload.password).await;
+
+    Ok(user)
+}
+
+/// Re-hashes the password with the current Argon2 parameters when the stored hash is weaker.
+/// Failures are only logged; the login itself already succeeded.
+async fn upgrade_password_hash(state: &AppState, user: &User, password: &str) {
+    if !needs_rehash(&user.password_hash) {
+        return;
+    }
+    let password_hash = match hash_password(password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            warn!("Password rehash failed for user {}: {}", user.id, e);
+            return;
+        }
+    };
+    // Only replace the hash that was verified, in case the password changed meanwhile.
+    let updated =
+        sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2 AND password_hash = $3")
+            .bind(&password_hash)
+            .bind(user.id)
+            .bind(&user.password_hash)
+            .execute(&state.db)
+            .await;
+    match updated {
+        Ok(_) => info!("Upgraded password hash parameters for user {}", user.id),
+        Err(e) => warn!(
+            "Failed to store upgraded password hash for {}: {:?}",
+            user.id, e
+        ),
+    }
+}
+
+pub(crate) async fn build_auth_response(
+    state: &AppState,
+    user: User,
+    client: &ClientInfo,
+) -> Result<AuthResponse, (StatusCode, Json<ErrorResponse>)> {
+    // Every login path (password, OAuth, second factor) ends here, so suspension is
+    // enforced once before any token is issued.
+    let suspended =
+        sqlx::query_scalar::<_, bool>("SELECT suspended_at IS NOT NULL FROM users WHERE id = $1")
+            .bind(user.id)
+            .fetch_one(&state.db)
+            .await
+            .map_err(|e| {
+                error!("DB Error (Suspension Check): {:?}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        error: "Internal server error".to_string(),
+                    }),
+                )
+            })?;
+    if suspended {
+        warn!("Suspended user {} attempted to sign in", user.id);
         return Err((
-            StatusCode::UNAUTHORIZED,
+            StatusCode::FORBIDDEN,
             Json(ErrorResponse {
-                error: "Invalid email or password".to_string(),
+                error: "Account suspended".to_string(),
             }),
         ));
     }
 
-    Ok(user)
-}
-
-fn build_auth_response(user: User) -> Result<AuthResponse, (StatusCode, Json<ErrorResponse>)> {
     let (token, refresh_token, expires_in) =
-        sign_jwt(&user.id.to_string(), &user.email, &user.role).map_err(|e| {
-            error!("JWT generation error: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "Failed to generate token".to_string(),
-                }),
-            )
-        })?;
+        issue_token_pair(&state.db, user.id, &user.email, &user.role, 3600, client)
+            .await
+            .map_err(|e| {
+                error!("Token issue error: {:?}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        error: "Failed to generate token".to_string(),
+                    }),
+                )
+            })?;
 
     Ok(AuthResponse {
         token,
@@ -80,15 +257,56 @@ fn build_auth_response(user: User) -> Result<AuthResponse, (StatusCode, Json<Err
     })
 }
 
+/// Finishes a login whose first factor passed. Roles with admin access get a challenge
+/// token instead of real tokens until they present a TOTP or recovery code.
+pub(crate) async fn complete_login(
+    state: &AppState,
+    user: User,
+    client: &ClientInfo,
+) -> Result<LoginResponse, (StatusCode, Json<ErrorResponse>)> {
+    let admin_account = is_admin(&state.db, &user.role).await.map_err(|e| {
+        error!("Database error during permission lookup: {:?}", e);
+        login_internal_error()
+    })?;
+    if !admin_account {
+        return Ok(LoginResponse::Authenticated(
+            build_auth_response(state, user, client).await?,
+        ));
+    }
+
+    let enrolled = is_two_factor_enabled(&state.db, user.id)
+        .await
+        .map_err(|e| {
+            error!("Database error during 2FA lookup: {:?}", e);
+            login_internal_error()
+        })?;
+    let challenge_token = issue_challenge_token(user.id, &user.email).map_err(|e| {
+        error!("Challenge token issue error: {:?}", e);
+        login_internal_error()
+    })?;
+
+    info!("Second factor required for user {}", user.id);
+    Ok(LoginResponse::TwoFactorRequired(TwoFactorChallenge {
+        two_factor_required: true,
+        challenge_token,
+        expires_in: CHALLENGE_TTL_SECS,
+        enrollment_required: !enrolled,
+    }))
+}
+
 #[instrument(skip(state, payload), fields(email = %payload.email))]
 pub async fn login_handler(
     State(state): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(payload): Json<LoginPayload>,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let user = authenticate_user(&state, &payload).await?;
-    let response = build_auth_response(user)?;
+) -> Result<Json<LoginResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let user = authenticate_user(&state, &payload, &client, false).await?;
+    let user_id = user.id;
+    let response = complete_login(&state, user, &client).await?;
 
-    info!("User logged in successfully: {}", response.user.id);
+    if let LoginResponse::Authenticated(_) = response {
+        info!("User logged in successfully: {}", user_id);
+    }
 
     Ok(Json(response))
 }
@@ -96,11 +314,16 @@ pub async fn login_handler(
 #[instrument(skip(state, payload), fields(email = %payload.email))]
 pub async fn admin_login_handler(
     State(state): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(payload): Json<LoginPayload>,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let user = authenticate_user(&state, &payload).await?;
+) -> Result<Json<LoginResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let user = authenticate_user(&state, &payload, &client, true).await?;
 
-    if !is_admin_role(&user.role) {
+    let admin_account = is_admin(&state.db, &user.role).await.map_err(|e| {
+        error!("Database error during permission lookup: {:?}", e);
+        login_internal_error()
+    })?;
+    if !admin_account {
         warn!("Admin login denied: non-admin user {}", user.id);
         return Err((
             StatusCode::FORBIDDEN,
@@ -110,8 +333,11 @@ pub async fn admin_login_handler(
         ));
     }
 
-    let response = build_auth_response(user)?;
-    info!("Admin logged in successfully: {}", response.user.id);
+    info!(
+        "Admin password accepted, awaiting second factor: {}",
+        user.id
+    );
+    let response = complete_login(&state, user, &client).await?;
 
     Ok(Json(response))
 }
@@ -132,10 +358,17 @@ mod tests {
             password,
         };
 
-        let result = login_handler(State(state.clone()), Json(login_payload)).await;
+        let result = login_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(login_payload),
+        )
+        .await;
 
         assert!(result.is_ok());
-        let response = result.unwrap().0;
+        let LoginResponse::Authenticated(response) = result.unwrap().0 else {
+            panic!("users without 2FA should get tokens directly");
+        };
 
         assert_eq!(response.user.email, email);
         assert_eq!(response.user.role, "user".to_string());
@@ -160,7 +393,12 @@ mod tests {
             password: "wrong_password_123".to_string(),
         };
 
-        let result = login_handler(State(state.clone()), Json(login_payload)).await;
+        let result = login_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(login_payload),
+        )
+        .await;
 
         assert!(result.is_err());
         let (status, _) = result.unwrap_err();
@@ -183,7 +421,12 @@ mod tests {
             password,
         };
 
-        let result = admin_login_handler(State(state.clone()), Json(login_payload)).await;
+        let result = admin_login_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(login_payload),
+        )
+        .await;
 
         assert!(result.is_err());
         let (status, _) = result.unwrap_err();
@@ -195,7 +438,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_admin_login_success() {
+    async fn test_admin_login_requires_second_factor() {
         let state = setup_db().await;
 
         let (email, password) =
@@ -212,11 +455,85 @@ mod tests {
             password,
         };
 
-        let result = admin_login_handler(State(state.clone()), Json(login_payload)).await;
+        let result = admin_login_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(login_payload),
+        )
+        .await;
 
         assert!(result.is_ok());
-        let response = result.unwrap().0;
-        assert_eq!(response.user.role, "super_admin".to_string());
+        let LoginResponse::TwoFactorRequired(challenge) = result.unwrap().0 else {
+            panic!("admin login must not return tokens before the second factor");
+        };
+        assert!(challenge.enrollment_required);
+        assert!(crate::utils::totp::decode_challenge_token(&challenge.challenge_token).is_some());
+
+        let _ = sqlx::query!("DELETE FROM users WHERE email = $1", email)
+            .execute(&state.db)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_login_lockout_and_admin_unlock() {
+        let state = setup_db().await;
+
+        let (email, password) =
+            register_test_account(state.clone(), "login_lockout".to_string()).await;
+        let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+
+        for _ in 0..USER_ACCOUNT_POLICY.max_failures {
+            let _ = login_handler(
+                State(state.clone()),
+                ClientInfo::default(),
+                Json(LoginPayload {
+                    email: email.clone(),
+                    password: "wrong_password_123".to_string(),
+                }),
+            )
+            .await;
+        }
+
+        let locked = login_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(LoginPayload {
+                email: email.clone(),
+                password: password.clone(),
+            }),
+        )
+        .await;
+        assert!(matches!(locked, Err((StatusCode::TOO_MANY_REQUESTS, _))));
+
+        let admin = crate::utils::jwt::AuthUser {
+            user_id,
+            email: email.clone(),
+            role: "super_admin".to_string(),
+            session_id: None,
+            api_token_id: None,
+        };
+        let unlocked = crate::api::admin::unlock_user_handler(
+            admin,
+            axum::extract::Path(user_id),
+            State(state.clone()),
+        )
+        .await;
+        assert!(unlocked.is_ok());
+
+        let result = login_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(LoginPayload {
+                email: email.clone(),
+                password,
+            }),
+        )
+        .await;
+        assert!(result.is_ok());
 
         let _ = sqlx::query!("DELETE FROM users WHERE email = $1", email)
             .execute(&state.db)
@@ -0,0 +1,64 @@
+use crate::{
+    api::model::{ErrorResponse, RefreshTokenPayload},
+    model::AppState,
+    utils::{jwt::decode_jwt, session::revoke_session},
+};
+use axum::{Json, extract::State, http::StatusCode};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+/// Revokes the session (and whole refresh token family) the presented token belongs to.
+pub async fn logout_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RefreshTokenPayload>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let claims = decode_jwt(&payload.refresh_token).map_err(|e| {
+        warn!("Invalid refresh token on logout: {:?}", e);
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Invalid or expired refresh token".to_string(),
+            }),
+        )
+    })?;
+
+    let family_id = claims
+        .fam
+        .as_deref()
+        .filter(|_| claims.token_type == "refresh")
+        .and_then(|fam| uuid::Uuid::parse_str(fam).ok())
+        .ok_or((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Invalid or expired refresh token".to_string(),
+            }),
+        ))?;
+
+    let user_id = uuid::Uuid::parse_str(&claims.sub).map_err(|_| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Invalid token format".to_string(),
+            }),
+        )
+    })?;
+
+    let revoked = revoke_session(&state.db, family_id, user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Logout): {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Internal server error".to_string(),
+                }),
+            )
+        })?;
+
+    if revoked {
+        info!("User {} logged out of session {}", user_id, family_id);
+    }
+
+    Ok(Json(json!({ "message": "Logged out successfully" })))
+}
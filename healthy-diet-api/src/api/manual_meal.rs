@@ -0,0 +1,371 @@
+use axum::{Json, extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{env, sync::Arc, time::Duration};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    api::{
+        diet::{
+            AnalyzedMeal, GEMINI_TIMEOUT_SECS, estimate_from_items, load_meal_profile, save_meal,
+            score_meal,
+        },
+        model::ErrorResponse,
+    },
+    model::{AppState, ENVKey, OutSideURL},
+    utils::{
+        diet_items::{ENTRY_SOURCE_MANUAL, MealItem},
+        foods::{FoodNutrients, load_foods, match_food_name},
+        jwt::AuthUser,
+        meal_text::{DEFAULT_SERVING_G, MealTextEntry, conjunction_splits, parse_meal_text},
+        nutrition_engine::NutrientVector,
+    },
+};
+
+/// Foods per meal, structured and typed together.
+const MAX_MEAL_ITEMS: usize = 30;
+const MAX_ITEM_WEIGHT_G: f64 = 5000.0;
+const MAX_TEXT_CHARS: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct ManualFoodEntry {
+    pub food_id: Uuid,
+    pub weight_g: Option<f64>,
+    /// Multiples of a 100 g serving, used when `weight_g` is missing.
+    pub servings: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ManualMealPayload {
+    #[serde(default)]
+    pub items: Vec<ManualFoodEntry>,
+    /// Free text such as "一碗白飯 + 滷雞腿".
+    pub text: Option<String>,
+    /// Ask the AI about typed foods the name matching could not place.
+    #[serde(default)]
+    pub ai_assist: bool,
+}
+
+#[derive(Serialize)]
+pub struct ManualMealResponse {
+    pub record_id: Uuid,
+    pub total_calories: f64,
+    pub nutrients: NutrientVector,
+    pub items: Vec<MealItem>,
+    pub ai_score: i32,
+    pub ai_comment: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AiFoodSuggestion {
+    index: usize,
+    food: String,
+    #[serde(default)]
+    grams: Option<f64>,
+}
+
+fn bad_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            error: message.into(),
+        }),
+    )
+}
+
+fn db_error(e: sqlx::Error) -> (StatusCode, Json<ErrorResponse>) {
+    error!("DB Error (Manual Meal): {:?}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "資料庫連線錯誤".into(),
+        }),
+    )
+}
+
+fn valid_weight(weight_g: f64) -> bool {
+    weight_g.is_finite() && weight_g > 0.0 && weight_g <= MAX_ITEM_WEIGHT_G
+}
+
+/// A portion of a food from the foods table, rounded as shown to users.
+pub(crate) fn portion_item(
+    food: &FoodNutrients,
+    weight_g: f64,
+    source_text: Option<String>,
+) -> MealItem {
+    let factor = weight_g / 100.0;
+    MealItem {
+        class: food.engine_class.clone(),
+        food_id: Some(food.id),
+        name: Some(food.name_zh.clone()),
+        source_text,
+        weight_g: (weight_g * 10.0).round() / 10.0,
+        calories: (food.kcal_per_100g * factor * 10.0).round() / 10.0,
+        nutrients: food.nutrients_per_100g().scaled(factor).rounded(),
+        detection: None,
+    }
+}
+
+/// Finds the food for a typed entry. The whole segment is tried first, so names that start
+/// with a numeral or unit (三杯雞) are not split. With `exact_only` the name must equal a food
+/// name or alias.
+async fn match_entry(
+    state: &AppState,
+    entry: &MealTextEntry,
+    exact_only: bool,
+) -> Result<Option<MealItem>, sqlx::Error> {
+    if entry.text != entry.name
+        && let Some(food) = match_food_name(&state.db, &entry.text, true).await?
+    {
+        return Ok(Some(portion_item(
+            &food,
+            DEFAULT_SERVING_G,
+            Some(entry.text.clone()),
+        )));
+    }
+    Ok(match_food_name(&state.db, &entry.name, exact_only)
+        .await?
+        .map(|food| portion_item(&food, entry.weight_g, Some(entry.text.clone()))))
+}
+
+/// Matches a typed entry, reading conjunctions as separators only when every side is a known
+/// food: "白飯和滷雞腿" is two foods, 和風沙拉 stays one. Sides are taken leftmost first.
+async fn match_segment(
+    state: &AppState,
+    entry: &MealTextEntry,
+) -> Result<Option<Vec<MealItem>>, sqlx::Error> {
+    if let Some(item) = match_entry(state, entry, true).await? {
+        return Ok(Some(vec![item]));
+    }
+
+    let mut joined = Vec::new();
+    let mut rest = entry.clone();
+    'sides: loop {
+        for (left, right) in conjunction_splits(&rest) {
+            if let Some(item) = match_entry(state, &left, true).await? {
+                joined.push(item);
+                if let Some(item) = match_entry(state, &right, true).await? {
+                    joined.push(item);
+                    return Ok(Some(joined));
+                }
+                rest = right;
+                continue 'sides;
+            }
+        }
+        break;
+    }
+
+    Ok(match_entry(state, entry, false)
+        .await?
+        .map(|item| vec![item]))
+}
+
+/// Asks Gemini for the standard food name and weight of entries the matching missed. Any
+/// failure yields no suggestions; the entries are then reported as unrecognised.
+async fn suggest_foods(entries: &[&MealTextEntry]) -> Vec<AiFoodSuggestion> {
+    let Ok(api_key) = env::var(ENVKey::GEMINI_API_KEY) else {
+        return Vec::new();
+    };
+    let ai_url = format!("{}{}", OutSideURL::GEMINI_API_URL, api_key);
+    let system_instruction = "你是台灣的營養師助理。請將每一項使用者輸入的食物改寫成衛福部食品營養成分資料庫中的標準食物名稱，並估計份量的公克數。\n\
+        只回傳 JSON 陣列，例如：[{\"index\": 0, \"food\": \"白飯\", \"grams\": 200}]。無法判斷的項目請省略。";
+    let user_prompt = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| format!("{}. {}", index, entry.text))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let response = reqwest::Client::new()
+        .post(&ai_url)
+        .timeout(Duration::from_secs(GEMINI_TIMEOUT_SECS))
+        .json(&json!({
+            "system_instruction": { "parts": { "text": system_instruction } },
+            "contents": [{ "role": "user", "parts": [{ "text": user_prompt }] }]
+        }))
+        .send()
+        .await;
+    let res_body: serde_json::Value = match response {
+        Ok(response) => response.json().await.unwrap_or_default(),
+        Err(e) => {
+            warn!("AI food suggestion request failed: {:?}", e);
+            return Vec::new();
+        }
+    };
+    let text_reply = res_body["candidates"][0]["content"]["parts"][0]["text"]
+        .as_str()
+        .unwrap_or("[]")
+        .replace("```json", "")
+        .replace("```", "");
+
+    serde_json::from_str::<Vec<AiFoodSuggestion>>(text_reply.trim()).unwrap_or_else(|_| {
+        warn!("AI food suggestion 解析失敗：{}", text_reply);
+        Vec::new()
+    })
+}
+
+/// Logs a meal without a photo. Foods come from `items` (ids from `GET /api/foods`) and from
+/// `text`, parsed into foods by name; the meal is scored like a photographed one.
+pub async fn create_manual_meal_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ManualMealPayload>,
+) -> Result<(StatusCode, Json<ManualMealResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let text = payload.text.as_deref().unwrap_or_default().trim();
+    if text.chars().count() > MAX_TEXT_CHARS {
+        return Err(bad_request("輸入文字過長"));
+    }
+    let entries = parse_meal_text(text);
+    if payload.items.is_empty() && entries.is_empty() {
+        return Err(bad_request("請輸入至少一項食物"));
+    }
+    if payload.items.len() + entries.len() > MAX_MEAL_ITEMS {
+        return Err(bad_request(format!("每餐最多 {} 項食物", MAX_MEAL_ITEMS)));
+    }
+
+    let profile = load_meal_profile(&state, auth_user.user_id).await?;
+
+    let mut items: Vec<MealItem> = Vec::with_capacity(payload.items.len() + entries.len());
+    let food_ids: Vec<Uuid> = payload.items.iter().map(|entry| entry.food_id).collect();
+    let foods = load_foods(&state.db, &food_ids).await.map_err(db_error)?;
+    for entry in &payload.items {
+        let food = foods
+            .get(&entry.food_id)
+            .ok_or_else(|| bad_request(format!("找不到食物 {}", entry.food_id)))?;
+        let weight_g = match (entry.weight_g, entry.servings) {
+            (Some(weight_g), _) => weight_g,
+            (None, Some(servings)) => servings * DEFAULT_SERVING_G,
+            (None, None) => DEFAULT_SERVING_G,
+        };
+        if !valid_weight(weight_g) {
+            return Err(bad_request(format!(
+                "份量需介於 0 到 {} 公克之間",
+                MAX_ITEM_WEIGHT_G
+            )));
+        }
+        items.push(portion_item(food, weight_g, None));
+    }
+
+    let mut unmatched: Vec<&MealTextEntry> = Vec::new();
+    for entry in &entries {
+        if !valid_weight(entry.weight_g) {
+            return Err(bad_request(format!("份量不合理：{}", entry.text)));
+        }
+        match match_segment(&state, entry).await.map_err(db_error)? {
+            Some(matched) => items.extend(matched),
+            None => unmatched.push(entry),
+        }
+    }
+    if items.len() > MAX_MEAL_ITEMS {
+        return Err(bad_request(format!("每餐最多 {} 項食物", MAX_MEAL_ITEMS)));
+    }
+
+    if !unmatched.is_empty() && payload.ai_assist {
+        let mut resolved = vec![false; unmatched.len()];
+        for suggestion in suggest_foods(&unmatched).await {
+            let Some(entry) = unmatched.get(suggestion.index) else {
+                continue;
+            };
+            if resolved[suggestion.index] {
+                continue;
+            }
+            let Some(food) = match_food_name(&state.db, suggestion.food.trim(), false)
+                .await
+                .map_err(db_error)?
+            else {
+                continue;
+            };
+            let weight_g = suggestion
+                .grams
+                .filter(|grams| valid_weight(*grams))
+                .unwrap_or(entry.weight_g);
+            items.push(portion_item(&food, weight_g, Some(entry.text.clone())));
+            resolved[suggestion.index] = true;
+        }
+        let mut resolved = resolved.into_iter();
+        unmatched.retain(|_| !resolved.next().unwrap_or(false));
+        if items.len() > MAX_MEAL_ITEMS {
+            return Err(bad_request(format!("每餐最多 {} 項食物", MAX_MEAL_ITEMS)));
+        }
+    }
+
+    if !unmatched.is_empty() {
+        let names: Vec<&str> = unmatched.iter().map(|entry| entry.text.as_str()).collect();
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ErrorResponse {
+                error: format!("無法辨識的食物：{}", names.join("、")),
+            }),
+        ));
+    }
+
+    let estimate = estimate_from_items(&items);
+    let evaluation = score_meal(&profile, &estimate).await?;
+    let record_id = save_meal(
+        &state,
+        &AnalyzedMeal {
+            user_id: auth_user.user_id,
+            email: &auth_user.email,
+            profile: &profile,
+            estimate: &estimate,
+            evaluation: &evaluation,
+            entry_source: ENTRY_SOURCE_MANUAL,
+            result_image_path: None,
+            original_image_path: None,
+        },
+    )
+    .await
+    .ok_or_else(|| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "無法儲存飲食紀錄".into(),
+            }),
+        )
+    })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ManualMealResponse {
+            record_id,
+            total_calories: (estimate.total_calories * 10.0).round() / 10.0,
+            nutrients: estimate.nutrients.rounded(),
+            items,
+            ai_score: evaluation.score,
+            ai_comment: evaluation.comment,
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_portion_items_total_without_squashing() {
+        let rice = FoodNutrients {
+            id: Uuid::new_v4(),
+            name_zh: "白飯".to_string(),
+            engine_class: "grain".to_string(),
+            kcal_per_100g: 183.0,
+            carbohydrate_g: 41.0,
+            protein_g: 3.1,
+            fat_g: 0.3,
+            fiber_g: 0.6,
+            sodium_mg: 2.0,
+        };
+        let item = portion_item(&rice, 200.0, Some("一碗白飯".to_string()));
+        assert_eq!(item.calories, 366.0);
+        assert_eq!(item.nutrients.carbohydrate_g, 82.0);
+        assert_eq!(item.name.as_deref(), Some("白飯"));
+
+        // Six bowls are over the engine's squash threshold but are taken as entered.
+        let items = vec![item; 6];
+        let estimate = estimate_from_items(&items);
+        assert_eq!(estimate.total_calories, 2196.0);
+        assert_eq!(estimate.stats["grain"], (2196.0, 0.0));
+        assert_eq!(estimate.stats["dairy"], (0.0, 0.0));
+        assert!(estimate.engine_version.is_none());
+    }
+}
@@ -1,22 +1,37 @@
+pub mod account;
 pub mod admin;
 pub mod agent_approve;
 pub mod agent_content;
 pub mod announcement;
+pub mod api_token;
 pub mod basic_calculator;
+pub mod caregiver;
 pub mod chat;
 pub mod chat_room;
+pub mod dataset;
 pub mod diet;
 pub mod diet_image;
+pub mod diet_job;
 pub mod diet_record;
+pub mod email_verification;
+pub mod food;
 pub mod gemma4;
 pub mod health;
+pub mod jwks;
 pub mod knowledge_graph;
 pub mod login;
+pub mod logout;
+pub mod manual_meal;
 pub mod model;
+pub mod nutritionist;
 pub mod openapi;
+pub mod password_reset;
 pub mod ping;
 pub mod rag_document;
 pub mod record;
 pub mod refresh;
 pub mod register;
+pub mod role;
+pub mod session;
+pub mod two_factor;
 pub mod user;
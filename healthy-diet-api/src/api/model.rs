@@ -3,18 +3,14 @@ use sqlx::FromRow;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::utils::{
+    annotation::DietRecordAnnotation, diet_items::StoredMealItem, nutrition_engine::NutrientVector,
+};
+
 pub const ROLE_USER: &str = "user";
 pub const ROLE_OPERATOR: &str = "operator";
 pub const ROLE_SUPER_ADMIN: &str = "super_admin";
 
-pub fn is_admin_role(role: &str) -> bool {
-    matches!(role, ROLE_OPERATOR | ROLE_SUPER_ADMIN)
-}
-
-pub fn is_super_admin(role: &str) -> bool {
-    role == ROLE_SUPER_ADMIN
-}
-
 #[derive(Debug, FromRow)]
 pub struct User {
     pub id: Uuid,
@@ -30,7 +26,7 @@ pub struct RegisterPayload {
     #[validate(email(message = "Email formate not correct"))]
     pub email: String,
 
-    #[validate(length(min = 6, message = "paasword must great than 6 characters"))]
+    /// Checked against the configured password policy rather than a fixed length.
     pub password: String,
 
     #[validate(length(
@@ -59,6 +55,68 @@ pub struct AuthResponse {
     pub user: UserProfile,
 }
 
+/// Result of a successful password or provider login. Accounts that need a second factor
+/// get a challenge instead of tokens.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    pub expires_in: usize,
+    /// The account must enroll TOTP (admins without it) before it can log in.
+    pub enrollment_required: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorVerifyPayload {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpSetupPayload {
+    pub challenge_token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpSetupResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpConfirmPayload {
+    pub challenge_token: Option<String>,
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpConfirmResponse {
+    /// Shown once; each can replace a TOTP code a single time.
+    pub recovery_codes: Vec<String>,
+    /// Present when enrolling from a login challenge.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TotpCodePayload {
+    pub code: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserProfile {
     pub id: String,
@@ -68,6 +126,32 @@ pub struct UserProfile {
     pub role: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct EmailTokenPayload {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordPayload {
+    #[validate(email(message = "Email formate not correct"))]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordPayload {
+    pub token: String,
+
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePasswordPayload {
+    pub current_password: String,
+    pub new_password: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
     pub error: String,
@@ -126,4 +210,12 @@ pub struct DietRecordResponse {
     pub nuts_area: Option<f64>,
     pub ai_health_score: Option<i32>,
     pub ai_evaluation: Option<String>,
+    /// `photo` or `manual`.
+    pub entry_source: String,
+    /// Meal totals; `None` for records from before nutrients were estimated.
+    pub nutrients: Option<NutrientVector>,
+    /// Nutritionist feedback and the user's replies, oldest first.
+    pub annotations: Vec<DietRecordAnnotation>,
+    /// Detected or logged foods in order; empty for records from before items were kept.
+    pub items: Vec<StoredMealItem>,
 }
@@ -0,0 +1,543 @@
+use crate::{
+    api::{
+        chat_room::{ChatRoomTitleItem, fetch_chat_room_titles, fetch_room_history},
+        diet_record::fetch_diet_records,
+        model::{DietRecordResponse, ErrorResponse},
+    },
+    model::AppState,
+    utils::{
+        annotation::{
+            AUTHOR_NUTRITIONIST, DietRecordAnnotation, NewAnnotation, create_annotation, mark_read,
+            normalize_comment, normalize_tags, record_belongs_to,
+        },
+        audit::write_audit_log,
+        caregiver::LOW_SCORE_ALERT_THRESHOLD,
+        jwt::AuthUser,
+        permission::{CLIENTS_WORKSPACE, has_permission},
+    },
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Meals scored below this are flagged for review; the same cut-off as caregiver alerts.
+pub const FLAGGED_MEAL_SCORE: i32 = LOW_SCORE_ALERT_THRESHOLD;
+/// Averages and flag counts in the client list cover this many days.
+const WORKSPACE_WINDOW_DAYS: i32 = 30;
+/// Upper bound for a corrected calorie value of a single meal.
+const MAX_CORRECTED_CALORIES: f64 = 10_000.0;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssignClientPayload {
+    pub user_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceDietRecordsQuery {
+    /// Only meals scored below `FLAGGED_MEAL_SCORE`.
+    pub flagged: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotateRecordPayload {
+    pub comment: Option<String>,
+    pub corrected_calories: Option<f64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientAssignment {
+    pub user_id: Uuid,
+    pub email: String,
+    pub nickname: Option<String>,
+    pub assigned_at: DateTime<Utc>,
+    pub assigned_by: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceClient {
+    pub user_id: Uuid,
+    pub email: String,
+    pub nickname: Option<String>,
+    pub assigned_at: DateTime<Utc>,
+    pub last_meal_at: Option<DateTime<Utc>>,
+    pub recent_meal_count: i64,
+    pub average_health_score: Option<f64>,
+    pub flagged_meal_count: i64,
+    /// Client replies to the caller's annotations not read yet.
+    pub unread_reply_count: i64,
+}
+
+fn db_error(context: &str, e: sqlx::Error) -> (StatusCode, Json<ErrorResponse>) {
+    error!("DB Error ({}): {:?}", context, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+fn error_response(status: StatusCode, message: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        status,
+        Json(ErrorResponse {
+            error: message.to_string(),
+        }),
+    )
+}
+
+fn is_flagged(record: &DietRecordResponse) -> bool {
+    record
+        .ai_health_score
+        .is_some_and(|score| score < FLAGGED_MEAL_SCORE)
+}
+
+/// Nutritionists are accounts whose role grants the workspace.
+async fn ensure_nutritionist(
+    state: &AppState,
+    nutritionist_id: Uuid,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let role = sqlx::query_scalar::<_, String>("SELECT role FROM users WHERE id = $1")
+        .bind(nutritionist_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| db_error("Find Nutritionist", e))?
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, "Nutritionist not found"))?;
+
+    let allowed = has_permission(&state.db, &role, CLIENTS_WORKSPACE)
+        .await
+        .map_err(|e| db_error("Find Nutritionist", e))?;
+    if !allowed {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            &format!("The role {} has no {}", role, CLIENTS_WORKSPACE),
+        ));
+    }
+    Ok(())
+}
+
+/// Every workspace drill-down goes through this: clients outside the caller's own
+/// assignments look the same as missing ones.
+async fn ensure_assigned(
+    state: &AppState,
+    nutritionist_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let assigned = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM nutritionist_clients WHERE nutritionist_id = $1 AND user_id = $2)",
+    )
+    .bind(nutritionist_id)
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| db_error("Client Assignment Check", e))?;
+
+    if !assigned {
+        return Err(error_response(StatusCode::NOT_FOUND, "Client not found"));
+    }
+    Ok(())
+}
+
+pub async fn list_nutritionist_clients_handler(
+    _admin_user: AuthUser,
+    Path(nutritionist_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ClientAssignment>>, (StatusCode, Json<ErrorResponse>)> {
+    let clients = sqlx::query_as::<_, ClientAssignment>(
+        r#"
+        SELECT u.id AS user_id, u.email, u.nickname, c.assigned_at, c.assigned_by
+        FROM nutritionist_clients c
+        JOIN users u ON u.id = c.user_id
+        WHERE c.nutritionist_id = $1
+        ORDER BY u.email ASC
+        "#,
+    )
+    .bind(nutritionist_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| db_error("List Nutritionist Clients", e))?;
+
+    Ok(Json(clients))
+}
+
+pub async fn assign_client_handler(
+    admin_user: AuthUser,
+    Path(nutritionist_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AssignClientPayload>,
+) -> Result<(StatusCode, Json<ClientAssignment>), (StatusCode, Json<ErrorResponse>)> {
+    if payload.user_id == nutritionist_id {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "A nutritionist cannot be their own client",
+        ));
+    }
+    ensure_nutritionist(&state, nutritionist_id).await?;
+
+    let assignment = sqlx::query_as::<_, ClientAssignment>(
+        r#"
+        WITH inserted AS (
+            INSERT INTO nutritionist_clients (nutritionist_id, user_id, assigned_by)
+            SELECT $1, id, $3 FROM users WHERE id = $2
+            ON CONFLICT (nutritionist_id, user_id) DO NOTHING
+            RETURNING user_id, assigned_at, assigned_by
+        )
+        SELECT u.id AS user_id, u.email, u.nickname, i.assigned_at, i.assigned_by
+        FROM inserted i
+        JOIN users u ON u.id = i.user_id
+        "#,
+    )
+    .bind(nutritionist_id)
+    .bind(payload.user_id)
+    .bind(admin_user.user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| db_error("Assign Client", e))?;
+
+    let Some(assignment) = assignment else {
+        let exists =
+            sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+                .bind(payload.user_id)
+                .fetch_one(&state.db)
+                .await
+                .map_err(|e| db_error("Assign Client", e))?;
+        return Err(if exists {
+            error_response(StatusCode::CONFLICT, "Client is already assigned")
+        } else {
+            error_response(StatusCode::NOT_FOUND, "User not found")
+        });
+    };
+
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "CLIENT_ASSIGNED",
+        "user",
+        &payload.user_id.to_string(),
+        json!({ "nutritionistId": nutritionist_id }),
+    )
+    .await;
+
+    info!(
+        "Admin {} assigned client {} to nutritionist {}",
+        admin_user.user_id, payload.user_id, nutritionist_id
+    );
+    Ok((StatusCode::CREATED, Json(assignment)))
+}
+
+pub async fn unassign_client_handler(
+    admin_user: AuthUser,
+    Path((nutritionist_id, user_id)): Path<(Uuid, Uuid)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let result =
+        sqlx::query("DELETE FROM nutritionist_clients WHERE nutritionist_id = $1 AND user_id = $2")
+            .bind(nutritionist_id)
+            .bind(user_id)
+            .execute(&state.db)
+            .await
+            .map_err(|e| db_error("Unassign Client", e))?;
+
+    if result.rows_affected() == 0 {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Assignment not found",
+        ));
+    }
+
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "CLIENT_UNASSIGNED",
+        "user",
+        &user_id.to_string(),
+        json!({ "nutritionistId": nutritionist_id }),
+    )
+    .await;
+
+    info!(
+        "Admin {} unassigned client {} from nutritionist {}",
+        admin_user.user_id, user_id, nutritionist_id
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The caller's clients with their latest meal and 30-day score summary, the ones with the
+/// most flagged meals first.
+pub async fn workspace_clients_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<WorkspaceClient>>, (StatusCode, Json<ErrorResponse>)> {
+    let clients = sqlx::query_as::<_, WorkspaceClient>(
+        r#"
+        SELECT
+            u.id AS user_id,
+            u.email,
+            u.nickname,
+            c.assigned_at,
+            MAX(d.created_at) AS last_meal_at,
+            COUNT(d.id) FILTER (
+                WHERE d.created_at >= now() - make_interval(days => $2)
+            ) AS recent_meal_count,
+            (AVG(d.ai_health_score) FILTER (
+                WHERE d.created_at >= now() - make_interval(days => $2)
+            ))::float8 AS average_health_score,
+            COUNT(d.id) FILTER (
+                WHERE d.created_at >= now() - make_interval(days => $2)
+                  AND d.ai_health_score < $3
+            ) AS flagged_meal_count,
+            (
+                SELECT COUNT(*)
+                FROM diet_record_annotations r
+                JOIN diet_record_annotations p ON p.id = r.parent_id
+                JOIN diet_records rd ON rd.id = r.record_id
+                WHERE rd.user_id = c.user_id AND p.author_id = $1 AND r.read_at IS NULL
+            ) AS unread_reply_count
+        FROM nutritionist_clients c
+        JOIN users u ON u.id = c.user_id
+        LEFT JOIN diet_records d ON d.user_id = c.user_id
+        WHERE c.nutritionist_id = $1
+        GROUP BY u.id, u.email, u.nickname, c.assigned_at
+        ORDER BY flagged_meal_count DESC, last_meal_at DESC NULLS LAST, u.email ASC
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(WORKSPACE_WINDOW_DAYS)
+    .bind(FLAGGED_MEAL_SCORE)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| db_error("Workspace Clients", e))?;
+
+    Ok(Json(clients))
+}
+
+pub async fn workspace_client_diet_records_handler(
+    auth_user: AuthUser,
+    Path(user_id): Path<Uuid>,
+    Query(params): Query<WorkspaceDietRecordsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DietRecordResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    ensure_assigned(&state, auth_user.user_id, user_id).await?;
+
+    let mut records = fetch_diet_records(&state.db, user_id)
+        .await
+        .map_err(|e| db_error("Workspace Diet Records", e))?;
+    if params.flagged == Some(true) {
+        records.retain(is_flagged);
+    }
+    Ok(Json(records))
+}
+
+pub async fn workspace_client_chat_rooms_handler(
+    auth_user: AuthUser,
+    Path(user_id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ChatRoomTitleItem>>, (StatusCode, Json<ErrorResponse>)> {
+    ensure_assigned(&state, auth_user.user_id, user_id).await?;
+
+    let rooms = fetch_chat_room_titles(&state.db, user_id)
+        .await
+        .map_err(|e| db_error("Workspace Chat Rooms", e))?;
+    Ok(Json(rooms))
+}
+
+pub async fn workspace_client_room_history_handler(
+    auth_user: AuthUser,
+    Path((user_id, room_id)): Path<(Uuid, String)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    ensure_assigned(&state, auth_user.user_id, user_id).await?;
+
+    let history = fetch_room_history(&state.db, user_id, &room_id)
+        .await
+        .map_err(|e| db_error("Workspace Room History", e))?;
+    Ok(Json(json!({ "history": history })))
+}
+
+/// The assigned client's record must exist; records of anyone else look missing.
+async fn ensure_client_record(
+    state: &AppState,
+    nutritionist_id: Uuid,
+    user_id: Uuid,
+    record_id: Uuid,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    ensure_assigned(state, nutritionist_id, user_id).await?;
+    let owned = record_belongs_to(&state.db, record_id, user_id)
+        .await
+        .map_err(|e| db_error("Client Record Check", e))?;
+    if !owned {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Diet record not found",
+        ));
+    }
+    Ok(())
+}
+
+/// Attaches a comment, a corrected calorie value and/or tags to a client's diet record.
+pub async fn annotate_client_record_handler(
+    auth_user: AuthUser,
+    Path((user_id, record_id)): Path<(Uuid, Uuid)>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AnnotateRecordPayload>,
+) -> Result<(StatusCode, Json<DietRecordAnnotation>), (StatusCode, Json<ErrorResponse>)> {
+    let comment = normalize_comment(payload.comment.as_deref())
+        .map_err(|message| error_response(StatusCode::BAD_REQUEST, &message))?;
+    let tags = normalize_tags(&payload.tags)
+        .map_err(|message| error_response(StatusCode::BAD_REQUEST, &message))?;
+    if payload
+        .corrected_calories
+        .is_some_and(|calories| !(0.0..=MAX_CORRECTED_CALORIES).contains(&calories))
+    {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            &format!(
+                "correctedCalories must be between 0 and {}",
+                MAX_CORRECTED_CALORIES
+            ),
+        ));
+    }
+    if comment.is_none() && payload.corrected_calories.is_none() && tags.is_empty() {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "Provide a comment, correctedCalories or tags",
+        ));
+    }
+
+    ensure_client_record(&state, auth_user.user_id, user_id, record_id).await?;
+
+    let annotation = create_annotation(
+        &state.db,
+        &NewAnnotation {
+            record_id,
+            parent_id: None,
+            author_id: auth_user.user_id,
+            author_role: AUTHOR_NUTRITIONIST,
+            comment: comment.as_deref(),
+            corrected_calories: payload.corrected_calories,
+            tags: &tags,
+        },
+    )
+    .await
+    .map_err(|e| db_error("Annotate Record", e))?;
+
+    info!(
+        "Nutritionist {} annotated diet record {} of {}",
+        auth_user.user_id, record_id, user_id
+    );
+    Ok((StatusCode::CREATED, Json(annotation)))
+}
+
+/// Marks the client's replies to the caller's annotations on the record as read.
+pub async fn mark_client_record_read_handler(
+    auth_user: AuthUser,
+    Path((user_id, record_id)): Path<(Uuid, Uuid)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    ensure_client_record(&state, auth_user.user_id, user_id, record_id).await?;
+
+    let marked = mark_read(&state.db, record_id, auth_user.user_id)
+        .await
+        .map_err(|e| db_error("Mark Replies Read", e))?;
+    Ok(Json(json!({ "marked": marked })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::{register_test_account, setup_db};
+
+    async fn test_user(state: Arc<AppState>, prefix: &str, role: &str) -> AuthUser {
+        let (email, _) = register_test_account(state.clone(), prefix.to_string()).await;
+        let user_id: Uuid =
+            sqlx::query_scalar("UPDATE users SET role = $1 WHERE email = $2 RETURNING id")
+                .bind(role)
+                .bind(&email)
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        AuthUser {
+            user_id,
+            email,
+            role: role.to_string(),
+            session_id: None,
+            api_token_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_workspace_is_limited_to_assigned_clients() {
+        let state = setup_db().await;
+        let admin = test_user(state.clone(), "nutri_admin", "super_admin").await;
+        let nutritionist = test_user(state.clone(), "nutri_operator", "operator").await;
+        let client = test_user(state.clone(), "nutri_client", "user").await;
+        let stranger = test_user(state.clone(), "nutri_stranger", "user").await;
+
+        let (status, _) = assign_client_handler(
+            admin.clone(),
+            Path(nutritionist.user_id),
+            State(state.clone()),
+            Json(AssignClientPayload {
+                user_id: client.user_id,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+
+        let Json(clients) = workspace_clients_handler(nutritionist.clone(), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].user_id, client.user_id);
+
+        assert!(
+            workspace_client_chat_rooms_handler(
+                nutritionist.clone(),
+                Path(client.user_id),
+                State(state.clone()),
+            )
+            .await
+            .is_ok()
+        );
+        let denied = workspace_client_chat_rooms_handler(
+            nutritionist.clone(),
+            Path(stranger.user_id),
+            State(state.clone()),
+        )
+        .await;
+        assert!(matches!(denied, Err((StatusCode::NOT_FOUND, _))));
+
+        assert_eq!(
+            unassign_client_handler(
+                admin.clone(),
+                Path((nutritionist.user_id, client.user_id)),
+                State(state.clone()),
+            )
+            .await
+            .unwrap(),
+            StatusCode::NO_CONTENT
+        );
+
+        for user in [&admin, &nutritionist, &client, &stranger] {
+            let _ = sqlx::query("DELETE FROM users WHERE id = $1")
+                .bind(user.user_id)
+                .execute(&state.db)
+                .await;
+        }
+    }
+}
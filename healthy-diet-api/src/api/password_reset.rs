@@ -0,0 +1,316 @@
+use crate::{
+    api::{
+        email_verification::frontend_link,
+        model::{ErrorResponse, ForgotPasswordPayload, ResetPasswordPayload},
+    },
+    model::AppState,
+    utils::{
+        client_info::ClientInfo,
+        email_token::{EmailTokenError, EmailTokenPurpose, consume_email_token, issue_email_token},
+        hash::hash_password,
+        login_guard::{
+            PASSWORD_RESET_EMAIL_POLICY, PASSWORD_RESET_IP_POLICY, SCOPE_PASSWORD_RESET_EMAIL,
+            SCOPE_PASSWORD_RESET_IP, account_key, locked_until, record_failure,
+        },
+        mailer::EmailMessage,
+        password_policy::password_policy,
+        session::revoke_all_sessions,
+    },
+};
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use validator::Validate;
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+fn validation_error(e: validator::ValidationErrors) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            error: format!("Validation failed: {}", e),
+        }),
+    )
+}
+
+fn too_many_requests(until: DateTime<Utc>) -> (StatusCode, Json<ErrorResponse>) {
+    let retry_after = (until - Utc::now()).num_seconds().max(1);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(ErrorResponse {
+            error: format!(
+                "Too many password reset requests. Try again in {} seconds",
+                retry_after
+            ),
+        }),
+    )
+}
+
+/// Issues a reset token and mails the reset link. Shared with the admin forced reset.
+pub(crate) async fn send_password_reset_email(
+    state: &AppState,
+    user_id: uuid::Uuid,
+    email: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let token = issue_email_token(&state.db, user_id, email, EmailTokenPurpose::PasswordReset)
+        .await
+        .map_err(|e| {
+            error!("Password reset token error: {:?}", e);
+            internal_error()
+        })?;
+
+    let sent = state
+        .mailer
+        .send(&EmailMessage {
+            to: email.to_string(),
+            subject: "Reset your Healthy Diet password".to_string(),
+            body: format!(
+                "Open the link below to choose a new password (valid for 1 hour):\n\n{}\n\nIf you did not ask for this, you can ignore this email.\n",
+                frontend_link("/reset-password", &token)
+            ),
+        })
+        .await;
+    if let Err(e) = sent {
+        error!("Failed to send password reset email: {}", e);
+        return Err(internal_error());
+    }
+    Ok(())
+}
+
+/// Counts a reset request against the email and the caller's IP. Fails while the IP is
+/// locked; returns whether the email is over its limit.
+async fn limit_reset_requests(
+    state: &AppState,
+    email: &str,
+    ip_address: Option<&str>,
+) -> Result<bool, (StatusCode, Json<ErrorResponse>)> {
+    let email_key = account_key(email);
+    let mut keys = vec![(
+        SCOPE_PASSWORD_RESET_EMAIL,
+        email_key.as_str(),
+        PASSWORD_RESET_EMAIL_POLICY,
+    )];
+    keys.extend(ip_address.map(|ip| (SCOPE_PASSWORD_RESET_IP, ip, PASSWORD_RESET_IP_POLICY)));
+
+    let mut email_locked = false;
+    for (scope, key, _) in &keys {
+        let locked = locked_until(&state.db, scope, key).await.map_err(|e| {
+            error!("DB Error (Password Reset Limit): {:?}", e);
+            internal_error()
+        })?;
+        match locked {
+            Some(until) if *scope == SCOPE_PASSWORD_RESET_IP => {
+                warn!("Password reset rejected: IP locked until {}", until);
+                return Err(too_many_requests(until));
+            }
+            Some(_) => email_locked = true,
+            None => {}
+        }
+    }
+    for (scope, key, policy) in &keys {
+        if let Err(e) = record_failure(&state.db, scope, key, policy).await {
+            error!("Failed to count password reset request: {:?}", e);
+        }
+    }
+    Ok(email_locked)
+}
+
+/// Answers the same way, at the same speed, whether or not the email is registered: the mail
+/// goes out in the background and its failures are only logged. Requests are limited per
+/// email and per IP.
+pub async fn forgot_password_handler(
+    State(state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(payload): Json<ForgotPasswordPayload>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    payload.validate().map_err(validation_error)?;
+    let response = Json(json!({
+        "message": "If the email is registered, a reset link has been sent"
+    }));
+
+    let email_locked =
+        limit_reset_requests(&state, &payload.email, client.ip_address.as_deref()).await?;
+    if email_locked {
+        warn!("Password reset skipped: too many requests for this email");
+        return Ok(response);
+    }
+
+    let user_id = sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM users WHERE email = $1")
+        .bind(&payload.email)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Forgot Password): {:?}", e);
+            internal_error()
+        })?;
+
+    match user_id {
+        Some(user_id) => {
+            info!("Password reset requested for user {}", user_id);
+            let email = payload.email.clone();
+            tokio::spawn(async move {
+                // The helper logs its own failures.
+                let _ = send_password_reset_email(&state, user_id, &email).await;
+            });
+        }
+        None => warn!("Password reset requested for unknown email"),
+    }
+
+    Ok(response)
+}
+
+/// Sets a new password and signs the user out of every session.
+pub async fn reset_password_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ResetPasswordPayload>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    payload.validate().map_err(validation_error)?;
+
+    let mut tx = state.db.begin().await.map_err(|e| {
+        error!("DB Error (Reset Password): {:?}", e);
+        internal_error()
+    })?;
+
+    let user_id = consume_email_token(&mut tx, &payload.token, EmailTokenPurpose::PasswordReset)
+        .await
+        .map_err(|e| match e {
+            EmailTokenError::Invalid => {
+                warn!("Invalid password reset token");
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: "Invalid or expired token".to_string(),
+                    }),
+                )
+            }
+            e => {
+                error!("Password reset error: {:?}", e);
+                internal_error()
+            }
+        })?;
+
+    let email = sqlx::query_scalar::<_, String>("SELECT email FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Reset Password): {:?}", e);
+            internal_error()
+        })?;
+    // Returning here rolls the transaction back, so the link can be used again with a better
+    // password.
+    if let Err(e) = password_policy().check(&payload.new_password, &email) {
+        warn!("Password reset rejected by password policy: {}", e);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        ));
+    }
+
+    let password_hash = hash_password(&payload.new_password).map_err(|e| {
+        error!("Password hashing failed: {:?}", e);
+        internal_error()
+    })?;
+
+    // Receiving the reset link also proves the user owns the address.
+    sqlx::query(
+        r#"
+        UPDATE users
+        SET password_hash = $1, email_verified_at = COALESCE(email_verified_at, now())
+        WHERE id = $2
+        "#,
+    )
+    .bind(&password_hash)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        error!("DB Error (Reset Password): {:?}", e);
+        internal_error()
+    })?;
+
+    tx.commit().await.map_err(|e| {
+        error!("DB Error (Reset Password): {:?}", e);
+        internal_error()
+    })?;
+
+    let revoked = revoke_all_sessions(&state.db, user_id).await.map_err(|e| {
+        error!("DB Error (Reset Password Sessions): {:?}", e);
+        internal_error()
+    })?;
+
+    info!(
+        "Password reset for user {}, {} session(s) revoked",
+        user_id, revoked
+    );
+    Ok(Json(json!({ "message": "Password has been reset" })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::{login::login_handler, model::LoginPayload},
+        utils::{
+            client_info::ClientInfo,
+            test::{register_test_account, setup_db},
+        },
+    };
+
+    #[tokio::test]
+    async fn test_password_reset_token_is_single_use() {
+        let state = setup_db().await;
+        let (email, _) = register_test_account(state.clone(), "reset".to_string()).await;
+        let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+
+        let token = issue_email_token(&state.db, user_id, &email, EmailTokenPurpose::PasswordReset)
+            .await
+            .unwrap();
+
+        let reset = reset_password_handler(
+            State(state.clone()),
+            Json(ResetPasswordPayload {
+                token: token.clone(),
+                new_password: "brand-new-password".to_string(),
+            }),
+        )
+        .await;
+        assert!(reset.is_ok());
+
+        let login = login_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(LoginPayload {
+                email: email.clone(),
+                password: "brand-new-password".to_string(),
+            }),
+        )
+        .await;
+        assert!(login.is_ok());
+
+        let reused = reset_password_handler(
+            State(state.clone()),
+            Json(ResetPasswordPayload {
+                token,
+                new_password: "another-password".to_string(),
+            }),
+        )
+        .await;
+        assert!(matches!(reused, Err((StatusCode::BAD_REQUEST, _))));
+    }
+}
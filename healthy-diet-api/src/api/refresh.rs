@@ -1,7 +1,11 @@
 use crate::{
     api::model::{AuthResponse, ErrorResponse, RefreshTokenPayload, User, UserProfile},
     model::AppState,
-    utils::jwt::{decode_jwt, sign_jwt},
+    utils::{
+        client_info::ClientInfo,
+        jwt::{decode_jwt, sign_jwt_with_refresh_ids},
+        refresh_token::{RefreshTokenError, rotate_refresh_token},
+    },
 };
 use axum::{Json, extract::State, http::status::StatusCode};
 use std::sync::Arc;
@@ -9,6 +13,7 @@ use tracing::{error, warn};
 
 pub async fn refresh_handler(
     State(state): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(payload): Json<RefreshTokenPayload>,
 ) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
     let claims = decode_jwt(&payload.refresh_token).map_err(|e| {
@@ -42,6 +47,20 @@ pub async fn refresh_handler(
         )
     })?;
 
+    let presented_jti = claims
+        .jti
+        .as_deref()
+        .and_then(|jti| uuid::Uuid::parse_str(jti).ok())
+        .ok_or_else(|| {
+            warn!("Refresh token without jti rejected for user {}", claims.sub);
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse {
+                    error: "Invalid or expired refresh token".to_string(),
+                }),
+            )
+        })?;
+
     let user = sqlx::query_as::<_, User>(
         "SELECT id, email, password_hash, nickname, avatar_url, role FROM users WHERE id = $1",
     )
@@ -71,17 +90,51 @@ pub async fn refresh_handler(
         }
     };
 
-    let (new_token, new_refresh_token, expires_in) =
-        sign_jwt(&user.id.to_string(), &user.email, &user.role).map_err(|e| {
-            error!("JWT generation error: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
+    let next_ids = rotate_refresh_token(&state.db, presented_jti, user.id, &client)
+        .await
+        .map_err(|e| match e {
+            RefreshTokenError::Reused => (
+                StatusCode::UNAUTHORIZED,
                 Json(ErrorResponse {
-                    error: "Interal server error".to_string(),
+                    error: "Refresh token reuse detected, please log in again".to_string(),
                 }),
-            )
+            ),
+            RefreshTokenError::NotFound
+            | RefreshTokenError::Revoked
+            | RefreshTokenError::Expired => (
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse {
+                    error: "Invalid or expired refresh token".to_string(),
+                }),
+            ),
+            RefreshTokenError::Jwt(_) | RefreshTokenError::Database(_) => {
+                error!("Refresh token rotation error: {:?}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        error: "Internal server error".to_string(),
+                    }),
+                )
+            }
         })?;
 
+    let (new_token, new_refresh_token, expires_in) = sign_jwt_with_refresh_ids(
+        &user.id.to_string(),
+        &user.email,
+        &user.role,
+        3600,
+        &next_ids,
+    )
+    .map_err(|e| {
+        error!("JWT generation error: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "Interal server error".to_string(),
+            }),
+        )
+    })?;
+
     Ok(Json(AuthResponse {
         token: new_token,
         refresh_token: new_refresh_token,
@@ -101,6 +154,7 @@ mod tests {
     use super::*;
     use crate::utils::{
         jwt::sign_jwt,
+        refresh_token::issue_token_pair,
         test::{register_test_account, setup_db},
     };
 
@@ -115,10 +169,20 @@ mod tests {
             .await
             .unwrap();
 
-        let (_, refresh_token, _) = sign_jwt(&user.id.to_string(), &email, "user").unwrap();
+        let (_, refresh_token, _) = issue_token_pair(
+            &state.db,
+            user.id,
+            &email,
+            "user",
+            3600,
+            &ClientInfo::default(),
+        )
+        .await
+        .unwrap();
 
         let payload = RefreshTokenPayload { refresh_token };
-        let result = refresh_handler(State(state.clone()), Json(payload)).await;
+        let result =
+            refresh_handler(State(state.clone()), ClientInfo::default(), Json(payload)).await;
 
         assert!(result.is_ok());
         let res = result.unwrap().0;
@@ -131,6 +195,83 @@ mod tests {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_refresh_token_reuse_revokes_family() {
+        let state = setup_db().await;
+
+        let (email, _) =
+            register_test_account(state.clone(), "test_refresh_reuse".to_string()).await;
+
+        let user = sqlx::query!("SELECT id FROM users WHERE email = $1", email)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+
+        let (_, first_refresh_token, _) = issue_token_pair(
+            &state.db,
+            user.id,
+            &email,
+            "user",
+            3600,
+            &ClientInfo::default(),
+        )
+        .await
+        .unwrap();
+
+        let rotated = refresh_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(RefreshTokenPayload {
+                refresh_token: first_refresh_token.clone(),
+            }),
+        )
+        .await
+        .expect("first refresh should succeed")
+        .0;
+
+        let reused = refresh_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(RefreshTokenPayload {
+                refresh_token: first_refresh_token,
+            }),
+        )
+        .await;
+        assert_eq!(reused.unwrap_err().0, StatusCode::UNAUTHORIZED);
+
+        let after_reuse = refresh_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(RefreshTokenPayload {
+                refresh_token: rotated.refresh_token,
+            }),
+        )
+        .await;
+        assert_eq!(after_reuse.unwrap_err().0, StatusCode::UNAUTHORIZED);
+
+        sqlx::query!("DELETE FROM users WHERE email = $1", email)
+            .execute(&state.db)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_refresh_without_stored_jti_is_rejected() {
+        let state = setup_db().await;
+
+        let (_, refresh_token, _) = sign_jwt(
+            &uuid::Uuid::new_v4().to_string(),
+            "nobody@example.com",
+            "user",
+        )
+        .unwrap();
+
+        let payload = RefreshTokenPayload { refresh_token };
+        let result = refresh_handler(State(state), ClientInfo::default(), Json(payload)).await;
+
+        assert_eq!(result.unwrap_err().0, StatusCode::UNAUTHORIZED);
+    }
+
     #[tokio::test]
     async fn test_refresh_with_bad_token() {
         let state = setup_db().await;
@@ -139,7 +280,7 @@ mod tests {
             refresh_token: "bad.token.value".to_string(),
         };
 
-        let result = refresh_handler(State(state), Json(payload)).await;
+        let result = refresh_handler(State(state), ClientInfo::default(), Json(payload)).await;
 
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().0, StatusCode::UNAUTHORIZED);
@@ -1,7 +1,13 @@
 use crate::{
-    api::model::{AuthResponse, ErrorResponse, ROLE_USER, RegisterPayload, User, UserProfile},
+    api::{
+        email_verification::send_verification_email,
+        model::{AuthResponse, ErrorResponse, ROLE_USER, RegisterPayload, User, UserProfile},
+    },
     model::AppState,
-    utils::{hash::hash_password, jwt::sign_jwt},
+    utils::{
+        client_info::ClientInfo, hash::hash_password, password_policy::password_policy,
+        refresh_token::issue_token_pair,
+    },
 };
 use axum::{Json, extract::State, http::StatusCode};
 use std::sync::Arc;
@@ -11,6 +17,7 @@ use validator::Validate;
 #[instrument(skip(state, payload), fields(email = %payload.email))]
 pub async fn register_handler(
     State(state): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(payload): Json<RegisterPayload>,
 ) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
     if let Err(e) = payload.validate() {
@@ -23,6 +30,16 @@ pub async fn register_handler(
         ));
     }
 
+    if let Err(e) = password_policy().check(&payload.password, &payload.email) {
+        warn!("Registration rejected by password policy: {}", e);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        ));
+    }
+
     let email_exists = sqlx::query!("SELECT id FROM users WHERE email = $1", payload.email)
         .fetch_optional(&state.db)
         .await
@@ -92,18 +109,31 @@ pub async fn register_handler(
     })?;
 
     let (token, refresh_token, expires_in) =
-        sign_jwt(&user.id.to_string(), &user.email, &user.role).map_err(|e| {
-            error!("JWT generation error: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "Failed to create token".to_string(),
-                }),
-            )
-        })?;
+        issue_token_pair(&state.db, user.id, &user.email, &user.role, 3600, &client)
+            .await
+            .map_err(|e| {
+                error!("Token issue error: {:?}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        error: "Failed to create token".to_string(),
+                    }),
+                )
+            })?;
 
     info!("New user registered: {}", user.id);
 
+    let mail_state = state.clone();
+    let (user_id, email) = (user.id, user.email.clone());
+    tokio::spawn(async move {
+        if let Err(e) = send_verification_email(&mail_state, user_id, &email).await {
+            error!(
+                "Failed to send verification email to user {}: {}",
+                user_id, e
+            );
+        }
+    });
+
     Ok(Json(AuthResponse {
         token,
         refresh_token,
@@ -136,7 +166,8 @@ mod tests {
             avatar_url: None,
         };
 
-        let result = register_handler(State(state.clone()), Json(payload)).await;
+        let result =
+            register_handler(State(state.clone()), ClientInfo::default(), Json(payload)).await;
 
         assert!(result.is_ok());
         let response = result.unwrap().0;
@@ -0,0 +1,167 @@
+use crate::{
+    api::model::ErrorResponse,
+    model::AppState,
+    utils::{
+        audit::write_audit_log,
+        jwt::AuthUser,
+        permission::{
+            ADMIN_ACCESS, PERMISSIONS, ROLES_MANAGE, RoleDefinition, is_known_permission,
+            list_roles, role_definition, save_role,
+        },
+    },
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+
+const MAX_ROLE_NAME_LEN: usize = 32;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveRolePayload {
+    pub description: Option<String>,
+    pub agent_role: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+fn bad_request(message: String) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse { error: message }),
+    )
+}
+
+fn is_valid_role_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_ROLE_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+pub async fn list_roles_handler(
+    _admin_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let roles = list_roles(&state.db).await.map_err(|e| {
+        error!("DB Error (List Roles): {:?}", e);
+        internal_error()
+    })?;
+
+    Ok(Json(json!({
+        "roles": roles,
+        "permissions": PERMISSIONS,
+    })))
+}
+
+/// Creates the role or replaces its description, agent role and whole permission set.
+pub async fn save_role_handler(
+    admin_user: AuthUser,
+    Path(role): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SaveRolePayload>,
+) -> Result<Json<RoleDefinition>, (StatusCode, Json<ErrorResponse>)> {
+    let role = role.trim().to_lowercase();
+    if !is_valid_role_name(&role) {
+        return Err(bad_request(format!(
+            "Role names are 1-{} lowercase letters, digits or underscores",
+            MAX_ROLE_NAME_LEN
+        )));
+    }
+
+    let mut permissions: Vec<String> = Vec::new();
+    for permission in payload.permissions {
+        let permission = permission.trim().to_string();
+        if !is_known_permission(&permission) {
+            return Err(bad_request(format!("Unknown permission: {}", permission)));
+        }
+        if !permissions.contains(&permission) {
+            permissions.push(permission);
+        }
+    }
+    permissions.sort();
+
+    // Editing your own role must not take away your way back into this endpoint.
+    if role == admin_user.role
+        && !(permissions.iter().any(|p| p == ADMIN_ACCESS)
+            && permissions.iter().any(|p| p == ROLES_MANAGE))
+    {
+        return Err(bad_request(format!(
+            "Your own role must keep {} and {}",
+            ADMIN_ACCESS, ROLES_MANAGE
+        )));
+    }
+
+    let description = payload
+        .description
+        .map(|description| description.trim().to_string())
+        .filter(|description| !description.is_empty());
+    let agent_role = payload
+        .agent_role
+        .map(|agent_role| agent_role.trim().to_string())
+        .filter(|agent_role| !agent_role.is_empty());
+
+    save_role(
+        &state.db,
+        &role,
+        description.as_deref(),
+        agent_role.as_deref(),
+        &permissions,
+    )
+    .await
+    .map_err(|e| {
+        error!("DB Error (Save Role): {:?}", e);
+        internal_error()
+    })?;
+
+    write_audit_log(
+        &state.db,
+        Some(admin_user.user_id),
+        "ROLE_SAVED",
+        "role",
+        &role,
+        json!({ "permissions": permissions, "agentRole": agent_role }),
+    )
+    .await;
+
+    let saved = role_definition(&state.db, &role)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Save Role): {:?}", e);
+            internal_error()
+        })?
+        .ok_or_else(internal_error)?;
+
+    info!("Admin {} saved role {}", admin_user.user_id, role);
+    Ok(Json(saved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_name_validation() {
+        assert!(is_valid_role_name("nutritionist"));
+        assert!(is_valid_role_name("content_editor_2"));
+        assert!(!is_valid_role_name(""));
+        assert!(!is_valid_role_name("Admin"));
+        assert!(!is_valid_role_name("rag.delete"));
+        assert!(!is_valid_role_name(&"a".repeat(MAX_ROLE_NAME_LEN + 1)));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    api::model::ErrorResponse,
+    model::AppState,
+    utils::{
+        jwt::AuthUser,
+        session::{SessionItem, list_active_sessions, revoke_all_sessions, revoke_session},
+    },
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info};
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+pub async fn list_sessions_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<SessionItem>>, (StatusCode, Json<ErrorResponse>)> {
+    let mut sessions = list_active_sessions(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (List Sessions): {:?}", e);
+            internal_error()
+        })?;
+
+    for session in &mut sessions {
+        session.current = auth_user.session_id == Some(session.id);
+    }
+
+    Ok(Json(sessions))
+}
+
+pub async fn revoke_session_handler(
+    auth_user: AuthUser,
+    Path(session_id): Path<uuid::Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let revoked = revoke_session(&state.db, session_id, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Revoke Session): {:?}", e);
+            internal_error()
+        })?;
+
+    if !revoked {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Session not found".to_string(),
+            }),
+        ));
+    }
+
+    info!("User {} revoked session {}", auth_user.user_id, session_id);
+    Ok(Json(json!({ "message": "Session revoked" })))
+}
+
+/// Signs the user out everywhere, including the session that made this request.
+pub async fn revoke_all_sessions_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let revoked = revoke_all_sessions(&state.db, auth_user.user_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Revoke All Sessions): {:?}", e);
+            internal_error()
+        })?;
+
+    info!(
+        "User {} signed out of {} session(s)",
+        auth_user.user_id, revoked
+    );
+    Ok(Json(json!({
+        "message": "Signed out of all sessions",
+        "revoked": revoked,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::{model::RefreshTokenPayload, refresh::refresh_handler},
+        utils::{
+            client_info::ClientInfo,
+            jwt::decode_jwt,
+            refresh_token::issue_token_pair,
+            test::{register_test_account, setup_db},
+        },
+    };
+    use axum::extract::FromRequestParts;
+
+    #[tokio::test]
+    async fn test_revoked_session_rejects_refresh_and_access() {
+        let state = setup_db().await;
+        let (email, _) = register_test_account(state.clone(), "session".to_string()).await;
+        let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+
+        let (access_token, refresh_token, _) = issue_token_pair(
+            &state.db,
+            user_id,
+            &email,
+            "user",
+            3600,
+            &ClientInfo::default(),
+        )
+        .await
+        .unwrap();
+        let session_id = decode_jwt(&refresh_token)
+            .unwrap()
+            .fam
+            .and_then(|fam| uuid::Uuid::parse_str(&fam).ok())
+            .unwrap();
+
+        let auth_user = AuthUser {
+            user_id,
+            email: email.clone(),
+            role: "user".to_string(),
+            session_id: Some(session_id),
+            api_token_id: None,
+        };
+        let Json(sessions) = list_sessions_handler(auth_user, State(state.clone()))
+            .await
+            .unwrap();
+        assert!(sessions.iter().any(|s| s.id == session_id && s.current));
+
+        let auth_user = AuthUser {
+            user_id,
+            email: email.clone(),
+            role: "user".to_string(),
+            session_id: Some(session_id),
+            api_token_id: None,
+        };
+        let revoked =
+            revoke_session_handler(auth_user, Path(session_id), State(state.clone())).await;
+        assert!(revoked.is_ok());
+
+        let refreshed = refresh_handler(
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(RefreshTokenPayload { refresh_token }),
+        )
+        .await;
+        assert!(matches!(refreshed, Err((StatusCode::UNAUTHORIZED, _))));
+
+        let request = axum::http::Request::builder()
+            .header("Authorization", format!("Bearer {}", access_token))
+            .body(())
+            .unwrap();
+        let (mut parts, _) = request.into_parts();
+        let rejected = AuthUser::from_request_parts(&mut parts, &state).await;
+        assert_eq!(
+            rejected.err().map(|response| response.status()),
+            Some(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
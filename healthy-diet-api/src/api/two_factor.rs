@@ -0,0 +1,443 @@
+use crate::{
+    api::{
+        login::{build_auth_response, locked_error},
+        model::{
+            AuthResponse, ErrorResponse, TotpCodePayload, TotpConfirmPayload, TotpConfirmResponse,
+            TotpSetupPayload, TotpSetupResponse, TwoFactorVerifyPayload, User,
+        },
+    },
+    model::AppState,
+    utils::{
+        audit::write_audit_log,
+        client_info::ClientInfo,
+        jwt::AuthUser,
+        login_guard::{
+            SCOPE_TWO_FACTOR, TWO_FACTOR_POLICY, clear_failures, locked_until, record_failure,
+        },
+        permission::is_admin,
+        totp::{
+            TwoFactorError, accept_totp_code, decode_challenge_token, generate_recovery_codes,
+            generate_totp_secret, is_two_factor_enabled, otpauth_uri, replace_recovery_codes,
+            start_enrollment, use_recovery_code,
+        },
+    },
+};
+use axum::{Json, extract::State, http::StatusCode};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+fn error_response(status: StatusCode, message: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        status,
+        Json(ErrorResponse {
+            error: message.to_string(),
+        }),
+    )
+}
+
+fn two_factor_internal_error(e: TwoFactorError) -> (StatusCode, Json<ErrorResponse>) {
+    error!("Two-factor error: {:?}", e);
+    internal_error()
+}
+
+async fn load_user(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<User, (StatusCode, Json<ErrorResponse>)> {
+    sqlx::query_as::<_, User>(
+        "SELECT id, email, password_hash, nickname, avatar_url, role FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        error!("DB Error (2FA User Lookup): {:?}", e);
+        internal_error()
+    })?
+    .ok_or_else(|| error_response(StatusCode::UNAUTHORIZED, "User not found"))
+}
+
+/// Account being enrolled: taken from the login challenge when one is given, otherwise from
+/// the bearer token of an admin who is already signed in.
+async fn enrolling_user(
+    state: &AppState,
+    auth_user: Option<AuthUser>,
+    challenge_token: Option<&str>,
+) -> Result<User, (StatusCode, Json<ErrorResponse>)> {
+    let user_id = match (challenge_token, auth_user) {
+        (Some(token), _) => decode_challenge_token(token).ok_or_else(|| {
+            error_response(
+                StatusCode::UNAUTHORIZED,
+                "Invalid or expired challenge token",
+            )
+        })?,
+        (None, Some(auth_user)) => auth_user.user_id,
+        (None, None) => {
+            return Err(error_response(
+                StatusCode::UNAUTHORIZED,
+                "Challenge token or Bearer token required",
+            ));
+        }
+    };
+
+    let user = load_user(state, user_id).await?;
+    let admin_account = is_admin(&state.db, &user.role).await.map_err(|e| {
+        error!("DB Error (2FA Permission Check): {:?}", e);
+        internal_error()
+    })?;
+    if !admin_account {
+        return Err(error_response(
+            StatusCode::FORBIDDEN,
+            "Two-factor authentication is only available to admin accounts",
+        ));
+    }
+    Ok(user)
+}
+
+async fn ensure_not_locked(
+    state: &AppState,
+    key: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let locked = locked_until(&state.db, SCOPE_TWO_FACTOR, key)
+        .await
+        .map_err(|e| {
+            error!("DB Error (2FA Lockout Check): {:?}", e);
+            internal_error()
+        })?;
+    match locked {
+        Some(until) => {
+            warn!(
+                "Two-factor attempt rejected: user {} locked until {}",
+                key, until
+            );
+            Err(locked_error(until))
+        }
+        None => Ok(()),
+    }
+}
+
+async fn reject_code(state: &AppState, key: &str) -> (StatusCode, Json<ErrorResponse>) {
+    warn!("Invalid two-factor code for user {}", key);
+    match record_failure(&state.db, SCOPE_TWO_FACTOR, key, &TWO_FACTOR_POLICY).await {
+        Ok(Some(until)) => return locked_error(until),
+        Ok(None) => {}
+        Err(e) => error!("Failed to record two-factor failure: {:?}", e),
+    }
+    error_response(StatusCode::UNAUTHORIZED, "Invalid two-factor code")
+}
+
+async fn reset_failures(state: &AppState, key: &str) {
+    if let Err(e) = clear_failures(&state.db, SCOPE_TWO_FACTOR, key).await {
+        warn!(
+            "Failed to reset two-factor failures for user {}: {:?}",
+            key, e
+        );
+    }
+}
+
+/// Exchanges a login challenge plus a TOTP or recovery code for the real token pair.
+pub async fn verify_two_factor_handler(
+    State(state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(payload): Json<TwoFactorVerifyPayload>,
+) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let user_id = decode_challenge_token(&payload.challenge_token).ok_or_else(|| {
+        error_response(
+            StatusCode::UNAUTHORIZED,
+            "Invalid or expired challenge token",
+        )
+    })?;
+    let key = user_id.to_string();
+    ensure_not_locked(&state, &key).await?;
+
+    let user = load_user(&state, user_id).await?;
+    let enabled = is_two_factor_enabled(&state.db, user.id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (2FA Lookup): {:?}", e);
+            internal_error()
+        })?;
+    if !enabled {
+        return Err(error_response(
+            StatusCode::CONFLICT,
+            "Two-factor enrollment required",
+        ));
+    }
+
+    let (accepted, used_recovery_code) = match (&payload.code, &payload.recovery_code) {
+        (Some(code), None) => (
+            accept_totp_code(&state.db, user.id, &user.email, code, false)
+                .await
+                .map_err(two_factor_internal_error)?,
+            false,
+        ),
+        (None, Some(recovery_code)) => (
+            use_recovery_code(&state.db, user.id, recovery_code)
+                .await
+                .map_err(|e| {
+                    error!("DB Error (Recovery Code): {:?}", e);
+                    internal_error()
+                })?,
+            true,
+        ),
+        _ => {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                "Provide exactly one of code or recoveryCode",
+            ));
+        }
+    };
+
+    if !accepted {
+        return Err(reject_code(&state, &key).await);
+    }
+    reset_failures(&state, &key).await;
+
+    if used_recovery_code {
+        write_audit_log(
+            &state.db,
+            Some(user.id),
+            "TWO_FACTOR_RECOVERY_CODE_USED",
+            "user",
+            &key,
+            json!({}),
+        )
+        .await;
+    }
+
+    let response = build_auth_response(&state, user, &client).await?;
+    info!("Second factor verified for user {}", response.user.id);
+    Ok(Json(response))
+}
+
+/// Starts (or restarts) TOTP enrollment and returns the secret for the authenticator app.
+pub async fn totp_setup_handler(
+    auth_user: Option<AuthUser>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<TotpSetupPayload>,
+) -> Result<Json<TotpSetupResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let user = enrolling_user(&state, auth_user, payload.challenge_token.as_deref()).await?;
+
+    let secret = generate_totp_secret();
+    let otpauth_uri = otpauth_uri(&secret, &user.email).map_err(two_factor_internal_error)?;
+    let started = start_enrollment(&state.db, user.id, &secret)
+        .await
+        .map_err(|e| {
+            error!("DB Error (TOTP Setup): {:?}", e);
+            internal_error()
+        })?;
+    if !started {
+        return Err(error_response(
+            StatusCode::CONFLICT,
+            "Two-factor authentication is already enabled",
+        ));
+    }
+
+    info!("TOTP enrollment started for user {}", user.id);
+    Ok(Json(TotpSetupResponse {
+        secret,
+        otpauth_uri,
+    }))
+}
+
+/// Activates the pending TOTP secret once the user proves their app produces valid codes, and
+/// hands out the recovery codes. Enrolling from a login challenge also completes that login.
+pub async fn totp_confirm_handler(
+    auth_user: Option<AuthUser>,
+    State(state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(payload): Json<TotpConfirmPayload>,
+) -> Result<Json<TotpConfirmResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let from_challenge = payload.challenge_token.is_some();
+    let user = enrolling_user(&state, auth_user, payload.challenge_token.as_deref()).await?;
+    let key = user.id.to_string();
+    ensure_not_locked(&state, &key).await?;
+
+    let confirmed = accept_totp_code(&state.db, user.id, &user.email, &payload.code, true)
+        .await
+        .map_err(two_factor_internal_error)?;
+    if !confirmed {
+        return Err(reject_code(&state, &key).await);
+    }
+    reset_failures(&state, &key).await;
+
+    let recovery_codes = generate_recovery_codes();
+    replace_recovery_codes(&state.db, user.id, &recovery_codes)
+        .await
+        .map_err(two_factor_internal_error)?;
+
+    write_audit_log(
+        &state.db,
+        Some(user.id),
+        "TWO_FACTOR_ENABLED",
+        "user",
+        &key,
+        json!({}),
+    )
+    .await;
+    info!("TOTP enrollment confirmed for user {}", user.id);
+
+    let auth = if from_challenge {
+        Some(build_auth_response(&state, user, &client).await?)
+    } else {
+        None
+    };
+
+    Ok(Json(TotpConfirmResponse {
+        recovery_codes,
+        auth,
+    }))
+}
+
+/// Replaces every recovery code; requires a current TOTP code so a stolen session alone
+/// cannot mint new ones.
+pub async fn regenerate_recovery_codes_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<TotpCodePayload>,
+) -> Result<Json<TotpConfirmResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let key = auth_user.user_id.to_string();
+    ensure_not_locked(&state, &key).await?;
+
+    let accepted = accept_totp_code(
+        &state.db,
+        auth_user.user_id,
+        &auth_user.email,
+        &payload.code,
+        false,
+    )
+    .await
+    .map_err(two_factor_internal_error)?;
+    if !accepted {
+        return Err(reject_code(&state, &key).await);
+    }
+    reset_failures(&state, &key).await;
+
+    let recovery_codes = generate_recovery_codes();
+    replace_recovery_codes(&state.db, auth_user.user_id, &recovery_codes)
+        .await
+        .map_err(two_factor_internal_error)?;
+
+    write_audit_log(
+        &state.db,
+        Some(auth_user.user_id),
+        "TWO_FACTOR_RECOVERY_CODES_REGENERATED",
+        "user",
+        &key,
+        json!({}),
+    )
+    .await;
+
+    Ok(Json(TotpConfirmResponse {
+        recovery_codes,
+        auth: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{
+        test::{register_test_account, setup_db},
+        totp::{TOTP_ISSUER, issue_challenge_token},
+    };
+    use totp_rs::{Algorithm, Secret, TOTP};
+
+    fn current_code(secret: &str, email: &str) -> String {
+        TOTP::new(
+            Algorithm::SHA1,
+            6,
+            1,
+            30,
+            Secret::Encoded(secret.to_string()).to_bytes().unwrap(),
+            Some(TOTP_ISSUER.to_string()),
+            email.to_string(),
+        )
+        .unwrap()
+        .generate_current()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_enroll_confirm_and_recovery_code_login() {
+        let state = setup_db().await;
+        let (email, _) = register_test_account(state.clone(), "two_factor".to_string()).await;
+        let user_id: Uuid =
+            sqlx::query_scalar("UPDATE users SET role = 'operator' WHERE email = $1 RETURNING id")
+                .bind(&email)
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        let challenge = issue_challenge_token(user_id, &email).unwrap();
+
+        let Json(setup) = totp_setup_handler(
+            None,
+            State(state.clone()),
+            Json(TotpSetupPayload {
+                challenge_token: Some(challenge.clone()),
+            }),
+        )
+        .await
+        .unwrap();
+
+        let wrong = totp_confirm_handler(
+            None,
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(TotpConfirmPayload {
+                challenge_token: Some(challenge.clone()),
+                code: "000000".to_string(),
+            }),
+        )
+        .await;
+        assert!(matches!(wrong, Err((StatusCode::UNAUTHORIZED, _))));
+
+        let Json(confirmed) = totp_confirm_handler(
+            None,
+            State(state.clone()),
+            ClientInfo::default(),
+            Json(TotpConfirmPayload {
+                challenge_token: Some(challenge.clone()),
+                code: current_code(&setup.secret, &email),
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(confirmed.auth.is_some());
+        assert_eq!(confirmed.recovery_codes.len(), 10);
+
+        let verify_with_recovery = || {
+            verify_two_factor_handler(
+                State(state.clone()),
+                ClientInfo::default(),
+                Json(TwoFactorVerifyPayload {
+                    challenge_token: challenge.clone(),
+                    code: None,
+                    recovery_code: Some(confirmed.recovery_codes[0].clone()),
+                }),
+            )
+        };
+        let Json(auth) = verify_with_recovery().await.unwrap();
+        assert_eq!(auth.user.role, "operator");
+        assert!(matches!(
+            verify_with_recovery().await,
+            Err((StatusCode::UNAUTHORIZED, _))
+        ));
+
+        let _ = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&state.db)
+            .await;
+    }
+}
@@ -1,11 +1,25 @@
 use crate::{
-    api::model::{ErrorResponse, UpdateProfilePayload, UserDetailResponse},
+    api::{
+        login::locked_error,
+        model::{ChangePasswordPayload, ErrorResponse, UpdateProfilePayload, UserDetailResponse},
+    },
     model::AppState,
-    utils::jwt::AuthUser,
+    utils::{
+        hash::{hash_password, verify_password},
+        jwt::AuthUser,
+        login_guard::{
+            ADMIN_ACCOUNT_POLICY, SCOPE_ACCOUNT, USER_ACCOUNT_POLICY, account_key, clear_failures,
+            locked_until, record_failure,
+        },
+        password_policy::password_policy,
+        permission::is_admin,
+        session::revoke_other_sessions,
+    },
 };
 use axum::{Json, extract::State, http::StatusCode};
+use serde_json::json;
 use std::sync::Arc;
-use tracing::error;
+use tracing::{error, info, warn};
 
 pub async fn get_profile_handler(
     auth_user: AuthUser,
@@ -104,6 +118,134 @@ pub async fn update_user_profile_handler(
     }))
 }
 
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: "Internal server error".to_string(),
+        }),
+    )
+}
+
+/// Changes the password after re-checking the current one, then signs out every other
+/// session. Wrong current passwords count towards the same lockout as failed logins.
+pub async fn change_password_handler(
+    auth_user: AuthUser,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ChangePasswordPayload>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let key = account_key(&auth_user.email);
+    let locked = locked_until(&state.db, SCOPE_ACCOUNT, &key)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Change Password Lockout): {:?}", e);
+            internal_error()
+        })?;
+    if let Some(until) = locked {
+        return Err(locked_error(until));
+    }
+
+    let current_hash =
+        sqlx::query_scalar::<_, String>("SELECT password_hash FROM users WHERE id = $1")
+            .bind(auth_user.user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| {
+                error!("DB Error (Change Password): {:?}", e);
+                internal_error()
+            })?
+            .ok_or((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "User not found".to_string(),
+                }),
+            ))?;
+
+    if !verify_password(&payload.current_password, &current_hash).unwrap_or(false) {
+        warn!(
+            "Password change rejected: wrong current password for user {}",
+            auth_user.user_id
+        );
+        let admin_account = is_admin(&state.db, &auth_user.role)
+            .await
+            .unwrap_or_else(|e| {
+                error!("DB Error (Permission Check): {:?}", e);
+                true
+            });
+        let policy = if admin_account {
+            ADMIN_ACCOUNT_POLICY
+        } else {
+            USER_ACCOUNT_POLICY
+        };
+        match record_failure(&state.db, SCOPE_ACCOUNT, &key, &policy).await {
+            Ok(Some(until)) => return Err(locked_error(until)),
+            Ok(None) => {}
+            Err(e) => error!("Failed to record login failure: {:?}", e),
+        }
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Current password is incorrect".to_string(),
+            }),
+        ));
+    }
+    if let Err(e) = clear_failures(&state.db, SCOPE_ACCOUNT, &key).await {
+        warn!(
+            "Failed to reset login failures for user {}: {:?}",
+            auth_user.user_id, e
+        );
+    }
+
+    if payload.new_password == payload.current_password {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "New password must differ from the current one".to_string(),
+            }),
+        ));
+    }
+    password_policy()
+        .check(&payload.new_password, &auth_user.email)
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+
+    let password_hash = hash_password(&payload.new_password).map_err(|e| {
+        error!("Password hashing failed: {:?}", e);
+        internal_error()
+    })?;
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+        .bind(&password_hash)
+        .bind(auth_user.user_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Change Password): {:?}", e);
+            internal_error()
+        })?;
+
+    let revoked = revoke_other_sessions(&state.db, auth_user.user_id, auth_user.session_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Change Password Sessions): {:?}", e);
+            internal_error()
+        })?;
+
+    info!(
+        "Password changed for user {}, {} other session(s) revoked",
+        auth_user.user_id, revoked
+    );
+    Ok(Json(json!({
+        "message": "Password has been changed",
+        "revokedSessions": revoked,
+    })))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,6 +266,8 @@ mod tests {
             user_id: user.id,
             email: email.clone(),
             role: "user".to_string(),
+            session_id: None,
+            api_token_id: None,
         };
 
         let result = get_profile_handler(auth_user, State(state.clone())).await;
@@ -152,6 +296,8 @@ mod tests {
             user_id: user.id,
             email: email.clone(),
             role: "user".to_string(),
+            session_id: None,
+            api_token_id: None,
         };
 
         let payload = UpdateProfilePayload {
@@ -197,4 +343,54 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn test_change_password_requires_current_password() {
+        let state = setup_db().await;
+
+        let (email, password) =
+            register_test_account(state.clone(), "password_change".to_string()).await;
+        let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        let auth_user = AuthUser {
+            user_id,
+            email: email.clone(),
+            role: "user".to_string(),
+            session_id: None,
+            api_token_id: None,
+        };
+        let change = |current: &str, new: &str| {
+            change_password_handler(
+                auth_user.clone(),
+                State(state.clone()),
+                Json(ChangePasswordPayload {
+                    current_password: current.to_string(),
+                    new_password: new.to_string(),
+                }),
+            )
+        };
+
+        let wrong = change("not-my-password", "a fresh passphrase").await;
+        assert!(matches!(wrong, Err((StatusCode::UNAUTHORIZED, _))));
+
+        let weak = change(&password, "short").await;
+        assert!(matches!(weak, Err((StatusCode::BAD_REQUEST, _))));
+
+        assert!(change(&password, "a fresh passphrase").await.is_ok());
+        let stored: String = sqlx::query_scalar("SELECT password_hash FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert!(verify_password("a fresh passphrase", &stored).unwrap());
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+    }
 }
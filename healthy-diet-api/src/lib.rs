@@ -1,5 +1,5 @@
 pub mod api;
-pub mod discord;
 pub mod model;
+pub mod oauth;
 pub mod router;
 pub mod utils;
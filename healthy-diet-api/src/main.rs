@@ -1,7 +1,14 @@
 use healthy_diet_api_server::{
     model::{AppState, ENVKey},
+    oauth::provider::IdentityProviders,
     router::create_app,
-    utils::rag_worker::start_rag_worker,
+    utils::{
+        account_jobs::start_account_job_worker, client_info::trusted_proxies,
+        diet_jobs::start_diet_job_worker, food_detector::food_detector_from_env,
+        hash::argon2_params, jwt_keys::keyring, mailer::mailer_from_env,
+        nutrition_engine::NutritionEngine, password_policy::password_policy,
+        rag_worker::start_rag_worker,
+    },
 };
 use sqlx::postgres::PgPoolOptions;
 use std::fs;
@@ -34,6 +41,21 @@ async fn main() {
         )
         .init();
 
+    // Fail at startup rather than on the first login if the JWT keys, password or proxy
+    // settings are misconfigured.
+    keyring();
+    argon2_params();
+    if trusted_proxies().is_empty() {
+        tracing::info!("No TRUSTED_PROXIES; client IPs come from the connection");
+    }
+    let policy = password_policy();
+    if let Some(breached) = &policy.breached {
+        tracing::info!(
+            "Checking passwords against breached hashes in {}",
+            breached.path().display()
+        );
+    }
+
     let database_url = env::var(ENVKey::DATABASE_URL).expect("DATABASE_URL must be set");
     let pool = PgPoolOptions::new()
         .max_connections(10)
@@ -50,8 +72,14 @@ async fn main() {
     let app_state = Arc::new(AppState {
         db: pool,
         ai_prompt_config,
+        identity_providers: Arc::new(IdentityProviders::from_env()),
+        mailer: mailer_from_env(),
+        food_detector: food_detector_from_env(),
+        nutrition_engine: Arc::new(NutritionEngine::from_env()),
     });
     start_rag_worker(app_state.clone());
+    start_account_job_worker(app_state.clone());
+    start_diet_job_worker(app_state.clone());
 
     let app = create_app(app_state);
 
@@ -59,10 +87,13 @@ async fn main() {
     tracing::info!("[Healthy-Diet-API] Start Server http://{}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
 }
 
 async fn shutdown_signal() {
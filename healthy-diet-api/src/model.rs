@@ -1,26 +1,70 @@
+use crate::{
+    oauth::provider::IdentityProviders,
+    utils::{food_detector::FoodDetector, mailer::Mailer, nutrition_engine::NutritionEngine},
+};
 use serde_json::Value;
 use sqlx::PgPool;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
     pub ai_prompt_config: Value,
+    pub identity_providers: Arc<IdentityProviders>,
+    pub mailer: Arc<dyn Mailer>,
+    pub food_detector: Arc<dyn FoodDetector>,
+    pub nutrition_engine: Arc<NutritionEngine>,
 }
 
 pub struct APIRouter;
 
 impl APIRouter {
     pub const PING: &'static str = "/api/ping";
+    pub const JWKS: &'static str = "/.well-known/jwks.json";
     pub const DISOCRD_LOGIN: &'static str = "/auth/discord/login";
     pub const DISOCRD_CALLBACK: &'static str = "/auth/discord/callbck";
+    pub const DISCORD_LINK: &'static str = "/auth/discord/link";
+    pub const OAUTH_LOGIN: &'static str = "/auth/oauth/{provider}/login";
+    pub const OAUTH_CALLBACK: &'static str = "/auth/oauth/{provider}/callback";
+    pub const OAUTH_LINK: &'static str = "/auth/oauth/link";
+    pub const EMAIL_VERIFY: &'static str = "/auth/email/verify";
+    pub const PASSWORD_FORGOT: &'static str = "/auth/password/forgot";
+    pub const PASSWORD_RESET: &'static str = "/auth/password/reset";
+    pub const EMAIL_VERIFICATION_REQUEST: &'static str = "/api/auth/email/verification";
     pub const REGISTER: &'static str = "/auth/register";
     pub const LOGIN: &'static str = "/auth/login";
     pub const ADMIN_LOGIN: &'static str = "/auth/admin/login";
+    pub const TWO_FACTOR_VERIFY: &'static str = "/auth/2fa/verify";
+    pub const TOTP_SETUP: &'static str = "/auth/2fa/totp/setup";
+    pub const TOTP_CONFIRM: &'static str = "/auth/2fa/totp/confirm";
+    pub const RECOVERY_CODES: &'static str = "/api/auth/2fa/recovery-codes";
     pub const REFRESH_TOKEN: &'static str = "/api/auth/refresh";
+    pub const LOGOUT: &'static str = "/api/auth/logout";
     pub const ADMIN: &'static str = "/admin";
     pub const ADMIN_ME: &'static str = "/me";
     pub const ADMIN_USERS: &'static str = "/users";
     pub const ADMIN_USER_DETAIL: &'static str = "/users/{user_id}";
+    pub const ADMIN_USER_UNLOCK: &'static str = "/users/{user_id}/unlock";
+    pub const ADMIN_USER_ROLE: &'static str = "/users/{user_id}/role";
+    pub const ADMIN_USER_SUSPEND: &'static str = "/users/{user_id}/suspend";
+    pub const ADMIN_USER_UNSUSPEND: &'static str = "/users/{user_id}/unsuspend";
+    pub const ADMIN_USER_PASSWORD_RESET: &'static str = "/users/{user_id}/password-reset";
+    pub const ADMIN_NUTRITIONIST_CLIENTS: &'static str = "/nutritionists/{nutritionist_id}/clients";
+    pub const ADMIN_NUTRITIONIST_CLIENT_DETAIL: &'static str =
+        "/nutritionists/{nutritionist_id}/clients/{user_id}";
+    pub const ADMIN_WORKSPACE_CLIENTS: &'static str = "/workspace/clients";
+    pub const ADMIN_WORKSPACE_CLIENT_DIET_RECORDS: &'static str =
+        "/workspace/clients/{user_id}/diet-records";
+    pub const ADMIN_WORKSPACE_CLIENT_RECORD_ANNOTATIONS: &'static str =
+        "/workspace/clients/{user_id}/diet-records/{record_id}/annotations";
+    pub const ADMIN_WORKSPACE_CLIENT_RECORD_ANNOTATIONS_READ: &'static str =
+        "/workspace/clients/{user_id}/diet-records/{record_id}/annotations/read";
+    pub const ADMIN_WORKSPACE_CLIENT_CHAT_ROOMS: &'static str =
+        "/workspace/clients/{user_id}/chat-rooms";
+    pub const ADMIN_WORKSPACE_CLIENT_CHAT_ROOM: &'static str =
+        "/workspace/clients/{user_id}/chat-rooms/{room_id}";
+    pub const ADMIN_FOODS_IMPORT: &'static str = "/foods/import";
+    pub const ADMIN_DATASET_YOLO: &'static str = "/datasets/yolo";
     pub const ADMIN_ROUTE_CONTROLS: &'static str = "/route-controls";
     pub const ADMIN_ROUTE_CONTROL_DETAIL: &'static str = "/route-controls/{route_key}";
     pub const ADMIN_ANNOUNCEMENTS: &'static str = "/announcements";
@@ -46,6 +90,10 @@ impl APIRouter {
     pub const ADMIN_KNOWLEDGE_GRAPH_DOCUMENT_DETAIL: &'static str =
         "/api/admin/knowledge-graph/documents/{document_id}";
     pub const ADMIN_AGENT_TOKEN: &'static str = "/agent-token";
+    pub const ADMIN_API_TOKENS: &'static str = "/api-tokens";
+    pub const ADMIN_API_TOKEN_DETAIL: &'static str = "/api-tokens/{token_id}";
+    pub const ADMIN_ROLES: &'static str = "/roles";
+    pub const ADMIN_ROLE_DETAIL: &'static str = "/roles/{role}";
     pub const NEWS_SYNC: &'static str = "/api/news/sync";
     pub const NEWS: &'static str = "/api/news";
     pub const NEWS_DETAIL: &'static str = "/api/news/{id}";
@@ -55,9 +103,34 @@ impl APIRouter {
     pub const RAG_SOURCE_FILE: &'static str = "/api/rag/sources/{document_id}/file";
     pub const RAG_SOURCE_PREVIEW: &'static str = "/api/rag/sources/{document_id}/preview";
     pub const PROFILE: &'static str = "/api/user/profile";
+    pub const USER_ACCOUNT: &'static str = "/api/user";
+    pub const USER_ACCOUNT_DELETION_CANCEL: &'static str = "/api/user/deletion/cancel";
+    pub const USER_EXPORT: &'static str = "/api/user/export";
+    pub const USER_PASSWORD: &'static str = "/api/user/password";
+    pub const USER_SESSIONS: &'static str = "/api/user/sessions";
+    pub const USER_SESSION_DETAIL: &'static str = "/api/user/sessions/{session_id}";
+    pub const CAREGIVERS: &'static str = "/api/caregivers";
+    pub const CAREGIVER_ACCEPT: &'static str = "/api/caregivers/accept";
+    pub const CAREGIVER_DETAIL: &'static str = "/api/caregivers/{link_id}";
+    pub const CAREGIVING: &'static str = "/api/caregiving";
+    pub const CAREGIVING_DIET_RECORDS: &'static str = "/api/caregiving/{link_id}/diet-records";
+    pub const CAREGIVING_DIET_STATS: &'static str = "/api/caregiving/{link_id}/diet-stats";
+    pub const CAREGIVING_CHAT_SUMMARIES: &'static str = "/api/caregiving/{link_id}/chat-summaries";
+    pub const CALCULATOR: &'static str = "/api/calculator";
     pub const DIET: &'static str = "/api/diet";
+    pub const DIET_JOBS: &'static str = "/api/diet/jobs";
+    pub const DIET_JOB_DETAIL: &'static str = "/api/diet/jobs/{job_id}";
+    pub const DIET_JOB_EVENTS: &'static str = "/api/diet/jobs/{job_id}/events";
+    pub const FOODS: &'static str = "/api/foods";
+    pub const FOOD_DETAIL: &'static str = "/api/foods/{food_id}";
     pub const HEALTH: &'static str = "/api/health";
     pub const DIET_RECORD: &'static str = "/api/diet_record";
+    pub const DIET_RECORD_DETAIL: &'static str = "/api/diet_record/{record_id}";
+    pub const DIET_RECORD_ANNOTATIONS_UNREAD: &'static str = "/api/diet_record/annotations/unread";
+    pub const DIET_RECORD_ANNOTATIONS_READ: &'static str =
+        "/api/diet_record/{record_id}/annotations/read";
+    pub const DIET_RECORD_ANNOTATION_REPLIES: &'static str =
+        "/api/diet_record/{record_id}/annotations/{annotation_id}/replies";
     pub const DIET_IMAGE: &'static str = "/api/diet_image";
     pub const RECORD: &'static str = "/api/record";
     pub const MONTH_STATS: &'static str = "/api/month_stats";
@@ -80,9 +153,44 @@ impl ENVKey {
     pub const CORS_ALLOWED_ORIGINS: &'static str = "CORS_ALLOWED_ORIGINS";
     pub const GEMINI_API_KEY: &'static str = "GEMINI_API_KEY";
     pub const JWT_SECRET: &'static str = "JWT_SECRET";
+    pub const JWT_ALGORITHM: &'static str = "JWT_ALGORITHM";
+    pub const JWT_PRIVATE_KEY_FILE: &'static str = "JWT_PRIVATE_KEY_FILE";
+    pub const JWT_KEY_ID: &'static str = "JWT_KEY_ID";
+    pub const JWT_VERIFICATION_KEYS_FILE: &'static str = "JWT_VERIFICATION_KEYS_FILE";
+    pub const JWT_ACCEPT_LEGACY_HS256: &'static str = "JWT_ACCEPT_LEGACY_HS256";
+    pub const ARGON2_MEMORY_KIB: &'static str = "ARGON2_MEMORY_KIB";
+    pub const ARGON2_ITERATIONS: &'static str = "ARGON2_ITERATIONS";
+    pub const ARGON2_PARALLELISM: &'static str = "ARGON2_PARALLELISM";
+    pub const PASSWORD_MIN_LENGTH: &'static str = "PASSWORD_MIN_LENGTH";
+    pub const PASSWORD_MAX_LENGTH: &'static str = "PASSWORD_MAX_LENGTH";
+    pub const PASSWORD_BREACHED_HASHES_FILE: &'static str = "PASSWORD_BREACHED_HASHES_FILE";
+    pub const ACCOUNT_DELETION_GRACE_DAYS: &'static str = "ACCOUNT_DELETION_GRACE_DAYS";
+    pub const ACCOUNT_EXPORT_DIR: &'static str = "ACCOUNT_EXPORT_DIR";
     pub const AGENT_API_URL: &'static str = "AGENT_API_URL";
     pub const CHAT_IMAGE_UPLOAD_DIR: &'static str = "CHAT_IMAGE_UPLOAD_DIR";
     pub const RAG_DOCS_ROOT: &'static str = "RAG_DOCS_ROOT";
+    pub const DISCORD_CLIENT_ID: &'static str = "DISCORD_CLIENT_ID";
+    pub const DISCORD_CLIENT_SECRET: &'static str = "DISCORD_CLIENT_SECRET";
+    pub const DISCORD_REDIRECT_URL: &'static str = "DISCORD_REDIRECT_URL";
+    pub const MAILER: &'static str = "MAILER";
+    pub const SMTP_URL: &'static str = "SMTP_URL";
+    pub const MAIL_FROM: &'static str = "MAIL_FROM";
+    pub const MAIL_OUTBOX_DIR: &'static str = "MAIL_OUTBOX_DIR";
+    pub const YOLO_SCRIPT_PATH: &'static str = "YOLO_SCRIPT_PATH";
+    pub const DIET_JOB_WORKER_CONCURRENCY: &'static str = "DIET_JOB_WORKER_CONCURRENCY";
+    pub const NUTRITION_ENGINE_PATH: &'static str = "NUTRITION_ENGINE_PATH";
+    pub const FOOD_DETECTOR: &'static str = "FOOD_DETECTOR";
+    pub const FOOD_DETECTOR_PYTHON: &'static str = "FOOD_DETECTOR_PYTHON";
+    pub const FOOD_DETECTOR_URL: &'static str = "FOOD_DETECTOR_URL";
+    pub const FOOD_DETECTOR_MODEL: &'static str = "FOOD_DETECTOR_MODEL";
+    pub const FOOD_DETECTOR_LABELS: &'static str = "FOOD_DETECTOR_LABELS";
+    pub const FOOD_DETECTOR_CONFIDENCE: &'static str = "FOOD_DETECTOR_CONFIDENCE";
+    pub const FOOD_DETECTOR_TIMEOUT_SECS: &'static str = "FOOD_DETECTOR_TIMEOUT_SECS";
+    pub const FOOD_DETECTOR_MAX_CONCURRENCY: &'static str = "FOOD_DETECTOR_MAX_CONCURRENCY";
+    pub const FRONTEND_BASE_URL: &'static str = "FRONTEND_BASE_URL";
+    pub const TRUSTED_PROXIES: &'static str = "TRUSTED_PROXIES";
+    pub const OAUTH_PROVIDERS: &'static str = "OAUTH_PROVIDERS";
+    pub const OAUTH_FRONTEND_REDIRECT_URL: &'static str = "OAUTH_FRONTEND_REDIRECT_URL";
 }
 
 pub struct OutSideURL;
@@ -0,0 +1,380 @@
+use crate::{
+    api::model::{ROLE_USER, User},
+    oauth::provider::ExternalIdentity,
+    utils::{
+        hash::{hash_password, verify_password},
+        login_guard::{
+            ADMIN_ACCOUNT_POLICY, SCOPE_ACCOUNT, USER_ACCOUNT_POLICY, account_key, clear_failures,
+            locked_until, record_failure,
+        },
+        permission::is_admin,
+    },
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// How long a pending "link this provider to your existing account" confirmation stays valid.
+pub const LINK_REQUEST_TTL_SECS: i64 = 900;
+/// Wrong passwords a link request takes before it is deleted and the login has to start over.
+pub const MAX_LINK_ATTEMPTS: i32 = 3;
+
+/// Domain for accounts whose provider did not share a verified email. `.invalid` is reserved
+/// (RFC 2606), so these addresses can never collide with a real registration.
+const PLACEHOLDER_EMAIL_DOMAIN: &str = "oauth.invalid";
+
+#[derive(Debug)]
+pub enum ResolvedAccount {
+    /// A user already linked to this provider identity.
+    Existing(User),
+    /// A new user created from the provider profile.
+    Created(User),
+    /// The verified provider email belongs to an account that is not linked yet; the owner
+    /// has to confirm with their password before the identity is attached.
+    LinkRequired { link_token: Uuid, email: String },
+}
+
+#[derive(Debug)]
+pub enum ResolveAccountError {
+    Database(sqlx::Error),
+    Hash(String),
+}
+
+impl From<sqlx::Error> for ResolveAccountError {
+    fn from(e: sqlx::Error) -> Self {
+        ResolveAccountError::Database(e)
+    }
+}
+
+/// Finds the user behind a third-party login, creating one when neither the identity nor
+/// the verified email is known.
+pub async fn resolve_identity(
+    db: &PgPool,
+    identity: &ExternalIdentity,
+) -> Result<ResolvedAccount, ResolveAccountError> {
+    let linked = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE user_identities i
+        SET last_login_at = now()
+        FROM users u
+        WHERE u.id = i.user_id AND i.provider = $1 AND i.subject = $2
+        RETURNING u.id, u.email, u.password_hash, u.nickname, u.avatar_url, u.role
+        "#,
+    )
+    .bind(&identity.provider)
+    .bind(&identity.subject)
+    .fetch_optional(db)
+    .await?;
+
+    if let Some(user) = linked {
+        return Ok(ResolvedAccount::Existing(user));
+    }
+
+    if let Some(email) = identity.verified_email() {
+        let existing_id = sqlx::query_scalar::<_, Uuid>("SELECT id FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_optional(db)
+            .await?;
+
+        if let Some(user_id) = existing_id {
+            let link_token = Uuid::new_v4();
+            sqlx::query(
+                r#"
+                INSERT INTO oauth_link_requests (token, user_id, provider, provider_user_id, expires_at, created_at)
+                VALUES ($1, $2, $3, $4, now() + make_interval(secs => $5::double precision), now())
+                "#,
+            )
+            .bind(link_token)
+            .bind(user_id)
+            .bind(&identity.provider)
+            .bind(&identity.subject)
+            .bind(LINK_REQUEST_TTL_SECS as f64)
+            .execute(db)
+            .await?;
+
+            return Ok(ResolvedAccount::LinkRequired {
+                link_token,
+                email: email.to_string(),
+            });
+        }
+    }
+
+    let email = identity
+        .verified_email()
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            format!(
+                "{}.{}@{}",
+                identity.provider,
+                Uuid::new_v4().simple(),
+                PLACEHOLDER_EMAIL_DOMAIN
+            )
+        });
+
+    // Provider-only accounts get a random password nobody knows; password login stays closed
+    // until the user sets one.
+    let password_hash =
+        hash_password(&Uuid::new_v4().to_string()).map_err(ResolveAccountError::Hash)?;
+
+    let mut tx = db.begin().await?;
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        INSERT INTO users (email, password_hash, nickname, avatar_url, role, email_verified_at)
+        VALUES ($1, $2, $3, $4, $5, CASE WHEN $6 THEN now() END)
+        RETURNING id, email, password_hash, nickname, avatar_url, role
+        "#,
+    )
+    .bind(&email)
+    .bind(&password_hash)
+    .bind(&identity.name)
+    .bind(&identity.avatar_url)
+    .bind(ROLE_USER)
+    .bind(identity.verified_email().is_some())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    insert_identity(
+        &mut tx,
+        user.id,
+        &identity.provider,
+        &identity.subject,
+        identity.email.as_deref(),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(ResolvedAccount::Created(user))
+}
+
+async fn insert_identity(
+    conn: &mut sqlx::PgConnection,
+    user_id: Uuid,
+    provider: &str,
+    subject: &str,
+    email: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_identities (user_id, provider, subject, email, created_at, last_login_at)
+        VALUES ($1, $2, $3, $4, now(), now())
+        "#,
+    )
+    .bind(user_id)
+    .bind(provider)
+    .bind(subject)
+    .bind(email)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum LinkConfirmError {
+    NotFound,
+    InvalidPassword,
+    /// The account is under a login lockout.
+    Locked(DateTime<Utc>),
+    AlreadyLinked,
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for LinkConfirmError {
+    fn from(e: sqlx::Error) -> Self {
+        LinkConfirmError::Database(e)
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct PendingLink {
+    user_id: Uuid,
+    provider: String,
+    provider_user_id: String,
+}
+
+/// Attaches the identity from a pending link request once the account owner proves they
+/// know the password. Wrong passwords count towards the account's login lockout, and a
+/// request is deleted after `MAX_LINK_ATTEMPTS` of them.
+pub async fn confirm_identity_link(
+    db: &PgPool,
+    link_token: Uuid,
+    password: &str,
+) -> Result<User, LinkConfirmError> {
+    let mut tx = db.begin().await?;
+
+    let pending = sqlx::query_as::<_, PendingLink>(
+        r#"
+        SELECT user_id, provider, provider_user_id
+        FROM oauth_link_requests
+        WHERE token = $1 AND expires_at > now()
+        FOR UPDATE
+        "#,
+    )
+    .bind(link_token)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(LinkConfirmError::NotFound)?;
+
+    let user = sqlx::query_as::<_, User>(
+        "SELECT id, email, password_hash, nickname, avatar_url, role FROM users WHERE id = $1",
+    )
+    .bind(pending.user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(LinkConfirmError::NotFound)?;
+
+    let key = account_key(&user.email);
+    if let Some(until) = locked_until(db, SCOPE_ACCOUNT, &key).await? {
+        warn!(
+            "OAuth link rejected: user {} locked until {}",
+            user.id, until
+        );
+        return Err(LinkConfirmError::Locked(until));
+    }
+
+    if !verify_password(password, &user.password_hash).unwrap_or(false) {
+        warn!("OAuth link failed: invalid password for user {}", user.id);
+        let attempts = sqlx::query_scalar::<_, i32>(
+            "UPDATE oauth_link_requests SET failed_attempts = failed_attempts + 1 WHERE token = $1 RETURNING failed_attempts",
+        )
+        .bind(link_token)
+        .fetch_one(&mut *tx)
+        .await?;
+        if attempts >= MAX_LINK_ATTEMPTS {
+            sqlx::query("DELETE FROM oauth_link_requests WHERE token = $1")
+                .bind(link_token)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        let admin_account = is_admin(db, &user.role).await.unwrap_or_else(|e| {
+            // Fall back to the stricter admin policy when the role cannot be resolved.
+            error!("DB Error (Permission Check): {:?}", e);
+            true
+        });
+        let policy = if admin_account {
+            ADMIN_ACCOUNT_POLICY
+        } else {
+            USER_ACCOUNT_POLICY
+        };
+        if let Some(until) = record_failure(db, SCOPE_ACCOUNT, &key, &policy).await? {
+            return Err(LinkConfirmError::Locked(until));
+        }
+        return Err(LinkConfirmError::InvalidPassword);
+    }
+
+    insert_identity(
+        &mut tx,
+        user.id,
+        &pending.provider,
+        &pending.provider_user_id,
+        Some(&user.email),
+    )
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("duplicate key value") {
+            LinkConfirmError::AlreadyLinked
+        } else {
+            LinkConfirmError::Database(e)
+        }
+    })?;
+
+    sqlx::query("DELETE FROM oauth_link_requests WHERE token = $1")
+        .bind(link_token)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    if let Err(e) = clear_failures(db, SCOPE_ACCOUNT, &key).await {
+        warn!(
+            "Failed to reset login failures for user {}: {:?}",
+            user.id, e
+        );
+    }
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::{register_test_account, setup_db};
+
+    fn identity(email: Option<&str>, verified: bool) -> ExternalIdentity {
+        ExternalIdentity {
+            provider: "google".to_string(),
+            subject: Uuid::new_v4().to_string(),
+            email: email.map(str::to_string),
+            email_verified: verified,
+            name: Some("OAuth Tester".to_string()),
+            avatar_url: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oauth_login_creates_then_finds_user() {
+        let state = setup_db().await;
+        let email = format!("oauth_{}@example.com", Uuid::new_v4());
+        let profile = identity(Some(&email), true);
+
+        let created = resolve_identity(&state.db, &profile).await.unwrap();
+        let ResolvedAccount::Created(created) = created else {
+            panic!("expected a new user, got {:?}", created);
+        };
+        assert_eq!(created.email, email);
+
+        let again = resolve_identity(&state.db, &profile).await.unwrap();
+        let ResolvedAccount::Existing(existing) = again else {
+            panic!("expected the linked user, got {:?}", again);
+        };
+        assert_eq!(created.id, existing.id);
+    }
+
+    #[tokio::test]
+    async fn test_oauth_login_links_existing_email_after_password_confirmation() {
+        let state = setup_db().await;
+        let (email, password) =
+            register_test_account(state.clone(), "oauth_link".to_string()).await;
+        let profile = identity(Some(&email), true);
+
+        let resolved = resolve_identity(&state.db, &profile).await.unwrap();
+        let ResolvedAccount::LinkRequired { link_token, .. } = resolved else {
+            panic!("expected a link confirmation, got {:?}", resolved);
+        };
+
+        // Too many wrong passwords use the request up.
+        for _ in 0..MAX_LINK_ATTEMPTS {
+            let wrong = confirm_identity_link(&state.db, link_token, "wrong-password").await;
+            assert!(matches!(wrong, Err(LinkConfirmError::InvalidPassword)));
+        }
+        let spent = confirm_identity_link(&state.db, link_token, &password).await;
+        assert!(matches!(spent, Err(LinkConfirmError::NotFound)));
+
+        let resolved = resolve_identity(&state.db, &profile).await.unwrap();
+        let ResolvedAccount::LinkRequired { link_token, .. } = resolved else {
+            panic!("expected a link confirmation, got {:?}", resolved);
+        };
+        let linked = confirm_identity_link(&state.db, link_token, &password)
+            .await
+            .unwrap();
+        assert_eq!(linked.email, email);
+
+        let again = resolve_identity(&state.db, &profile).await.unwrap();
+        assert!(matches!(again, ResolvedAccount::Existing(user) if user.id == linked.id));
+    }
+
+    #[tokio::test]
+    async fn test_unverified_email_never_links_existing_account() {
+        let state = setup_db().await;
+        let (email, _) = register_test_account(state.clone(), "oauth_unverified".to_string()).await;
+
+        let resolved = resolve_identity(&state.db, &identity(Some(&email), false))
+            .await
+            .unwrap();
+        let ResolvedAccount::Created(user) = resolved else {
+            panic!("expected a separate account, got {:?}", resolved);
+        };
+        assert!(user.email.ends_with(PLACEHOLDER_EMAIL_DOMAIN));
+    }
+}
@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::oauth::provider::{
+    AuthorizationRequest, ExternalIdentity, IdentityProvider, PendingAuthorization, ProviderClient,
+    ProviderError, exchange_authorization_code,
+};
+
+const AUTH_URL: &str = "https://discord.com/api/oauth2/authorize";
+const TOKEN_URL: &str = "https://discord.com/api/oauth2/token";
+const USER_URL: &str = "https://discord.com/api/users/@me";
+
+/// Discord is plain OAuth 2.0 (no id_token), so the profile comes from `/users/@me`.
+pub struct DiscordProvider {
+    client: ProviderClient,
+    http: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscordUser {
+    id: String,
+    username: String,
+    #[serde(default)]
+    global_name: Option<String>,
+    avatar: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    verified: Option<bool>,
+}
+
+impl DiscordProvider {
+    pub fn new(client: ProviderClient) -> Self {
+        DiscordProvider {
+            client,
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for DiscordProvider {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn authorization_url(
+        &self,
+        request: &AuthorizationRequest<'_>,
+    ) -> Result<String, ProviderError> {
+        let url = Url::parse_with_params(
+            AUTH_URL,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.client.client_id.as_str()),
+                ("redirect_uri", self.client.redirect_url.as_str()),
+                ("scope", self.client.scopes.join(" ").as_str()),
+                ("state", request.state),
+                ("code_challenge", request.pkce_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| ProviderError::Config(e.to_string()))?;
+        Ok(url.to_string())
+    }
+
+    async fn exchange_code(
+        &self,
+        code: &str,
+        pending: &PendingAuthorization,
+    ) -> Result<ExternalIdentity, ProviderError> {
+        let token = exchange_authorization_code(
+            &self.http,
+            TOKEN_URL,
+            &self.client,
+            code,
+            &pending.pkce_verifier,
+        )
+        .await?;
+
+        let user = self
+            .http
+            .get(USER_URL)
+            .bearer_auth(&token.access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<DiscordUser>()
+            .await?;
+
+        Ok(ExternalIdentity {
+            provider: "discord".to_string(),
+            avatar_url: user.avatar.as_ref().map(|hash| {
+                format!(
+                    "https://cdn.discordapp.com/avatars/{}/{}.png",
+                    user.id, hash
+                )
+            }),
+            name: user.global_name.or(Some(user.username)),
+            email_verified: user.verified.unwrap_or(false),
+            email: user.email,
+            subject: user.id,
+        })
+    }
+}
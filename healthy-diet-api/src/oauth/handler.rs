@@ -0,0 +1,290 @@
+use std::{env, sync::Arc};
+
+use axum::{
+    Form, Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Redirect,
+};
+use oauth2::{CsrfToken, PkceCodeChallenge, url::form_urlencoded};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    api::{
+        login::{complete_login, locked_error},
+        model::{AuthResponse, ErrorResponse, LoginResponse, TwoFactorChallenge},
+    },
+    model::{AppState, ENVKey},
+    oauth::{
+        account::{LinkConfirmError, ResolvedAccount, confirm_identity_link, resolve_identity},
+        provider::AuthorizationRequest,
+        state::{store_oauth_state, take_oauth_state},
+    },
+    utils::client_info::ClientInfo,
+};
+
+const DISCORD: &str = "discord";
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OAuthLinkPayload {
+    #[serde(rename = "linkToken")]
+    pub link_token: uuid::Uuid,
+    pub password: String,
+}
+
+/// Sends the browser back to the frontend with the result in the URL fragment, so tokens
+/// never reach server logs or `Referer` headers.
+fn frontend_redirect(params: &[(&str, &str)]) -> Redirect {
+    let base = env::var(ENVKey::OAUTH_FRONTEND_REDIRECT_URL).unwrap_or_else(|_| "/".to_string());
+    let fragment = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    Redirect::to(&format!("{}#{}", base, fragment))
+}
+
+fn frontend_error(code: &str) -> Redirect {
+    frontend_redirect(&[("error", code)])
+}
+
+fn auth_redirect(response: &AuthResponse) -> Redirect {
+    frontend_redirect(&[
+        ("token", &response.token),
+        ("refreshToken", &response.refresh_token),
+        ("expiresIn", &response.expires_in.to_string()),
+    ])
+}
+
+fn challenge_redirect(challenge: &TwoFactorChallenge) -> Redirect {
+    frontend_redirect(&[
+        ("challengeToken", &challenge.challenge_token),
+        ("expiresIn", &challenge.expires_in.to_string()),
+        (
+            "enrollmentRequired",
+            if challenge.enrollment_required {
+                "true"
+            } else {
+                "false"
+            },
+        ),
+    ])
+}
+
+async fn start_login(
+    state: &AppState,
+    provider_name: &str,
+) -> Result<Redirect, (StatusCode, Json<ErrorResponse>)> {
+    let provider = state.identity_providers.get(provider_name).ok_or((
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "Login provider not found".to_string(),
+        }),
+    ))?;
+
+    let unavailable = || {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: "Login provider is unavailable".to_string(),
+            }),
+        )
+    };
+
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let csrf_state = CsrfToken::new_random();
+    let nonce = CsrfToken::new_random();
+
+    let auth_url = provider
+        .authorization_url(&AuthorizationRequest {
+            state: csrf_state.secret(),
+            pkce_challenge: pkce_challenge.as_str(),
+            nonce: nonce.secret(),
+        })
+        .await
+        .map_err(|e| {
+            error!(
+                "[oauth {}] Failed to build authorization url: {}",
+                provider_name, e
+            );
+            unavailable()
+        })?;
+
+    store_oauth_state(
+        &state.db,
+        provider_name,
+        csrf_state.secret(),
+        pkce_verifier.secret(),
+        Some(nonce.secret()),
+    )
+    .await
+    .map_err(|e| {
+        error!("DB Error (OAuth State): {:?}", e);
+        unavailable()
+    })?;
+
+    Ok(Redirect::to(&auth_url))
+}
+
+async fn finish_login(
+    state: &AppState,
+    provider_name: &str,
+    client: &ClientInfo,
+    params: CallbackParams,
+) -> Redirect {
+    let Some(provider) = state.identity_providers.get(provider_name) else {
+        return frontend_error("unknown_provider");
+    };
+
+    if let Some(error) = params.error {
+        warn!("[oauth {}] Authorization denied: {}", provider_name, error);
+        return frontend_error("access_denied");
+    }
+
+    let (Some(code), Some(csrf_state)) = (params.code, params.state) else {
+        return frontend_error("invalid_request");
+    };
+
+    let pending = match take_oauth_state(&state.db, provider_name, &csrf_state).await {
+        Ok(Some(pending)) => pending,
+        Ok(None) => {
+            warn!("[oauth {}] Unknown or expired state", provider_name);
+            return frontend_error("invalid_state");
+        }
+        Err(e) => {
+            error!("DB Error (OAuth State): {:?}", e);
+            return frontend_error("server_error");
+        }
+    };
+
+    let identity = match provider.exchange_code(&code, &pending).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            warn!("[oauth {}] Code exchange failed: {}", provider_name, e);
+            return frontend_error("exchange_failed");
+        }
+    };
+
+    let user = match resolve_identity(&state.db, &identity).await {
+        Ok(ResolvedAccount::Existing(user)) => user,
+        Ok(ResolvedAccount::Created(user)) => {
+            info!("New user registered via {}: {}", provider_name, user.id);
+            user
+        }
+        Ok(ResolvedAccount::LinkRequired { link_token, email }) => {
+            return frontend_redirect(&[
+                ("linkToken", &link_token.to_string()),
+                ("provider", provider_name),
+                ("email", &email),
+            ]);
+        }
+        Err(e) => {
+            error!(
+                "[oauth {}] Account resolution failed: {:?}",
+                provider_name, e
+            );
+            return frontend_error("server_error");
+        }
+    };
+
+    match complete_login(state, user, client).await {
+        Ok(LoginResponse::Authenticated(response)) => {
+            info!("User logged in via {}: {}", provider_name, response.user.id);
+            auth_redirect(&response)
+        }
+        Ok(LoginResponse::TwoFactorRequired(challenge)) => challenge_redirect(&challenge),
+        Err(_) => frontend_error("server_error"),
+    }
+}
+
+pub async fn oauth_login_handler(
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+) -> Result<Redirect, (StatusCode, Json<ErrorResponse>)> {
+    start_login(&state, &provider).await
+}
+
+pub async fn oauth_callback_handler(
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+    client: ClientInfo,
+    Query(params): Query<CallbackParams>,
+) -> Redirect {
+    finish_login(&state, &provider, &client, params).await
+}
+
+/// Callback for providers using `response_mode=form_post` (Apple).
+pub async fn oauth_callback_form_handler(
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+    client: ClientInfo,
+    Form(params): Form<CallbackParams>,
+) -> Redirect {
+    let form_post = state
+        .identity_providers
+        .get(&provider)
+        .is_some_and(|provider| provider.uses_form_post());
+    if !form_post {
+        return frontend_error("invalid_request");
+    }
+    finish_login(&state, &provider, &client, params).await
+}
+
+/// Kept for the Discord redirect URL registered before the generic provider routes existed.
+pub async fn login_discord(
+    State(state): State<Arc<AppState>>,
+) -> Result<Redirect, (StatusCode, Json<ErrorResponse>)> {
+    start_login(&state, DISCORD).await
+}
+
+pub async fn discord_callback(
+    State(state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Query(params): Query<CallbackParams>,
+) -> Redirect {
+    finish_login(&state, DISCORD, &client, params).await
+}
+
+/// Completes a third-party login that matched an existing email account by checking that
+/// account's password.
+pub async fn oauth_link_handler(
+    State(state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(payload): Json<OAuthLinkPayload>,
+) -> Result<Json<LoginResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let user = confirm_identity_link(&state.db, payload.link_token, &payload.password)
+        .await
+        .map_err(|e| {
+            let (status, message) = match e {
+                LinkConfirmError::Locked(until) => return locked_error(until),
+                LinkConfirmError::NotFound => {
+                    (StatusCode::NOT_FOUND, "Link request not found or expired")
+                }
+                LinkConfirmError::InvalidPassword => (StatusCode::UNAUTHORIZED, "Invalid password"),
+                LinkConfirmError::AlreadyLinked => {
+                    (StatusCode::CONFLICT, "Login provider is already linked")
+                }
+                LinkConfirmError::Database(e) => {
+                    error!("DB Error (OAuth Link): {:?}", e);
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+                }
+            };
+            (
+                status,
+                Json(ErrorResponse {
+                    error: message.to_string(),
+                }),
+            )
+        })?;
+
+    info!("User linked login provider: {}", user.id);
+    let response = complete_login(&state, user, &client).await?;
+
+    Ok(Json(response))
+}
@@ -0,0 +1,6 @@
+pub mod account;
+pub mod discord;
+pub mod handler;
+pub mod oidc;
+pub mod provider;
+pub mod state;
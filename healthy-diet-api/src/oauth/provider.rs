@@ -0,0 +1,305 @@
+use std::{collections::HashMap, env, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{
+    model::ENVKey,
+    oauth::{discord::DiscordProvider, oidc::OidcProvider},
+};
+
+/// The user as reported by a third-party identity provider.
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub provider: String,
+    /// Stable account id at the provider (`sub` for OIDC).
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl ExternalIdentity {
+    /// Only a verified email may be used to match or create local accounts.
+    pub fn verified_email(&self) -> Option<&str> {
+        self.email.as_deref().filter(|_| self.email_verified)
+    }
+}
+
+/// Values generated for one authorization request.
+pub struct AuthorizationRequest<'a> {
+    pub state: &'a str,
+    pub pkce_challenge: &'a str,
+    pub nonce: &'a str,
+}
+
+/// What was stored server-side when the authorization request was created.
+pub struct PendingAuthorization {
+    pub pkce_verifier: String,
+    pub nonce: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ProviderError {
+    Config(String),
+    Http(reqwest::Error),
+    InvalidResponse(String),
+    InvalidIdToken(String),
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::Config(e) => write!(f, "invalid provider configuration: {}", e),
+            ProviderError::Http(e) => write!(f, "provider request failed: {}", e),
+            ProviderError::InvalidResponse(e) => write!(f, "invalid provider response: {}", e),
+            ProviderError::InvalidIdToken(e) => write!(f, "invalid id_token: {}", e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(e: reqwest::Error) -> Self {
+        ProviderError::Http(e)
+    }
+}
+
+#[async_trait]
+pub trait IdentityProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether the provider posts the callback as a form (`response_mode=form_post`).
+    fn uses_form_post(&self) -> bool {
+        false
+    }
+
+    async fn authorization_url(
+        &self,
+        request: &AuthorizationRequest<'_>,
+    ) -> Result<String, ProviderError>;
+
+    async fn exchange_code(
+        &self,
+        code: &str,
+        pending: &PendingAuthorization,
+    ) -> Result<ExternalIdentity, ProviderError>;
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TokenEndpointResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub id_token: Option<String>,
+}
+
+/// Authorization code exchange with `client_secret_post`, shared by every provider.
+pub(crate) async fn exchange_authorization_code(
+    http: &reqwest::Client,
+    token_endpoint: &str,
+    client: &ProviderClient,
+    code: &str,
+    pkce_verifier: &str,
+) -> Result<TokenEndpointResponse, ProviderError> {
+    let response = http
+        .post(token_endpoint)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", client.redirect_url.as_str()),
+            ("client_id", client.client_id.as_str()),
+            ("client_secret", client.client_secret.as_str()),
+            ("code_verifier", pkce_verifier),
+        ])
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(ProviderError::InvalidResponse(format!(
+            "token endpoint returned {}: {}",
+            status, body
+        )));
+    }
+
+    Ok(response.json::<TokenEndpointResponse>().await?)
+}
+
+/// Client registration shared by every provider kind.
+#[derive(Debug, Clone)]
+pub struct ProviderClient {
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+    pub scopes: Vec<String>,
+}
+
+/// Provider-specific settings read from `OAUTH_<NAME>_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct ProviderSettings {
+    pub name: String,
+    pub client: ProviderClient,
+    pub issuer: Option<String>,
+    pub extra_issuers: Vec<String>,
+    pub form_post: bool,
+    /// Treat the email as verified even without an `email_verified` claim (LINE only hands
+    /// out the verified address of the account but does not send the claim).
+    pub trust_email: bool,
+}
+
+struct Preset {
+    issuer: &'static str,
+    extra_issuers: &'static [&'static str],
+    scopes: &'static str,
+    form_post: bool,
+    trust_email: bool,
+}
+
+fn preset(name: &str) -> Option<Preset> {
+    match name {
+        "google" => Some(Preset {
+            issuer: "https://accounts.google.com",
+            extra_issuers: &["accounts.google.com"],
+            scopes: "openid email profile",
+            form_post: false,
+            trust_email: false,
+        }),
+        "line" => Some(Preset {
+            issuer: "https://access.line.me",
+            extra_issuers: &[],
+            scopes: "openid profile email",
+            form_post: false,
+            trust_email: true,
+        }),
+        "apple" => Some(Preset {
+            issuer: "https://appleid.apple.com",
+            extra_issuers: &[],
+            scopes: "openid email name",
+            form_post: true,
+            trust_email: false,
+        }),
+        _ => None,
+    }
+}
+
+impl ProviderSettings {
+    /// Reads `OAUTH_<NAME>_CLIENT_ID`, `_CLIENT_SECRET`, `_REDIRECT_URL` and the optional
+    /// `_ISSUER`, `_SCOPES`, `_FORM_POST`, `_TRUST_EMAIL`. Known providers (google, line,
+    /// apple) come with defaults for everything but the client registration.
+    pub fn from_env(name: &str) -> Result<Self, ProviderError> {
+        let prefix = format!("OAUTH_{}_", name.to_ascii_uppercase());
+        let var = |key: &str| {
+            env::var(format!("{}{}", prefix, key))
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let required = |key: &str, legacy: Option<&str>| {
+            var(key)
+                .or_else(|| legacy.and_then(|legacy| env::var(legacy).ok()))
+                .ok_or_else(|| ProviderError::Config(format!("missing {}{}", prefix, key)))
+        };
+        let flag = |key: &str, default: bool| {
+            var(key)
+                .map(|value| matches!(value.as_str(), "1" | "true" | "yes"))
+                .unwrap_or(default)
+        };
+
+        let is_discord = name == "discord";
+        let preset = preset(name);
+
+        let client = ProviderClient {
+            client_id: required("CLIENT_ID", is_discord.then_some(ENVKey::DISCORD_CLIENT_ID))?,
+            client_secret: required(
+                "CLIENT_SECRET",
+                is_discord.then_some(ENVKey::DISCORD_CLIENT_SECRET),
+            )?,
+            redirect_url: required(
+                "REDIRECT_URL",
+                is_discord.then_some(ENVKey::DISCORD_REDIRECT_URL),
+            )?,
+            scopes: var("SCOPES")
+                .or_else(|| preset.as_ref().map(|p| p.scopes.to_string()))
+                .unwrap_or_else(|| {
+                    if is_discord {
+                        "identify email".to_string()
+                    } else {
+                        "openid email profile".to_string()
+                    }
+                })
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+        };
+
+        let issuer = var("ISSUER").or_else(|| preset.as_ref().map(|p| p.issuer.to_string()));
+        if issuer.is_none() && !is_discord {
+            return Err(ProviderError::Config(format!("missing {}ISSUER", prefix)));
+        }
+
+        Ok(ProviderSettings {
+            name: name.to_string(),
+            client,
+            issuer,
+            extra_issuers: preset
+                .as_ref()
+                .map(|p| p.extra_issuers.iter().map(|i| i.to_string()).collect())
+                .unwrap_or_default(),
+            form_post: flag("FORM_POST", preset.as_ref().is_some_and(|p| p.form_post)),
+            trust_email: flag(
+                "TRUST_EMAIL",
+                preset.as_ref().is_some_and(|p| p.trust_email),
+            ),
+        })
+    }
+}
+
+/// All identity providers enabled for this deployment, keyed by their route name.
+#[derive(Default)]
+pub struct IdentityProviders {
+    providers: HashMap<String, Arc<dyn IdentityProvider>>,
+}
+
+impl IdentityProviders {
+    /// Builds the providers listed in `OAUTH_PROVIDERS` (comma separated). When unset, Discord
+    /// is enabled if its legacy `DISCORD_*` variables are present. Misconfigured providers are
+    /// skipped with a warning so one bad entry does not take the API down.
+    pub fn from_env() -> Self {
+        let names: Vec<String> = match env::var(ENVKey::OAUTH_PROVIDERS) {
+            Ok(value) => value
+                .split(',')
+                .map(|name| name.trim().to_ascii_lowercase())
+                .filter(|name| !name.is_empty())
+                .collect(),
+            Err(_) if env::var(ENVKey::DISCORD_CLIENT_ID).is_ok() => vec!["discord".to_string()],
+            Err(_) => Vec::new(),
+        };
+
+        let mut registry = IdentityProviders::default();
+        for name in names {
+            match ProviderSettings::from_env(&name) {
+                Ok(settings) => {
+                    let provider: Arc<dyn IdentityProvider> = if name == "discord" {
+                        Arc::new(DiscordProvider::new(settings.client))
+                    } else {
+                        Arc::new(OidcProvider::new(settings))
+                    };
+                    info!("OAuth provider enabled: {}", name);
+                    registry.insert(provider);
+                }
+                Err(e) => warn!("OAuth provider {} disabled: {}", name, e),
+            }
+        }
+        registry
+    }
+
+    pub fn insert(&mut self, provider: Arc<dyn IdentityProvider>) {
+        self.providers.insert(provider.name().to_string(), provider);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn IdentityProvider>> {
+        self.providers.get(name).cloned()
+    }
+}
@@ -0,0 +1,68 @@
+use crate::oauth::provider::PendingAuthorization;
+use sqlx::{FromRow, PgPool};
+
+/// How long a user has to finish the provider consent screen.
+pub const OAUTH_STATE_TTL_SECS: i64 = 600;
+
+#[derive(Debug, FromRow)]
+struct PendingOAuthState {
+    pkce_verifier: String,
+    nonce: Option<String>,
+    is_expired: bool,
+}
+
+/// Records the CSRF `state` of an authorization request together with its PKCE verifier and
+/// OIDC nonce.
+pub async fn store_oauth_state(
+    db: &PgPool,
+    provider: &str,
+    state: &str,
+    pkce_verifier: &str,
+    nonce: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM oauth_states WHERE expires_at <= now()")
+        .execute(db)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO oauth_states (state, provider, pkce_verifier, nonce, expires_at, created_at)
+        VALUES ($1, $2, $3, $4, now() + make_interval(secs => $5::double precision), now())
+        "#,
+    )
+    .bind(state)
+    .bind(provider)
+    .bind(pkce_verifier)
+    .bind(nonce)
+    .bind(OAUTH_STATE_TTL_SECS as f64)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Consumes a pending state and returns what is needed to finish the code exchange. A state
+/// can only be used once; unknown, expired or already-used states yield `None`.
+pub async fn take_oauth_state(
+    db: &PgPool,
+    provider: &str,
+    state: &str,
+) -> Result<Option<PendingAuthorization>, sqlx::Error> {
+    let pending = sqlx::query_as::<_, PendingOAuthState>(
+        r#"
+        DELETE FROM oauth_states
+        WHERE state = $1 AND provider = $2
+        RETURNING pkce_verifier, nonce, expires_at <= now() AS is_expired
+        "#,
+    )
+    .bind(state)
+    .bind(provider)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(pending
+        .filter(|pending| !pending.is_expired)
+        .map(|pending| PendingAuthorization {
+            pkce_verifier: pending.pkce_verifier,
+            nonce: pending.nonce,
+        }))
+}
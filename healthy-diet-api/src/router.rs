@@ -1,11 +1,16 @@
 use crate::{
     api::{
+        account::{
+            cancel_account_deletion_handler, export_account_handler,
+            request_account_deletion_handler,
+        },
         admin::{
             admin_announcements_handler, admin_me_handler, admin_route_controls_handler,
             admin_user_detail_handler, admin_users_handler, archive_announcement_handler,
             create_agent_admin_token_handler, create_announcement_handler,
-            publish_announcement_handler, update_announcement_handler,
-            update_route_control_handler,
+            force_password_reset_handler, publish_announcement_handler, suspend_user_handler,
+            unlock_user_handler, unsuspend_user_handler, update_announcement_handler,
+            update_route_control_handler, update_user_role_handler,
         },
         agent_approve::approve_agent,
         agent_content::{
@@ -13,16 +18,36 @@ use crate::{
             rag_search_get_handler, rag_search_post_handler,
         },
         announcement::current_announcement_handler,
+        api_token::{create_api_token_handler, list_api_tokens_handler, revoke_api_token_handler},
+        basic_calculator::basic_calculate_handler,
+        caregiver::{
+            accept_caregiver_invite_handler, caregiving_chat_summaries_handler,
+            caregiving_diet_records_handler, caregiving_diet_stats_handler,
+            invite_caregiver_handler, list_caregivers_handler, list_caregiving_handler,
+            revoke_caregiver_link_handler,
+        },
         chat::{chat_check_handler, chat_handler},
         chat_room::{
             get_chat_room_titles_handler, get_chat_rooms_handler,
             get_room_history_by_index_handler, get_room_history_handler,
         },
+        dataset::admin_export_yolo_dataset_handler,
         diet::yolo_handler,
         diet_image::diet_image_handler,
-        diet_record::diet_records_handler,
+        diet_job::{create_diet_job_handler, diet_job_events_handler, get_diet_job_handler},
+        diet_record::{
+            delete_diet_record_handler, diet_record_detail_handler, diet_records_handler,
+            mark_annotations_read_handler, reply_to_annotation_handler, unread_annotations_handler,
+            update_diet_record_handler,
+        },
+        email_verification::{request_email_verification_handler, verify_email_handler},
+        food::{
+            FOOD_IMPORT_MAX_BYTES, admin_import_foods_handler, food_detail_handler,
+            search_foods_handler,
+        },
         gemma4::gemma4_health_handler,
         health::healthy_server_handler,
+        jwks::jwks_handler,
         knowledge_graph::{
             admin_knowledge_graph_document_detail_handler, admin_knowledge_graph_extract_handler,
             admin_knowledge_graph_rebuild_handler, knowledge_graph_node_detail_handler,
@@ -30,7 +55,17 @@ use crate::{
             knowledge_graph_relation_evidence_handler, knowledge_graph_status_handler,
         },
         login::{admin_login_handler, login_handler},
+        logout::logout_handler,
+        manual_meal::create_manual_meal_handler,
+        nutritionist::{
+            annotate_client_record_handler, assign_client_handler,
+            list_nutritionist_clients_handler, mark_client_record_read_handler,
+            unassign_client_handler, workspace_client_chat_rooms_handler,
+            workspace_client_diet_records_handler, workspace_client_room_history_handler,
+            workspace_clients_handler,
+        },
         openapi::openapi_yaml_handler,
+        password_reset::{forgot_password_handler, reset_password_handler},
         ping::ping_handler,
         rag_document::{
             admin_rag_delete_handler, admin_rag_document_detail_handler,
@@ -41,10 +76,19 @@ use crate::{
         record::{record_visit_handler, weekly_stats_handler},
         refresh::refresh_handler,
         register::register_handler,
-        user::{get_profile_handler, update_user_profile_handler},
+        role::{list_roles_handler, save_role_handler},
+        session::{list_sessions_handler, revoke_all_sessions_handler, revoke_session_handler},
+        two_factor::{
+            regenerate_recovery_codes_handler, totp_confirm_handler, totp_setup_handler,
+            verify_two_factor_handler,
+        },
+        user::{change_password_handler, get_profile_handler, update_user_profile_handler},
     },
-    discord::login::{discord_callback, login_discord},
     model::{APIRouter, AppState, ENVKey},
+    oauth::handler::{
+        discord_callback, login_discord, oauth_callback_form_handler, oauth_callback_handler,
+        oauth_link_handler, oauth_login_handler,
+    },
     utils::jwt::require_admin_middleware,
     utils::route_control::{RouteControlGuardState, require_route_enabled_middleware},
 };
@@ -54,7 +98,7 @@ use axum::{
     http::{HeaderValue, Method, Request, header},
     middleware,
     response::Response,
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
 use std::{env, sync::Arc, time::Duration};
 use tower_http::{
@@ -70,8 +114,72 @@ pub fn create_app(state: Arc<AppState>) -> Router {
             APIRouter::ADMIN_AGENT_TOKEN,
             post(create_agent_admin_token_handler),
         )
+        .route(
+            APIRouter::ADMIN_API_TOKENS,
+            get(list_api_tokens_handler).post(create_api_token_handler),
+        )
+        .route(
+            APIRouter::ADMIN_API_TOKEN_DETAIL,
+            delete(revoke_api_token_handler),
+        )
+        .route(APIRouter::ADMIN_ROLES, get(list_roles_handler))
+        .route(APIRouter::ADMIN_ROLE_DETAIL, put(save_role_handler))
         .route(APIRouter::ADMIN_USERS, get(admin_users_handler))
         .route(APIRouter::ADMIN_USER_DETAIL, get(admin_user_detail_handler))
+        .route(APIRouter::ADMIN_USER_UNLOCK, post(unlock_user_handler))
+        .route(
+            APIRouter::ADMIN_USER_ROLE,
+            axum::routing::patch(update_user_role_handler),
+        )
+        .route(APIRouter::ADMIN_USER_SUSPEND, post(suspend_user_handler))
+        .route(
+            APIRouter::ADMIN_USER_UNSUSPEND,
+            post(unsuspend_user_handler),
+        )
+        .route(
+            APIRouter::ADMIN_USER_PASSWORD_RESET,
+            post(force_password_reset_handler),
+        )
+        .route(
+            APIRouter::ADMIN_NUTRITIONIST_CLIENTS,
+            get(list_nutritionist_clients_handler).post(assign_client_handler),
+        )
+        .route(
+            APIRouter::ADMIN_NUTRITIONIST_CLIENT_DETAIL,
+            delete(unassign_client_handler),
+        )
+        .route(
+            APIRouter::ADMIN_WORKSPACE_CLIENTS,
+            get(workspace_clients_handler),
+        )
+        .route(
+            APIRouter::ADMIN_WORKSPACE_CLIENT_DIET_RECORDS,
+            get(workspace_client_diet_records_handler),
+        )
+        .route(
+            APIRouter::ADMIN_WORKSPACE_CLIENT_RECORD_ANNOTATIONS,
+            post(annotate_client_record_handler),
+        )
+        .route(
+            APIRouter::ADMIN_WORKSPACE_CLIENT_RECORD_ANNOTATIONS_READ,
+            post(mark_client_record_read_handler),
+        )
+        .route(
+            APIRouter::ADMIN_WORKSPACE_CLIENT_CHAT_ROOMS,
+            get(workspace_client_chat_rooms_handler),
+        )
+        .route(
+            APIRouter::ADMIN_WORKSPACE_CLIENT_CHAT_ROOM,
+            get(workspace_client_room_history_handler),
+        )
+        .route(
+            APIRouter::ADMIN_FOODS_IMPORT,
+            post(admin_import_foods_handler).layer(DefaultBodyLimit::max(FOOD_IMPORT_MAX_BYTES)),
+        )
+        .route(
+            APIRouter::ADMIN_DATASET_YOLO,
+            get(admin_export_yolo_dataset_handler),
+        )
         .route(
             APIRouter::ADMIN_ROUTE_CONTROLS,
             get(admin_route_controls_handler),
@@ -96,7 +204,10 @@ pub fn create_app(state: Arc<AppState>) -> Router {
             APIRouter::ADMIN_ANNOUNCEMENT_ARCHIVE,
             post(archive_announcement_handler),
         )
-        .route_layer(middleware::from_fn(require_admin_middleware));
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_admin_middleware,
+        ));
 
     let rag_admin_router = Router::new()
         .route(
@@ -131,23 +242,96 @@ pub fn create_app(state: Arc<AppState>) -> Router {
             APIRouter::ADMIN_KNOWLEDGE_GRAPH_DOCUMENT_EXTRACT,
             post(admin_knowledge_graph_extract_handler),
         )
-        .route_layer(middleware::from_fn(require_admin_middleware));
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_admin_middleware,
+        ));
 
     Router::new()
         .route("/", get(async || "Connect Success!"))
         .route("/openapi.yml", get(openapi_yaml_handler))
         .route("/ping", get(ping_handler))
         .route(APIRouter::HEALTH, get(healthy_server_handler))
+        .route(APIRouter::JWKS, get(jwks_handler))
         .route(APIRouter::DISOCRD_LOGIN, get(login_discord))
         .route(APIRouter::DISOCRD_CALLBACK, get(discord_callback))
+        .route(APIRouter::DISCORD_LINK, post(oauth_link_handler))
+        .route(APIRouter::OAUTH_LOGIN, get(oauth_login_handler))
+        .route(
+            APIRouter::OAUTH_CALLBACK,
+            get(oauth_callback_handler).post(oauth_callback_form_handler),
+        )
+        .route(APIRouter::OAUTH_LINK, post(oauth_link_handler))
+        .route(APIRouter::EMAIL_VERIFY, post(verify_email_handler))
+        .route(
+            APIRouter::EMAIL_VERIFICATION_REQUEST,
+            post(request_email_verification_handler),
+        )
+        .route(APIRouter::PASSWORD_FORGOT, post(forgot_password_handler))
+        .route(APIRouter::PASSWORD_RESET, post(reset_password_handler))
         .route(APIRouter::REGISTER, post(register_handler))
         .route(APIRouter::LOGIN, post(login_handler))
         .route(APIRouter::ADMIN_LOGIN, post(admin_login_handler))
+        .route(
+            APIRouter::TWO_FACTOR_VERIFY,
+            post(verify_two_factor_handler),
+        )
+        .route(APIRouter::TOTP_SETUP, post(totp_setup_handler))
+        .route(APIRouter::TOTP_CONFIRM, post(totp_confirm_handler))
+        .route(
+            APIRouter::RECOVERY_CODES,
+            post(regenerate_recovery_codes_handler),
+        )
         .route(APIRouter::REFRESH_TOKEN, post(refresh_handler))
+        .route(APIRouter::LOGOUT, post(logout_handler))
         .route(
             APIRouter::PROFILE,
             get(get_profile_handler).put(update_user_profile_handler),
         )
+        .route(APIRouter::USER_PASSWORD, put(change_password_handler))
+        .route(
+            APIRouter::USER_ACCOUNT,
+            delete(request_account_deletion_handler),
+        )
+        .route(
+            APIRouter::USER_ACCOUNT_DELETION_CANCEL,
+            post(cancel_account_deletion_handler),
+        )
+        .route(APIRouter::USER_EXPORT, get(export_account_handler))
+        .route(
+            APIRouter::USER_SESSIONS,
+            get(list_sessions_handler).delete(revoke_all_sessions_handler),
+        )
+        .route(
+            APIRouter::USER_SESSION_DETAIL,
+            delete(revoke_session_handler),
+        )
+        .route(
+            APIRouter::CAREGIVERS,
+            get(list_caregivers_handler).post(invite_caregiver_handler),
+        )
+        .route(
+            APIRouter::CAREGIVER_ACCEPT,
+            post(accept_caregiver_invite_handler),
+        )
+        .route(
+            APIRouter::CAREGIVER_DETAIL,
+            delete(revoke_caregiver_link_handler),
+        )
+        .route(APIRouter::CAREGIVING, get(list_caregiving_handler))
+        .route(
+            APIRouter::CAREGIVING_DIET_RECORDS,
+            get(caregiving_diet_records_handler),
+        )
+        .route(
+            APIRouter::CAREGIVING_DIET_STATS,
+            get(caregiving_diet_stats_handler),
+        )
+        .route(
+            APIRouter::CAREGIVING_CHAT_SUMMARIES,
+            get(caregiving_chat_summaries_handler),
+        )
+        .route(APIRouter::CALCULATOR, post(basic_calculate_handler))
         .route(
             APIRouter::DIET,
             post(yolo_handler).route_layer(middleware::from_fn_with_state(
@@ -158,7 +342,42 @@ pub fn create_app(state: Arc<AppState>) -> Router {
                 require_route_enabled_middleware,
             )),
         )
-        .route(APIRouter::DIET_RECORD, get(diet_records_handler))
+        .route(
+            APIRouter::DIET_JOBS,
+            post(create_diet_job_handler).route_layer(middleware::from_fn_with_state(
+                RouteControlGuardState {
+                    app_state: state.clone(),
+                    route_key: "diet",
+                },
+                require_route_enabled_middleware,
+            )),
+        )
+        .route(APIRouter::DIET_JOB_DETAIL, get(get_diet_job_handler))
+        .route(APIRouter::DIET_JOB_EVENTS, get(diet_job_events_handler))
+        .route(
+            APIRouter::DIET_RECORD,
+            get(diet_records_handler).post(create_manual_meal_handler),
+        )
+        .route(
+            APIRouter::DIET_RECORD_DETAIL,
+            get(diet_record_detail_handler)
+                .patch(update_diet_record_handler)
+                .delete(delete_diet_record_handler),
+        )
+        .route(APIRouter::FOODS, get(search_foods_handler))
+        .route(APIRouter::FOOD_DETAIL, get(food_detail_handler))
+        .route(
+            APIRouter::DIET_RECORD_ANNOTATIONS_UNREAD,
+            get(unread_annotations_handler),
+        )
+        .route(
+            APIRouter::DIET_RECORD_ANNOTATIONS_READ,
+            post(mark_annotations_read_handler),
+        )
+        .route(
+            APIRouter::DIET_RECORD_ANNOTATION_REPLIES,
+            post(reply_to_annotation_handler),
+        )
         .route(
             APIRouter::DIET_IMAGE,
             post(diet_image_handler).route_layer(middleware::from_fn_with_state(
@@ -335,6 +554,14 @@ fn build_cors_layer() -> CorsLayer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        oauth::provider::IdentityProviders,
+        utils::{
+            food_detector::{DEFAULT_YOLO_SCRIPT, SubprocessDetector},
+            mailer::OutboxMailer,
+            nutrition_engine::NutritionEngine,
+        },
+    };
     use axum::{
         body::Body,
         http::{Method, Request, StatusCode, header},
@@ -352,6 +579,13 @@ mod tests {
         let state = Arc::new(AppState {
             db,
             ai_prompt_config: json!({}),
+            identity_providers: Arc::new(IdentityProviders::default()),
+            mailer: Arc::new(OutboxMailer::new(
+                std::env::temp_dir().join("healthy_diet_test_outbox"),
+                "Healthy Diet Test <no-reply@localhost>".to_string(),
+            )),
+            food_detector: Arc::new(SubprocessDetector::new("python3", DEFAULT_YOLO_SCRIPT)),
+            nutrition_engine: Arc::new(NutritionEngine::embedded()),
         });
 
         create_app(state)
@@ -593,8 +827,64 @@ mod tests {
 
         for path in [
             "/auth/admin/login",
+            "/auth/2fa/verify",
+            "/auth/2fa/totp/setup",
+            "/auth/2fa/totp/confirm",
+            "/api/auth/2fa/recovery-codes",
+            "/api/user/password",
+            "/api/user",
+            "/api/user/deletion/cancel",
+            "/api/user/export",
             "/api/auth/refresh",
+            "/api/auth/logout",
+            "/auth/discord/link",
+            "/auth/oauth/{provider}/login",
+            "/auth/oauth/{provider}/callback",
+            "/auth/oauth/link",
+            "/auth/email/verify",
+            "/api/auth/email/verification",
+            "/auth/password/forgot",
+            "/auth/password/reset",
+            "/api/user/sessions",
+            "/api/user/sessions/{session_id}",
+            "/api/caregivers",
+            "/api/caregivers/accept",
+            "/api/caregivers/{link_id}",
+            "/api/caregiving",
+            "/api/caregiving/{link_id}/diet-records",
+            "/api/caregiving/{link_id}/diet-stats",
+            "/api/caregiving/{link_id}/chat-summaries",
+            "/api/diet_record/{record_id}",
+            "/api/diet_record/annotations/unread",
+            "/api/diet_record/{record_id}/annotations/read",
+            "/api/diet_record/{record_id}/annotations/{annotation_id}/replies",
+            "/api/calculator",
+            "/api/diet/jobs",
+            "/api/diet/jobs/{job_id}",
+            "/api/diet/jobs/{job_id}/events",
+            "/api/foods",
+            "/api/foods/{food_id}",
             "/admin/me",
+            "/admin/api-tokens",
+            "/admin/api-tokens/{token_id}",
+            "/.well-known/jwks.json",
+            "/admin/users/{user_id}/unlock",
+            "/admin/users/{user_id}/role",
+            "/admin/roles",
+            "/admin/roles/{role}",
+            "/admin/users/{user_id}/suspend",
+            "/admin/users/{user_id}/unsuspend",
+            "/admin/users/{user_id}/password-reset",
+            "/admin/nutritionists/{nutritionist_id}/clients",
+            "/admin/nutritionists/{nutritionist_id}/clients/{user_id}",
+            "/admin/workspace/clients",
+            "/admin/workspace/clients/{user_id}/diet-records",
+            "/admin/workspace/clients/{user_id}/diet-records/{record_id}/annotations",
+            "/admin/workspace/clients/{user_id}/diet-records/{record_id}/annotations/read",
+            "/admin/workspace/clients/{user_id}/chat-rooms",
+            "/admin/workspace/clients/{user_id}/chat-rooms/{room_id}",
+            "/admin/foods/import",
+            "/admin/datasets/yolo",
             "/api/gemma4/health",
             "/openapi.yml",
             "/api/chat",
@@ -0,0 +1,638 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::HashSet,
+    env,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    model::{AppState, ENVKey},
+    utils::audit::write_audit_log,
+};
+
+pub const KIND_EXPORT: &str = "export";
+pub const KIND_DELETION: &str = "deletion";
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_PROCESSING: &str = "processing";
+pub const STATUS_COMPLETED: &str = "completed";
+pub const STATUS_FAILED: &str = "failed";
+pub const STATUS_CANCELLED: &str = "cancelled";
+pub const STATUS_EXPIRED: &str = "expired";
+
+const DEFAULT_DELETION_GRACE_DAYS: i64 = 14;
+/// Finished exports are downloadable for this long, then the ZIP is removed.
+const EXPORT_RETENTION_DAYS: i64 = 7;
+const WORKER_POLL_SECS: u64 = 30;
+/// Jobs left in `processing` this long (e.g. by a crash) are picked up again.
+const PROCESSING_TIMEOUT_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountJob {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub kind: String,
+    pub status: String,
+    pub run_after: DateTime<Utc>,
+    #[serde(skip)]
+    pub file_path: Option<String>,
+    pub error_message: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+const JOB_COLUMNS: &str = "id, user_id, kind, status, run_after, file_path, error_message, created_at, completed_at, expires_at";
+
+pub fn deletion_grace_days() -> i64 {
+    env::var(ENVKey::ACCOUNT_DELETION_GRACE_DAYS)
+        .ok()
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(DEFAULT_DELETION_GRACE_DAYS)
+        .clamp(0, 90)
+}
+
+fn export_dir() -> PathBuf {
+    env::var(ENVKey::ACCOUNT_EXPORT_DIR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("exports"))
+}
+
+/// The deletion still waiting out its grace period, if any.
+pub async fn pending_deletion(
+    db: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<AccountJob>, sqlx::Error> {
+    sqlx::query_as::<_, AccountJob>(&format!(
+        "SELECT {} FROM account_jobs WHERE user_id = $1 AND kind = $2 AND status IN ($3, $4)",
+        JOB_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(KIND_DELETION)
+    .bind(STATUS_PENDING)
+    .bind(STATUS_PROCESSING)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn schedule_deletion(db: &PgPool, user_id: Uuid) -> Result<AccountJob, sqlx::Error> {
+    sqlx::query_as::<_, AccountJob>(&format!(
+        r#"
+        INSERT INTO account_jobs (id, user_id, kind, status, run_after, created_at)
+        VALUES ($1, $2, $3, $4, now() + make_interval(days => $5::int), now())
+        RETURNING {}
+        "#,
+        JOB_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(KIND_DELETION)
+    .bind(STATUS_PENDING)
+    .bind(deletion_grace_days() as i32)
+    .fetch_one(db)
+    .await
+}
+
+/// Cancels a deletion that has not started yet.
+pub async fn cancel_deletion(
+    db: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<AccountJob>, sqlx::Error> {
+    sqlx::query_as::<_, AccountJob>(&format!(
+        r#"
+        UPDATE account_jobs
+        SET status = $3, completed_at = now()
+        WHERE user_id = $1 AND kind = $2 AND status = $4
+        RETURNING {}
+        "#,
+        JOB_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(KIND_DELETION)
+    .bind(STATUS_CANCELLED)
+    .bind(STATUS_PENDING)
+    .fetch_optional(db)
+    .await
+}
+
+/// Latest export that is queued, running or still downloadable.
+pub async fn current_export(db: &PgPool, user_id: Uuid) -> Result<Option<AccountJob>, sqlx::Error> {
+    sqlx::query_as::<_, AccountJob>(&format!(
+        r#"
+        SELECT {} FROM account_jobs
+        WHERE user_id = $1 AND kind = $2
+          AND status IN ($3, $4, $5)
+          AND (expires_at IS NULL OR expires_at > now())
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#,
+        JOB_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(KIND_EXPORT)
+    .bind(STATUS_PENDING)
+    .bind(STATUS_PROCESSING)
+    .bind(STATUS_COMPLETED)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn enqueue_export(db: &PgPool, user_id: Uuid) -> Result<AccountJob, sqlx::Error> {
+    sqlx::query_as::<_, AccountJob>(&format!(
+        r#"
+        INSERT INTO account_jobs (id, user_id, kind, status, run_after, created_at)
+        VALUES ($1, $2, $3, $4, now(), now())
+        RETURNING {}
+        "#,
+        JOB_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(KIND_EXPORT)
+    .bind(STATUS_PENDING)
+    .fetch_one(db)
+    .await
+}
+
+async fn claim_due_job(db: &PgPool) -> Result<Option<AccountJob>, sqlx::Error> {
+    sqlx::query_as::<_, AccountJob>(&format!(
+        r#"
+        WITH candidate AS (
+            SELECT id AS candidate_id
+            FROM account_jobs
+            WHERE (status = $1 AND run_after <= now())
+               OR (status = $2 AND started_at < now() - make_interval(secs => $3::double precision))
+            ORDER BY run_after ASC
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE account_jobs
+        SET status = $2, started_at = now()
+        FROM candidate
+        WHERE id = candidate.candidate_id
+        RETURNING {}
+        "#,
+        JOB_COLUMNS
+    ))
+    .bind(STATUS_PENDING)
+    .bind(STATUS_PROCESSING)
+    .bind(PROCESSING_TIMEOUT_SECS as f64)
+    .fetch_optional(db)
+    .await
+}
+
+async fn mark_job_completed(
+    db: &PgPool,
+    id: Uuid,
+    file_path: Option<&str>,
+    retention_days: Option<i64>,
+) {
+    if let Err(e) = sqlx::query(
+        r#"
+        UPDATE account_jobs
+        SET status = $2,
+            file_path = $3,
+            error_message = NULL,
+            completed_at = now(),
+            expires_at = now() + make_interval(days => $4::int)
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(STATUS_COMPLETED)
+    .bind(file_path)
+    .bind(retention_days.map(|days| days as i32))
+    .execute(db)
+    .await
+    {
+        error!("Failed to mark account job {} completed: {:?}", id, e);
+    }
+}
+
+async fn mark_job_failed(db: &PgPool, id: Uuid, error_message: &str) {
+    if let Err(e) = sqlx::query(
+        "UPDATE account_jobs SET status = $2, error_message = $3, completed_at = now() WHERE id = $1",
+    )
+    .bind(id)
+    .bind(STATUS_FAILED)
+    .bind(error_message)
+    .execute(db)
+    .await
+    {
+        error!("Failed to mark account job {} failed: {:?}", id, e);
+    }
+}
+
+/// Files on disk that belong to the user's meals, chats and queued meal uploads.
+async fn user_file_paths(db: &PgPool, user_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
+    let mut paths = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT path FROM (
+            SELECT original_image_path AS path FROM diet_records WHERE user_id = $1
+            UNION
+            SELECT result_image_path FROM diet_records WHERE user_id = $1
+            UNION
+            SELECT image_path FROM diet_chat_history WHERE user_id = $1
+            UNION
+            SELECT image_path FROM diet_analysis_jobs WHERE user_id = $1
+        ) files
+        WHERE path IS NOT NULL AND path <> ''
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    paths.sort();
+    Ok(paths)
+}
+
+async fn json_rows(db: &PgPool, table: &str, user_id: Uuid) -> Result<Value, sqlx::Error> {
+    sqlx::query_scalar::<_, Value>(&format!(
+        "SELECT COALESCE(jsonb_agg(to_jsonb(t) ORDER BY t.created_at), '[]'::jsonb) FROM {} t WHERE t.user_id = $1",
+        table
+    ))
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+}
+
+/// Tables keyed by diet record rather than user: archive name, table and row order.
+const RECORD_DOCUMENTS: [(&str, &str, &str); 2] = [
+    (
+        "diet_record_items.json",
+        "diet_record_items",
+        "t.record_id, t.position",
+    ),
+    (
+        "diet_record_annotations.json",
+        "diet_record_annotations",
+        "t.created_at",
+    ),
+];
+
+async fn record_rows(
+    db: &PgPool,
+    table: &str,
+    order_by: &str,
+    user_id: Uuid,
+) -> Result<Value, sqlx::Error> {
+    sqlx::query_scalar::<_, Value>(&format!(
+        "SELECT COALESCE(jsonb_agg(to_jsonb(t) ORDER BY {}), '[]'::jsonb) FROM {} t JOIN diet_records r ON r.id = t.record_id WHERE r.user_id = $1",
+        order_by, table
+    ))
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+}
+
+/// Writes the export archive: one JSON file per data set plus every referenced image under
+/// `images/`, with `images.json` mapping stored paths to archive names.
+fn write_export_zip(
+    target: &Path,
+    documents: &[(&str, Value)],
+    image_paths: &[String],
+) -> Result<(), String> {
+    let file = File::create(target).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+
+    let mut used_names = HashSet::new();
+    let mut image_index = serde_json::Map::new();
+    for (i, path) in image_paths.iter().enumerate() {
+        let Ok(bytes) = std::fs::read(path) else {
+            warn!("Export skipped missing image {}", path);
+            continue;
+        };
+        let base = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| format!("image-{}", i));
+        let name = if used_names.insert(base.clone()) {
+            format!("images/{}", base)
+        } else {
+            format!("images/{}-{}", i, base)
+        };
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| e.to_string())?;
+        zip.write_all(&bytes).map_err(|e| e.to_string())?;
+        image_index.insert(path.clone(), Value::String(name));
+    }
+
+    let images = Value::Object(image_index);
+    for (name, value) in documents.iter().chain([("images.json", images)].iter()) {
+        let body = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+        zip.start_file(*name, options).map_err(|e| e.to_string())?;
+        zip.write_all(&body).map_err(|e| e.to_string())?;
+    }
+
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+async fn run_export(db: &PgPool, job: &AccountJob) -> Result<String, String> {
+    let db_error = |e: sqlx::Error| format!("Database error: {}", e);
+
+    // Listed explicitly so credentials, moderation notes and future internal columns stay out.
+    let profile = sqlx::query_scalar::<_, Value>(
+        r#"
+        SELECT jsonb_build_object(
+            'id', id,
+            'email', email,
+            'email_verified_at', email_verified_at,
+            'nickname', nickname,
+            'avatar_url', avatar_url,
+            'role', role,
+            'height', height,
+            'weight', weight,
+            'age', age,
+            'gender', gender,
+            'taboo', taboo,
+            'disease', disease
+        )
+        FROM users WHERE id = $1
+        "#,
+    )
+    .bind(job.user_id)
+    .fetch_optional(db)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(|| "User no longer exists".to_string())?;
+
+    let mut documents = vec![
+        ("profile.json", profile),
+        (
+            "diet_records.json",
+            json_rows(db, "diet_records", job.user_id)
+                .await
+                .map_err(db_error)?,
+        ),
+        (
+            "chat_rooms.json",
+            json_rows(db, "chat_rooms", job.user_id)
+                .await
+                .map_err(db_error)?,
+        ),
+        (
+            "chat_messages.json",
+            json_rows(db, "diet_chat_history", job.user_id)
+                .await
+                .map_err(db_error)?,
+        ),
+    ];
+    for (name, table, order_by) in RECORD_DOCUMENTS {
+        documents.push((
+            name,
+            record_rows(db, table, order_by, job.user_id)
+                .await
+                .map_err(db_error)?,
+        ));
+    }
+    let image_paths = user_file_paths(db, job.user_id).await.map_err(db_error)?;
+
+    let dir = export_dir();
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Cannot create export dir: {}", e))?;
+    let target = dir.join(format!("{}.zip", job.id));
+    let target_str = target.to_string_lossy().to_string();
+
+    tokio::task::spawn_blocking(move || write_export_zip(&target, &documents, &image_paths))
+        .await
+        .map_err(|e| format!("Export task failed: {}", e))??;
+
+    Ok(target_str)
+}
+
+/// Removes files, ignoring ones already gone. Returns how many were removed.
+pub(crate) async fn remove_files(paths: &[String]) -> usize {
+    let mut removed = 0;
+    for path in paths {
+        match tokio::fs::remove_file(path).await {
+            Ok(()) => removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove {}: {}", path, e),
+        }
+    }
+    removed
+}
+
+async fn run_deletion(db: &PgPool, job: &AccountJob) -> Result<Value, String> {
+    let db_error = |e: sqlx::Error| format!("Database error: {}", e);
+
+    let mut files = user_file_paths(db, job.user_id).await.map_err(db_error)?;
+    let export_files = sqlx::query_scalar::<_, String>(
+        "SELECT file_path FROM account_jobs WHERE user_id = $1 AND kind = $2 AND file_path IS NOT NULL",
+    )
+    .bind(job.user_id)
+    .bind(KIND_EXPORT)
+    .fetch_all(db)
+    .await
+    .map_err(db_error)?;
+    files.extend(export_files);
+
+    let mut tx = db.begin().await.map_err(db_error)?;
+    let mut counts = serde_json::Map::new();
+    for table in ["diet_chat_history", "chat_rooms", "diet_records"] {
+        let deleted = sqlx::query(&format!("DELETE FROM {} WHERE user_id = $1", table))
+            .bind(job.user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?
+            .rows_affected();
+        counts.insert(table.to_string(), json!(deleted));
+    }
+    // Everything else owned by the user goes through `on delete cascade`.
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(job.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    sqlx::query(
+        r#"
+        UPDATE account_jobs
+        SET status = $3, file_path = NULL, completed_at = COALESCE(completed_at, now())
+        WHERE user_id = $1 AND kind = $2
+        "#,
+    )
+    .bind(job.user_id)
+    .bind(KIND_EXPORT)
+    .bind(STATUS_EXPIRED)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+
+    counts.insert("files".to_string(), json!(remove_files(&files).await));
+    Ok(Value::Object(counts))
+}
+
+async fn process_job(db: &PgPool, job: AccountJob) {
+    let user_id = job.user_id.to_string();
+    match job.kind.as_str() {
+        KIND_EXPORT => match run_export(db, &job).await {
+            Ok(path) => {
+                mark_job_completed(db, job.id, Some(&path), Some(EXPORT_RETENTION_DAYS)).await;
+                write_audit_log(
+                    db,
+                    None,
+                    "ACCOUNT_EXPORT_COMPLETED",
+                    "user",
+                    &user_id,
+                    json!({ "jobId": job.id }),
+                )
+                .await;
+                info!("Account export {} ready for user {}", job.id, user_id);
+            }
+            Err(e) => {
+                error!("Account export {} failed: {}", job.id, e);
+                mark_job_failed(db, job.id, &e).await;
+            }
+        },
+        KIND_DELETION => match run_deletion(db, &job).await {
+            Ok(deleted) => {
+                mark_job_completed(db, job.id, None, None).await;
+                write_audit_log(
+                    db,
+                    None,
+                    "ACCOUNT_DELETED",
+                    "user",
+                    &user_id,
+                    json!({ "jobId": job.id, "deleted": deleted }),
+                )
+                .await;
+                info!("Account {} deleted", user_id);
+            }
+            Err(e) => {
+                error!("Account deletion {} failed: {}", job.id, e);
+                mark_job_failed(db, job.id, &e).await;
+            }
+        },
+        other => {
+            warn!("Unknown account job kind {} ({})", other, job.id);
+            mark_job_failed(db, job.id, "Unknown job kind").await;
+        }
+    }
+}
+
+async fn remove_expired_exports(db: &PgPool) {
+    let expired = sqlx::query_scalar::<_, String>(
+        r#"
+        WITH expired AS (
+            SELECT id AS expired_id, file_path AS expired_path
+            FROM account_jobs
+            WHERE kind = $1 AND status = $3 AND expires_at <= now() AND file_path IS NOT NULL
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE account_jobs
+        SET status = $2, file_path = NULL
+        FROM expired
+        WHERE id = expired.expired_id
+        RETURNING expired.expired_path
+        "#,
+    )
+    .bind(KIND_EXPORT)
+    .bind(STATUS_EXPIRED)
+    .bind(STATUS_COMPLETED)
+    .fetch_all(db)
+    .await;
+    match expired {
+        Ok(paths) if !paths.is_empty() => {
+            let removed = remove_files(&paths).await;
+            info!("Removed {} expired account export(s)", removed);
+        }
+        Ok(_) => {}
+        Err(e) => error!("Failed to expire account exports: {:?}", e),
+    }
+}
+
+/// Runs exports and due deletions in the background, one job at a time.
+pub fn start_account_job_worker(state: Arc<AppState>) {
+    let pool = state.db.clone();
+    info!(
+        "Account job worker started. poll={}s deletion_grace={}d",
+        WORKER_POLL_SECS,
+        deletion_grace_days()
+    );
+
+    tokio::spawn(async move {
+        loop {
+            remove_expired_exports(&pool).await;
+
+            loop {
+                match claim_due_job(&pool).await {
+                    Ok(Some(job)) => {
+                        info!("Account job worker processing {} job {}", job.kind, job.id);
+                        process_job(&pool, job).await;
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!("Account job worker claim error: {:?}", e);
+                        break;
+                    }
+                }
+            }
+
+            tokio::time::sleep(Duration::from_secs(WORKER_POLL_SECS)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_export_zip_contains_documents_and_images() {
+        let dir = env::temp_dir().join(format!("account_export_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("meal.jpg");
+        std::fs::write(&image, b"jpeg-bytes").unwrap();
+        let image = image.to_string_lossy().to_string();
+        let missing = dir.join("gone.jpg").to_string_lossy().to_string();
+        let target = dir.join("export.zip");
+
+        let mut documents = vec![("profile.json", json!({ "email": "user@example.com" }))];
+        for (name, _, _) in RECORD_DOCUMENTS {
+            documents.push((name, json!([{ "record_id": Uuid::nil() }])));
+        }
+        write_export_zip(&target, &documents, &[image.clone(), missing]).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&target).unwrap()).unwrap();
+        let mut profile = String::new();
+        archive
+            .by_name("profile.json")
+            .unwrap()
+            .read_to_string(&mut profile)
+            .unwrap();
+        assert!(profile.contains("user@example.com"));
+        for name in ["diet_record_items.json", "diet_record_annotations.json"] {
+            assert!(archive.by_name(name).is_ok(), "{} missing", name);
+        }
+
+        let mut index = String::new();
+        archive
+            .by_name("images.json")
+            .unwrap()
+            .read_to_string(&mut index)
+            .unwrap();
+        let index: Value = serde_json::from_str(&index).unwrap();
+        assert_eq!(index[&image], "images/meal.jpg");
+        assert!(archive.by_name("images/meal.jpg").is_ok());
+        assert_eq!(archive.len(), 5);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Written by the user's assigned nutritionist: a comment, a corrected calorie value and/or tags.
+pub const AUTHOR_NUTRITIONIST: &str = "nutritionist";
+/// The record owner's reply to a nutritionist annotation.
+pub const AUTHOR_USER: &str = "user";
+
+pub const MAX_COMMENT_CHARS: usize = 2000;
+pub const MAX_TAGS: usize = 10;
+pub const MAX_TAG_CHARS: usize = 32;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DietRecordAnnotation {
+    pub id: Uuid,
+    pub record_id: Uuid,
+    /// The annotation a user reply answers; `None` for nutritionist annotations.
+    pub parent_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub author_role: String,
+    pub author_nickname: Option<String>,
+    pub comment: Option<String>,
+    pub corrected_calories: Option<f64>,
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// When the other party read it.
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// Trims and de-duplicates tags, rejecting empty, overlong or too many.
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_CHARS {
+            return Err(format!("Tags must be 1-{} characters", MAX_TAG_CHARS));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(format!("At most {} tags are allowed", MAX_TAGS));
+    }
+    Ok(normalized)
+}
+
+/// Trims the comment; blank becomes `None`.
+pub fn normalize_comment(comment: Option<&str>) -> Result<Option<String>, String> {
+    let comment = comment.map(str::trim).filter(|comment| !comment.is_empty());
+    if comment.is_some_and(|comment| comment.chars().count() > MAX_COMMENT_CHARS) {
+        return Err(format!(
+            "Comments are limited to {} characters",
+            MAX_COMMENT_CHARS
+        ));
+    }
+    Ok(comment.map(str::to_string))
+}
+
+const ANNOTATION_COLUMNS: &str = "a.id, a.record_id, a.parent_id, a.author_id, a.author_role, \
+     u.nickname AS author_nickname, a.comment, a.corrected_calories, a.tags, a.created_at, a.read_at";
+
+/// Annotations of the given records in thread order, keyed by record.
+pub async fn annotations_for_records(
+    db: &PgPool,
+    record_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<DietRecordAnnotation>>, sqlx::Error> {
+    if record_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let annotations = sqlx::query_as::<_, DietRecordAnnotation>(&format!(
+        r#"
+        SELECT {ANNOTATION_COLUMNS}
+        FROM diet_record_annotations a
+        LEFT JOIN users u ON u.id = a.author_id
+        WHERE a.record_id = ANY($1)
+        ORDER BY a.created_at ASC
+        "#
+    ))
+    .bind(record_ids)
+    .fetch_all(db)
+    .await?;
+
+    let mut by_record: HashMap<Uuid, Vec<DietRecordAnnotation>> = HashMap::new();
+    for annotation in annotations {
+        by_record
+            .entry(annotation.record_id)
+            .or_default()
+            .push(annotation);
+    }
+    Ok(by_record)
+}
+
+/// Whether `record_id` is one of `user_id`'s diet records.
+pub async fn record_belongs_to(
+    db: &PgPool,
+    record_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM diet_records WHERE id = $1 AND user_id = $2)",
+    )
+    .bind(record_id)
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+}
+
+pub struct NewAnnotation<'a> {
+    pub record_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub author_role: &'a str,
+    pub comment: Option<&'a str>,
+    pub corrected_calories: Option<f64>,
+    pub tags: &'a [String],
+}
+
+pub async fn create_annotation(
+    db: &PgPool,
+    annotation: &NewAnnotation<'_>,
+) -> Result<DietRecordAnnotation, sqlx::Error> {
+    sqlx::query_as::<_, DietRecordAnnotation>(&format!(
+        r#"
+        WITH a AS (
+            INSERT INTO diet_record_annotations
+                (record_id, parent_id, author_id, author_role, comment, corrected_calories, tags)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+        )
+        SELECT {ANNOTATION_COLUMNS}
+        FROM a
+        LEFT JOIN users u ON u.id = a.author_id
+        "#
+    ))
+    .bind(annotation.record_id)
+    .bind(annotation.parent_id)
+    .bind(annotation.author_id)
+    .bind(annotation.author_role)
+    .bind(annotation.comment)
+    .bind(annotation.corrected_calories)
+    .bind(annotation.tags)
+    .fetch_one(db)
+    .await
+}
+
+/// A nutritionist annotation on `record_id` that a user reply can attach to.
+pub async fn find_thread_root(
+    db: &PgPool,
+    record_id: Uuid,
+    annotation_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM diet_record_annotations
+            WHERE id = $1 AND record_id = $2 AND parent_id IS NULL AND author_role = $3
+        )
+        "#,
+    )
+    .bind(annotation_id)
+    .bind(record_id)
+    .bind(AUTHOR_NUTRITIONIST)
+    .fetch_one(db)
+    .await
+}
+
+/// Marks what `reader_id` received on the record as read: nutritionist annotations for the
+/// record owner, replies to their own annotations for a nutritionist.
+pub async fn mark_read(db: &PgPool, record_id: Uuid, reader_id: Uuid) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE diet_record_annotations a
+        SET read_at = now()
+        WHERE a.record_id = $1
+          AND a.read_at IS NULL
+          AND a.author_id <> $2
+          AND (
+              EXISTS(SELECT 1 FROM diet_records d WHERE d.id = a.record_id AND d.user_id = $2)
+              OR EXISTS(
+                  SELECT 1 FROM diet_record_annotations p
+                  WHERE p.id = a.parent_id AND p.author_id = $2
+              )
+          )
+        "#,
+    )
+    .bind(record_id)
+    .bind(reader_id)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Nutritionist annotations on the user's records they have not read yet.
+pub async fn unread_count_for_user(db: &PgPool, user_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT COUNT(*)
+        FROM diet_record_annotations a
+        JOIN diet_records d ON d.id = a.record_id
+        WHERE d.user_id = $1 AND a.author_id <> $1 AND a.read_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tags_and_comment() {
+        let tags = vec![
+            " Salty ".to_string(),
+            "salty".to_string(),
+            "fried".to_string(),
+        ];
+        assert_eq!(normalize_tags(&tags).unwrap(), vec!["salty", "fried"]);
+        assert!(normalize_tags(&["  ".to_string()]).is_err());
+        let many: Vec<String> = (0..=MAX_TAGS).map(|i| format!("tag{}", i)).collect();
+        assert!(normalize_tags(&many).is_err());
+
+        assert_eq!(normalize_comment(Some("  ")).unwrap(), None);
+        assert_eq!(
+            normalize_comment(Some(" Less rice ")).unwrap().as_deref(),
+            Some("Less rice")
+        );
+        assert!(normalize_comment(Some(&"a".repeat(MAX_COMMENT_CHARS + 1))).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+/// Bearer tokens starting with this prefix are API tokens rather than JWTs.
+pub const API_TOKEN_PREFIX: &str = "hdk_";
+
+pub const SCOPE_RAG_READ: &str = "rag:read";
+pub const SCOPE_RAG_WRITE: &str = "rag:write";
+pub const SCOPE_KG_READ: &str = "kg:read";
+pub const SCOPE_KG_WRITE: &str = "kg:write";
+pub const SCOPE_USERS_READ: &str = "users:read";
+pub const SCOPE_USERS_WRITE: &str = "users:write";
+pub const SCOPE_ANNOUNCEMENTS_READ: &str = "announcements:read";
+pub const SCOPE_ANNOUNCEMENTS_WRITE: &str = "announcements:write";
+pub const SCOPE_ROUTES_READ: &str = "routes:read";
+pub const SCOPE_ROUTES_WRITE: &str = "routes:write";
+
+pub const API_TOKEN_SCOPES: [&str; 10] = [
+    SCOPE_RAG_READ,
+    SCOPE_RAG_WRITE,
+    SCOPE_KG_READ,
+    SCOPE_KG_WRITE,
+    SCOPE_USERS_READ,
+    SCOPE_USERS_WRITE,
+    SCOPE_ANNOUNCEMENTS_READ,
+    SCOPE_ANNOUNCEMENTS_WRITE,
+    SCOPE_ROUTES_READ,
+    SCOPE_ROUTES_WRITE,
+];
+
+/// Scope an API token needs for a route, keyed by method and matched path. Routes missing
+/// here (token management, `/admin/me`, every user-facing route) are closed to API tokens.
+const ROUTE_SCOPES: &[(Method, &str, &str)] = &[
+    (Method::GET, "/admin/rag/documents", SCOPE_RAG_READ),
+    (Method::POST, "/admin/rag/documents", SCOPE_RAG_WRITE),
+    (
+        Method::GET,
+        "/admin/rag/documents/{document_id}",
+        SCOPE_RAG_READ,
+    ),
+    (
+        Method::DELETE,
+        "/admin/rag/documents/{document_id}",
+        SCOPE_RAG_WRITE,
+    ),
+    (
+        Method::POST,
+        "/admin/rag/documents/{document_id}/reindex",
+        SCOPE_RAG_WRITE,
+    ),
+    (
+        Method::GET,
+        "/admin/rag/documents/{document_id}/file",
+        SCOPE_RAG_READ,
+    ),
+    (
+        Method::GET,
+        "/admin/rag/documents/{document_id}/preview",
+        SCOPE_RAG_READ,
+    ),
+    (
+        Method::POST,
+        "/api/admin/knowledge-graph/rebuild",
+        SCOPE_KG_WRITE,
+    ),
+    (
+        Method::GET,
+        "/api/admin/knowledge-graph/documents/{document_id}",
+        SCOPE_KG_READ,
+    ),
+    (
+        Method::POST,
+        "/api/admin/knowledge-graph/documents/{document_id}/extract",
+        SCOPE_KG_WRITE,
+    ),
+    (Method::GET, "/admin/users", SCOPE_USERS_READ),
+    (Method::GET, "/admin/users/{user_id}", SCOPE_USERS_READ),
+    (
+        Method::POST,
+        "/admin/users/{user_id}/unlock",
+        SCOPE_USERS_WRITE,
+    ),
+    (
+        Method::POST,
+        "/admin/users/{user_id}/suspend",
+        SCOPE_USERS_WRITE,
+    ),
+    (
+        Method::POST,
+        "/admin/users/{user_id}/unsuspend",
+        SCOPE_USERS_WRITE,
+    ),
+    (
+        Method::POST,
+        "/admin/users/{user_id}/password-reset",
+        SCOPE_USERS_WRITE,
+    ),
+    (Method::GET, "/admin/route-controls", SCOPE_ROUTES_READ),
+    (
+        Method::PATCH,
+        "/admin/route-controls/{route_key}",
+        SCOPE_ROUTES_WRITE,
+    ),
+    (
+        Method::GET,
+        "/admin/announcements",
+        SCOPE_ANNOUNCEMENTS_READ,
+    ),
+    (
+        Method::POST,
+        "/admin/announcements",
+        SCOPE_ANNOUNCEMENTS_WRITE,
+    ),
+    (
+        Method::PATCH,
+        "/admin/announcements/{id}",
+        SCOPE_ANNOUNCEMENTS_WRITE,
+    ),
+    (
+        Method::POST,
+        "/admin/announcements/{id}/publish",
+        SCOPE_ANNOUNCEMENTS_WRITE,
+    ),
+    (
+        Method::POST,
+        "/admin/announcements/{id}/archive",
+        SCOPE_ANNOUNCEMENTS_WRITE,
+    ),
+];
+
+pub fn required_scope(method: &Method, matched_path: &str) -> Option<&'static str> {
+    ROUTE_SCOPES
+        .iter()
+        .find(|(route_method, path, _)| route_method == method && *path == matched_path)
+        .map(|(_, _, scope)| *scope)
+}
+
+pub fn is_known_scope(scope: &str) -> bool {
+    API_TOKEN_SCOPES.contains(&scope)
+}
+
+pub fn hash_api_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Returns the plaintext token (shown once) and the hash to store.
+pub fn generate_api_token() -> (String, String) {
+    let token = format!(
+        "{}{}{}",
+        API_TOKEN_PREFIX,
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    );
+    let hash = hash_api_token(&token);
+    (token, hash)
+}
+
+/// The owner an API token acts for, with the scopes it was granted.
+#[derive(Debug, Clone, FromRow)]
+pub struct ApiTokenPrincipal {
+    pub token_id: Uuid,
+    pub user_id: Uuid,
+    pub email: String,
+    pub role: String,
+    pub scopes: Vec<String>,
+}
+
+impl ApiTokenPrincipal {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope)
+    }
+}
+
+/// Looks up an active (not revoked, not expired) token whose owner is not suspended and
+/// records that it was used.
+pub async fn authenticate_api_token(
+    db: &PgPool,
+    token: &str,
+) -> Result<Option<ApiTokenPrincipal>, sqlx::Error> {
+    sqlx::query_as::<_, ApiTokenPrincipal>(
+        r#"
+        UPDATE api_tokens t
+        SET last_used_at = now()
+        FROM users u
+        WHERE t.token_hash = $1
+          AND u.id = t.created_by
+          AND u.suspended_at IS NULL
+          AND t.revoked_at IS NULL
+          AND (t.expires_at IS NULL OR t.expires_at > now())
+        RETURNING t.id AS token_id, u.id AS user_id, u.email, u.role, t.scopes
+        "#,
+    )
+    .bind(hash_api_token(token))
+    .fetch_optional(db)
+    .await
+}
+
+#[derive(Debug, serde::Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiTokenItem {
+    pub id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_route_scope_is_a_known_scope() {
+        for (_, _, scope) in ROUTE_SCOPES {
+            assert!(is_known_scope(scope), "unknown scope {}", scope);
+        }
+        assert_eq!(
+            required_scope(&Method::GET, "/admin/rag/documents"),
+            Some(SCOPE_RAG_READ)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/admin/rag/documents"),
+            Some(SCOPE_RAG_WRITE)
+        );
+        assert_eq!(required_scope(&Method::GET, "/admin/me"), None);
+    }
+
+    #[test]
+    fn test_generated_token_matches_its_hash() {
+        let (token, hash) = generate_api_token();
+        assert!(token.starts_with(API_TOKEN_PREFIX));
+        assert_eq!(hash_api_token(&token), hash);
+        assert_ne!(generate_api_token().0, token);
+    }
+}
@@ -0,0 +1,35 @@
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Appends a row to `admin_audit_logs`. `admin_id` is `None` for events the system records on
+/// its own (e.g. automatic lockouts). Failures are logged, never returned: an audit hiccup must
+/// not undo the action being audited.
+pub async fn write_audit_log(
+    db: &PgPool,
+    admin_id: Option<Uuid>,
+    action: &str,
+    target_type: &str,
+    target_id: &str,
+    metadata: serde_json::Value,
+) {
+    if let Err(e) = sqlx::query(
+        r#"
+        INSERT INTO admin_audit_logs (admin_id, action, target_type, target_id, metadata, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+    )
+    .bind(admin_id)
+    .bind(action)
+    .bind(target_type)
+    .bind(target_id)
+    .bind(metadata)
+    .execute(db)
+    .await
+    {
+        warn!(
+            "Audit log write failed for {} {} {}: {:?}",
+            action, target_type, target_id, e
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{model::AppState, utils::mailer::EmailMessage};
+
+/// Caregiver may read the linked user's diet records and diet stats.
+pub const SCOPE_MEALS: &str = "meals";
+/// Caregiver may read chat room titles and summaries, never the messages themselves.
+pub const SCOPE_CHAT_SUMMARIES: &str = "chat_summaries";
+/// Caregiver is emailed when the linked user logs a poorly rated meal.
+pub const SCOPE_ALERTS: &str = "alerts";
+
+pub const CAREGIVER_SCOPES: [&str; 3] = [SCOPE_MEALS, SCOPE_CHAT_SUMMARIES, SCOPE_ALERTS];
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_ACTIVE: &str = "active";
+pub const STATUS_REVOKED: &str = "revoked";
+
+pub const INVITE_TTL_DAYS: i64 = 7;
+/// Meals scored below this by the AI evaluation trigger caregiver alerts.
+pub const LOW_SCORE_ALERT_THRESHOLD: i32 = 40;
+
+/// Unambiguous characters for codes read aloud or typed from a phone (no 0/O, 1/I).
+const INVITE_CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const INVITE_CODE_GROUPS: usize = 3;
+const INVITE_CODE_GROUP_LEN: usize = 4;
+
+pub fn is_known_scope(scope: &str) -> bool {
+    CAREGIVER_SCOPES.contains(&scope)
+}
+
+/// A code like `K7QM-2XWD-P9RT` (60 bits) and the hash to store.
+pub fn generate_invite_code() -> (String, String) {
+    let mut bytes = [0u8; INVITE_CODE_GROUPS * INVITE_CODE_GROUP_LEN];
+    OsRng.fill_bytes(&mut bytes);
+    let chars: Vec<char> = bytes
+        .iter()
+        .map(|b| INVITE_CODE_ALPHABET[(*b & 31) as usize] as char)
+        .collect();
+    let code = chars
+        .chunks(INVITE_CODE_GROUP_LEN)
+        .map(|group| group.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-");
+    let hash = hash_invite_code(&code);
+    (code, hash)
+}
+
+/// Case and dashes are ignored so `k7qm2xwdp9rt` matches `K7QM-2XWD-P9RT`.
+pub fn hash_invite_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct CaregiverLink {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub caregiver_id: Option<Uuid>,
+    pub invite_email: Option<String>,
+    pub scopes: Vec<String>,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub invite_expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// The other party's email: the caregiver's for the user, the user's for the caregiver.
+    #[sqlx(default)]
+    pub counterpart_email: Option<String>,
+    #[sqlx(default)]
+    pub counterpart_nickname: Option<String>,
+}
+
+impl CaregiverLink {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+const LINK_COLUMNS: &str = "l.id, l.user_id, l.caregiver_id, l.invite_email, l.scopes, l.status, \
+     l.created_at, l.invite_expires_at, l.accepted_at, l.revoked_at";
+
+pub async fn create_invite(
+    db: &PgPool,
+    user_id: Uuid,
+    invite_email: Option<&str>,
+    scopes: &[String],
+    code_hash: &str,
+) -> Result<CaregiverLink, sqlx::Error> {
+    sqlx::query_as::<_, CaregiverLink>(&format!(
+        r#"
+        INSERT INTO caregiver_links AS l
+            (user_id, invite_email, scopes, status, invite_code_hash, invite_expires_at)
+        VALUES ($1, $2, $3, '{STATUS_PENDING}', $4, $5)
+        RETURNING {LINK_COLUMNS}
+        "#
+    ))
+    .bind(user_id)
+    .bind(invite_email)
+    .bind(scopes)
+    .bind(code_hash)
+    .bind(Utc::now() + Duration::days(INVITE_TTL_DAYS))
+    .fetch_one(db)
+    .await
+}
+
+/// Links the caller to the pending invite matching the code. Returns `None` for unknown,
+/// used or expired codes and for users trying to accept their own invite.
+pub async fn accept_invite(
+    db: &PgPool,
+    caregiver_id: Uuid,
+    code_hash: &str,
+) -> Result<Option<CaregiverLink>, sqlx::Error> {
+    sqlx::query_as::<_, CaregiverLink>(&format!(
+        r#"
+        UPDATE caregiver_links AS l
+        SET caregiver_id = $1, status = '{STATUS_ACTIVE}', accepted_at = now(),
+            invite_code_hash = NULL
+        WHERE invite_code_hash = $2
+          AND status = '{STATUS_PENDING}'
+          AND invite_expires_at > now()
+          AND user_id <> $1
+        RETURNING {LINK_COLUMNS}
+        "#
+    ))
+    .bind(caregiver_id)
+    .bind(code_hash)
+    .fetch_optional(db)
+    .await
+}
+
+/// Links the user shared (pending and active), with the caregiver as counterpart.
+pub async fn links_of_user(db: &PgPool, user_id: Uuid) -> Result<Vec<CaregiverLink>, sqlx::Error> {
+    sqlx::query_as::<_, CaregiverLink>(&format!(
+        r#"
+        SELECT {LINK_COLUMNS}, u.email AS counterpart_email, u.nickname AS counterpart_nickname
+        FROM caregiver_links l
+        LEFT JOIN users u ON u.id = l.caregiver_id
+        WHERE l.user_id = $1 AND l.status <> '{STATUS_REVOKED}'
+        ORDER BY l.created_at DESC
+        "#
+    ))
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
+
+/// Active links where the caller is the caregiver, with the cared-for user as counterpart.
+pub async fn links_of_caregiver(
+    db: &PgPool,
+    caregiver_id: Uuid,
+) -> Result<Vec<CaregiverLink>, sqlx::Error> {
+    sqlx::query_as::<_, CaregiverLink>(&format!(
+        r#"
+        SELECT {LINK_COLUMNS}, u.email AS counterpart_email, u.nickname AS counterpart_nickname
+        FROM caregiver_links l
+        JOIN users u ON u.id = l.user_id
+        WHERE l.caregiver_id = $1 AND l.status = '{STATUS_ACTIVE}'
+        ORDER BY l.accepted_at DESC
+        "#
+    ))
+    .bind(caregiver_id)
+    .fetch_all(db)
+    .await
+}
+
+/// The active link `link_id` as seen by its caregiver.
+pub async fn active_link_for_caregiver(
+    db: &PgPool,
+    link_id: Uuid,
+    caregiver_id: Uuid,
+) -> Result<Option<CaregiverLink>, sqlx::Error> {
+    sqlx::query_as::<_, CaregiverLink>(&format!(
+        r#"
+        SELECT {LINK_COLUMNS}
+        FROM caregiver_links l
+        WHERE l.id = $1 AND l.caregiver_id = $2 AND l.status = '{STATUS_ACTIVE}'
+        "#
+    ))
+    .bind(link_id)
+    .bind(caregiver_id)
+    .fetch_optional(db)
+    .await
+}
+
+/// Either side may revoke; pending invites can be withdrawn by the user.
+pub async fn revoke_link(
+    db: &PgPool,
+    link_id: Uuid,
+    actor_id: Uuid,
+) -> Result<Option<CaregiverLink>, sqlx::Error> {
+    sqlx::query_as::<_, CaregiverLink>(&format!(
+        r#"
+        UPDATE caregiver_links AS l
+        SET status = '{STATUS_REVOKED}', revoked_at = now(), revoked_by = $2,
+            invite_code_hash = NULL
+        WHERE id = $1
+          AND (user_id = $2 OR caregiver_id = $2)
+          AND status <> '{STATUS_REVOKED}'
+        RETURNING {LINK_COLUMNS}
+        "#
+    ))
+    .bind(link_id)
+    .bind(actor_id)
+    .fetch_optional(db)
+    .await
+}
+
+/// Emails of active caregivers of the user holding `scope`.
+pub async fn caregiver_emails_with_scope(
+    db: &PgPool,
+    user_id: Uuid,
+    scope: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(&format!(
+        r#"
+        SELECT u.email
+        FROM caregiver_links l
+        JOIN users u ON u.id = l.caregiver_id
+        WHERE l.user_id = $1 AND l.status = '{STATUS_ACTIVE}' AND $2 = ANY(l.scopes)
+        "#
+    ))
+    .bind(user_id)
+    .bind(scope)
+    .fetch_all(db)
+    .await
+}
+
+/// Emails every caregiver holding the `alerts` scope about a poorly rated meal. Failures are
+/// only logged; the meal has already been saved.
+pub async fn send_low_score_alerts(
+    state: &AppState,
+    user_id: Uuid,
+    display_name: &str,
+    score: i32,
+) {
+    let recipients = match caregiver_emails_with_scope(&state.db, user_id, SCOPE_ALERTS).await {
+        Ok(recipients) => recipients,
+        Err(e) => {
+            error!("DB Error (Caregiver Alerts): {:?}", e);
+            return;
+        }
+    };
+
+    for to in recipients {
+        let sent = state
+            .mailer
+            .send(&EmailMessage {
+                to,
+                subject: format!("Healthy Diet alert for {}", display_name),
+                body: format!(
+                    "{} just logged a meal that scored {}/100 in the diet evaluation.\n\nYou receive this because you are linked as a caregiver with alerts enabled.\n",
+                    display_name, score
+                ),
+            })
+            .await;
+        if let Err(e) = sent {
+            error!("Failed to send caregiver alert for user {}: {}", user_id, e);
+        }
+    }
+    info!("Sent low score caregiver alerts for user {}", user_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invite_code_shape_and_normalized_hash() {
+        let (code, hash) = generate_invite_code();
+        assert_eq!(code.len(), 14);
+        assert_eq!(code.matches('-').count(), 2);
+        assert!(
+            code.bytes()
+                .all(|b| b == b'-' || INVITE_CODE_ALPHABET.contains(&b))
+        );
+        assert_eq!(
+            hash_invite_code(&code.to_lowercase().replace('-', "")),
+            hash
+        );
+        assert_ne!(generate_invite_code().1, hash);
+    }
+}
@@ -0,0 +1,257 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{HeaderMap, header::USER_AGENT, request::Parts},
+};
+use std::{
+    convert::Infallible,
+    env,
+    net::{IpAddr, SocketAddr},
+    sync::OnceLock,
+};
+
+use crate::model::ENVKey;
+
+pub const DEVICE_ID_HEADER: &str = "X-Device-Id";
+const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+const REAL_IP_HEADER: &str = "X-Real-IP";
+/// Hops of `X-Forwarded-For` kept, counted from the right where the trusted proxies append.
+const MAX_FORWARDED_HOPS: usize = 32;
+
+/// Best-effort description of the calling client, stored on session records.
+#[derive(Debug, Default, Clone)]
+pub struct ClientInfo {
+    pub device_id: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+fn header_value(parts: &Parts, name: &str, max_len: usize) -> Option<String> {
+    parts
+        .headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| value.chars().take(max_len).collect())
+}
+
+/// Every `X-Forwarded-For` line joined in order, keeping only the right-most hops so a long
+/// forged prefix cannot push out the ones added by our proxies.
+fn forwarded_for(headers: &HeaderMap) -> Option<String> {
+    let joined = headers
+        .get_all(FORWARDED_FOR_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect::<Vec<_>>()
+        .join(",");
+    if joined.is_empty() {
+        return None;
+    }
+    let mut hops: Vec<&str> = joined.rsplit(',').take(MAX_FORWARDED_HOPS).collect();
+    hops.reverse();
+    Some(hops.join(","))
+}
+
+/// Peers allowed to name the client through `X-Forwarded-For` / `X-Real-IP`: addresses or
+/// CIDR ranges from `TRUSTED_PROXIES`, e.g. `10.0.0.0/8,127.0.0.1`.
+#[derive(Debug, Default, Clone)]
+pub struct TrustedProxies {
+    ranges: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut ranges = Vec::new();
+        for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (address, prefix) = match entry.split_once('/') {
+                Some((address, prefix)) => (address, Some(prefix)),
+                None => (entry, None),
+            };
+            let address: IpAddr = address
+                .parse()
+                .map_err(|_| format!("invalid trusted proxy `{}`", entry))?;
+            let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+            let prefix = match prefix {
+                Some(prefix) => prefix
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|prefix| *prefix <= max_prefix)
+                    .ok_or_else(|| format!("invalid trusted proxy `{}`", entry))?,
+                None => max_prefix,
+            };
+            ranges.push((address.to_canonical(), prefix));
+        }
+        Ok(Self { ranges })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.ranges
+            .iter()
+            .any(|(network, prefix)| match (network, ip) {
+                (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                    let mask = u32::MAX.checked_shl(32 - u32::from(*prefix)).unwrap_or(0);
+                    u32::from(*network) & mask == u32::from(ip) & mask
+                }
+                (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                    let mask = u128::MAX.checked_shl(128 - u32::from(*prefix)).unwrap_or(0);
+                    u128::from(*network) & mask == u128::from(ip) & mask
+                }
+                _ => false,
+            })
+    }
+}
+
+static TRUSTED_PROXIES: OnceLock<TrustedProxies> = OnceLock::new();
+
+/// Process-wide proxy list loaded from the environment on first use.
+pub fn trusted_proxies() -> &'static TrustedProxies {
+    TRUSTED_PROXIES.get_or_init(|| {
+        TrustedProxies::parse(&env::var(ENVKey::TRUSTED_PROXIES).unwrap_or_default())
+            .unwrap_or_else(|e| panic!("Invalid TRUSTED_PROXIES: {}", e))
+    })
+}
+
+/// The client address. Forwarding headers only count when the direct peer is a trusted
+/// proxy; `X-Forwarded-For` is then read from the right, and the first hop that is not a
+/// trusted proxy is the client, since anything left of it may be forged.
+fn resolve_client_ip(
+    peer: IpAddr,
+    forwarded_for: Option<&str>,
+    real_ip: Option<&str>,
+    trusted: &TrustedProxies,
+) -> IpAddr {
+    if !trusted.contains(peer) {
+        return peer;
+    }
+    if let Some(forwarded_for) = forwarded_for {
+        let mut client = peer;
+        for hop in forwarded_for.rsplit(',') {
+            let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+            client = ip.to_canonical();
+            if !trusted.contains(client) {
+                break;
+            }
+        }
+        return client;
+    }
+    real_ip
+        .and_then(|ip| ip.parse::<IpAddr>().ok())
+        .map(|ip| ip.to_canonical())
+        .unwrap_or(peer)
+}
+
+fn client_ip(parts: &Parts) -> Option<String> {
+    let ConnectInfo(peer) = parts.extensions.get::<ConnectInfo<SocketAddr>>()?;
+    let ip = resolve_client_ip(
+        peer.ip().to_canonical(),
+        forwarded_for(&parts.headers).as_deref(),
+        header_value(parts, REAL_IP_HEADER, 64).as_deref(),
+        trusted_proxies(),
+    );
+    Some(ip.to_string())
+}
+
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ClientInfo {
+            device_id: header_value(parts, DEVICE_ID_HEADER, 128),
+            user_agent: header_value(parts, USER_AGENT.as_str(), 512),
+            ip_address: client_ip(parts),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_forwarded_headers_need_a_trusted_peer() {
+        let trusted = TrustedProxies::parse("10.0.0.0/8, 127.0.0.1, fd00::/8").unwrap();
+        assert!(trusted.contains(ip("10.1.2.3")));
+        assert!(trusted.contains(ip("::ffff:127.0.0.1")));
+        assert!(trusted.contains(ip("fd12::1")));
+        assert!(!trusted.contains(ip("11.0.0.1")));
+
+        // A direct client cannot pick its address.
+        let direct = resolve_client_ip(
+            ip("203.0.113.9"),
+            Some("1.2.3.4"),
+            Some("5.6.7.8"),
+            &trusted,
+        );
+        assert_eq!(direct, ip("203.0.113.9"));
+
+        // Behind the proxy, the right-most untrusted hop is the client; the forged left part
+        // is ignored.
+        let proxied = resolve_client_ip(
+            ip("10.0.0.2"),
+            Some("6.6.6.6, 198.51.100.7, 10.0.0.5"),
+            None,
+            &trusted,
+        );
+        assert_eq!(proxied, ip("198.51.100.7"));
+        let garbled = resolve_client_ip(ip("10.0.0.2"), Some("evil, 10.0.0.5"), None, &trusted);
+        assert_eq!(garbled, ip("10.0.0.5"));
+        let real_ip = resolve_client_ip(ip("127.0.0.1"), None, Some("198.51.100.7"), &trusted);
+        assert_eq!(real_ip, ip("198.51.100.7"));
+
+        let none = TrustedProxies::default();
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), Some("1.2.3.4"), None, &none),
+            ip("10.0.0.2")
+        );
+        assert!(TrustedProxies::parse("10.0.0.0/33").is_err());
+        assert!(TrustedProxies::parse("proxy.local").is_err());
+    }
+
+    #[test]
+    fn test_long_forged_forwarded_for_keeps_proxy_hop() {
+        let trusted = TrustedProxies::parse("10.0.0.0/8").unwrap();
+        let forged = vec!["6.6.6.6"; 200].join(", ");
+
+        let mut headers = HeaderMap::new();
+        headers.append(FORWARDED_FOR_HEADER, forged.parse().unwrap());
+        // The proxy appends the real client on a second header line.
+        headers.append(FORWARDED_FOR_HEADER, "198.51.100.7".parse().unwrap());
+
+        let value = forwarded_for(&headers).unwrap();
+        assert_eq!(value.split(',').count(), MAX_FORWARDED_HOPS);
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), Some(&value), None, &trusted),
+            ip("198.51.100.7")
+        );
+
+        let mut single = HeaderMap::new();
+        single.insert(
+            FORWARDED_FOR_HEADER,
+            format!("{}, 198.51.100.7, 10.0.0.5", forged)
+                .parse()
+                .unwrap(),
+        );
+        let value = forwarded_for(&single).unwrap();
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), Some(&value), None, &trusted),
+            ip("198.51.100.7")
+        );
+        assert_eq!(forwarded_for(&HeaderMap::new()), None);
+    }
+}
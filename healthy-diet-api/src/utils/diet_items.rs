@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::utils::nutrition_engine::NutrientVector;
+
+/// How a diet record was created, stored in `diet_records.entry_source`.
+pub const ENTRY_SOURCE_PHOTO: &str = "photo";
+pub const ENTRY_SOURCE_MANUAL: &str = "manual";
+
+/// One food of a diet record.
+#[derive(Debug, Clone, Serialize)]
+pub struct MealItem {
+    /// Nutrition engine class; the record category the item is totalled under.
+    pub class: String,
+    /// The `foods` row the item was logged from.
+    pub food_id: Option<Uuid>,
+    pub name: Option<String>,
+    /// The typed text the item was parsed from.
+    pub source_text: Option<String>,
+    pub weight_g: f64,
+    pub calories: f64,
+    pub nutrients: NutrientVector,
+    /// Where the detector found the item; `None` for foods logged by hand.
+    pub detection: Option<ItemDetection>,
+}
+
+/// The detector box an item was estimated from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemDetection {
+    /// `[x_min, y_min, x_max, y_max]` in pixels of the uploaded image.
+    pub bbox: [f64; 4],
+    pub confidence: f64,
+    /// Share of the reference plate the box covers, 0–1.
+    pub area_ratio: f64,
+    /// The user moved the box or changed what it shows; such labels are exported for
+    /// retraining.
+    #[serde(default)]
+    pub corrected: bool,
+}
+
+impl MealItem {
+    /// The same food at another weight; calories and nutrients scale with it.
+    pub fn with_weight(&self, weight_g: f64) -> Self {
+        let factor = if self.weight_g > 0.0 {
+            weight_g / self.weight_g
+        } else {
+            0.0
+        };
+        Self {
+            weight_g: (weight_g * 10.0).round() / 10.0,
+            calories: (self.calories * factor * 10.0).round() / 10.0,
+            nutrients: self.nutrients.scaled(factor).rounded(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Stores the items of a record in the given order; run inside the record's transaction.
+pub async fn insert_items(
+    conn: &mut PgConnection,
+    record_id: Uuid,
+    items: &[MealItem],
+) -> Result<(), sqlx::Error> {
+    for (position, item) in items.iter().enumerate() {
+        let detection = item.detection.as_ref();
+        sqlx::query(
+            r#"
+            INSERT INTO diet_record_items (
+                record_id, position, class, food_id, name, source_text, weight_g, calories,
+                carbohydrate_g, protein_g, fat_g, fiber_g, sodium_mg, bbox, confidence, area_ratio,
+                label_corrected
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            "#,
+        )
+        .bind(record_id)
+        .bind(position as i32)
+        .bind(&item.class)
+        .bind(item.food_id)
+        .bind(&item.name)
+        .bind(&item.source_text)
+        .bind(item.weight_g)
+        .bind(item.calories)
+        .bind(item.nutrients.carbohydrate_g)
+        .bind(item.nutrients.protein_g)
+        .bind(item.nutrients.fat_g)
+        .bind(item.nutrients.fiber_g)
+        .bind(item.nutrients.sodium_mg)
+        .bind(detection.map(|detection| detection.bbox.to_vec()))
+        .bind(detection.map(|detection| detection.confidence))
+        .bind(detection.map(|detection| detection.area_ratio))
+        .bind(detection.is_some_and(|detection| detection.corrected))
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// An item as stored, with the id used to correct it.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredMealItem {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub item: MealItem,
+}
+
+#[derive(FromRow)]
+struct MealItemRow {
+    id: Uuid,
+    record_id: Uuid,
+    class: String,
+    food_id: Option<Uuid>,
+    name: Option<String>,
+    source_text: Option<String>,
+    weight_g: f64,
+    calories: f64,
+    carbohydrate_g: f64,
+    protein_g: f64,
+    fat_g: f64,
+    fiber_g: f64,
+    sodium_mg: f64,
+    bbox: Option<Vec<f64>>,
+    confidence: Option<f64>,
+    area_ratio: Option<f64>,
+    label_corrected: bool,
+}
+
+impl MealItemRow {
+    fn detection(&self) -> Option<ItemDetection> {
+        Some(ItemDetection {
+            bbox: self.bbox.as_deref()?.try_into().ok()?,
+            confidence: self.confidence?,
+            area_ratio: self.area_ratio?,
+            corrected: self.label_corrected,
+        })
+    }
+}
+
+/// Items of the given records, keyed by record id, in their stored order.
+pub async fn items_for_records(
+    db: &PgPool,
+    record_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<StoredMealItem>>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, MealItemRow>(
+        r#"
+        SELECT id, record_id, class, food_id, name, source_text, weight_g, calories,
+               carbohydrate_g, protein_g, fat_g, fiber_g, sodium_mg, bbox, confidence, area_ratio,
+               label_corrected
+        FROM diet_record_items
+        WHERE record_id = ANY($1)
+        ORDER BY record_id, position
+        "#,
+    )
+    .bind(record_ids)
+    .fetch_all(db)
+    .await?;
+
+    let mut items: HashMap<Uuid, Vec<StoredMealItem>> = HashMap::new();
+    for row in rows {
+        let item = MealItem {
+            detection: row.detection(),
+            class: row.class,
+            food_id: row.food_id,
+            name: row.name,
+            source_text: row.source_text,
+            weight_g: row.weight_g,
+            calories: row.calories,
+            nutrients: NutrientVector {
+                carbohydrate_g: row.carbohydrate_g,
+                protein_g: row.protein_g,
+                fat_g: row.fat_g,
+                fiber_g: row.fiber_g,
+                sodium_mg: row.sodium_mg,
+            },
+        };
+        items
+            .entry(row.record_id)
+            .or_default()
+            .push(StoredMealItem { id: row.id, item });
+    }
+    Ok(items)
+}
+
+/// Replaces the items of a record; run inside the transaction that updates its totals.
+pub async fn replace_items(
+    conn: &mut PgConnection,
+    record_id: Uuid,
+    items: &[MealItem],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM diet_record_items WHERE record_id = $1")
+        .bind(record_id)
+        .execute(&mut *conn)
+        .await?;
+    insert_items(conn, record_id, items).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_weight_scales_calories_and_nutrients() {
+        let item = MealItem {
+            class: "grain".to_string(),
+            food_id: None,
+            name: Some("白飯".to_string()),
+            source_text: None,
+            weight_g: 200.0,
+            calories: 366.0,
+            nutrients: NutrientVector {
+                carbohydrate_g: 82.0,
+                protein_g: 6.2,
+                fat_g: 0.6,
+                fiber_g: 1.2,
+                sodium_mg: 4.0,
+            },
+            detection: None,
+        };
+        let half = item.with_weight(100.0);
+        assert_eq!(half.weight_g, 100.0);
+        assert_eq!(half.calories, 183.0);
+        assert_eq!(half.nutrients.carbohydrate_g, 41.0);
+        assert_eq!(half.name, item.name);
+    }
+}
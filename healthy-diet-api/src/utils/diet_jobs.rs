@@ -0,0 +1,454 @@
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{FromRow, PgPool};
+use std::{
+    env,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+use tokio::sync::{Notify, Semaphore};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    api::diet::{
+        AnalyzedMeal, detect_food, estimate_meal, load_meal_profile, save_meal, score_meal,
+    },
+    model::{AppState, ENVKey},
+    utils::{account_jobs::remove_files, diet_items::ENTRY_SOURCE_PHOTO},
+};
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_PROCESSING: &str = "processing";
+pub const STATUS_COMPLETED: &str = "completed";
+pub const STATUS_FAILED: &str = "failed";
+
+/// The upload is on disk and the job is queued.
+pub const STAGE_STORED: &str = "stored";
+pub const STAGE_DETECTING: &str = "detecting";
+pub const STAGE_ESTIMATING: &str = "estimating";
+pub const STAGE_SCORING: &str = "scoring";
+pub const STAGE_DONE: &str = "done";
+
+const DEFAULT_WORKER_CONCURRENCY: usize = 2;
+/// Fallback poll for jobs queued by other instances; local uploads wake the worker directly.
+const WORKER_POLL_SECS: u64 = 2;
+/// Jobs left in `processing` this long (e.g. by a crash) are picked up again.
+const PROCESSING_TIMEOUT_SECS: i64 = 600;
+/// A job claimed this many times without finishing is failed instead of retried.
+const MAX_ATTEMPTS: i32 = 3;
+
+static JOB_QUEUED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DietJob {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub status: String,
+    pub stage: String,
+    /// Partial results so far, using the field names of the synchronous `/api/diet` response.
+    pub result: Value,
+    pub record_id: Option<Uuid>,
+    pub error_message: Option<String>,
+    #[serde(skip)]
+    pub image_path: String,
+    #[serde(skip)]
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl DietJob {
+    pub fn is_finished(&self) -> bool {
+        self.status == STATUS_COMPLETED || self.status == STATUS_FAILED
+    }
+
+    /// The SSE event name: the current stage, or `failed`.
+    pub fn event_name(&self) -> &str {
+        if self.status == STATUS_FAILED {
+            STATUS_FAILED
+        } else {
+            &self.stage
+        }
+    }
+}
+
+const JOB_COLUMNS: &str = "id, user_id, status, stage, result, record_id, error_message, image_path, attempts, created_at, updated_at, completed_at";
+
+pub async fn enqueue_job(
+    db: &PgPool,
+    user_id: Uuid,
+    image_path: &str,
+) -> Result<DietJob, sqlx::Error> {
+    let job = sqlx::query_as::<_, DietJob>(&format!(
+        r#"
+        INSERT INTO diet_analysis_jobs (id, user_id, status, stage, image_path)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING {}
+        "#,
+        JOB_COLUMNS
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(STATUS_PENDING)
+    .bind(STAGE_STORED)
+    .bind(image_path)
+    .fetch_one(db)
+    .await?;
+    JOB_QUEUED.notify_one();
+    Ok(job)
+}
+
+pub async fn find_job(
+    db: &PgPool,
+    job_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<DietJob>, sqlx::Error> {
+    sqlx::query_as::<_, DietJob>(&format!(
+        "SELECT {} FROM diet_analysis_jobs WHERE id = $1 AND user_id = $2",
+        JOB_COLUMNS
+    ))
+    .bind(job_id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+}
+
+/// Claims the oldest pending job, or one stuck in `processing` past the timeout.
+async fn claim_job(db: &PgPool) -> Result<Option<DietJob>, sqlx::Error> {
+    sqlx::query_as::<_, DietJob>(&format!(
+        r#"
+        WITH candidate AS (
+            SELECT id AS candidate_id
+            FROM diet_analysis_jobs
+            WHERE status = $1
+               OR (status = $2 AND started_at < now() - make_interval(secs => $3::double precision))
+            ORDER BY created_at ASC
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE diet_analysis_jobs
+        SET status = $2, started_at = now(), updated_at = now(), attempts = attempts + 1
+        FROM candidate
+        WHERE id = candidate.candidate_id
+        RETURNING {}
+        "#,
+        JOB_COLUMNS
+    ))
+    .bind(STATUS_PENDING)
+    .bind(STATUS_PROCESSING)
+    .bind(PROCESSING_TIMEOUT_SECS as f64)
+    .fetch_optional(db)
+    .await
+}
+
+/// The condition every write of a worker carries: the job is still `processing` under the
+/// attempt that worker claimed. A job reclaimed after the timeout belongs to the new attempt.
+const CLAIM_HELD: &str = "id = $1 AND attempts = $2 AND status = 'processing'";
+
+/// Moves the job to `stage` and merges `partial` into its result.
+async fn update_stage(db: &PgPool, job: &DietJob, stage: &str, partial: Value) {
+    if let Err(e) = sqlx::query(&format!(
+        "UPDATE diet_analysis_jobs SET stage = $3, result = result || $4, updated_at = now() WHERE {}",
+        CLAIM_HELD
+    ))
+    .bind(job.id)
+    .bind(job.attempts)
+    .bind(stage)
+    .bind(partial)
+    .execute(db)
+    .await
+    {
+        error!("Failed to move diet job {} to {}: {:?}", job.id, stage, e);
+    }
+}
+
+/// Checks the claim is still held and restarts its timeout, so the job is not picked up again
+/// while the meal is being saved.
+async fn renew_claim(db: &PgPool, job: &DietJob) -> bool {
+    match sqlx::query(&format!(
+        "UPDATE diet_analysis_jobs SET started_at = now(), updated_at = now() WHERE {}",
+        CLAIM_HELD
+    ))
+    .bind(job.id)
+    .bind(job.attempts)
+    .execute(db)
+    .await
+    {
+        Ok(result) => result.rows_affected() == 1,
+        Err(e) => {
+            error!("Failed to renew diet job {}: {:?}", job.id, e);
+            false
+        }
+    }
+}
+
+async fn mark_job_completed(db: &PgPool, job: &DietJob, record_id: Option<Uuid>, partial: Value) {
+    if let Err(e) = sqlx::query(&format!(
+        r#"
+        UPDATE diet_analysis_jobs
+        SET status = $3, stage = $4, result = result || $5, record_id = $6,
+            error_message = NULL, updated_at = now(), completed_at = now()
+        WHERE {}
+        "#,
+        CLAIM_HELD
+    ))
+    .bind(job.id)
+    .bind(job.attempts)
+    .bind(STATUS_COMPLETED)
+    .bind(STAGE_DONE)
+    .bind(partial)
+    .bind(record_id)
+    .execute(db)
+    .await
+    {
+        error!("Failed to mark diet job {} completed: {:?}", job.id, e);
+    }
+}
+
+async fn mark_job_failed(db: &PgPool, job: &DietJob, error_message: &str) {
+    if let Err(e) = sqlx::query(&format!(
+        r#"
+        UPDATE diet_analysis_jobs
+        SET status = $3, error_message = $4, updated_at = now(), completed_at = now()
+        WHERE {}
+        "#,
+        CLAIM_HELD
+    ))
+    .bind(job.id)
+    .bind(job.attempts)
+    .bind(STATUS_FAILED)
+    .bind(error_message)
+    .execute(db)
+    .await
+    {
+        error!("Failed to mark diet job {} failed: {:?}", job.id, e);
+    }
+}
+
+/// Deletes the upload of a job that gave up, unless an earlier attempt saved it on a meal.
+async fn remove_abandoned_upload(db: &PgPool, job: &DietJob) {
+    let referenced = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM diet_records WHERE user_id = $1 AND original_image_path = $2)",
+    )
+    .bind(job.user_id)
+    .bind(&job.image_path)
+    .fetch_one(db)
+    .await;
+    match referenced {
+        Ok(false) => {
+            remove_files(std::slice::from_ref(&job.image_path)).await;
+        }
+        Ok(true) => {}
+        Err(e) => error!(
+            "DB Error (Diet Job Upload Check) for job {}: {:?}",
+            job.id, e
+        ),
+    }
+}
+
+/// Runs the same steps as `POST /api/diet`, recording each stage as it starts.
+async fn process_job(state: &Arc<AppState>, job: DietJob) {
+    let db = &state.db;
+    if job.attempts > MAX_ATTEMPTS {
+        mark_job_failed(db, &job, "Processing did not finish, giving up").await;
+        remove_abandoned_upload(db, &job).await;
+        return;
+    }
+
+    let profile = match load_meal_profile(state, job.user_id).await {
+        Ok(profile) => profile,
+        Err((_, Json(e))) => return mark_job_failed(db, &job, &e.error).await,
+    };
+    let email = sqlx::query_scalar::<_, String>("SELECT email FROM users WHERE id = $1")
+        .bind(job.user_id)
+        .fetch_optional(db)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+
+    update_stage(db, &job, STAGE_DETECTING, json!({})).await;
+    let detection = match detect_food(state, &job.image_path).await {
+        Ok(detection) => detection,
+        Err((_, Json(e))) => return mark_job_failed(db, &job, &e.error).await,
+    };
+    let result_image_path = detection
+        .annotated_image_path
+        .unwrap_or_else(|| job.image_path.clone());
+    let detected_classes: Vec<&str> = detection
+        .detections
+        .iter()
+        .map(|detection| detection.class_name.as_str())
+        .collect();
+    update_stage(
+        db,
+        &job,
+        STAGE_ESTIMATING,
+        json!({ "detected_classes": detected_classes }),
+    )
+    .await;
+
+    let estimate = estimate_meal(&state.nutrition_engine, detection.detections);
+    update_stage(
+        db,
+        &job,
+        STAGE_SCORING,
+        json!({
+            "total_calories": (estimate.total_calories * 10.0).round() / 10.0,
+            "detected_items": estimate.detected_items,
+            "nutrients": estimate.nutrients.rounded(),
+        }),
+    )
+    .await;
+
+    let evaluation = match score_meal(&profile, &estimate).await {
+        Ok(evaluation) => evaluation,
+        Err((_, Json(e))) => return mark_job_failed(db, &job, &e.error).await,
+    };
+    // A worker that outlived its claim must not save the meal a second time.
+    if !renew_claim(db, &job).await {
+        warn!("Diet job {} was reclaimed; dropping this attempt", job.id);
+        return;
+    }
+    let record_id = save_meal(
+        state,
+        &AnalyzedMeal {
+            user_id: job.user_id,
+            email: &email,
+            profile: &profile,
+            estimate: &estimate,
+            evaluation: &evaluation,
+            entry_source: ENTRY_SOURCE_PHOTO,
+            result_image_path: Some(&result_image_path),
+            original_image_path: Some(&job.image_path),
+        },
+    )
+    .await;
+    if record_id.is_none() {
+        return mark_job_failed(db, &job, "飲食紀錄儲存失敗").await;
+    }
+
+    mark_job_completed(
+        db,
+        &job,
+        record_id,
+        json!({ "ai_score": evaluation.score, "ai_comment": evaluation.comment }),
+    )
+    .await;
+    info!("Diet job {} completed", job.id);
+}
+
+/// Runs queued meal analyses in the background, up to `DIET_JOB_WORKER_CONCURRENCY` at a time.
+pub fn start_diet_job_worker(state: Arc<AppState>) {
+    let concurrency = env::var(ENVKey::DIET_JOB_WORKER_CONCURRENCY)
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_WORKER_CONCURRENCY)
+        .clamp(1, 16);
+    info!(
+        "Diet job worker started. concurrency={} poll={}s",
+        concurrency, WORKER_POLL_SECS
+    );
+
+    let permits = Arc::new(Semaphore::new(concurrency));
+    tokio::spawn(async move {
+        loop {
+            let Ok(permit) = permits.clone().acquire_owned().await else {
+                return;
+            };
+            match claim_job(&state.db).await {
+                Ok(Some(job)) => {
+                    info!("Diet job worker processing job {}", job.id);
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        process_job(&state, job).await;
+                        drop(permit);
+                    });
+                }
+                Ok(None) => {
+                    drop(permit);
+                    let _ = tokio::time::timeout(
+                        Duration::from_secs(WORKER_POLL_SECS),
+                        JOB_QUEUED.notified(),
+                    )
+                    .await;
+                }
+                Err(e) => {
+                    drop(permit);
+                    warn!("Diet job worker claim error: {:?}", e);
+                    tokio::time::sleep(Duration::from_secs(WORKER_POLL_SECS)).await;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::{register_test_account, setup_db};
+
+    #[tokio::test]
+    async fn test_job_stages_merge_partial_results() {
+        let state = setup_db().await;
+        let (email, _) = register_test_account(state.clone(), "diet_job".to_string()).await;
+        let user_id: Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+
+        let job = enqueue_job(&state.db, user_id, "/tmp/meal.jpg")
+            .await
+            .unwrap();
+        assert_eq!(job.stage, STAGE_STORED);
+        assert_eq!(job.event_name(), STAGE_STORED);
+        assert!(!job.is_finished());
+
+        // Claimed as by the worker; a write under an older attempt is ignored.
+        let job = sqlx::query_as::<_, DietJob>(&format!(
+            "UPDATE diet_analysis_jobs SET status = $2, attempts = 2 WHERE id = $1 RETURNING {}",
+            JOB_COLUMNS
+        ))
+        .bind(job.id)
+        .bind(STATUS_PROCESSING)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        let stale = DietJob {
+            attempts: 1,
+            ..job.clone()
+        };
+        assert!(!renew_claim(&state.db, &stale).await);
+        mark_job_failed(&state.db, &stale, "timed out").await;
+        assert!(renew_claim(&state.db, &job).await);
+
+        update_stage(
+            &state.db,
+            &job,
+            STAGE_SCORING,
+            json!({ "total_calories": 520.0 }),
+        )
+        .await;
+        mark_job_completed(&state.db, &job, None, json!({ "ai_score": 72 })).await;
+
+        let job = find_job(&state.db, job.id, user_id).await.unwrap().unwrap();
+        assert!(job.is_finished());
+        assert_eq!(job.status, STATUS_COMPLETED);
+        assert_eq!(job.event_name(), STAGE_DONE);
+        assert_eq!(job.result["total_calories"], 520.0);
+        assert_eq!(job.result["ai_score"], 72);
+        assert!(
+            find_job(&state.db, job.id, Uuid::new_v4())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::utils::jwt::{decode_jwt, sign_single_use_token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailTokenPurpose {
+    EmailVerification,
+    PasswordReset,
+}
+
+impl EmailTokenPurpose {
+    /// Stored in the database and used as the JWT `token_type`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EmailTokenPurpose::EmailVerification => "email_verification",
+            EmailTokenPurpose::PasswordReset => "password_reset",
+        }
+    }
+
+    pub fn ttl_secs(self) -> usize {
+        match self {
+            EmailTokenPurpose::EmailVerification => 86400,
+            EmailTokenPurpose::PasswordReset => 3600,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EmailTokenError {
+    /// Bad signature, wrong purpose, expired, unknown or already used.
+    Invalid,
+    Jwt(jsonwebtoken::errors::Error),
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for EmailTokenError {
+    fn from(e: sqlx::Error) -> Self {
+        EmailTokenError::Database(e)
+    }
+}
+
+/// Signs a new token and records its `jti`. Older unused tokens of the same purpose are
+/// invalidated so only the most recent email works.
+pub async fn issue_email_token(
+    db: &PgPool,
+    user_id: Uuid,
+    email: &str,
+    purpose: EmailTokenPurpose,
+) -> Result<String, EmailTokenError> {
+    let jti = Uuid::new_v4();
+    let token = sign_single_use_token(
+        &user_id.to_string(),
+        email,
+        purpose.as_str(),
+        jti,
+        purpose.ttl_secs(),
+    )
+    .map_err(EmailTokenError::Jwt)?;
+
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        r#"
+        UPDATE email_tokens
+        SET used_at = now()
+        WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(purpose.as_str())
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO email_tokens (jti, user_id, purpose, expires_at, created_at)
+        VALUES ($1, $2, $3, now() + make_interval(secs => $4::double precision), now())
+        "#,
+    )
+    .bind(jti)
+    .bind(user_id)
+    .bind(purpose.as_str())
+    .bind(purpose.ttl_secs() as f64)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(token)
+}
+
+/// Verifies the signature and purpose, then marks the token used. Returns the user it was
+/// issued for.
+pub async fn consume_email_token(
+    conn: &mut PgConnection,
+    token: &str,
+    purpose: EmailTokenPurpose,
+) -> Result<Uuid, EmailTokenError> {
+    let claims = decode_jwt(token).map_err(|_| EmailTokenError::Invalid)?;
+    if claims.token_type != purpose.as_str() {
+        return Err(EmailTokenError::Invalid);
+    }
+
+    let jti = claims
+        .jti
+        .as_deref()
+        .and_then(|jti| Uuid::parse_str(jti).ok())
+        .ok_or(EmailTokenError::Invalid)?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| EmailTokenError::Invalid)?;
+
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        UPDATE email_tokens
+        SET used_at = now()
+        WHERE jti = $1 AND user_id = $2 AND purpose = $3
+          AND used_at IS NULL AND expires_at > now()
+        RETURNING user_id
+        "#,
+    )
+    .bind(jti)
+    .bind(user_id)
+    .bind(purpose.as_str())
+    .fetch_optional(conn)
+    .await?
+    .ok_or(EmailTokenError::Invalid)
+}
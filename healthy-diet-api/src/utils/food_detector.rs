@@ -0,0 +1,823 @@
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    process::Command,
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+use tracing::{error, info};
+
+use crate::model::ENVKey;
+
+const DEFAULT_PYTHON: &str = "python3";
+pub const DEFAULT_YOLO_SCRIPT: &str = "../healthy-diet-yolo/predict.py";
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_MAX_CONCURRENCY: usize = 2;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Detection {
+    #[serde(rename = "class")]
+    pub class_name: String,
+    pub confidence: f64,
+    pub bbox: [f64; 4], // [x_min, y_min, x_max, y_max]
+}
+
+#[derive(Deserialize, Debug)]
+pub struct YoloScriptOutput {
+    pub status: String,
+    pub image_path: String,
+    pub json_path: String,
+    pub detections: Vec<Detection>,
+}
+
+#[derive(Debug)]
+pub struct DetectionResult {
+    pub detections: Vec<Detection>,
+    /// The image with boxes drawn on it, when the backend produces one.
+    pub annotated_image_path: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum DetectorError {
+    /// The backend could not be reached or started.
+    Unavailable(String),
+    /// The backend ran but reported an error.
+    Failed(String),
+    InvalidOutput(String),
+    Timeout(Duration),
+}
+
+impl fmt::Display for DetectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectorError::Unavailable(e) => write!(f, "detector unavailable: {}", e),
+            DetectorError::Failed(e) => write!(f, "detection failed: {}", e),
+            DetectorError::InvalidOutput(e) => write!(f, "invalid detector output: {}", e),
+            DetectorError::Timeout(limit) => {
+                write!(f, "detection timed out after {}s", limit.as_secs())
+            }
+        }
+    }
+}
+
+#[async_trait]
+pub trait FoodDetector: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn detect(&self, image_path: &Path) -> Result<DetectionResult, DetectorError>;
+
+    /// Runs `detect` while holding a `LimitedDetector` permit. Backends whose work outlives a
+    /// dropped future (e.g. a blocking thread) must keep the permit until that work ends.
+    async fn detect_with_permit(
+        &self,
+        image_path: &Path,
+        permit: OwnedSemaphorePermit,
+    ) -> Result<DetectionResult, DetectorError> {
+        let result = self.detect(image_path).await;
+        drop(permit);
+        result
+    }
+}
+
+/// The JSON object in the script's stdout; the libraries it loads may print around it.
+fn extract_json_object(stdout: &str) -> &str {
+    let json_start = stdout.find('{').unwrap_or(0);
+    let json_end = stdout.rfind('}').unwrap_or(stdout.len().saturating_sub(1)) + 1;
+    if json_start < json_end {
+        &stdout[json_start..json_end]
+    } else {
+        "{}"
+    }
+}
+
+/// Runs `predict.py` once per image. The model is reloaded every time, so this is the slowest
+/// backend; the child process is awaited without blocking a worker and killed on timeout.
+pub struct SubprocessDetector {
+    python: String,
+    script: PathBuf,
+}
+
+impl SubprocessDetector {
+    pub fn new(python: impl Into<String>, script: impl Into<PathBuf>) -> Self {
+        Self {
+            python: python.into(),
+            script: script.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl FoodDetector for SubprocessDetector {
+    fn name(&self) -> &'static str {
+        "subprocess"
+    }
+
+    async fn detect(&self, image_path: &Path) -> Result<DetectionResult, DetectorError> {
+        let output = Command::new(&self.python)
+            .arg(&self.script)
+            .arg("--input")
+            .arg(image_path)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| DetectorError::Unavailable(e.to_string()))?;
+
+        if !output.status.success() {
+            return Err(DetectorError::Failed(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let result: YoloScriptOutput = serde_json::from_str(extract_json_object(&stdout))
+            .map_err(|e| DetectorError::InvalidOutput(format!("{}, raw output: {}", e, stdout)))?;
+
+        Ok(DetectionResult {
+            detections: result.detections,
+            annotated_image_path: Some(result.image_path),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct HttpDetectorResponse {
+    detections: Vec<Detection>,
+    /// Optional annotated image, saved next to the upload.
+    #[serde(default)]
+    image_base64: Option<String>,
+}
+
+/// Posts the image as multipart field `image` to an inference server that keeps the model
+/// loaded and answers `{"detections": [...], "image_base64": "..."}`.
+pub struct HttpDetector {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpDetector {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+}
+
+/// `<upload>_result.jpg` next to the uploaded image.
+fn annotated_path_for(image_path: &Path) -> PathBuf {
+    let stem = image_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("detection");
+    image_path.with_file_name(format!("{}_result.jpg", stem))
+}
+
+#[async_trait]
+impl FoodDetector for HttpDetector {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    async fn detect(&self, image_path: &Path) -> Result<DetectionResult, DetectorError> {
+        let bytes = tokio::fs::read(image_path)
+            .await
+            .map_err(|e| DetectorError::Failed(e.to_string()))?;
+        let file_name = image_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("image.jpg")
+            .to_string();
+        let part = reqwest::multipart::Part::bytes(bytes)
+            .file_name(file_name)
+            .mime_str("image/jpeg")
+            .map_err(|e| DetectorError::Failed(e.to_string()))?;
+
+        let response = self
+            .client
+            .post(&self.url)
+            .multipart(reqwest::multipart::Form::new().part("image", part))
+            .send()
+            .await
+            .map_err(|e| DetectorError::Unavailable(e.to_string()))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(DetectorError::Failed(format!("{}: {}", status, body)));
+        }
+
+        let result: HttpDetectorResponse = response
+            .json()
+            .await
+            .map_err(|e| DetectorError::InvalidOutput(e.to_string()))?;
+
+        let mut annotated_image_path = None;
+        if let Some(encoded) = result.image_base64 {
+            let decoded = general_purpose::STANDARD
+                .decode(encoded.trim())
+                .map_err(|e| DetectorError::InvalidOutput(e.to_string()))?;
+            let path = annotated_path_for(image_path);
+            match tokio::fs::write(&path, decoded).await {
+                Ok(()) => annotated_image_path = Some(path.to_string_lossy().to_string()),
+                Err(e) => error!("Failed to save annotated image {}: {}", path.display(), e),
+            }
+        }
+
+        Ok(DetectionResult {
+            detections: result.detections,
+            annotated_image_path,
+        })
+    }
+}
+
+/// Bounds how many detections run at once and how long each may take, queueing included.
+pub struct LimitedDetector {
+    inner: Arc<dyn FoodDetector>,
+    permits: Arc<Semaphore>,
+    timeout: Duration,
+}
+
+impl LimitedDetector {
+    pub fn new(inner: Arc<dyn FoodDetector>, max_concurrency: usize, timeout: Duration) -> Self {
+        Self {
+            inner,
+            permits: Arc::new(Semaphore::new(max_concurrency.max(1))),
+            timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl FoodDetector for LimitedDetector {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn detect(&self, image_path: &Path) -> Result<DetectionResult, DetectorError> {
+        let run = async {
+            let permit = self
+                .permits
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|e| DetectorError::Unavailable(e.to_string()))?;
+            self.inner.detect_with_permit(image_path, permit).await
+        };
+        tokio::time::timeout(self.timeout, run)
+            .await
+            .map_err(|_| DetectorError::Timeout(self.timeout))?
+    }
+}
+
+#[cfg(feature = "onnx")]
+mod onnx {
+    use std::{
+        env,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use image::{Rgb, RgbImage, imageops::FilterType};
+    use ort::{session::Session, value::Tensor};
+
+    use tokio::sync::OwnedSemaphorePermit;
+
+    use super::{Detection, DetectionResult, DetectorError, FoodDetector, annotated_path_for};
+    use crate::model::ENVKey;
+
+    const INPUT_SIZE: u32 = 640;
+    const DEFAULT_CONFIDENCE: f32 = 0.5;
+    /// Same overlap threshold as `predict.py`.
+    const IOU_THRESHOLD: f32 = 0.2;
+    const BOX_COLOR: Rgb<u8> = Rgb([255, 64, 64]);
+    /// Ultralytics pads letterboxed images with this grey.
+    const PAD_COLOR: Rgb<u8> = Rgb([114, 114, 114]);
+
+    /// How an image was fitted into the square model input: scaled to keep its aspect ratio,
+    /// then centred with padding.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub(super) struct Letterbox {
+        scale: f64,
+        pad_x: f64,
+        pad_y: f64,
+    }
+
+    impl Letterbox {
+        pub(super) fn new(width: u32, height: u32) -> (Self, u32, u32) {
+            let scale = (INPUT_SIZE as f64 / width as f64).min(INPUT_SIZE as f64 / height as f64);
+            let scaled_width = ((width as f64 * scale).round() as u32).clamp(1, INPUT_SIZE);
+            let scaled_height = ((height as f64 * scale).round() as u32).clamp(1, INPUT_SIZE);
+            let letterbox = Self {
+                scale,
+                pad_x: ((INPUT_SIZE - scaled_width) / 2) as f64,
+                pad_y: ((INPUT_SIZE - scaled_height) / 2) as f64,
+            };
+            (letterbox, scaled_width, scaled_height)
+        }
+
+        /// Maps a box in model input coordinates back onto the original image.
+        pub(super) fn unmap(&self, bbox: [f64; 4], width: u32, height: u32) -> [f64; 4] {
+            let x = |value: f64| ((value - self.pad_x) / self.scale).clamp(0.0, width as f64);
+            let y = |value: f64| ((value - self.pad_y) / self.scale).clamp(0.0, height as f64);
+            [
+                x(bbox[0]).round(),
+                y(bbox[1]).round(),
+                x(bbox[2]).round(),
+                y(bbox[3]).round(),
+            ]
+        }
+    }
+
+    /// Resizes without stretching, as the model was trained on letterboxed images.
+    fn letterbox_image(image: &RgbImage) -> (RgbImage, Letterbox) {
+        let (width, height) = image.dimensions();
+        let (letterbox, scaled_width, scaled_height) = Letterbox::new(width, height);
+        let resized =
+            image::imageops::resize(image, scaled_width, scaled_height, FilterType::Triangle);
+        let mut input = RgbImage::from_pixel(INPUT_SIZE, INPUT_SIZE, PAD_COLOR);
+        image::imageops::replace(
+            &mut input,
+            &resized,
+            letterbox.pad_x as i64,
+            letterbox.pad_y as i64,
+        );
+        (input, letterbox)
+    }
+
+    struct OnnxModel {
+        session: Mutex<Session>,
+        labels: Vec<String>,
+        confidence: f32,
+    }
+
+    /// A YOLOv8 model exported with `yolo export format=onnx`, run on the CPU in-process. The
+    /// ONNX Runtime library is loaded from `ORT_DYLIB_PATH` (default `libonnxruntime.so`).
+    pub struct OnnxDetector {
+        model: Arc<OnnxModel>,
+    }
+
+    /// Ultralytics stores class names as metadata `names`, e.g. `{0: 'grain', 1: 'fruit'}`.
+    pub(super) fn parse_names_metadata(names: &str) -> Vec<String> {
+        let mut entries: Vec<(usize, String)> = names
+            .trim()
+            .trim_start_matches('{')
+            .trim_end_matches('}')
+            .split(',')
+            .filter_map(|entry| {
+                let (index, name) = entry.split_once(':')?;
+                let index = index.trim().parse().ok()?;
+                let name = name.trim().trim_matches(|c| c == '\'' || c == '"');
+                Some((index, name.to_string()))
+            })
+            .collect();
+        entries.sort_by_key(|(index, _)| *index);
+        entries.into_iter().map(|(_, name)| name).collect()
+    }
+
+    fn iou(a: &[f64; 4], b: &[f64; 4]) -> f64 {
+        let width = (a[2].min(b[2]) - a[0].max(b[0])).max(0.0);
+        let height = (a[3].min(b[3]) - a[1].max(b[1])).max(0.0);
+        let intersection = width * height;
+        let union = (a[2] - a[0]) * (a[3] - a[1]) + (b[2] - b[0]) * (b[3] - b[1]) - intersection;
+        if union <= 0.0 {
+            0.0
+        } else {
+            intersection / union
+        }
+    }
+
+    /// Keeps the most confident box of each overlapping group of the same class.
+    pub(super) fn non_max_suppression(
+        mut candidates: Vec<Detection>,
+        iou_threshold: f64,
+    ) -> Vec<Detection> {
+        candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        let mut kept: Vec<Detection> = Vec::new();
+        for candidate in candidates {
+            let overlaps = kept.iter().any(|kept| {
+                kept.class_name == candidate.class_name
+                    && iou(&kept.bbox, &candidate.bbox) > iou_threshold
+            });
+            if !overlaps {
+                kept.push(candidate);
+            }
+        }
+        kept
+    }
+
+    fn draw_box(image: &mut RgbImage, bbox: &[f64; 4]) {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return;
+        }
+        let clamp_x = |x: f64| (x.max(0.0) as u32).min(width - 1);
+        let clamp_y = |y: f64| (y.max(0.0) as u32).min(height - 1);
+        let (x0, y0, x1, y1) = (
+            clamp_x(bbox[0]),
+            clamp_y(bbox[1]),
+            clamp_x(bbox[2]),
+            clamp_y(bbox[3]),
+        );
+        for thickness in 0..3 {
+            for x in x0..=x1 {
+                image.put_pixel(x, (y0 + thickness).min(height - 1), BOX_COLOR);
+                image.put_pixel(x, y1.saturating_sub(thickness), BOX_COLOR);
+            }
+            for y in y0..=y1 {
+                image.put_pixel((x0 + thickness).min(width - 1), y, BOX_COLOR);
+                image.put_pixel(x1.saturating_sub(thickness), y, BOX_COLOR);
+            }
+        }
+    }
+
+    impl OnnxModel {
+        fn detect(&self, image_path: &Path) -> Result<DetectionResult, DetectorError> {
+            let mut image = image::open(image_path)
+                .map_err(|e| DetectorError::Failed(e.to_string()))?
+                .to_rgb8();
+            let (width, height) = image.dimensions();
+            let (resized, letterbox) = letterbox_image(&image);
+
+            let plane = (INPUT_SIZE * INPUT_SIZE) as usize;
+            let mut input = vec![0f32; 3 * plane];
+            for (i, pixel) in resized.pixels().enumerate() {
+                for channel in 0..3 {
+                    input[channel * plane + i] = pixel[channel] as f32 / 255.0;
+                }
+            }
+            let tensor =
+                Tensor::from_array(([1usize, 3, INPUT_SIZE as usize, INPUT_SIZE as usize], input))
+                    .map_err(|e| DetectorError::Failed(e.to_string()))?;
+
+            let mut session = self
+                .session
+                .lock()
+                .map_err(|_| DetectorError::Unavailable("ONNX session poisoned".to_string()))?;
+            let outputs = session
+                .run(ort::inputs![tensor])
+                .map_err(|e| DetectorError::Failed(e.to_string()))?;
+            let (shape, data) = outputs[0]
+                .try_extract_tensor::<f32>()
+                .map_err(|e| DetectorError::InvalidOutput(e.to_string()))?;
+
+            // YOLOv8 output: [1, 4 + classes, anchors], boxes as centre x/y, width, height.
+            if shape.len() != 3 || shape[1] < 5 {
+                return Err(DetectorError::InvalidOutput(format!(
+                    "unexpected output shape {:?}",
+                    shape
+                )));
+            }
+            let classes = shape[1] as usize - 4;
+            let anchors = shape[2] as usize;
+
+            let mut candidates = Vec::new();
+            for anchor in 0..anchors {
+                let (class, score) = (0..classes)
+                    .map(|class| (class, data[(4 + class) * anchors + anchor]))
+                    .fold((0, f32::MIN), |best, current| {
+                        if current.1 > best.1 { current } else { best }
+                    });
+                if score < self.confidence {
+                    continue;
+                }
+                let cx = data[anchor] as f64;
+                let cy = data[anchors + anchor] as f64;
+                let w = data[2 * anchors + anchor] as f64;
+                let h = data[3 * anchors + anchor] as f64;
+                candidates.push(Detection {
+                    class_name: self
+                        .labels
+                        .get(class)
+                        .cloned()
+                        .unwrap_or_else(|| class.to_string()),
+                    confidence: (score as f64 * 1000.0).round() / 1000.0,
+                    bbox: letterbox.unmap(
+                        [cx - w / 2.0, cy - h / 2.0, cx + w / 2.0, cy + h / 2.0],
+                        width,
+                        height,
+                    ),
+                });
+            }
+            drop(outputs);
+            drop(session);
+
+            let detections = non_max_suppression(candidates, IOU_THRESHOLD as f64);
+
+            for detection in &detections {
+                draw_box(&mut image, &detection.bbox);
+            }
+            let annotated = annotated_path_for(image_path);
+            let annotated_image_path = match image.save(&annotated) {
+                Ok(()) => Some(annotated.to_string_lossy().to_string()),
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to save annotated image {}: {}",
+                        annotated.display(),
+                        e
+                    );
+                    None
+                }
+            };
+
+            Ok(DetectionResult {
+                detections,
+                annotated_image_path,
+            })
+        }
+    }
+
+    impl OnnxDetector {
+        /// Loads the model once; `FOOD_DETECTOR_LABELS` overrides the names in its metadata.
+        pub fn from_env() -> Result<Self, DetectorError> {
+            let model_path: PathBuf = env::var(ENVKey::FOOD_DETECTOR_MODEL)
+                .map_err(|_| {
+                    DetectorError::Unavailable("FOOD_DETECTOR_MODEL must be set".to_string())
+                })?
+                .into();
+            let confidence = env::var(ENVKey::FOOD_DETECTOR_CONFIDENCE)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_CONFIDENCE);
+
+            let session = Session::builder()
+                .and_then(|builder| builder.commit_from_file(&model_path))
+                .map_err(|e| DetectorError::Unavailable(e.to_string()))?;
+
+            let labels = match env::var(ENVKey::FOOD_DETECTOR_LABELS) {
+                Ok(labels) => labels
+                    .split(',')
+                    .map(|label| label.trim().to_string())
+                    .collect(),
+                Err(_) => session
+                    .metadata()
+                    .and_then(|metadata| metadata.custom("names"))
+                    .ok()
+                    .flatten()
+                    .map(|names| parse_names_metadata(&names))
+                    .unwrap_or_default(),
+            };
+            if labels.is_empty() {
+                return Err(DetectorError::Unavailable(
+                    "model has no class names; set FOOD_DETECTOR_LABELS".to_string(),
+                ));
+            }
+
+            Ok(Self {
+                model: Arc::new(OnnxModel {
+                    session: Mutex::new(session),
+                    labels,
+                    confidence,
+                }),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl FoodDetector for OnnxDetector {
+        fn name(&self) -> &'static str {
+            "onnx"
+        }
+
+        /// Inference is CPU-bound, so it runs on the blocking pool.
+        async fn detect(&self, image_path: &Path) -> Result<DetectionResult, DetectorError> {
+            let model = self.model.clone();
+            let image_path = image_path.to_path_buf();
+            tokio::task::spawn_blocking(move || model.detect(&image_path))
+                .await
+                .map_err(|e| DetectorError::Failed(e.to_string()))?
+        }
+
+        /// A timed-out caller cannot stop a blocking thread, so the permit stays with the
+        /// inference and is released only once it finishes.
+        async fn detect_with_permit(
+            &self,
+            image_path: &Path,
+            permit: OwnedSemaphorePermit,
+        ) -> Result<DetectionResult, DetectorError> {
+            let model = self.model.clone();
+            let image_path = image_path.to_path_buf();
+            tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                model.detect(&image_path)
+            })
+            .await
+            .map_err(|e| DetectorError::Failed(e.to_string()))?
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_names_metadata_and_nms() {
+            assert_eq!(
+                parse_names_metadata("{0: 'grain', 2: 'fruit', 1: 'vegetable'}"),
+                vec!["grain", "vegetable", "fruit"]
+            );
+
+            let detection = |class: &str, confidence: f64, x: f64| Detection {
+                class_name: class.to_string(),
+                confidence,
+                bbox: [x, 0.0, x + 100.0, 100.0],
+            };
+            let kept = non_max_suppression(
+                vec![
+                    detection("grain", 0.6, 0.0),
+                    detection("grain", 0.9, 10.0),
+                    detection("fruit", 0.7, 10.0),
+                    detection("grain", 0.8, 500.0),
+                ],
+                0.2,
+            );
+            assert_eq!(kept.len(), 3);
+            assert_eq!(kept[0].confidence, 0.9);
+        }
+
+        #[test]
+        fn test_letterbox_maps_boxes_back() {
+            // 1280x640 is halved to 640x320 and padded by 160 above and below.
+            let (letterbox, scaled_width, scaled_height) = Letterbox::new(1280, 640);
+            assert_eq!((scaled_width, scaled_height), (640, 320));
+            assert_eq!(
+                letterbox.unmap([100.0, 200.0, 300.0, 400.0], 1280, 640),
+                [200.0, 80.0, 600.0, 480.0]
+            );
+            // Boxes reaching into the padding are clamped to the image.
+            assert_eq!(
+                letterbox.unmap([0.0, 100.0, 640.0, 500.0], 1280, 640),
+                [0.0, 0.0, 1280.0, 640.0]
+            );
+
+            let image = RgbImage::from_pixel(1280, 640, Rgb([0, 0, 0]));
+            let (input, _) = letterbox_image(&image);
+            assert_eq!(input.dimensions(), (INPUT_SIZE, INPUT_SIZE));
+            assert_eq!(*input.get_pixel(320, 10), PAD_COLOR);
+            assert_eq!(*input.get_pixel(320, 320), Rgb([0, 0, 0]));
+        }
+    }
+}
+
+/// `FOOD_DETECTOR` picks the backend: `http` (needs `FOOD_DETECTOR_URL`), `onnx` (needs the
+/// `onnx` build feature and `FOOD_DETECTOR_MODEL`) or the `predict.py` subprocess (default).
+/// A misconfigured backend falls back to the subprocess.
+pub fn food_detector_from_env() -> Arc<dyn FoodDetector> {
+    let backend = env::var(ENVKey::FOOD_DETECTOR).unwrap_or_default();
+    let detector: Arc<dyn FoodDetector> = match backend.as_str() {
+        "http" => match env::var(ENVKey::FOOD_DETECTOR_URL) {
+            Ok(url) => Arc::new(HttpDetector::new(url)),
+            Err(_) => {
+                error!("Food detector: FOOD_DETECTOR_URL must be set, falling back to subprocess");
+                subprocess_detector_from_env()
+            }
+        },
+        "onnx" => onnx_detector_from_env(),
+        _ => subprocess_detector_from_env(),
+    };
+
+    let timeout = Duration::from_secs(
+        env::var(ENVKey::FOOD_DETECTOR_TIMEOUT_SECS)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS),
+    );
+    let max_concurrency = env::var(ENVKey::FOOD_DETECTOR_MAX_CONCURRENCY)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CONCURRENCY);
+
+    info!(
+        "Food detector: {} (max {} concurrent, {}s timeout)",
+        detector.name(),
+        max_concurrency,
+        timeout.as_secs()
+    );
+    Arc::new(LimitedDetector::new(detector, max_concurrency, timeout))
+}
+
+fn subprocess_detector_from_env() -> Arc<dyn FoodDetector> {
+    let python =
+        env::var(ENVKey::FOOD_DETECTOR_PYTHON).unwrap_or_else(|_| DEFAULT_PYTHON.to_string());
+    let script =
+        env::var(ENVKey::YOLO_SCRIPT_PATH).unwrap_or_else(|_| DEFAULT_YOLO_SCRIPT.to_string());
+    Arc::new(SubprocessDetector::new(python, script))
+}
+
+#[cfg(feature = "onnx")]
+fn onnx_detector_from_env() -> Arc<dyn FoodDetector> {
+    match onnx::OnnxDetector::from_env() {
+        Ok(detector) => Arc::new(detector),
+        Err(e) => {
+            error!(
+                "Food detector: ONNX disabled ({}), falling back to subprocess",
+                e
+            );
+            subprocess_detector_from_env()
+        }
+    }
+}
+
+#[cfg(not(feature = "onnx"))]
+fn onnx_detector_from_env() -> Arc<dyn FoodDetector> {
+    error!("Food detector: built without the onnx feature, falling back to subprocess");
+    subprocess_detector_from_env()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SlowDetector;
+
+    #[async_trait]
+    impl FoodDetector for SlowDetector {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        async fn detect(&self, _image_path: &Path) -> Result<DetectionResult, DetectorError> {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(DetectionResult {
+                detections: Vec::new(),
+                annotated_image_path: None,
+            })
+        }
+    }
+
+    /// Keeps working on a blocking thread after its caller gives up, like ONNX inference.
+    struct BlockingDetector;
+
+    #[async_trait]
+    impl FoodDetector for BlockingDetector {
+        fn name(&self) -> &'static str {
+            "blocking"
+        }
+
+        async fn detect(&self, _image_path: &Path) -> Result<DetectionResult, DetectorError> {
+            unreachable!("only called through detect_with_permit")
+        }
+
+        async fn detect_with_permit(
+            &self,
+            _image_path: &Path,
+            permit: OwnedSemaphorePermit,
+        ) -> Result<DetectionResult, DetectorError> {
+            tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                std::thread::sleep(Duration::from_millis(200));
+            })
+            .await
+            .map_err(|e| DetectorError::Failed(e.to_string()))?;
+            Ok(DetectionResult {
+                detections: Vec::new(),
+                annotated_image_path: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limited_detector_holds_permit_until_blocking_work_ends() {
+        let detector =
+            LimitedDetector::new(Arc::new(BlockingDetector), 1, Duration::from_millis(50));
+
+        let timed_out = detector.detect(Path::new("a.jpg")).await;
+        assert!(matches!(timed_out, Err(DetectorError::Timeout(_))));
+        assert_eq!(detector.permits.available_permits(), 0);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(detector.permits.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_limited_detector_times_out_queued_requests() {
+        let detector = Arc::new(LimitedDetector::new(
+            Arc::new(SlowDetector),
+            1,
+            Duration::from_millis(300),
+        ));
+
+        let first = {
+            let detector = detector.clone();
+            tokio::spawn(async move { detector.detect(Path::new("a.jpg")).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // Waits ~180ms for the permit, then needs 200ms more: over the 300ms budget.
+        let second = detector.detect(Path::new("b.jpg")).await;
+
+        assert!(first.await.unwrap().is_ok());
+        assert!(matches!(second, Err(DetectorError::Timeout(_))));
+    }
+
+    #[test]
+    fn test_extract_json_object_ignores_surrounding_logs() {
+        let stdout = "Ultralytics 8.3\n{\"status\": \"success\"}\nDone\n";
+        assert_eq!(extract_json_object(stdout), "{\"status\": \"success\"}");
+        assert_eq!(extract_json_object("}{"), "{}");
+    }
+}
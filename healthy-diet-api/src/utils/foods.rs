@@ -0,0 +1,860 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::{collections::HashMap, fmt};
+use uuid::Uuid;
+
+use crate::utils::nutrition_engine::{FALLBACK_CLASS, NutrientVector};
+
+pub const SOURCE_TFDA: &str = "tfda";
+pub const LANG_ZH: &str = "zh";
+pub const LANG_EN: &str = "en";
+
+/// Search results are capped at this many foods.
+pub const MAX_SEARCH_LIMIT: i64 = 50;
+
+/// Which CSV layout an upload uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// The Taiwan FDA food nutrient composition dataset: one row per food and analyte.
+    Tfda,
+    /// One row per food with per-100 g columns; see `WIDE_COLUMNS`.
+    Wide,
+}
+
+impl ImportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "tfda" => Some(Self::Tfda),
+            "wide" => Some(Self::Wide),
+            _ => None,
+        }
+    }
+}
+
+/// Header row of the wide format. Only `code`, `name_zh` and `kcal_per_100g` are required.
+pub const WIDE_COLUMNS: [&str; 12] = [
+    "code",
+    "name_zh",
+    "name_en",
+    "aliases_zh",
+    "aliases_en",
+    "food_group",
+    "kcal_per_100g",
+    "carbohydrate_g",
+    "protein_g",
+    "fat_g",
+    "fiber_g",
+    "sodium_mg",
+];
+
+#[derive(Debug)]
+pub enum FoodImportError {
+    /// The file is not readable CSV (including non UTF-8 text).
+    Csv(String),
+    MissingColumn(&'static str),
+    /// A row that cannot be used, with its 1-based line number.
+    InvalidRow(u64, String),
+    Empty,
+}
+
+impl fmt::Display for FoodImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv(e) => write!(f, "invalid CSV: {}", e),
+            Self::MissingColumn(column) => write!(f, "missing column {}", column),
+            Self::InvalidRow(line, reason) => write!(f, "line {}: {}", line, reason),
+            Self::Empty => write!(f, "no foods found"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, FromRow)]
+pub struct FoodAlias {
+    pub alias: String,
+    pub lang: String,
+}
+
+/// A parsed food before it is stored. Nutrient values are per 100 g edible portion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedFood {
+    pub source_code: String,
+    pub name_zh: String,
+    pub name_en: Option<String>,
+    pub food_group: Option<String>,
+    pub kcal_per_100g: f64,
+    pub nutrients_per_100g: NutrientVector,
+    pub aliases: Vec<FoodAlias>,
+}
+
+/// Maps a food group (TFDA 食品分類 or an English name) to the nutrition engine class it
+/// is closest to, so looked-up foods can be logged alongside detected ones.
+pub fn engine_class_for_group(group: &str) -> &'static str {
+    const GROUPS: [(&str, &[&str]); 7] = [
+        (
+            "grain",
+            &["穀", "澱粉", "糕餅", "點心", "grain", "cereal", "starch"],
+        ),
+        (
+            "protein_meat",
+            &["肉", "魚", "貝", "蛋", "meat", "fish", "seafood", "egg"],
+        ),
+        ("protein_bean", &["豆", "bean", "legume", "soy"]),
+        ("vegetable", &["蔬菜", "菇", "藻", "vegetable", "mushroom"]),
+        ("fruit", &["水果", "fruit"]),
+        ("dairy", &["乳", "dairy", "milk"]),
+        ("nuts", &["堅果", "種子", "nut", "seed"]),
+    ];
+    let group = group.to_lowercase();
+    GROUPS
+        .iter()
+        .find(|(_, keywords)| keywords.iter().any(|keyword| group.contains(keyword)))
+        .map(|(class, _)| *class)
+        .unwrap_or(FALLBACK_CLASS)
+}
+
+/// Splits a list of alternative names as written in the datasets (`、`, `,`, `;`, `/`).
+pub fn split_aliases(value: &str, lang: &str) -> Vec<FoodAlias> {
+    let mut aliases: Vec<FoodAlias> = Vec::new();
+    for alias in value.split(['、', ',', '，', ';', '；', '/']) {
+        let alias = alias.trim();
+        if alias.is_empty() || aliases.iter().any(|a| a.alias == alias) {
+            continue;
+        }
+        aliases.push(FoodAlias {
+            alias: alias.to_string(),
+            lang: lang.to_string(),
+        });
+    }
+    aliases
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Reads a number as the datasets write it; blanks, `-` and trace marks count as zero.
+fn parse_amount(value: &str) -> Option<f64> {
+    let value = value.trim().replace(',', "");
+    if value.is_empty() || value == "-" || value.eq_ignore_ascii_case("tr") {
+        return Some(0.0);
+    }
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|amount| amount.is_finite() && *amount >= 0.0)
+}
+
+fn csv_reader(data: &[u8]) -> csv::Reader<&[u8]> {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data)
+}
+
+fn column_index(headers: &csv::StringRecord, names: &[&'static str]) -> Option<usize> {
+    headers.iter().position(|header| names.contains(&header))
+}
+
+fn required_column(
+    headers: &csv::StringRecord,
+    names: &[&'static str],
+) -> Result<usize, FoodImportError> {
+    column_index(headers, names).ok_or(FoodImportError::MissingColumn(names[0]))
+}
+
+fn line_of(record: &csv::StringRecord) -> u64 {
+    record.position().map(|p| p.line()).unwrap_or_default()
+}
+
+/// Parses the TFDA long format: columns 整合編號, 樣品名稱, 俗名, 樣品英文名稱, 食品分類,
+/// 分析項 and 每100克含量, one row per analyte. Analytes other than energy, carbohydrate,
+/// protein, fat, fiber and sodium are ignored; foods without an energy row are skipped.
+pub fn parse_tfda_csv(data: &[u8]) -> Result<Vec<ImportedFood>, FoodImportError> {
+    let mut reader = csv_reader(data);
+    let headers = reader
+        .headers()
+        .map_err(|e| FoodImportError::Csv(e.to_string()))?
+        .clone();
+    let code_col = required_column(&headers, &["整合編號"])?;
+    let name_col = required_column(&headers, &["樣品名稱"])?;
+    let analyte_col = required_column(&headers, &["分析項"])?;
+    let amount_col = required_column(&headers, &["每100克含量", "每100克含量(g)"])?;
+    let common_col = column_index(&headers, &["俗名"]);
+    let english_col = column_index(&headers, &["樣品英文名稱"]);
+    let group_col = column_index(&headers, &["食品分類"]);
+
+    struct Partial {
+        food: ImportedFood,
+        kcal: Option<f64>,
+        adjusted_kcal: Option<f64>,
+    }
+    let mut order: Vec<String> = Vec::new();
+    let mut partials: HashMap<String, Partial> = HashMap::new();
+
+    for record in reader.records() {
+        let record = record.map_err(|e| FoodImportError::Csv(e.to_string()))?;
+        let field = |col: usize| record.get(col).unwrap_or_default();
+        let code = field(code_col);
+        if code.is_empty() {
+            continue;
+        }
+        let partial = partials.entry(code.to_string()).or_insert_with(|| {
+            order.push(code.to_string());
+            Partial {
+                food: ImportedFood {
+                    source_code: code.to_string(),
+                    name_zh: field(name_col).to_string(),
+                    // English names are descriptive ("Rice, cooked") and kept whole.
+                    name_en: english_col.and_then(|col| non_empty(field(col))),
+                    food_group: group_col.and_then(|col| non_empty(field(col))),
+                    kcal_per_100g: 0.0,
+                    nutrients_per_100g: NutrientVector::default(),
+                    aliases: common_col
+                        .map(|col| split_aliases(field(col), LANG_ZH))
+                        .unwrap_or_default(),
+                },
+                kcal: None,
+                adjusted_kcal: None,
+            }
+        });
+
+        let amount = || {
+            parse_amount(field(amount_col)).ok_or_else(|| {
+                FoodImportError::InvalidRow(
+                    line_of(&record),
+                    format!("invalid amount {:?}", field(amount_col)),
+                )
+            })
+        };
+        let nutrients = &mut partial.food.nutrients_per_100g;
+        match field(analyte_col) {
+            "熱量" => partial.kcal = Some(amount()?),
+            "修正熱量" => partial.adjusted_kcal = Some(amount()?),
+            "總碳水化合物" => nutrients.carbohydrate_g = amount()?,
+            "粗蛋白" => nutrients.protein_g = amount()?,
+            "粗脂肪" => nutrients.fat_g = amount()?,
+            "膳食纖維" => nutrients.fiber_g = amount()?,
+            "鈉" => nutrients.sodium_mg = amount()?,
+            _ => {}
+        }
+    }
+
+    let foods: Vec<ImportedFood> = order
+        .into_iter()
+        .filter_map(|code| partials.remove(&code))
+        .filter(|partial| !partial.food.name_zh.is_empty())
+        .filter_map(|partial| {
+            // 修正熱量 uses the current Atwater factors; older releases only have 熱量.
+            let kcal = partial.adjusted_kcal.or(partial.kcal)?;
+            Some(ImportedFood {
+                kcal_per_100g: kcal,
+                ..partial.food
+            })
+        })
+        .collect();
+    if foods.is_empty() {
+        return Err(FoodImportError::Empty);
+    }
+    Ok(foods)
+}
+
+/// Parses the wide format described by `WIDE_COLUMNS`. Alias cells hold several names
+/// separated by `、` or `;`.
+pub fn parse_wide_csv(data: &[u8]) -> Result<Vec<ImportedFood>, FoodImportError> {
+    let mut reader = csv_reader(data);
+    let headers = reader
+        .headers()
+        .map_err(|e| FoodImportError::Csv(e.to_string()))?
+        .clone();
+    let code_col = required_column(&headers, &["code"])?;
+    let name_col = required_column(&headers, &["name_zh"])?;
+    let kcal_col = required_column(&headers, &["kcal_per_100g"])?;
+    let optional = |name: &'static str| column_index(&headers, &[name]);
+    let name_en_col = optional("name_en");
+    let aliases_zh_col = optional("aliases_zh");
+    let aliases_en_col = optional("aliases_en");
+    let group_col = optional("food_group");
+    let nutrient_cols = [
+        optional("carbohydrate_g"),
+        optional("protein_g"),
+        optional("fat_g"),
+        optional("fiber_g"),
+        optional("sodium_mg"),
+    ];
+
+    let mut foods: Vec<ImportedFood> = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| FoodImportError::Csv(e.to_string()))?;
+        let field = |col: usize| record.get(col).unwrap_or_default();
+        let optional_field = |col: Option<usize>| col.map(field).unwrap_or_default();
+        let code = field(code_col);
+        let name_zh = field(name_col);
+        if code.is_empty() && name_zh.is_empty() {
+            continue;
+        }
+        if code.is_empty() || name_zh.is_empty() {
+            return Err(FoodImportError::InvalidRow(
+                line_of(&record),
+                "code and name_zh are required".to_string(),
+            ));
+        }
+        if foods.iter().any(|food| food.source_code == code) {
+            return Err(FoodImportError::InvalidRow(
+                line_of(&record),
+                format!("duplicate code {}", code),
+            ));
+        }
+
+        let amount = |col: Option<usize>| {
+            let value = optional_field(col);
+            parse_amount(value).ok_or_else(|| {
+                FoodImportError::InvalidRow(line_of(&record), format!("invalid amount {:?}", value))
+            })
+        };
+        let [carbohydrate_g, protein_g, fat_g, fiber_g, sodium_mg] = nutrient_cols;
+        let mut aliases = split_aliases(optional_field(aliases_zh_col), LANG_ZH);
+        aliases.extend(split_aliases(optional_field(aliases_en_col), LANG_EN));
+        foods.push(ImportedFood {
+            source_code: code.to_string(),
+            name_zh: name_zh.to_string(),
+            name_en: non_empty(optional_field(name_en_col)),
+            food_group: non_empty(optional_field(group_col)),
+            kcal_per_100g: amount(Some(kcal_col))?,
+            nutrients_per_100g: NutrientVector {
+                carbohydrate_g: amount(carbohydrate_g)?,
+                protein_g: amount(protein_g)?,
+                fat_g: amount(fat_g)?,
+                fiber_g: amount(fiber_g)?,
+                sodium_mg: amount(sodium_mg)?,
+            },
+            aliases,
+        });
+    }
+    if foods.is_empty() {
+        return Err(FoodImportError::Empty);
+    }
+    Ok(foods)
+}
+
+pub fn parse_foods_csv(
+    format: ImportFormat,
+    data: &[u8],
+) -> Result<Vec<ImportedFood>, FoodImportError> {
+    match format {
+        ImportFormat::Tfda => parse_tfda_csv(data),
+        ImportFormat::Wide => parse_wide_csv(data),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportSummary {
+    pub source: String,
+    pub inserted: u64,
+    pub updated: u64,
+}
+
+/// Upserts the foods of one source by `(source, source_code)` in a single transaction and
+/// replaces their aliases. Foods missing from the upload are left as they are.
+pub async fn import_foods(
+    db: &PgPool,
+    source: &str,
+    foods: &[ImportedFood],
+) -> Result<ImportSummary, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let mut summary = ImportSummary {
+        source: source.to_string(),
+        inserted: 0,
+        updated: 0,
+    };
+
+    for food in foods {
+        let nutrients = &food.nutrients_per_100g;
+        let (food_id, inserted): (Uuid, bool) = sqlx::query_as(
+            r#"
+            INSERT INTO foods (
+                id, source, source_code, name_zh, name_en, food_group, engine_class,
+                kcal_per_100g, carbohydrate_g, protein_g, fat_g, fiber_g, sodium_mg
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            ON CONFLICT (source, source_code) DO UPDATE
+            SET name_zh = EXCLUDED.name_zh,
+                name_en = EXCLUDED.name_en,
+                food_group = EXCLUDED.food_group,
+                engine_class = EXCLUDED.engine_class,
+                kcal_per_100g = EXCLUDED.kcal_per_100g,
+                carbohydrate_g = EXCLUDED.carbohydrate_g,
+                protein_g = EXCLUDED.protein_g,
+                fat_g = EXCLUDED.fat_g,
+                fiber_g = EXCLUDED.fiber_g,
+                sodium_mg = EXCLUDED.sodium_mg,
+                updated_at = now()
+            RETURNING id, (xmax = 0) AS inserted
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(source)
+        .bind(&food.source_code)
+        .bind(&food.name_zh)
+        .bind(&food.name_en)
+        .bind(&food.food_group)
+        .bind(
+            food.food_group
+                .as_deref()
+                .map(engine_class_for_group)
+                .unwrap_or(FALLBACK_CLASS),
+        )
+        .bind(food.kcal_per_100g)
+        .bind(nutrients.carbohydrate_g)
+        .bind(nutrients.protein_g)
+        .bind(nutrients.fat_g)
+        .bind(nutrients.fiber_g)
+        .bind(nutrients.sodium_mg)
+        .fetch_one(&mut *tx)
+        .await?;
+        if inserted {
+            summary.inserted += 1;
+        } else {
+            summary.updated += 1;
+        }
+
+        sqlx::query("DELETE FROM food_aliases WHERE food_id = $1")
+            .bind(food_id)
+            .execute(&mut *tx)
+            .await?;
+        if !food.aliases.is_empty() {
+            let (aliases, langs): (Vec<&str>, Vec<&str>) = food
+                .aliases
+                .iter()
+                .map(|alias| (alias.alias.as_str(), alias.lang.as_str()))
+                .unzip();
+            sqlx::query(
+                r#"
+                INSERT INTO food_aliases (food_id, alias, lang)
+                SELECT $1, alias, lang FROM unnest($2::text[], $3::text[]) AS t(alias, lang)
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(food_id)
+            .bind(&aliases)
+            .bind(&langs)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(summary)
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct FoodSummary {
+    pub id: Uuid,
+    pub name_zh: String,
+    pub name_en: Option<String>,
+    pub food_group: Option<String>,
+    pub engine_class: String,
+    pub kcal_per_100g: f64,
+    /// Trigram similarity of the best matching name or alias, 0-1.
+    pub score: f64,
+}
+
+/// Exact and substring matches on the names and aliases come first, then trigram matches.
+pub async fn search_foods(
+    db: &PgPool,
+    query: &str,
+    limit: i64,
+) -> Result<Vec<FoodSummary>, sqlx::Error> {
+    let pattern = format!("%{}%", escape_like(query));
+    sqlx::query_as::<_, FoodSummary>(
+        r#"
+        WITH names AS (
+            SELECT id AS food_id, name_zh AS name FROM foods
+            WHERE name_zh ILIKE $2 OR name_zh % $1
+            UNION ALL
+            SELECT id, name_en FROM foods
+            WHERE name_en ILIKE $2 OR name_en % $1
+            UNION ALL
+            SELECT food_id, alias FROM food_aliases
+            WHERE alias ILIKE $2 OR alias % $1
+        ),
+        matches AS (
+            SELECT food_id,
+                   bool_or(lower(name) = lower($1)) AS exact,
+                   bool_or(name ILIKE $2) AS contains,
+                   max(similarity(name, $1))::float8 AS score
+            FROM names
+            GROUP BY food_id
+        )
+        SELECT f.id, f.name_zh, f.name_en, f.food_group, f.engine_class, f.kcal_per_100g, m.score
+        FROM matches m
+        JOIN foods f ON f.id = m.food_id
+        ORDER BY m.exact DESC, m.contains DESC, m.score DESC, char_length(f.name_zh), f.name_zh
+        LIMIT $3
+        "#,
+    )
+    .bind(query)
+    .bind(pattern)
+    .bind(limit.clamp(1, MAX_SEARCH_LIMIT))
+    .fetch_all(db)
+    .await
+}
+
+#[derive(Debug, FromRow)]
+struct FoodRow {
+    id: Uuid,
+    source: String,
+    source_code: String,
+    name_zh: String,
+    name_en: Option<String>,
+    food_group: Option<String>,
+    engine_class: String,
+    kcal_per_100g: f64,
+    carbohydrate_g: f64,
+    protein_g: f64,
+    fat_g: f64,
+    fiber_g: f64,
+    sodium_mg: f64,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FoodDetail {
+    pub id: Uuid,
+    pub source: String,
+    pub source_code: String,
+    pub name_zh: String,
+    pub name_en: Option<String>,
+    pub food_group: Option<String>,
+    /// The nutrition engine class this food is estimated as.
+    pub engine_class: String,
+    pub kcal_per_100g: f64,
+    pub nutrients_per_100g: NutrientVector,
+    pub aliases: Vec<FoodAlias>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub async fn find_food(db: &PgPool, food_id: Uuid) -> Result<Option<FoodDetail>, sqlx::Error> {
+    let Some(row) = sqlx::query_as::<_, FoodRow>(
+        r#"
+        SELECT id, source, source_code, name_zh, name_en, food_group, engine_class,
+               kcal_per_100g, carbohydrate_g, protein_g, fat_g, fiber_g, sodium_mg, updated_at
+        FROM foods
+        WHERE id = $1
+        "#,
+    )
+    .bind(food_id)
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let aliases = sqlx::query_as::<_, FoodAlias>(
+        "SELECT alias, lang FROM food_aliases WHERE food_id = $1 ORDER BY lang DESC, alias",
+    )
+    .bind(food_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(Some(FoodDetail {
+        id: row.id,
+        source: row.source,
+        source_code: row.source_code,
+        name_zh: row.name_zh,
+        name_en: row.name_en,
+        food_group: row.food_group,
+        engine_class: row.engine_class,
+        kcal_per_100g: row.kcal_per_100g,
+        nutrients_per_100g: NutrientVector {
+            carbohydrate_g: row.carbohydrate_g,
+            protein_g: row.protein_g,
+            fat_g: row.fat_g,
+            fiber_g: row.fiber_g,
+            sodium_mg: row.sodium_mg,
+        },
+        aliases,
+        updated_at: row.updated_at,
+    }))
+}
+
+/// What logging a portion of a food needs.
+#[derive(Debug, Clone, FromRow)]
+pub struct FoodNutrients {
+    pub id: Uuid,
+    pub name_zh: String,
+    pub engine_class: String,
+    pub kcal_per_100g: f64,
+    pub carbohydrate_g: f64,
+    pub protein_g: f64,
+    pub fat_g: f64,
+    pub fiber_g: f64,
+    pub sodium_mg: f64,
+}
+
+impl FoodNutrients {
+    pub fn nutrients_per_100g(&self) -> NutrientVector {
+        NutrientVector {
+            carbohydrate_g: self.carbohydrate_g,
+            protein_g: self.protein_g,
+            fat_g: self.fat_g,
+            fiber_g: self.fiber_g,
+            sodium_mg: self.sodium_mg,
+        }
+    }
+}
+
+const NUTRIENT_COLUMNS: &str = "f.id, f.name_zh, f.engine_class, f.kcal_per_100g, f.carbohydrate_g, f.protein_g, f.fat_g, f.fiber_g, f.sodium_mg";
+
+/// Typed names need at least this trigram similarity to match a food nothing else matched.
+const MIN_MATCH_SCORE: f64 = 0.4;
+/// Longest food name looked for inside a typed name.
+const MAX_CONTAINED_NAME_CHARS: usize = 16;
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Every run of 2 to `MAX_CONTAINED_NAME_CHARS` characters of `name`, escaped for `ILIKE`, so
+/// the names it contains can be found through the trigram indexes.
+fn contained_name_patterns(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut patterns: Vec<String> = (0..chars.len())
+        .flat_map(|start| {
+            let chars = &chars;
+            (start + 2..=chars.len().min(start + MAX_CONTAINED_NAME_CHARS))
+                .map(move |end| escape_like(&chars[start..end].iter().collect::<String>()))
+        })
+        .collect();
+    patterns.sort();
+    patterns.dedup();
+    patterns
+}
+
+pub async fn load_foods(
+    db: &PgPool,
+    food_ids: &[Uuid],
+) -> Result<HashMap<Uuid, FoodNutrients>, sqlx::Error> {
+    let foods = sqlx::query_as::<_, FoodNutrients>(&format!(
+        "SELECT {} FROM foods f WHERE f.id = ANY($1)",
+        NUTRIENT_COLUMNS
+    ))
+    .bind(food_ids)
+    .fetch_all(db)
+    .await?;
+    Ok(foods.into_iter().map(|food| (food.id, food)).collect())
+}
+
+/// The food a typed name most likely means, trying in turn: a name or alias equal to it, the
+/// shortest one containing it, the longest one it contains ("滷雞腿" → "雞腿"), and trigram
+/// similarity. With `exact_only` only the first is tried. Candidates are narrowed with
+/// indexed `ILIKE` and `%` filters before they are ranked.
+pub async fn match_food_name(
+    db: &PgPool,
+    name: &str,
+    exact_only: bool,
+) -> Result<Option<FoodNutrients>, sqlx::Error> {
+    sqlx::query_as::<_, FoodNutrients>(&format!(
+        r#"
+        WITH names AS (
+            SELECT id AS food_id, name_zh AS name FROM foods
+            WHERE name_zh ILIKE $4 OR name_zh ILIKE ANY($5) OR ($2 = 0 AND name_zh % $1)
+            UNION ALL
+            SELECT id, name_en FROM foods
+            WHERE name_en ILIKE $4 OR name_en ILIKE ANY($5) OR ($2 = 0 AND name_en % $1)
+            UNION ALL
+            SELECT food_id, alias FROM food_aliases
+            WHERE alias ILIKE $4 OR alias ILIKE ANY($5) OR ($2 = 0 AND alias % $1)
+        ),
+        ranked AS (
+            SELECT food_id,
+                   CASE
+                       WHEN lower(name) = lower($1) THEN 3
+                       WHEN strpos(lower(name), lower($1)) > 0 THEN 2
+                       WHEN char_length(name) >= 2 AND strpos(lower($1), lower(name)) > 0 THEN 1
+                       ELSE 0
+                   END AS rank,
+                   char_length(name) AS name_length,
+                   similarity(name, $1)::float8 AS score
+            FROM names
+        )
+        SELECT {}
+        FROM ranked r
+        JOIN foods f ON f.id = r.food_id
+        WHERE r.rank >= $2 AND (r.rank > 0 OR r.score >= $3)
+        ORDER BY r.rank DESC,
+                 CASE WHEN r.rank = 1 THEN -r.name_length ELSE r.name_length END,
+                 r.score DESC, char_length(f.name_zh), f.id
+        LIMIT 1
+        "#,
+        NUTRIENT_COLUMNS
+    ))
+    .bind(name)
+    .bind(if exact_only { 3 } else { 0 })
+    .bind(MIN_MATCH_SCORE)
+    .bind(if exact_only {
+        escape_like(name)
+    } else {
+        format!("%{}%", escape_like(name))
+    })
+    .bind(if exact_only {
+        Vec::new()
+    } else {
+        contained_name_patterns(name)
+    })
+    .fetch_optional(db)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::setup_db;
+
+    #[test]
+    fn test_contained_name_patterns() {
+        assert_eq!(
+            contained_name_patterns("滷雞腿"),
+            vec!["滷雞", "滷雞腿", "雞腿"]
+        );
+        assert_eq!(
+            contained_name_patterns("a%b"),
+            vec!["\\%b", "a\\%", "a\\%b"]
+        );
+        assert!(contained_name_patterns("飯").is_empty());
+        assert_eq!(
+            contained_name_patterns(&"飯".repeat(40)).len(),
+            MAX_CONTAINED_NAME_CHARS - 1
+        );
+    }
+
+    // Exported files start with a UTF-8 byte order mark.
+    const TFDA_SAMPLE: &str = concat!(
+        "\u{feff}",
+        r#"食品分類,資料類別,整合編號,樣品名稱,俗名,樣品英文名稱,內容物描述,廢棄率,分析項分類,分析項,含量單位,每單位含量,每100克含量
+穀物類,樣品基本資料,A1010101,白飯,米飯、白米飯,"Rice, cooked",,0,一般成分,熱量,kcal,,183
+穀物類,樣品基本資料,A1010101,白飯,米飯、白米飯,"Rice, cooked",,0,一般成分,修正熱量,kcal,,179
+穀物類,樣品基本資料,A1010101,白飯,米飯、白米飯,"Rice, cooked",,0,一般成分,總碳水化合物,g,,41.0
+穀物類,樣品基本資料,A1010101,白飯,米飯、白米飯,"Rice, cooked",,0,一般成分,粗蛋白,g,,3.1
+穀物類,樣品基本資料,A1010101,白飯,米飯、白米飯,"Rice, cooked",,0,礦物質,鈉,mg,,Tr
+水果類,樣品基本資料,H0100101,香蕉,芎蕉,Banana,,35,一般成分,熱量,kcal,,85
+水果類,樣品基本資料,H0100101,香蕉,芎蕉,Banana,,35,一般成分,膳食纖維,g,,1.6
+水果類,樣品基本資料,H0100101,香蕉,芎蕉,Banana,,35,維生素B群,維生素B1,mg,,0.02
+"#
+    );
+
+    #[test]
+    fn test_parse_tfda_groups_analytes_per_food() {
+        let foods = parse_tfda_csv(TFDA_SAMPLE.as_bytes()).unwrap();
+        assert_eq!(foods.len(), 2);
+
+        let rice = &foods[0];
+        assert_eq!(rice.source_code, "A1010101");
+        assert_eq!(rice.name_zh, "白飯");
+        assert_eq!(rice.name_en.as_deref(), Some("Rice, cooked"));
+        assert_eq!(rice.food_group.as_deref(), Some("穀物類"));
+        assert_eq!(rice.kcal_per_100g, 179.0);
+        assert_eq!(rice.nutrients_per_100g.carbohydrate_g, 41.0);
+        assert_eq!(rice.nutrients_per_100g.sodium_mg, 0.0);
+        let aliases: Vec<&str> = rice.aliases.iter().map(|a| a.alias.as_str()).collect();
+        assert_eq!(aliases, ["米飯", "白米飯"]);
+
+        let banana = &foods[1];
+        assert_eq!(banana.kcal_per_100g, 85.0);
+        assert_eq!(banana.nutrients_per_100g.fiber_g, 1.6);
+    }
+
+    #[test]
+    fn test_parse_tfda_rejects_missing_columns() {
+        let err = parse_tfda_csv("樣品名稱,分析項\n白飯,熱量\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, FoodImportError::MissingColumn("整合編號")));
+    }
+
+    #[test]
+    fn test_parse_wide_csv() {
+        let csv = "code,name_zh,name_en,aliases_zh,aliases_en,food_group,kcal_per_100g,protein_g,sodium_mg
+T1,滷雞腿,Braised chicken leg,雞腿;大雞腿,drumstick,肉類,210,24.5,410
+";
+        let foods = parse_wide_csv(csv.as_bytes()).unwrap();
+        assert_eq!(foods.len(), 1);
+        assert_eq!(foods[0].kcal_per_100g, 210.0);
+        assert_eq!(foods[0].nutrients_per_100g.protein_g, 24.5);
+        assert_eq!(foods[0].nutrients_per_100g.fat_g, 0.0);
+        assert_eq!(foods[0].aliases.len(), 3);
+        assert_eq!(foods[0].aliases[2].lang, LANG_EN);
+
+        let err = parse_wide_csv("code,name_zh,kcal_per_100g\nT1,雞腿,abc\n".as_bytes());
+        assert!(matches!(err, Err(FoodImportError::InvalidRow(2, _))));
+        let err = parse_wide_csv("code,name_zh,kcal_per_100g\n".as_bytes());
+        assert!(matches!(err, Err(FoodImportError::Empty)));
+    }
+
+    #[test]
+    fn test_engine_class_for_group() {
+        assert_eq!(engine_class_for_group("穀物類"), "grain");
+        assert_eq!(engine_class_for_group("魚貝類"), "protein_meat");
+        assert_eq!(engine_class_for_group("豆類"), "protein_bean");
+        assert_eq!(engine_class_for_group("堅果及種子類"), "nuts");
+        assert_eq!(engine_class_for_group("Fruit"), "fruit");
+        assert_eq!(engine_class_for_group("調味料及香辛料類"), FALLBACK_CLASS);
+    }
+
+    #[tokio::test]
+    async fn test_import_search_and_find() {
+        let state = setup_db().await;
+        let source = format!("test_{}", Uuid::new_v4().simple());
+        let foods = parse_tfda_csv(TFDA_SAMPLE.as_bytes()).unwrap();
+
+        let summary = import_foods(&state.db, &source, &foods).await.unwrap();
+        assert_eq!((summary.inserted, summary.updated), (2, 0));
+        let summary = import_foods(&state.db, &source, &foods).await.unwrap();
+        assert_eq!((summary.inserted, summary.updated), (0, 2));
+
+        let results = search_foods(&state.db, "芎蕉", 10).await.unwrap();
+        let banana = results
+            .iter()
+            .find(|food| food.name_zh == "香蕉")
+            .expect("alias match");
+        assert_eq!(banana.engine_class, "fruit");
+
+        let detail = find_food(&state.db, banana.id).await.unwrap().unwrap();
+        assert_eq!(detail.source, source);
+        assert_eq!(detail.nutrients_per_100g.fiber_g, 1.6);
+        assert_eq!(detail.aliases[0].alias, "芎蕉");
+        assert!(
+            find_food(&state.db, Uuid::new_v4())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let matched = match_food_name(&state.db, "米飯", true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(matched.name_zh, "白飯");
+        let matched = match_food_name(&state.db, "大香蕉", false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(matched.id, banana.id);
+        let matched = match_food_name(&state.db, "滷汁白飯", false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(matched.name_zh, "白飯");
+        let loaded = load_foods(&state.db, &[banana.id]).await.unwrap();
+        assert_eq!(loaded[&banana.id].nutrients_per_100g().fiber_g, 1.6);
+
+        sqlx::query("DELETE FROM foods WHERE source = $1")
+            .bind(&source)
+            .execute(&state.db)
+            .await
+            .unwrap();
+    }
+}
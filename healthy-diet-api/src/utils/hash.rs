@@ -1,11 +1,37 @@
 use argon2::{
-    Argon2,
+    Algorithm, Argon2, Params, Version,
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
 };
+use std::{env, sync::OnceLock};
 
-pub fn hash_password(password: &str) -> Result<String, String> {
+use crate::model::ENVKey;
+
+static ARGON2_PARAMS: OnceLock<Params> = OnceLock::new();
+
+fn env_cost(key: &str, default: u32) -> u32 {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Cost parameters for new hashes, from `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS` and
+/// `ARGON2_PARALLELISM` (defaulting to the argon2 crate's recommendation).
+pub fn argon2_params() -> &'static Params {
+    ARGON2_PARAMS.get_or_init(|| {
+        Params::new(
+            env_cost(ENVKey::ARGON2_MEMORY_KIB, Params::DEFAULT_M_COST),
+            env_cost(ENVKey::ARGON2_ITERATIONS, Params::DEFAULT_T_COST),
+            env_cost(ENVKey::ARGON2_PARALLELISM, Params::DEFAULT_P_COST),
+            None,
+        )
+        .unwrap_or_else(|e| panic!("Invalid Argon2 parameters: {}", e))
+    })
+}
+
+fn hash_with_params(password: &str, params: Params) -> Result<String, String> {
     let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
 
     argon2
         .hash_password(password.as_bytes(), &salt)
@@ -13,6 +39,12 @@ pub fn hash_password(password: &str) -> Result<String, String> {
         .map_err(|e| e.to_string())
 }
 
+pub fn hash_password(password: &str) -> Result<String, String> {
+    hash_with_params(password, argon2_params().clone())
+}
+
+/// Argon2 verification takes the algorithm and costs from the PHC string itself, so hashes
+/// made with older parameters keep working.
 pub fn verify_password(password: &str, password_hash: &str) -> Result<bool, String> {
     let parsed_hash = PasswordHash::new(password_hash).map_err(|e| e.to_string())?;
 
@@ -20,3 +52,46 @@ pub fn verify_password(password: &str, password_hash: &str) -> Result<bool, Stri
         .verify_password(password.as_bytes(), &parsed_hash)
         .is_ok())
 }
+
+fn is_weaker_than(password_hash: &str, current: &Params) -> bool {
+    let Ok(parsed) = PasswordHash::new(password_hash) else {
+        return true;
+    };
+    if parsed.algorithm.as_str() != Algorithm::Argon2id.as_ref()
+        || parsed.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+    match Params::try_from(&parsed) {
+        Ok(params) => {
+            params.m_cost() < current.m_cost()
+                || params.t_cost() < current.t_cost()
+                || params.p_cost() < current.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+/// Whether a stored hash should be replaced after the next successful verification because
+/// its parameters are weaker than the configured ones.
+pub fn needs_rehash(password_hash: &str) -> bool {
+    is_weaker_than(password_hash, argon2_params())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weaker_hash_needs_rehash_and_still_verifies() {
+        let weak = Params::new(8 * 1024, 1, 1, None).unwrap();
+        let strong = Params::new(16 * 1024, 2, 1, None).unwrap();
+        let weak_hash = hash_with_params("correct horse", weak.clone()).unwrap();
+        let strong_hash = hash_with_params("correct horse", strong.clone()).unwrap();
+
+        assert!(verify_password("correct horse", &weak_hash).unwrap());
+        assert!(is_weaker_than(&weak_hash, &strong));
+        assert!(!is_weaker_than(&strong_hash, &strong));
+        assert!(!is_weaker_than(&strong_hash, &weak));
+    }
+}
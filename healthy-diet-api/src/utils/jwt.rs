@@ -1,7 +1,7 @@
 use axum::{
     Json, RequestPartsExt,
-    extract::{FromRequestParts, Request},
-    http::{StatusCode, header::AUTHORIZATION, request::Parts},
+    extract::{FromRef, FromRequestParts, MatchedPath, Request, State},
+    http::{Method, StatusCode, header::AUTHORIZATION, request::Parts},
     middleware::Next,
     response::{IntoResponse, Response},
 };
@@ -9,15 +9,21 @@ use axum_extra::{
     TypedHeader,
     headers::{Authorization, authorization::Bearer},
 };
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
-use std::env;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::error;
 
 use crate::{
-    api::model::{ErrorResponse, ROLE_USER, is_admin_role},
-    model::ENVKey,
+    api::model::{ErrorResponse, ROLE_USER},
+    model::AppState,
+    utils::{
+        api_token::{API_TOKEN_PREFIX, authenticate_api_token, required_scope},
+        jwt_keys::keyring,
+        permission::{ADMIN_ACCESS, has_permission, required_permission},
+        session::{AccountAccess, check_account_access},
+    },
 };
 
 fn default_user_role() -> String {
@@ -33,6 +39,20 @@ pub struct Claims {
     pub exp: usize,
     pub iat: usize,
     pub token_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fam: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
+
+pub const REFRESH_TOKEN_TTL_SECS: usize = 86400 * 7;
+
+/// Identifies the server-side record backing a refresh token.
+pub struct RefreshTokenIds {
+    pub jti: uuid::Uuid,
+    pub family_id: uuid::Uuid,
 }
 
 pub fn sign_jwt(
@@ -48,18 +68,35 @@ pub fn sign_jwt_with_access_ttl(
     email: &str,
     role: &str,
     access_expires_in: usize,
+) -> Result<(String, String, usize), jsonwebtoken::errors::Error> {
+    encode_token_pair(user_id, email, role, access_expires_in, None)
+}
+
+pub fn sign_jwt_with_refresh_ids(
+    user_id: &str,
+    email: &str,
+    role: &str,
+    access_expires_in: usize,
+    refresh_ids: &RefreshTokenIds,
+) -> Result<(String, String, usize), jsonwebtoken::errors::Error> {
+    encode_token_pair(user_id, email, role, access_expires_in, Some(refresh_ids))
+}
+
+fn encode_token_pair(
+    user_id: &str,
+    email: &str,
+    role: &str,
+    access_expires_in: usize,
+    refresh_ids: Option<&RefreshTokenIds>,
 ) -> Result<(String, String, usize), jsonwebtoken::errors::Error> {
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs() as usize;
-    let refresh_expires_in = 86400 * 7;
+    let refresh_expires_in = REFRESH_TOKEN_TTL_SECS;
     let access_exp = now + access_expires_in;
     let refresh_exp = now + refresh_expires_in;
 
-    let secret = env::var(ENVKey::JWT_SECRET).expect("JWT_SECRET must be set");
-    let key = EncodingKey::from_secret(secret.as_bytes());
-
     let access_claims = Claims {
         sub: user_id.to_owned(),
         email: email.to_owned(),
@@ -67,8 +104,11 @@ pub fn sign_jwt_with_access_ttl(
         exp: access_exp,
         iat: now,
         token_type: "access".to_string(),
+        jti: None,
+        fam: None,
+        sid: refresh_ids.map(|ids| ids.family_id.to_string()),
     };
-    let access_token = encode(&Header::default(), &access_claims, &key)?;
+    let access_token = keyring().encode(&access_claims)?;
 
     let refresh_claims = Claims {
         sub: user_id.to_owned(),
@@ -77,23 +117,55 @@ pub fn sign_jwt_with_access_ttl(
         exp: refresh_exp,
         iat: now,
         token_type: "refresh".to_string(),
+        jti: refresh_ids.map(|ids| ids.jti.to_string()),
+        fam: refresh_ids.map(|ids| ids.family_id.to_string()),
+        sid: None,
     };
-    let refresh_token = encode(&Header::default(), &refresh_claims, &key)?;
+    let refresh_token = keyring().encode(&refresh_claims)?;
 
     Ok((access_token, refresh_token, access_expires_in))
 }
 
+/// Signs a one-off token (email verification, password reset) whose `jti` is tracked in the
+/// database so it can only be used once.
+pub fn sign_single_use_token(
+    user_id: &str,
+    email: &str,
+    token_type: &str,
+    jti: uuid::Uuid,
+    expires_in: usize,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as usize;
+
+    let claims = Claims {
+        sub: user_id.to_owned(),
+        email: email.to_owned(),
+        role: ROLE_USER.to_string(),
+        exp: now + expires_in,
+        iat: now,
+        token_type: token_type.to_string(),
+        jti: Some(jti.to_string()),
+        fam: None,
+        sid: None,
+    };
+    keyring().encode(&claims)
+}
+
 pub fn decode_jwt(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let secret = env::var(ENVKey::JWT_SECRET).expect("JWT_SECRET must be set");
-    let key = DecodingKey::from_secret(secret.as_bytes());
-    let token_data = decode::<Claims>(token, &key, &Validation::default())?;
-    Ok(token_data.claims)
+    keyring().decode::<Claims>(token)
 }
 
+#[derive(Debug, Clone)]
 pub struct AuthUser {
     pub user_id: uuid::Uuid,
     pub email: String,
     pub role: String,
+    pub session_id: Option<uuid::Uuid>,
+    /// Set when the caller used a scoped API token; it acts as the admin who created it.
+    pub api_token_id: Option<uuid::Uuid>,
 }
 
 fn auth_error_response(status: StatusCode, message: &str) -> Response {
@@ -106,7 +178,137 @@ fn auth_error_response(status: StatusCode, message: &str) -> Response {
         .into_response()
 }
 
-pub async fn require_admin_middleware(request: Request, next: Next) -> Response {
+/// API tokens only reach routes listed in `api_token::required_scope`, and only with the
+/// matching scope.
+async fn authenticate_api_token_user(
+    state: &AppState,
+    token: &str,
+    method: &Method,
+    matched_path: Option<&str>,
+) -> Result<AuthUser, Response> {
+    let principal = authenticate_api_token(&state.db, token)
+        .await
+        .map_err(|e| {
+            error!("DB Error (API Token Check): {:?}", e);
+            auth_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+        })?
+        .ok_or_else(|| auth_error_response(StatusCode::UNAUTHORIZED, "Invalid Token"))?;
+
+    let is_admin = has_permission(&state.db, &principal.role, ADMIN_ACCESS)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Permission Check): {:?}", e);
+            auth_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+        })?;
+    if !is_admin {
+        return Err(auth_error_response(
+            StatusCode::FORBIDDEN,
+            "API token owner is no longer an admin",
+        ));
+    }
+
+    let Some(scope) = matched_path.and_then(|path| required_scope(method, path)) else {
+        return Err(auth_error_response(
+            StatusCode::FORBIDDEN,
+            "API tokens cannot access this route",
+        ));
+    };
+    if !principal.has_scope(scope) {
+        return Err(auth_error_response(
+            StatusCode::FORBIDDEN,
+            &format!("API token is missing scope {}", scope),
+        ));
+    }
+
+    Ok(AuthUser {
+        user_id: principal.user_id,
+        email: principal.email,
+        role: principal.role,
+        session_id: None,
+        api_token_id: Some(principal.token_id),
+    })
+}
+
+async fn authenticate_access_token(state: &AppState, token: &str) -> Result<AuthUser, Response> {
+    let claims = decode_jwt(token)
+        .map_err(|_| auth_error_response(StatusCode::UNAUTHORIZED, "Invalid Token"))?;
+
+    let user_id = uuid::Uuid::parse_str(&claims.sub)
+        .map_err(|_| auth_error_response(StatusCode::UNAUTHORIZED, "Invalid User ID format"))?;
+
+    if claims.token_type != "access" {
+        return Err(auth_error_response(
+            StatusCode::UNAUTHORIZED,
+            "Invalid Token Type",
+        ));
+    }
+
+    let session_id = match claims.sid.as_deref() {
+        Some(sid) => Some(
+            uuid::Uuid::parse_str(sid)
+                .map_err(|_| auth_error_response(StatusCode::UNAUTHORIZED, "Invalid Token"))?,
+        ),
+        None => None,
+    };
+
+    let access = check_account_access(&state.db, user_id, session_id)
+        .await
+        .map_err(|e| {
+            error!("DB Error (Session Check): {:?}", e);
+            auth_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+        })?;
+    match access {
+        AccountAccess::Active => {}
+        AccountAccess::SessionRevoked => {
+            return Err(auth_error_response(
+                StatusCode::UNAUTHORIZED,
+                "Session has been revoked",
+            ));
+        }
+        AccountAccess::Suspended => {
+            return Err(auth_error_response(
+                StatusCode::FORBIDDEN,
+                "Account suspended",
+            ));
+        }
+        AccountAccess::Missing => {
+            return Err(auth_error_response(
+                StatusCode::UNAUTHORIZED,
+                "Invalid Token",
+            ));
+        }
+    }
+
+    Ok(AuthUser {
+        user_id,
+        email: claims.email,
+        role: claims.role,
+        session_id,
+        api_token_id: None,
+    })
+}
+
+async fn authenticate_bearer(
+    state: &AppState,
+    token: &str,
+    method: &Method,
+    matched_path: Option<&str>,
+) -> Result<AuthUser, Response> {
+    if token.starts_with(API_TOKEN_PREFIX) {
+        authenticate_api_token_user(state, token, method, matched_path).await
+    } else {
+        authenticate_access_token(state, token).await
+    }
+}
+
+/// Admits admin JWTs and scoped API tokens whose role holds `admin.access` plus the route's
+/// permission. The authenticated caller is stored in the request so the `AuthUser` extractor
+/// does not look it up again.
+pub async fn require_admin_middleware(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
     let auth_header = match request.headers().get(AUTHORIZATION) {
         Some(value) => value,
         None => return auth_error_response(StatusCode::UNAUTHORIZED, "Missing Bearer Token"),
@@ -121,72 +323,89 @@ pub async fn require_admin_middleware(request: Request, next: Next) -> Response
         return auth_error_response(StatusCode::UNAUTHORIZED, "Invalid Token");
     };
 
-    let claims = match decode_jwt(token) {
-        Ok(claims) => claims,
-        Err(_) => return auth_error_response(StatusCode::UNAUTHORIZED, "Invalid Token"),
-    };
-
-    if claims.token_type != "access" {
-        return auth_error_response(StatusCode::UNAUTHORIZED, "Invalid Token Type");
-    }
+    let matched_path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let auth_user =
+        match authenticate_bearer(&state, token, request.method(), matched_path.as_deref()).await {
+            Ok(auth_user) => auth_user,
+            Err(response) => return response,
+        };
 
-    if !is_admin_role(&claims.role) {
+    // Routes without an entry in the permission table are refused rather than left open.
+    let Some(permission) = matched_path
+        .as_deref()
+        .and_then(|path| required_permission(request.method(), path))
+    else {
+        error!(
+            "No permission defined for admin route {} {:?}",
+            request.method(),
+            matched_path
+        );
         return auth_error_response(StatusCode::FORBIDDEN, "Admin access required");
+    };
+    for required in [ADMIN_ACCESS, permission] {
+        match has_permission(&state.db, &auth_user.role, required).await {
+            Ok(true) => {}
+            Ok(false) if required == ADMIN_ACCESS => {
+                return auth_error_response(StatusCode::FORBIDDEN, "Admin access required");
+            }
+            Ok(false) => {
+                return auth_error_response(
+                    StatusCode::FORBIDDEN,
+                    &format!("Missing permission {}", required),
+                );
+            }
+            Err(e) => {
+                error!("DB Error (Permission Check): {:?}", e);
+                return auth_error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error",
+                );
+            }
+        }
     }
 
+    request.extensions_mut().insert(auth_user);
     next.run(request).await
 }
 
 impl<S> FromRequestParts<S> for AuthUser
 where
+    Arc<AppState>: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = Response;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(auth_user) = parts.extensions.get::<AuthUser>() {
+            return Ok(auth_user.clone());
+        }
+
         let TypedHeader(Authorization(bearer)) = parts
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
-            .map_err(|_| {
-                (
-                    StatusCode::UNAUTHORIZED,
-                    Json(ErrorResponse {
-                        error: "Missing Bearer Token".to_string(),
-                    }),
-                )
-                    .into_response()
-            })?;
-
-        let claims = decode_jwt(bearer.token()).map_err(|_| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(ErrorResponse {
-                    error: "Invalid Token".to_string(),
-                }),
-            )
-                .into_response()
-        })?;
-
-        let user_id = uuid::Uuid::parse_str(&claims.sub).map_err(|_| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(ErrorResponse {
-                    error: "Invalid User ID format".to_string(),
-                }),
-            )
-                .into_response()
-        })?;
+            .map_err(|_| auth_error_response(StatusCode::UNAUTHORIZED, "Missing Bearer Token"))?;
 
-        Ok(AuthUser {
-            user_id,
-            email: claims.email,
-            role: claims.role,
-        })
+        let app_state = Arc::<AppState>::from_ref(state);
+        let matched_path = parts
+            .extensions
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string());
+        authenticate_bearer(
+            &app_state,
+            bearer.token(),
+            &parts.method,
+            matched_path.as_deref(),
+        )
+        .await
     }
 }
 
 impl<S> axum::extract::OptionalFromRequestParts<S> for AuthUser
 where
+    Arc<AppState>: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = Infallible;
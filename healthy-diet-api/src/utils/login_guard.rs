@@ -0,0 +1,222 @@
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{FromRow, PgPool};
+
+use crate::utils::audit::write_audit_log;
+
+/// Failure counter scopes in `login_failures`.
+pub const SCOPE_ACCOUNT: &str = "account";
+pub const SCOPE_IP: &str = "ip";
+pub const SCOPE_ADMIN_IP: &str = "admin_ip";
+/// Second-factor attempts, keyed by user id.
+pub const SCOPE_TWO_FACTOR: &str = "two_factor";
+/// Password reset requests, keyed by normalized email and by IP. Every request counts.
+pub const SCOPE_PASSWORD_RESET_EMAIL: &str = "password_reset_email";
+pub const SCOPE_PASSWORD_RESET_IP: &str = "password_reset_ip";
+
+/// How many failures trigger a lockout and how long it lasts. Each further lockout of the same
+/// key doubles the duration up to `max_lockout_secs`.
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    pub max_failures: i32,
+    /// Failures older than this no longer count.
+    pub window_secs: i64,
+    pub base_lockout_secs: i64,
+    pub max_lockout_secs: i64,
+}
+
+pub const USER_ACCOUNT_POLICY: LockoutPolicy = LockoutPolicy {
+    max_failures: 5,
+    window_secs: 15 * 60,
+    base_lockout_secs: 60,
+    max_lockout_secs: 3600,
+};
+
+pub const ADMIN_ACCOUNT_POLICY: LockoutPolicy = LockoutPolicy {
+    max_failures: 3,
+    window_secs: 30 * 60,
+    base_lockout_secs: 300,
+    max_lockout_secs: 24 * 3600,
+};
+
+pub const USER_IP_POLICY: LockoutPolicy = LockoutPolicy {
+    max_failures: 20,
+    window_secs: 15 * 60,
+    base_lockout_secs: 60,
+    max_lockout_secs: 3600,
+};
+
+pub const ADMIN_IP_POLICY: LockoutPolicy = LockoutPolicy {
+    max_failures: 10,
+    window_secs: 30 * 60,
+    base_lockout_secs: 300,
+    max_lockout_secs: 24 * 3600,
+};
+
+pub const TWO_FACTOR_POLICY: LockoutPolicy = LockoutPolicy {
+    max_failures: 5,
+    window_secs: 15 * 60,
+    base_lockout_secs: 300,
+    max_lockout_secs: 24 * 3600,
+};
+
+pub const PASSWORD_RESET_EMAIL_POLICY: LockoutPolicy = LockoutPolicy {
+    max_failures: 3,
+    window_secs: 3600,
+    base_lockout_secs: 3600,
+    max_lockout_secs: 24 * 3600,
+};
+
+pub const PASSWORD_RESET_IP_POLICY: LockoutPolicy = LockoutPolicy {
+    max_failures: 10,
+    window_secs: 3600,
+    base_lockout_secs: 900,
+    max_lockout_secs: 24 * 3600,
+};
+
+/// Lockout history is forgotten after a day without failures.
+const LOCKOUT_HISTORY_RESET_SECS: i64 = 86400;
+
+impl LockoutPolicy {
+    pub fn lockout_secs(&self, previous_lockouts: i32) -> i64 {
+        let factor = 1_i64
+            .checked_shl(previous_lockouts.clamp(0, 30) as u32)
+            .unwrap_or(i64::MAX);
+        self.base_lockout_secs
+            .saturating_mul(factor)
+            .min(self.max_lockout_secs)
+    }
+}
+
+/// Normalizes the email used as the account key so `A@x.com` and `a@x.com ` share a counter.
+pub fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+pub async fn locked_until(
+    db: &PgPool,
+    scope: &str,
+    key: &str,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar::<_, DateTime<Utc>>(
+        r#"
+        SELECT locked_until FROM login_failures
+        WHERE scope = $1 AND key = $2 AND locked_until > now()
+        "#,
+    )
+    .bind(scope)
+    .bind(key)
+    .fetch_optional(db)
+    .await
+}
+
+#[derive(Debug, FromRow)]
+struct FailureRow {
+    failures: i32,
+    lockout_count: i32,
+}
+
+/// Counts one failed attempt. Returns the new lock expiry when this failure triggered a
+/// lockout; the lockout is also written to `admin_audit_logs`.
+pub async fn record_failure(
+    db: &PgPool,
+    scope: &str,
+    key: &str,
+    policy: &LockoutPolicy,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let row = sqlx::query_as::<_, FailureRow>(
+        r#"
+        INSERT INTO login_failures (scope, key, failures, lockout_count, last_failure_at)
+        VALUES ($1, $2, 1, 0, now())
+        ON CONFLICT (scope, key) DO UPDATE SET
+            failures = CASE
+                WHEN login_failures.last_failure_at < now() - make_interval(secs => $3::double precision)
+                THEN 1
+                ELSE login_failures.failures + 1
+            END,
+            lockout_count = CASE
+                WHEN login_failures.last_failure_at < now() - make_interval(secs => $4::double precision)
+                THEN 0
+                ELSE login_failures.lockout_count
+            END,
+            last_failure_at = now()
+        RETURNING failures, lockout_count
+        "#,
+    )
+    .bind(scope)
+    .bind(key)
+    .bind(policy.window_secs as f64)
+    .bind(LOCKOUT_HISTORY_RESET_SECS as f64)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if row.failures < policy.max_failures {
+        tx.commit().await?;
+        return Ok(None);
+    }
+
+    let lockout_secs = policy.lockout_secs(row.lockout_count);
+    let locked_until = sqlx::query_scalar::<_, DateTime<Utc>>(
+        r#"
+        UPDATE login_failures
+        SET failures = 0,
+            lockout_count = lockout_count + 1,
+            locked_until = now() + make_interval(secs => $3::double precision)
+        WHERE scope = $1 AND key = $2
+        RETURNING locked_until
+        "#,
+    )
+    .bind(scope)
+    .bind(key)
+    .bind(lockout_secs as f64)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    write_audit_log(
+        db,
+        None,
+        "LOGIN_LOCKOUT",
+        scope,
+        key,
+        json!({
+            "failures": row.failures,
+            "lockoutSeconds": lockout_secs,
+            "lockedUntil": locked_until,
+        }),
+    )
+    .await;
+
+    Ok(Some(locked_until))
+}
+
+/// Forgets failures and any active lock for `key`. Returns whether a row existed.
+pub async fn clear_failures(db: &PgPool, scope: &str, key: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM login_failures WHERE scope = $1 AND key = $2")
+        .bind(scope)
+        .bind(key)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_duration_doubles_up_to_max() {
+        assert_eq!(USER_ACCOUNT_POLICY.lockout_secs(0), 60);
+        assert_eq!(USER_ACCOUNT_POLICY.lockout_secs(1), 120);
+        assert_eq!(USER_ACCOUNT_POLICY.lockout_secs(3), 480);
+        assert_eq!(USER_ACCOUNT_POLICY.lockout_secs(10), 3600);
+        assert_eq!(ADMIN_ACCOUNT_POLICY.lockout_secs(40), 24 * 3600);
+    }
+
+    #[test]
+    fn test_account_key_is_case_insensitive() {
+        assert_eq!(account_key(" User@Example.com "), "user@example.com");
+    }
+}
@@ -0,0 +1,261 @@
+/// Portion assumed when an entry names no unit, e.g. "滷雞腿".
+pub const DEFAULT_SERVING_G: f64 = 100.0;
+
+/// Household units and their weight in grams. Longer names come first so "小碗" is not read
+/// as "小" + "碗".
+const UNITS: [(&str, f64); 23] = [
+    ("公斤", 1000.0),
+    ("公克", 1.0),
+    ("毫升", 1.0),
+    ("茶匙", 5.0),
+    ("湯匙", 15.0),
+    ("小碗", 150.0),
+    ("大碗", 300.0),
+    ("kg", 1000.0),
+    ("ml", 1.0),
+    ("g", 1.0),
+    ("克", 1.0),
+    ("碗", 200.0),
+    ("盤", 250.0),
+    ("杯", 240.0),
+    ("份", 150.0),
+    ("匙", 15.0),
+    ("片", 30.0),
+    ("塊", 50.0),
+    ("顆", 60.0),
+    ("個", 60.0),
+    ("隻", 100.0),
+    ("根", 100.0),
+    ("條", 100.0),
+];
+
+const SEPARATORS: [char; 9] = ['+', '＋', '、', ',', '，', ';', '；', '\n', '/'];
+
+/// Words that join two foods ("白飯和滷雞腿") but also start food names (和風沙拉, 及第粥), so
+/// they only separate when both sides are known foods.
+const CONJUNCTIONS: [char; 5] = ['和', '跟', '與', '及', '配'];
+/// Segments with more conjunctions than this are not split on them, which bounds the lookups.
+const MAX_CONJUNCTIONS: usize = 6;
+
+/// One food of a typed meal, e.g. "一碗白飯" or "白飯 200g".
+#[derive(Debug, Clone, PartialEq)]
+pub struct MealTextEntry {
+    /// The segment as typed, for showing which part could not be matched.
+    pub text: String,
+    /// What is left once quantity and unit are removed; looked up as a food name.
+    pub name: String,
+    pub weight_g: f64,
+}
+
+fn chinese_digit(c: char) -> Option<f64> {
+    Some(match c {
+        '一' => 1.0,
+        '二' | '兩' => 2.0,
+        '三' => 3.0,
+        '四' => 4.0,
+        '五' => 5.0,
+        '六' => 6.0,
+        '七' => 7.0,
+        '八' => 8.0,
+        '九' => 9.0,
+        _ => return None,
+    })
+}
+
+/// Reads a leading quantity: Arabic digits ("1.5"), Chinese numerals up to 99 ("十二") or
+/// "半". Returns the quantity, whether it was written in Chinese, and the rest of the text.
+fn take_quantity(text: &str) -> Option<(f64, bool, &str)> {
+    let numeric_len = text
+        .char_indices()
+        .take_while(|(_, c)| c.is_ascii_digit() || *c == '.')
+        .map(|(i, c)| i + c.len_utf8())
+        .last();
+    if let Some(len) = numeric_len {
+        return text[..len]
+            .parse::<f64>()
+            .ok()
+            .map(|quantity| (quantity, false, &text[len..]));
+    }
+
+    if let Some(rest) = text.strip_prefix('半') {
+        return Some((0.5, true, rest));
+    }
+    let mut value = 0.0;
+    let mut current = None;
+    let mut len = 0;
+    for c in text.chars() {
+        if let Some(digit) = chinese_digit(c) {
+            current = Some(digit);
+        } else if c == '十' {
+            value += current.unwrap_or(1.0) * 10.0;
+            current = None;
+        } else {
+            break;
+        }
+        len += c.len_utf8();
+    }
+    if len == 0 {
+        return None;
+    }
+    Some((value + current.unwrap_or(0.0), true, &text[len..]))
+}
+
+fn take_unit(text: &str) -> Option<(f64, &str)> {
+    let lower = text.to_ascii_lowercase();
+    UNITS.iter().find_map(|(unit, grams)| {
+        lower
+            .starts_with(unit)
+            .then(|| (*grams, &text[unit.len()..]))
+    })
+}
+
+/// Reads a trailing weight such as "200g" or "150 公克".
+fn take_trailing_weight(text: &str) -> Option<(f64, &str)> {
+    let lower = text.to_ascii_lowercase();
+    let (unit, grams) = [("公克", 1.0), ("kg", 1000.0), ("g", 1.0), ("克", 1.0)]
+        .into_iter()
+        .find(|(unit, _)| lower.ends_with(unit))?;
+    let without_unit = text[..text.len() - unit.len()].trim_end();
+    let digits_start = without_unit
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_digit() || *c == '.')
+        .last()
+        .map(|(i, _)| i)?;
+    let amount = without_unit[digits_start..].parse::<f64>().ok()?;
+    Some((amount * grams, without_unit[..digits_start].trim_end()))
+}
+
+fn parse_entry(segment: &str) -> Option<MealTextEntry> {
+    let text = segment.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let quantity = take_quantity(text).map(|(quantity, chinese, rest)| {
+        let rest = rest.trim_start();
+        (quantity, chinese, rest, take_unit(rest))
+    });
+    let (weight_g, name) = match (take_trailing_weight(text), quantity) {
+        (Some((weight_g, name)), _) => (weight_g, name),
+        // A unit is only stripped after a quantity, so foods such as 碗粿 keep their name.
+        (None, Some((quantity, _, _, Some((grams, name))))) => (quantity * grams, name),
+        // Chinese numerals without a unit are usually part of the name: 三明治, 五穀飯.
+        (None, Some((quantity, false, rest, None))) => (quantity * DEFAULT_SERVING_G, rest),
+        _ => (DEFAULT_SERVING_G, text),
+    };
+
+    let name = name.trim();
+    if name.is_empty() || weight_g <= 0.0 || !weight_g.is_finite() {
+        return None;
+    }
+    Some(MealTextEntry {
+        text: text.to_string(),
+        name: name.to_string(),
+        weight_g,
+    })
+}
+
+/// Splits a typed meal ("一碗白飯 + 滷雞腿") into foods with an estimated weight each.
+/// Deterministic: the same text always gives the same entries.
+pub fn parse_meal_text(text: &str) -> Vec<MealTextEntry> {
+    text.split(SEPARATORS).filter_map(parse_entry).collect()
+}
+
+/// The ways an entry reads as a food, a conjunction and the rest, leftmost conjunction first.
+/// The caller decides whether the sides are foods; the rest may hold further conjunctions.
+pub fn conjunction_splits(entry: &MealTextEntry) -> Vec<(MealTextEntry, MealTextEntry)> {
+    let positions: Vec<(usize, char)> = entry
+        .text
+        .char_indices()
+        .filter(|(_, c)| CONJUNCTIONS.contains(c))
+        .collect();
+    if positions.len() > MAX_CONJUNCTIONS {
+        return Vec::new();
+    }
+    positions
+        .into_iter()
+        .filter_map(|(i, c)| {
+            let left = parse_entry(&entry.text[..i])?;
+            let right = parse_entry(&entry.text[i + c.len_utf8()..])?;
+            Some((left, right))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, weight_g: f64) -> (String, f64) {
+        (name.to_string(), weight_g)
+    }
+
+    fn parsed(text: &str) -> Vec<(String, f64)> {
+        parse_meal_text(text)
+            .into_iter()
+            .map(|entry| (entry.name, entry.weight_g))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_meal_text_units_and_quantities() {
+        assert_eq!(
+            parsed("一碗白飯 + 滷雞腿"),
+            [entry("白飯", 200.0), entry("滷雞腿", DEFAULT_SERVING_G)]
+        );
+        assert_eq!(
+            parsed("半碗飯、兩顆蛋+1.5杯豆漿"),
+            [entry("飯", 100.0), entry("蛋", 120.0), entry("豆漿", 360.0)]
+        );
+        assert_eq!(
+            parsed("白飯 200g，150公克雞胸肉"),
+            [entry("白飯", 200.0), entry("雞胸肉", 150.0)]
+        );
+        assert_eq!(parsed("十二片吐司"), [entry("吐司", 360.0)]);
+        assert_eq!(parsed("一小碗稀飯"), [entry("稀飯", 150.0)]);
+        assert_eq!(parsed("2 滷雞腿"), [entry("滷雞腿", 200.0)]);
+    }
+
+    #[test]
+    fn test_parse_meal_text_keeps_food_names_intact() {
+        // Without a quantity, a leading unit character is part of the name.
+        assert_eq!(parsed("碗粿"), [entry("碗粿", DEFAULT_SERVING_G)]);
+        assert_eq!(parsed("三明治"), [entry("三明治", DEFAULT_SERVING_G)]);
+        // Conjunctions are not separators on their own.
+        assert_eq!(parsed("和風沙拉"), [entry("和風沙拉", DEFAULT_SERVING_G)]);
+        assert_eq!(
+            parsed("及第粥、白飯"),
+            [
+                entry("及第粥", DEFAULT_SERVING_G),
+                entry("白飯", DEFAULT_SERVING_G)
+            ]
+        );
+        assert!(parse_meal_text(" + ，").is_empty());
+        assert!(parse_meal_text("兩碗").is_empty());
+        assert_eq!(parse_meal_text("一碗白飯")[0].text, "一碗白飯");
+    }
+
+    #[test]
+    fn test_conjunction_splits() {
+        let splits: Vec<((String, f64), (String, f64))> =
+            conjunction_splits(&parse_meal_text("一碗白飯和兩顆蛋配豆漿")[0])
+                .into_iter()
+                .map(|(left, right)| ((left.name, left.weight_g), (right.name, right.weight_g)))
+                .collect();
+        assert_eq!(
+            splits,
+            [
+                (entry("白飯", 200.0), entry("蛋配豆漿", 120.0)),
+                (
+                    entry("白飯和兩顆蛋", 200.0),
+                    entry("豆漿", DEFAULT_SERVING_G)
+                ),
+            ]
+        );
+        // A leading or trailing conjunction leaves one side empty.
+        assert!(conjunction_splits(&parse_meal_text("和風沙拉")[0]).is_empty());
+        let many = parse_meal_text(&"飯和".repeat(MAX_CONJUNCTIONS + 1));
+        assert!(conjunction_splits(&many[0]).is_empty());
+    }
+}
@@ -1,8 +1,29 @@
+pub mod account_jobs;
 pub mod ai_prompt;
-pub mod calculator;
+pub mod annotation;
+pub mod api_token;
+pub mod audit;
+pub mod caregiver;
+pub mod client_info;
+pub mod diet_items;
+pub mod diet_jobs;
+pub mod email_token;
+pub mod food_detector;
+pub mod foods;
 pub mod gemini;
 pub mod hash;
 pub mod jwt;
+pub mod jwt_keys;
+pub mod login_guard;
+pub mod mailer;
+pub mod meal_text;
+pub mod nutrition_engine;
+pub mod password_policy;
+pub mod permission;
 pub mod rag_worker;
+pub mod refresh_token;
 pub mod route_control;
+pub mod session;
 pub mod test;
+pub mod totp;
+pub mod yolo_dataset;
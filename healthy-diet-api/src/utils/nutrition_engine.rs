@@ -0,0 +1,432 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, fs};
+use tracing::{error, info};
+
+use crate::model::ENVKey;
+
+/// The copy shipped with the binary, used when `NUTRITION_ENGINE_PATH` cannot be loaded.
+const EMBEDDED_ENGINE: &str = include_str!("../../NutritionEngine.json");
+const DEFAULT_ENGINE_PATH: &str = "NutritionEngine.json";
+
+/// Classes without their own entry are estimated (and totalled) as this one.
+pub const FALLBACK_CLASS: &str = "other";
+/// The categories diet records keep calorie and area totals for.
+pub const CATEGORIES: [&str; 8] = [
+    "grain",
+    "protein_meat",
+    "protein_bean",
+    "vegetable",
+    "fruit",
+    "dairy",
+    "nuts",
+    FALLBACK_CLASS,
+];
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FoodProfile {
+    /// Grams per unit of damped area share, relative to the reference meal weight.
+    pub density: f64,
+    /// Used instead of `density` for the second and later item of the class in one meal.
+    #[serde(default)]
+    pub repeat_density: Option<f64>,
+    pub kcal_per_gram: f64,
+    pub max_weight_g: f64,
+    pub cooking_method: String,
+    pub nutrients_per_100g: NutrientVector,
+}
+
+/// Macronutrients plus fiber and sodium, the figures our diabetic, hypertensive and
+/// sarcopenic users track.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct NutrientVector {
+    pub carbohydrate_g: f64,
+    pub protein_g: f64,
+    pub fat_g: f64,
+    pub fiber_g: f64,
+    pub sodium_mg: f64,
+}
+
+impl NutrientVector {
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            carbohydrate_g: self.carbohydrate_g * factor,
+            protein_g: self.protein_g * factor,
+            fat_g: self.fat_g * factor,
+            fiber_g: self.fiber_g * factor,
+            sodium_mg: self.sodium_mg * factor,
+        }
+    }
+
+    pub fn add(&mut self, other: &Self) {
+        self.carbohydrate_g += other.carbohydrate_g;
+        self.protein_g += other.protein_g;
+        self.fat_g += other.fat_g;
+        self.fiber_g += other.fiber_g;
+        self.sodium_mg += other.sodium_mg;
+    }
+
+    /// One decimal, as shown to users.
+    pub fn rounded(&self) -> Self {
+        Self {
+            carbohydrate_g: round1(self.carbohydrate_g),
+            protein_g: round1(self.protein_g),
+            fat_g: round1(self.fat_g),
+            fiber_g: round1(self.fiber_g),
+            sodium_mg: round1(self.sodium_mg),
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        [
+            self.carbohydrate_g,
+            self.protein_g,
+            self.fat_g,
+            self.fiber_g,
+            self.sodium_mg,
+        ]
+        .iter()
+        .all(|value| value.is_finite() && *value >= 0.0)
+    }
+}
+
+/// Area shares above `knee` only count `slope` as much, since large boxes overlap the plate.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AreaDamping {
+    pub knee: f64,
+    pub slope: f64,
+}
+
+/// Calories estimated from plate area above `threshold_kcal` are pulled towards
+/// `anchor_kcal`. Weights the user entered are taken as they are.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MealSquash {
+    pub threshold_kcal: f64,
+    pub anchor_kcal: f64,
+    pub slope: f64,
+}
+
+/// Calorie estimation rules loaded from `NutritionEngine.json`. Every diet record stores the
+/// `version` that produced it.
+#[derive(Deserialize, Debug, Clone)]
+pub struct NutritionEngine {
+    pub version: String,
+    pub reference_meal_weight_g: f64,
+    /// Image area (px²) that corresponds to the reference meal.
+    pub reference_image_area_px: f64,
+    pub area_damping: AreaDamping,
+    pub min_item_weight_g: f64,
+    pub meal_squash: MealSquash,
+    pub foods: HashMap<String, FoodProfile>,
+}
+
+/// One food to estimate: either its share of the image or a weight entered by hand.
+#[derive(Deserialize, Debug, Clone)]
+pub struct FoodPortion {
+    pub class: String,
+    #[serde(default)]
+    pub confidence: Option<f64>,
+    #[serde(default)]
+    pub area_ratio: Option<f64>,
+    #[serde(default)]
+    pub weight_g: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EstimatedItem {
+    pub class: String,
+    /// The diet record category the item is totalled under.
+    pub category: &'static str,
+    pub confidence: Option<f64>,
+    pub estimated_weight_g: f64,
+    pub calories: f64,
+    pub cooking_method: String,
+    pub nutrients: NutrientVector,
+}
+
+#[derive(Debug, Clone)]
+pub struct NutritionEstimate {
+    pub engine_version: String,
+    pub total_calories: f64,
+    pub nutrients: NutrientVector,
+    /// Area-estimated items carry the squashed weight, so items, categories and totals agree.
+    pub items: Vec<EstimatedItem>,
+    /// Calories and area share (percent) per category; every category is present.
+    pub categories: HashMap<&'static str, (f64, f64)>,
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+impl NutritionEngine {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let engine: NutritionEngine = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if engine.version.trim().is_empty() {
+            return Err("version must not be empty".to_string());
+        }
+        if !engine.foods.contains_key(FALLBACK_CLASS) {
+            return Err(format!("foods must define `{}`", FALLBACK_CLASS));
+        }
+        let positive = [
+            engine.reference_meal_weight_g,
+            engine.reference_image_area_px,
+            engine.min_item_weight_g,
+        ];
+        if positive
+            .iter()
+            .any(|value| !value.is_finite() || *value <= 0.0)
+        {
+            return Err("reference weights and areas must be positive".to_string());
+        }
+        for (class, food) in &engine.foods {
+            let densities = [Some(food.density), food.repeat_density];
+            if densities
+                .into_iter()
+                .flatten()
+                .any(|density| density <= 0.0)
+                || food.kcal_per_gram < 0.0
+                || food.max_weight_g < engine.min_item_weight_g
+                || !food.nutrients_per_100g.is_valid()
+            {
+                return Err(format!("food `{}` has invalid attributes", class));
+            }
+        }
+        Ok(engine)
+    }
+
+    pub fn embedded() -> Self {
+        Self::from_json(EMBEDDED_ENGINE).expect("embedded NutritionEngine.json is valid")
+    }
+
+    /// Loads `NUTRITION_ENGINE_PATH` (default `NutritionEngine.json`), falling back to the
+    /// embedded copy when the file is missing or invalid.
+    pub fn from_env() -> Self {
+        let path =
+            env::var(ENVKey::NUTRITION_ENGINE_PATH).unwrap_or_else(|_| DEFAULT_ENGINE_PATH.into());
+        let loaded = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| Self::from_json(&json));
+        match loaded {
+            Ok(engine) => {
+                info!("Nutrition engine {} loaded from {}", engine.version, path);
+                engine
+            }
+            Err(e) => {
+                let engine = Self::embedded();
+                error!(
+                    "Nutrition engine: cannot load {} ({}), using embedded {}",
+                    path, e, engine.version
+                );
+                engine
+            }
+        }
+    }
+
+    /// The share of the reference image a `[x_min, y_min, x_max, y_max]` box covers.
+    pub fn area_ratio_of_bbox(&self, bbox: &[f64; 4]) -> f64 {
+        let area = (bbox[2] - bbox[0]) * (bbox[3] - bbox[1]);
+        (area / self.reference_image_area_px).clamp(0.0, 1.0)
+    }
+
+    fn damp(&self, area_ratio: f64) -> f64 {
+        let damping = &self.area_damping;
+        if area_ratio > damping.knee {
+            damping.knee + (area_ratio - damping.knee) * damping.slope
+        } else {
+            area_ratio
+        }
+    }
+
+    pub fn estimate(&self, portions: &[FoodPortion]) -> NutritionEstimate {
+        let mut categories: HashMap<&'static str, (f64, f64)> = CATEGORIES
+            .iter()
+            .map(|category| (*category, (0.0, 0.0)))
+            .collect();
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        let mut area_calories = 0.0;
+
+        // Weights first: the squash depends on the calories of every area-estimated item.
+        let weights: Vec<(f64, bool)> = portions
+            .iter()
+            .map(|portion| {
+                let food = self.food(&portion.class);
+                let count = seen.entry(portion.class.as_str()).or_default();
+                *count += 1;
+                match portion.weight_g {
+                    Some(weight_g) => (weight_g, false),
+                    None => {
+                        let density = match food.repeat_density {
+                            Some(repeat_density) if *count > 1 => repeat_density,
+                            _ => food.density,
+                        };
+                        let area_ratio = portion.area_ratio.unwrap_or(0.0).clamp(0.0, 1.0);
+                        let weight_g =
+                            (self.damp(area_ratio) * self.reference_meal_weight_g * density)
+                                .clamp(self.min_item_weight_g, food.max_weight_g);
+                        area_calories += weight_g * food.kcal_per_gram;
+                        (weight_g, true)
+                    }
+                }
+            })
+            .collect();
+
+        let squash = &self.meal_squash;
+        let squash_factor = if area_calories > squash.threshold_kcal {
+            (squash.anchor_kcal + (area_calories - squash.anchor_kcal) * squash.slope)
+                / area_calories
+        } else {
+            1.0
+        };
+
+        let mut total_calories = 0.0;
+        let mut nutrients = NutrientVector::default();
+        let mut items = Vec::with_capacity(portions.len());
+        for (portion, (weight_g, from_area)) in portions.iter().zip(weights) {
+            let category = CATEGORIES
+                .iter()
+                .find(|category| **category == portion.class)
+                .copied()
+                .unwrap_or(FALLBACK_CLASS);
+            let food = self.food(&portion.class);
+            let weight_g = if from_area {
+                weight_g * squash_factor
+            } else {
+                weight_g
+            };
+            let calories = weight_g * food.kcal_per_gram;
+            let item_nutrients = food.nutrients_per_100g.scaled(weight_g / 100.0);
+            total_calories += calories;
+            nutrients.add(&item_nutrients);
+
+            if let Some(entry) = categories.get_mut(category) {
+                entry.0 += calories;
+                entry.1 += portion.area_ratio.unwrap_or(0.0).clamp(0.0, 1.0) * 100.0;
+            }
+
+            items.push(EstimatedItem {
+                class: portion.class.clone(),
+                category,
+                confidence: portion.confidence,
+                estimated_weight_g: round1(weight_g),
+                calories: round1(calories),
+                cooking_method: food.cooking_method.clone(),
+                nutrients: item_nutrients.rounded(),
+            });
+        }
+
+        NutritionEstimate {
+            engine_version: self.version.clone(),
+            total_calories,
+            nutrients,
+            items,
+            categories,
+        }
+    }
+
+    fn food(&self, class: &str) -> &FoodProfile {
+        self.foods.get(class).unwrap_or(&self.foods[FALLBACK_CLASS])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portion(class: &str, area_ratio: f64) -> FoodPortion {
+        FoodPortion {
+            class: class.to_string(),
+            confidence: Some(0.9),
+            area_ratio: Some(area_ratio),
+            weight_g: None,
+        }
+    }
+
+    #[test]
+    fn test_embedded_engine_damps_and_caps() {
+        let engine = NutritionEngine::embedded();
+        assert_eq!(
+            engine.area_ratio_of_bbox(&[0.0, 0.0, 400.0, 400.0]),
+            160_000.0 / engine.reference_image_area_px
+        );
+
+        // 0.25 of the plate damps to 0.2: 0.2 × 550 g × 1.1 = 121 g of grain at 1.3 kcal/g.
+        let estimate = engine.estimate(&[portion("grain", 0.25), portion("grain", 0.25)]);
+        assert_eq!(estimate.items[0].estimated_weight_g, 121.0);
+        assert_eq!(estimate.items[0].calories, 157.3);
+        // The second bowl of rice uses the repeat density.
+        assert_eq!(estimate.items[1].estimated_weight_g, 44.0);
+        assert_eq!(estimate.categories["grain"].1, 50.0);
+        assert_eq!(estimate.engine_version, engine.version);
+
+        let estimate = engine.estimate(&[portion("cake", 0.5)]);
+        assert_eq!(estimate.items[0].category, FALLBACK_CLASS);
+        assert_eq!(estimate.items[0].estimated_weight_g, 150.0);
+
+        let manual = FoodPortion {
+            class: "nuts".to_string(),
+            confidence: None,
+            area_ratio: None,
+            weight_g: Some(200.0),
+        };
+        // Weighed portions are not squashed, however large.
+        let estimate = engine.estimate(&[manual]);
+        assert_eq!(estimate.items[0].calories, 1200.0);
+        assert_eq!(round1(estimate.total_calories), 1200.0);
+        assert_eq!(round1(estimate.categories["nuts"].0), 1200.0);
+    }
+
+    #[test]
+    fn test_nutrients_follow_weight_and_squash() {
+        let engine = NutritionEngine::embedded();
+        let grain = &engine.foods["grain"].nutrients_per_100g;
+
+        // 121 g of grain.
+        let estimate = engine.estimate(&[portion("grain", 0.25)]);
+        assert_eq!(estimate.items[0].nutrients, grain.scaled(1.21).rounded());
+        assert_eq!(estimate.nutrients.rounded(), grain.scaled(1.21).rounded());
+
+        let mut engine = engine.clone();
+        engine.meal_squash = MealSquash {
+            threshold_kcal: 500.0,
+            anchor_kcal: 500.0,
+            slope: 0.2,
+        };
+        let weighed = FoodPortion {
+            class: "nuts".to_string(),
+            confidence: None,
+            area_ratio: None,
+            weight_g: Some(200.0),
+        };
+        // 160 g of meat (368 kcal) and 196.6 g of grain (255.6 kcal) from the plate squash to
+        // 500 + 123.6 × 0.2 kcal; the weighed nuts keep their 1200 kcal.
+        let estimate =
+            engine.estimate(&[portion("protein_meat", 0.5), portion("grain", 0.5), weighed]);
+        let area_calories = 160.0 * 2.3 + 0.325 * 550.0 * 1.1 * 1.3;
+        let factor = (500.0 + (area_calories - 500.0) * 0.2) / area_calories;
+        assert_eq!(estimate.items[0].estimated_weight_g, round1(160.0 * factor));
+        assert_eq!(estimate.items[2].calories, 1200.0);
+        assert_eq!(
+            round1(estimate.total_calories),
+            round1(area_calories * factor + 1200.0)
+        );
+
+        let item_calories: f64 = estimate.items.iter().map(|item| item.calories).sum();
+        let category_calories: f64 = estimate.categories.values().map(|(kcal, _)| kcal).sum();
+        assert!((item_calories - estimate.total_calories).abs() < 0.2);
+        assert!((category_calories - estimate.total_calories).abs() < 1e-9);
+        assert_eq!(
+            estimate.items[0].nutrients,
+            engine.foods["protein_meat"]
+                .nutrients_per_100g
+                .scaled(1.6 * factor)
+                .rounded()
+        );
+        assert_eq!(estimate.categories["grain"].1, 50.0);
+    }
+
+    #[test]
+    fn test_engine_without_fallback_class_is_rejected() {
+        let json = EMBEDDED_ENGINE.replace("\"other\"", "\"misc\"");
+        assert!(NutritionEngine::from_json(&json).is_err());
+    }
+}
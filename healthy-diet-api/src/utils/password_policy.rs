@@ -0,0 +1,278 @@
+use sha1::{Digest, Sha1};
+use std::{
+    cmp::Ordering,
+    env, fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+use tracing::error;
+
+use crate::model::ENVKey;
+
+const DEFAULT_MIN_LENGTH: usize = 8;
+const DEFAULT_MAX_LENGTH: usize = 128;
+/// Shorter email local parts (`a@`, `jo@`) are too common to reject passwords over.
+const MIN_EMAIL_PART_LEN: usize = 3;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordPolicyError {
+    TooShort(usize),
+    TooLong(usize),
+    ContainsEmail,
+    Breached,
+}
+
+impl fmt::Display for PasswordPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordPolicyError::TooShort(min) => {
+                write!(f, "Password must be at least {} characters", min)
+            }
+            PasswordPolicyError::TooLong(max) => {
+                write!(f, "Password must be at most {} characters", max)
+            }
+            PasswordPolicyError::ContainsEmail => {
+                write!(f, "Password must not contain your email address")
+            }
+            PasswordPolicyError::Breached => write!(
+                f,
+                "This password has appeared in a data breach; choose a different one"
+            ),
+        }
+    }
+}
+
+/// Known-breached password hashes, looked up on disk so a multi-gigabyte list is never
+/// loaded into memory. The file holds one SHA-1 per line sorted by hash, optionally followed
+/// by `:count`, which is the ordered-by-hash Pwned Passwords download as-is.
+#[derive(Debug)]
+pub struct BreachedPasswords {
+    path: PathBuf,
+}
+
+impl BreachedPasswords {
+    /// Checks that the file can be read; lookups reopen it.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        File::open(&path)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A lookup that fails to read the file logs the error and lets the password through.
+    pub fn contains(&self, password: &str) -> bool {
+        let digest: String = Sha1::digest(password.as_bytes())
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        self.search(&digest).unwrap_or_else(|e| {
+            error!(
+                "Breached password lookup in {} failed: {}",
+                self.path.display(),
+                e
+            );
+            false
+        })
+    }
+
+    /// Binary search over byte offsets: `[low, high)` always holds the start of every line
+    /// that may still match.
+    fn search(&self, digest: &str) -> io::Result<bool> {
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        let mut low = 0;
+        let mut high = reader.get_ref().metadata()?.len();
+        let mut line = Vec::new();
+        while low < high {
+            let mid = low + (high - low) / 2;
+            // Find the first line starting at or after `mid`.
+            let start = if mid == 0 {
+                reader.seek(SeekFrom::Start(0))?;
+                0
+            } else {
+                reader.seek(SeekFrom::Start(mid - 1))?;
+                line.clear();
+                mid - 1 + reader.read_until(b'\n', &mut line)? as u64
+            };
+            if start >= high {
+                high = mid;
+                continue;
+            }
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)? as u64;
+            if read == 0 {
+                high = mid;
+                continue;
+            }
+
+            let hash = String::from_utf8_lossy(&line);
+            let hash = hash.split(':').next().unwrap_or("").trim();
+            match hash.to_ascii_uppercase().as_str().cmp(digest) {
+                Ordering::Equal => return Ok(true),
+                Ordering::Less => low = start + read,
+                Ordering::Greater => high = mid,
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[derive(Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub breached: Option<BreachedPasswords>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: DEFAULT_MIN_LENGTH,
+            max_length: DEFAULT_MAX_LENGTH,
+            breached: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Reads `PASSWORD_MIN_LENGTH`, `PASSWORD_MAX_LENGTH` and `PASSWORD_BREACHED_HASHES_FILE`.
+    pub fn from_env() -> Result<Self, String> {
+        let length = |key: &str, default: usize| -> Result<usize, String> {
+            match env::var(key) {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("{} must be a positive integer", key)),
+                Err(_) => Ok(default),
+            }
+        };
+        let min_length = length(ENVKey::PASSWORD_MIN_LENGTH, DEFAULT_MIN_LENGTH)?;
+        let max_length = length(ENVKey::PASSWORD_MAX_LENGTH, DEFAULT_MAX_LENGTH)?;
+        if min_length == 0 || max_length < min_length {
+            return Err(format!(
+                "{} must be at least 1 and not above {}",
+                ENVKey::PASSWORD_MIN_LENGTH,
+                ENVKey::PASSWORD_MAX_LENGTH
+            ));
+        }
+
+        let breached = match env::var(ENVKey::PASSWORD_BREACHED_HASHES_FILE) {
+            Ok(path) if !path.trim().is_empty() => Some(
+                BreachedPasswords::open(path.trim())
+                    .map_err(|e| format!("Cannot read {}: {}", path, e))?,
+            ),
+            _ => None,
+        };
+
+        Ok(Self {
+            min_length,
+            max_length,
+            breached,
+        })
+    }
+
+    pub fn check(&self, password: &str, email: &str) -> Result<(), PasswordPolicyError> {
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(PasswordPolicyError::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            return Err(PasswordPolicyError::TooLong(self.max_length));
+        }
+
+        let lowered = password.to_lowercase();
+        let email = email.trim().to_lowercase();
+        let local_part = email.split('@').next().unwrap_or("");
+        if local_part.chars().count() >= MIN_EMAIL_PART_LEN && lowered.contains(local_part) {
+            return Err(PasswordPolicyError::ContainsEmail);
+        }
+
+        if self
+            .breached
+            .as_ref()
+            .is_some_and(|breached| breached.contains(password))
+        {
+            return Err(PasswordPolicyError::Breached);
+        }
+        Ok(())
+    }
+}
+
+static PASSWORD_POLICY: OnceLock<PasswordPolicy> = OnceLock::new();
+
+/// Process-wide policy loaded from the environment on first use.
+pub fn password_policy() -> &'static PasswordPolicy {
+    PASSWORD_POLICY.get_or_init(|| {
+        PasswordPolicy::from_env()
+            .unwrap_or_else(|e| panic!("Invalid password policy configuration: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breached_file(contents: &str) -> BreachedPasswords {
+        let path = env::temp_dir().join(format!("breached_{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        BreachedPasswords::open(path).unwrap()
+    }
+
+    #[test]
+    fn test_policy_rejects_short_email_and_breached_passwords() {
+        let policy = PasswordPolicy {
+            breached: Some(breached_file(
+                // SHA-1 of "password123", in the `HASH:count` download format.
+                "CBFDAC6008F9CAB4083784CBD1874F76618D2A97:2500000\n",
+            )),
+            ..PasswordPolicy::default()
+        };
+
+        assert_eq!(
+            policy.check("short", "user@example.com"),
+            Err(PasswordPolicyError::TooShort(DEFAULT_MIN_LENGTH))
+        );
+        assert_eq!(
+            policy.check("my-Alice-secret", "alice@example.com"),
+            Err(PasswordPolicyError::ContainsEmail)
+        );
+        assert_eq!(
+            policy.check("password123", "user@example.com"),
+            Err(PasswordPolicyError::Breached)
+        );
+        assert_eq!(
+            policy.check("a perfectly fine passphrase", "user@example.com"),
+            Ok(())
+        );
+        let _ = std::fs::remove_file(policy.breached.unwrap().path());
+    }
+
+    #[test]
+    fn test_breached_lookup_searches_sorted_file() {
+        // SHA-1 of "123456", "password", "password123" and "qwerty", sorted, with a CRLF line
+        // and a lowercase hash.
+        let breached = breached_file(concat!(
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004\r\n",
+            "7C4A8D09CA3762AF61E59520943DC26494F8941B:37359195\n",
+            "b1b3773a05c0ed0176787a4f1574ff0075f7521e:4000000\n",
+            "CBFDAC6008F9CAB4083784CBD1874F76618D2A97:2500000",
+        ));
+
+        for password in ["password", "123456", "qwerty", "password123"] {
+            assert!(breached.contains(password), "{} should be found", password);
+        }
+        for password in ["", "a perfectly fine passphrase", "zzzzzzzz", "Password"] {
+            assert!(
+                !breached.contains(password),
+                "{} should not be found",
+                password
+            );
+        }
+        assert!(!breached_file("").contains("password"));
+        let _ = std::fs::remove_file(breached.path());
+    }
+}
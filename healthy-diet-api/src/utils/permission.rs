@@ -0,0 +1,517 @@
+use axum::{
+    Json,
+    http::{Method, StatusCode},
+};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tracing::{error, warn};
+
+use crate::api::model::{ErrorResponse, ROLE_OPERATOR, ROLE_SUPER_ADMIN, ROLE_USER};
+
+pub const ADMIN_ACCESS: &str = "admin.access";
+pub const USERS_READ: &str = "users.read";
+pub const USERS_WRITE: &str = "users.write";
+/// Acting on accounts whose role itself grants `admin.access`.
+pub const USERS_MANAGE_ADMINS: &str = "users.manage_admins";
+/// Assigning roles to users and editing the permission sets of roles.
+pub const ROLES_MANAGE: &str = "roles.manage";
+pub const ROUTES_READ: &str = "routes.read";
+pub const ROUTES_WRITE: &str = "routes.write";
+pub const ANNOUNCEMENTS_READ: &str = "announcements.read";
+pub const ANNOUNCEMENTS_WRITE: &str = "announcements.write";
+pub const RAG_READ: &str = "rag.read";
+pub const RAG_WRITE: &str = "rag.write";
+pub const RAG_DELETE: &str = "rag.delete";
+pub const KG_READ: &str = "kg.read";
+pub const KG_WRITE: &str = "kg.write";
+pub const API_TOKENS_MANAGE: &str = "api_tokens.manage";
+pub const AGENT_TOKENS_CREATE: &str = "agent_tokens.create";
+/// Assigning clients to nutritionists.
+pub const CLIENTS_ASSIGN: &str = "clients.assign";
+/// The nutritionist workspace; only ever covers the caller's own assigned clients.
+pub const CLIENTS_WORKSPACE: &str = "clients.workspace";
+/// Importing food composition tables.
+pub const FOODS_WRITE: &str = "foods.write";
+/// Downloading users' meal photos as a training dataset.
+pub const DATASETS_EXPORT: &str = "datasets.export";
+
+pub const PERMISSIONS: [&str; 20] = [
+    ADMIN_ACCESS,
+    USERS_READ,
+    USERS_WRITE,
+    USERS_MANAGE_ADMINS,
+    ROLES_MANAGE,
+    ROUTES_READ,
+    ROUTES_WRITE,
+    ANNOUNCEMENTS_READ,
+    ANNOUNCEMENTS_WRITE,
+    RAG_READ,
+    RAG_WRITE,
+    RAG_DELETE,
+    KG_READ,
+    KG_WRITE,
+    API_TOKENS_MANAGE,
+    AGENT_TOKENS_CREATE,
+    CLIENTS_ASSIGN,
+    CLIENTS_WORKSPACE,
+    FOODS_WRITE,
+    DATASETS_EXPORT,
+];
+
+/// Permission each admin route needs, keyed by method and matched path. Every route behind
+/// `require_admin_middleware` must be listed; unlisted ones are refused.
+const ROUTE_PERMISSIONS: &[(Method, &str, &str)] = &[
+    (Method::GET, "/admin/me", ADMIN_ACCESS),
+    (Method::POST, "/admin/agent-token", AGENT_TOKENS_CREATE),
+    (Method::GET, "/admin/api-tokens", API_TOKENS_MANAGE),
+    (Method::POST, "/admin/api-tokens", API_TOKENS_MANAGE),
+    (
+        Method::DELETE,
+        "/admin/api-tokens/{token_id}",
+        API_TOKENS_MANAGE,
+    ),
+    (Method::GET, "/admin/roles", ROLES_MANAGE),
+    (Method::PUT, "/admin/roles/{role}", ROLES_MANAGE),
+    (Method::GET, "/admin/users", USERS_READ),
+    (Method::GET, "/admin/users/{user_id}", USERS_READ),
+    (Method::POST, "/admin/users/{user_id}/unlock", USERS_WRITE),
+    (Method::PATCH, "/admin/users/{user_id}/role", ROLES_MANAGE),
+    (Method::POST, "/admin/users/{user_id}/suspend", USERS_WRITE),
+    (
+        Method::POST,
+        "/admin/users/{user_id}/unsuspend",
+        USERS_WRITE,
+    ),
+    (
+        Method::POST,
+        "/admin/users/{user_id}/password-reset",
+        USERS_WRITE,
+    ),
+    (
+        Method::GET,
+        "/admin/nutritionists/{nutritionist_id}/clients",
+        CLIENTS_ASSIGN,
+    ),
+    (
+        Method::POST,
+        "/admin/nutritionists/{nutritionist_id}/clients",
+        CLIENTS_ASSIGN,
+    ),
+    (
+        Method::DELETE,
+        "/admin/nutritionists/{nutritionist_id}/clients/{user_id}",
+        CLIENTS_ASSIGN,
+    ),
+    (Method::GET, "/admin/workspace/clients", CLIENTS_WORKSPACE),
+    (
+        Method::GET,
+        "/admin/workspace/clients/{user_id}/diet-records",
+        CLIENTS_WORKSPACE,
+    ),
+    (
+        Method::POST,
+        "/admin/workspace/clients/{user_id}/diet-records/{record_id}/annotations",
+        CLIENTS_WORKSPACE,
+    ),
+    (
+        Method::POST,
+        "/admin/workspace/clients/{user_id}/diet-records/{record_id}/annotations/read",
+        CLIENTS_WORKSPACE,
+    ),
+    (
+        Method::GET,
+        "/admin/workspace/clients/{user_id}/chat-rooms",
+        CLIENTS_WORKSPACE,
+    ),
+    (
+        Method::GET,
+        "/admin/workspace/clients/{user_id}/chat-rooms/{room_id}",
+        CLIENTS_WORKSPACE,
+    ),
+    (Method::POST, "/admin/foods/import", FOODS_WRITE),
+    (Method::GET, "/admin/datasets/yolo", DATASETS_EXPORT),
+    (Method::GET, "/admin/route-controls", ROUTES_READ),
+    (
+        Method::PATCH,
+        "/admin/route-controls/{route_key}",
+        ROUTES_WRITE,
+    ),
+    (Method::GET, "/admin/announcements", ANNOUNCEMENTS_READ),
+    (Method::POST, "/admin/announcements", ANNOUNCEMENTS_WRITE),
+    (
+        Method::PATCH,
+        "/admin/announcements/{id}",
+        ANNOUNCEMENTS_WRITE,
+    ),
+    (
+        Method::POST,
+        "/admin/announcements/{id}/publish",
+        ANNOUNCEMENTS_WRITE,
+    ),
+    (
+        Method::POST,
+        "/admin/announcements/{id}/archive",
+        ANNOUNCEMENTS_WRITE,
+    ),
+    (Method::GET, "/admin/rag/documents", RAG_READ),
+    (Method::POST, "/admin/rag/documents", RAG_WRITE),
+    (Method::GET, "/admin/rag/documents/{document_id}", RAG_READ),
+    (
+        Method::DELETE,
+        "/admin/rag/documents/{document_id}",
+        RAG_DELETE,
+    ),
+    (
+        Method::POST,
+        "/admin/rag/documents/{document_id}/reindex",
+        RAG_WRITE,
+    ),
+    (
+        Method::GET,
+        "/admin/rag/documents/{document_id}/file",
+        RAG_READ,
+    ),
+    (
+        Method::GET,
+        "/admin/rag/documents/{document_id}/preview",
+        RAG_READ,
+    ),
+    (Method::POST, "/api/admin/knowledge-graph/rebuild", KG_WRITE),
+    (
+        Method::GET,
+        "/api/admin/knowledge-graph/documents/{document_id}",
+        KG_READ,
+    ),
+    (
+        Method::POST,
+        "/api/admin/knowledge-graph/documents/{document_id}/extract",
+        KG_WRITE,
+    ),
+];
+
+pub fn required_permission(method: &Method, matched_path: &str) -> Option<&'static str> {
+    ROUTE_PERMISSIONS
+        .iter()
+        .find(|(route_method, path, _)| route_method == method && *path == matched_path)
+        .map(|(_, _, permission)| *permission)
+}
+
+pub fn is_known_permission(permission: &str) -> bool {
+    PERMISSIONS.contains(&permission)
+}
+
+/// A role and what it grants. `agent_role` is the role name forwarded to the downstream
+/// agent service, which has its own vocabulary; without it the role name is sent as is.
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleDefinition {
+    pub name: String,
+    pub description: Option<String>,
+    pub agent_role: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+impl RoleDefinition {
+    pub fn has(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+}
+
+/// The built-in roles, used when the `roles` table has not been seeded yet and written to it
+/// before the first role edit. They match `docs/supabase_permissions_setup.sql`.
+pub fn builtin_roles() -> Vec<RoleDefinition> {
+    let all: Vec<String> = PERMISSIONS.iter().map(|p| p.to_string()).collect();
+    let operator = all
+        .iter()
+        .filter(|p| {
+            ![
+                USERS_MANAGE_ADMINS,
+                ROLES_MANAGE,
+                API_TOKENS_MANAGE,
+                AGENT_TOKENS_CREATE,
+                CLIENTS_ASSIGN,
+                DATASETS_EXPORT,
+            ]
+            .contains(&p.as_str())
+        })
+        .cloned()
+        .collect();
+
+    vec![
+        RoleDefinition {
+            name: ROLE_SUPER_ADMIN.to_string(),
+            description: Some("Full access, including roles and API tokens".to_string()),
+            agent_role: Some("admin".to_string()),
+            permissions: all,
+        },
+        RoleDefinition {
+            name: ROLE_OPERATOR.to_string(),
+            description: Some("Day-to-day administration".to_string()),
+            agent_role: Some("nutritionist".to_string()),
+            permissions: operator,
+        },
+        RoleDefinition {
+            name: ROLE_USER.to_string(),
+            description: Some("Regular app user".to_string()),
+            agent_role: None,
+            permissions: Vec::new(),
+        },
+    ]
+}
+
+/// Other instances pick up role edits within this long.
+const ROLE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+type RoleTable = Arc<HashMap<String, RoleDefinition>>;
+
+static ROLE_CACHE: RwLock<Option<(Instant, RoleTable)>> = RwLock::new(None);
+
+pub async fn list_roles(db: &PgPool) -> Result<Vec<RoleDefinition>, sqlx::Error> {
+    sqlx::query_as::<_, RoleDefinition>(
+        r#"
+        SELECT r.name, r.description, r.agent_role,
+               COALESCE(
+                   array_agg(p.permission ORDER BY p.permission)
+                       FILTER (WHERE p.permission IS NOT NULL),
+                   '{}'
+               ) AS permissions
+        FROM roles r
+        LEFT JOIN role_permissions p ON p.role = r.name
+        GROUP BY r.name, r.description, r.agent_role
+        ORDER BY r.name
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+async fn role_table(db: &PgPool) -> Result<RoleTable, sqlx::Error> {
+    if let Some((loaded_at, table)) = ROLE_CACHE.read().unwrap().as_ref()
+        && loaded_at.elapsed() < ROLE_CACHE_TTL
+    {
+        return Ok(table.clone());
+    }
+
+    let mut roles = list_roles(db).await?;
+    if roles.is_empty() {
+        warn!("roles table is empty; using the built-in role permissions");
+        roles = builtin_roles();
+    }
+    let table: RoleTable = Arc::new(
+        roles
+            .into_iter()
+            .map(|role| (role.name.clone(), role))
+            .collect(),
+    );
+    *ROLE_CACHE.write().unwrap() = Some((Instant::now(), table.clone()));
+    Ok(table)
+}
+
+/// Drops the cached role table so this instance sees an edit immediately.
+pub fn invalidate_role_cache() {
+    *ROLE_CACHE.write().unwrap() = None;
+}
+
+pub async fn role_definition(
+    db: &PgPool,
+    role: &str,
+) -> Result<Option<RoleDefinition>, sqlx::Error> {
+    Ok(role_table(db).await?.get(role).cloned())
+}
+
+/// Unknown roles grant nothing.
+pub async fn has_permission(
+    db: &PgPool,
+    role: &str,
+    permission: &str,
+) -> Result<bool, sqlx::Error> {
+    Ok(role_table(db)
+        .await?
+        .get(role)
+        .is_some_and(|definition| definition.has(permission)))
+}
+
+/// Handler-side check with the handlers' error shape, for decisions the route table cannot
+/// make on its own (e.g. re-checking a role read from the database).
+pub async fn ensure_permission(
+    db: &PgPool,
+    role: &str,
+    permission: &str,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    match has_permission(db, role, permission).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: format!("Missing permission {}", permission),
+            }),
+        )),
+        Err(e) => {
+            error!("DB Error (Permission Check): {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Internal server error".to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Whether the role may use the admin console. Such accounts also need a second factor and
+/// get the stricter login lockout policy.
+pub async fn is_admin(db: &PgPool, role: &str) -> Result<bool, sqlx::Error> {
+    has_permission(db, role, ADMIN_ACCESS).await
+}
+
+/// Role name to forward to the agent service for a local role.
+pub async fn agent_role(db: &PgPool, role: &str) -> Result<String, sqlx::Error> {
+    Ok(role_table(db)
+        .await?
+        .get(role)
+        .and_then(|definition| definition.agent_role.clone())
+        .unwrap_or_else(|| role.to_string()))
+}
+
+/// Writes the built-in roles into an empty `roles` table. Once the table has rows only they
+/// count, so without this the first edit would strip super_admin and operator of everything.
+async fn seed_builtin_roles(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    // Serialises concurrent first edits so the emptiness check stays true until we insert.
+    sqlx::query("LOCK TABLE roles IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *conn)
+        .await?;
+    let seeded: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM roles)")
+        .fetch_one(&mut *conn)
+        .await?;
+    if seeded {
+        return Ok(());
+    }
+
+    warn!("Seeding the built-in roles before the first role edit");
+    for role in builtin_roles() {
+        sqlx::query("INSERT INTO roles (name, description, agent_role) VALUES ($1, $2, $3)")
+            .bind(&role.name)
+            .bind(&role.description)
+            .bind(&role.agent_role)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(
+            "INSERT INTO role_permissions (role, permission) SELECT $1, unnest($2::text[])",
+        )
+        .bind(&role.name)
+        .bind(&role.permissions)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Creates or replaces a role and its permission set.
+pub async fn save_role(
+    db: &PgPool,
+    name: &str,
+    description: Option<&str>,
+    agent_role: Option<&str>,
+    permissions: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    seed_builtin_roles(&mut tx).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO roles (name, description, agent_role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (name) DO UPDATE
+        SET description = EXCLUDED.description,
+            agent_role = EXCLUDED.agent_role,
+            updated_at = now()
+        "#,
+    )
+    .bind(name)
+    .bind(description)
+    .bind(agent_role)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM role_permissions WHERE role = $1")
+        .bind(name)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("INSERT INTO role_permissions (role, permission) SELECT $1, unnest($2::text[])")
+        .bind(name)
+        .bind(permissions)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    invalidate_role_cache();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_permissions_and_builtin_roles() {
+        for (_, _, permission) in ROUTE_PERMISSIONS {
+            assert!(
+                is_known_permission(permission),
+                "unknown permission {}",
+                permission
+            );
+        }
+        assert_eq!(
+            required_permission(&Method::DELETE, "/admin/rag/documents/{document_id}"),
+            Some(RAG_DELETE)
+        );
+        assert_eq!(required_permission(&Method::GET, "/api/profile"), None);
+
+        let roles = builtin_roles();
+        let role = |name: &str| roles.iter().find(|role| role.name == name).unwrap();
+        assert!(role(ROLE_SUPER_ADMIN).has(ROLES_MANAGE));
+        assert!(role(ROLE_OPERATOR).has(RAG_DELETE));
+        assert!(!role(ROLE_OPERATOR).has(API_TOKENS_MANAGE));
+        assert!(role(ROLE_OPERATOR).has(CLIENTS_WORKSPACE));
+        assert!(!role(ROLE_OPERATOR).has(CLIENTS_ASSIGN));
+        assert!(role(ROLE_OPERATOR).has(FOODS_WRITE));
+        assert!(!role(ROLE_OPERATOR).has(DATASETS_EXPORT));
+        assert!(role(ROLE_SUPER_ADMIN).has(DATASETS_EXPORT));
+        assert!(role(ROLE_USER).permissions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_saving_custom_role_keeps_builtin_roles() {
+        let state = crate::utils::test::setup_db().await;
+        let name = format!("test_{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+
+        save_role(&state.db, &name, None, None, &[FOODS_WRITE.to_string()])
+            .await
+            .unwrap();
+
+        assert!(
+            has_permission(&state.db, ROLE_SUPER_ADMIN, ADMIN_ACCESS)
+                .await
+                .unwrap()
+        );
+        assert!(
+            has_permission(&state.db, ROLE_OPERATOR, ADMIN_ACCESS)
+                .await
+                .unwrap()
+        );
+        assert!(has_permission(&state.db, &name, FOODS_WRITE).await.unwrap());
+
+        sqlx::query("DELETE FROM roles WHERE name = $1")
+            .bind(&name)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        invalidate_role_cache();
+    }
+}
@@ -0,0 +1,186 @@
+use crate::utils::{
+    client_info::ClientInfo,
+    jwt::{REFRESH_TOKEN_TTL_SECS, RefreshTokenIds, sign_jwt_with_refresh_ids},
+    session::{create_session, touch_session},
+};
+use sqlx::{FromRow, PgConnection, PgPool};
+use tracing::warn;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub enum RefreshTokenError {
+    NotFound,
+    Revoked,
+    Reused,
+    Expired,
+    Jwt(jsonwebtoken::errors::Error),
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for RefreshTokenError {
+    fn from(e: sqlx::Error) -> Self {
+        RefreshTokenError::Database(e)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for RefreshTokenError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        RefreshTokenError::Jwt(e)
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct StoredRefreshToken {
+    user_id: Uuid,
+    family_id: Uuid,
+    device_id: Option<String>,
+    revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    replaced_by: Option<Uuid>,
+    is_expired: bool,
+    session_revoked: bool,
+}
+
+pub async fn store_refresh_token(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    refresh_ids: &RefreshTokenIds,
+    device_id: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (jti, user_id, family_id, device_id, expires_at, created_at)
+        VALUES ($1, $2, $3, $4, now() + make_interval(secs => $5::double precision), now())
+        "#,
+    )
+    .bind(refresh_ids.jti)
+    .bind(user_id)
+    .bind(refresh_ids.family_id)
+    .bind(device_id)
+    .bind(REFRESH_TOKEN_TTL_SECS as f64)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Opens a new session and signs a token pair for its refresh family (login, register,
+/// agent tokens).
+pub async fn issue_token_pair(
+    db: &PgPool,
+    user_id: Uuid,
+    email: &str,
+    role: &str,
+    access_expires_in: usize,
+    client: &ClientInfo,
+) -> Result<(String, String, usize), RefreshTokenError> {
+    let refresh_ids = RefreshTokenIds {
+        jti: Uuid::new_v4(),
+        family_id: Uuid::new_v4(),
+    };
+
+    let tokens = sign_jwt_with_refresh_ids(
+        &user_id.to_string(),
+        email,
+        role,
+        access_expires_in,
+        &refresh_ids,
+    )?;
+
+    let mut tx = db.begin().await?;
+    create_session(&mut tx, refresh_ids.family_id, user_id, client).await?;
+    store_refresh_token(&mut tx, user_id, &refresh_ids, client.device_id.as_deref()).await?;
+    tx.commit().await?;
+
+    Ok(tokens)
+}
+
+/// Marks `presented_jti` as used and records a fresh successor in the same family.
+///
+/// Presenting a refresh token that was already rotated is treated as token theft: the whole
+/// family is revoked so neither the attacker nor the legitimate device can keep refreshing.
+pub async fn rotate_refresh_token(
+    db: &PgPool,
+    presented_jti: Uuid,
+    user_id: Uuid,
+    client: &ClientInfo,
+) -> Result<RefreshTokenIds, RefreshTokenError> {
+    let mut tx = db.begin().await?;
+
+    let stored = sqlx::query_as::<_, StoredRefreshToken>(
+        r#"
+        SELECT
+            t.user_id, t.family_id, t.device_id, t.revoked_at, t.replaced_by,
+            t.expires_at <= now() AS is_expired,
+            COALESCE(s.revoked_at IS NOT NULL, FALSE) AS session_revoked
+        FROM refresh_tokens t
+        LEFT JOIN user_sessions s ON s.id = t.family_id
+        WHERE t.jti = $1
+        FOR UPDATE OF t
+        "#,
+    )
+    .bind(presented_jti)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(RefreshTokenError::NotFound)?;
+
+    if stored.user_id != user_id {
+        return Err(RefreshTokenError::NotFound);
+    }
+
+    if stored.replaced_by.is_some() {
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = COALESCE(revoked_at, now())
+            WHERE family_id = $1
+            "#,
+        )
+        .bind(stored.family_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE user_sessions SET revoked_at = COALESCE(revoked_at, now()) WHERE id = $1",
+        )
+        .bind(stored.family_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        warn!(
+            "Refresh token reuse detected for user {}, family {} revoked",
+            user_id, stored.family_id
+        );
+        return Err(RefreshTokenError::Reused);
+    }
+
+    if stored.revoked_at.is_some() || stored.session_revoked {
+        return Err(RefreshTokenError::Revoked);
+    }
+
+    if stored.is_expired {
+        return Err(RefreshTokenError::Expired);
+    }
+
+    let next_ids = RefreshTokenIds {
+        jti: Uuid::new_v4(),
+        family_id: stored.family_id,
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = now(), replaced_by = $1
+        WHERE jti = $2
+        "#,
+    )
+    .bind(next_ids.jti)
+    .bind(presented_jti)
+    .execute(&mut *tx)
+    .await?;
+
+    store_refresh_token(&mut tx, user_id, &next_ids, stored.device_id.as_deref()).await?;
+    touch_session(&mut tx, next_ids.family_id, client).await?;
+
+    tx.commit().await?;
+
+    Ok(next_ids)
+}
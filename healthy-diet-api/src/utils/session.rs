@@ -0,0 +1,225 @@
+use crate::utils::client_info::ClientInfo;
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+/// A login session. Its id is shared with the refresh token family it owns and is carried
+/// in access tokens as the `sid` claim.
+#[derive(Debug, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionItem {
+    pub id: Uuid,
+    pub device_id: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: chrono::DateTime<chrono::Utc>,
+    #[sqlx(default)]
+    pub current: bool,
+}
+
+pub async fn create_session(
+    conn: &mut PgConnection,
+    session_id: Uuid,
+    user_id: Uuid,
+    client: &ClientInfo,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_sessions (id, user_id, device_id, user_agent, ip_address, created_at, last_used_at)
+        VALUES ($1, $2, $3, $4, $5, now(), now())
+        "#,
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .bind(&client.device_id)
+    .bind(&client.user_agent)
+    .bind(&client.ip_address)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn touch_session(
+    conn: &mut PgConnection,
+    session_id: Uuid,
+    client: &ClientInfo,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE user_sessions
+        SET last_used_at = now(),
+            user_agent = COALESCE($1, user_agent),
+            ip_address = COALESCE($2, ip_address)
+        WHERE id = $3
+        "#,
+    )
+    .bind(&client.user_agent)
+    .bind(&client.ip_address)
+    .bind(session_id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Outcome of checking whether an access token's subject may still act.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountAccess {
+    Active,
+    SessionRevoked,
+    Suspended,
+    /// The user row is gone, e.g. after account deletion.
+    Missing,
+}
+
+/// Checks the user's suspension state and, when the token carries a session, that the
+/// session has not been revoked. Runs as one query on every authenticated request.
+pub async fn check_account_access(
+    db: &PgPool,
+    user_id: Uuid,
+    session_id: Option<Uuid>,
+) -> Result<AccountAccess, sqlx::Error> {
+    let row = sqlx::query_as::<_, (bool, bool)>(
+        r#"
+        SELECT
+            u.suspended_at IS NOT NULL,
+            $2::uuid IS NULL OR EXISTS (
+                SELECT 1 FROM user_sessions s
+                WHERE s.id = $2 AND s.user_id = u.id AND s.revoked_at IS NULL
+            )
+        FROM users u
+        WHERE u.id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(session_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(match row {
+        None => AccountAccess::Missing,
+        Some((true, _)) => AccountAccess::Suspended,
+        Some((false, false)) => AccountAccess::SessionRevoked,
+        Some((false, true)) => AccountAccess::Active,
+    })
+}
+
+pub async fn list_active_sessions(
+    db: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<SessionItem>, sqlx::Error> {
+    sqlx::query_as::<_, SessionItem>(
+        r#"
+        SELECT s.id, s.device_id, s.user_agent, s.ip_address, s.created_at, s.last_used_at
+        FROM user_sessions s
+        WHERE s.user_id = $1
+          AND s.revoked_at IS NULL
+          AND EXISTS (
+              SELECT 1 FROM refresh_tokens t
+              WHERE t.family_id = s.id AND t.revoked_at IS NULL AND t.expires_at > now()
+          )
+        ORDER BY s.last_used_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
+
+/// Revokes one session and every refresh token in its family. Returns `false` when the
+/// session does not belong to `user_id` or was already revoked.
+pub async fn revoke_session(
+    db: &PgPool,
+    session_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let revoked = sqlx::query(
+        r#"
+        UPDATE user_sessions
+        SET revoked_at = now()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = now()
+        WHERE family_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(revoked > 0)
+}
+
+pub async fn revoke_all_sessions(db: &PgPool, user_id: Uuid) -> Result<u64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let revoked = sqlx::query(
+        "UPDATE user_sessions SET revoked_at = now() WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = now() WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(revoked)
+}
+
+/// Revokes every session of the user except `keep` (the caller's own), with their refresh
+/// tokens. Without `keep` all sessions are revoked.
+pub async fn revoke_other_sessions(
+    db: &PgPool,
+    user_id: Uuid,
+    keep: Option<Uuid>,
+) -> Result<u64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let revoked = sqlx::query(
+        r#"
+        UPDATE user_sessions
+        SET revoked_at = now()
+        WHERE user_id = $1 AND revoked_at IS NULL AND id IS DISTINCT FROM $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(keep)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = now()
+        WHERE user_id = $1 AND revoked_at IS NULL AND family_id IS DISTINCT FROM $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(keep)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(revoked)
+}
@@ -9,6 +9,13 @@ use uuid::Uuid;
 use crate::{
     api::{model::RegisterPayload, register::register_handler},
     model::{AppState, ENVKey},
+    oauth::provider::IdentityProviders,
+    utils::{
+        client_info::ClientInfo,
+        food_detector::{DEFAULT_YOLO_SCRIPT, SubprocessDetector},
+        mailer::OutboxMailer,
+        nutrition_engine::NutritionEngine,
+    },
 };
 
 pub async fn setup_db() -> Arc<AppState> {
@@ -23,6 +30,13 @@ pub async fn setup_db() -> Arc<AppState> {
     Arc::new(AppState {
         db: pool,
         ai_prompt_config,
+        identity_providers: Arc::new(IdentityProviders::default()),
+        mailer: Arc::new(OutboxMailer::new(
+            env::temp_dir().join("healthy_diet_test_outbox"),
+            "Healthy Diet Test <no-reply@localhost>".to_string(),
+        )),
+        food_detector: Arc::new(SubprocessDetector::new("python3", DEFAULT_YOLO_SCRIPT)),
+        nutrition_engine: Arc::new(NutritionEngine::embedded()),
     })
 }
 
@@ -37,9 +51,13 @@ pub async fn register_test_account(state: Arc<AppState>, email_name: String) ->
         nickname: None,
         avatar_url: None,
     };
-    let _ = register_handler(State(state.clone()), Json(reg_payload))
-        .await
-        .expect("register fail");
+    let _ = register_handler(
+        State(state.clone()),
+        ClientInfo::default(),
+        Json(reg_payload),
+    )
+    .await
+    .expect("register fail");
 
     info!(
         "register account success! email: {}, password: {}",
@@ -0,0 +1,309 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use crate::utils::{
+    hash::{hash_password, verify_password},
+    jwt::{decode_jwt, sign_single_use_token},
+};
+
+pub const TOTP_ISSUER: &str = "Healthy Diet";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECS: u64 = 30;
+/// Accept the previous and next code too, for clock drift.
+const TOTP_SKEW_STEPS: i64 = 1;
+const TOTP_SECRET_BYTES: usize = 20;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+pub const CHALLENGE_TOKEN_TYPE: &str = "2fa_challenge";
+pub const CHALLENGE_TTL_SECS: usize = 300;
+
+#[derive(Debug)]
+pub enum TwoFactorError {
+    Database(sqlx::Error),
+    Hash(String),
+    Totp(String),
+}
+
+impl From<sqlx::Error> for TwoFactorError {
+    fn from(e: sqlx::Error) -> Self {
+        TwoFactorError::Database(e)
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+/// New random secret, base32 encoded as authenticator apps expect.
+pub fn generate_totp_secret() -> String {
+    let mut bytes = [0u8; TOTP_SECRET_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+}
+
+fn build_totp(secret: &str, account: &str) -> Result<TOTP, TwoFactorError> {
+    let bytes = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| TwoFactorError::Totp(format!("{:?}", e)))?;
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        TOTP_SKEW_STEPS as u8,
+        TOTP_STEP_SECS,
+        bytes,
+        Some(TOTP_ISSUER.to_string()),
+        account.to_string(),
+    )
+    .map_err(|e| TwoFactorError::Totp(format!("{:?}", e)))
+}
+
+pub fn otpauth_uri(secret: &str, account: &str) -> Result<String, TwoFactorError> {
+    Ok(build_totp(secret, account)?.get_url())
+}
+
+/// Time step the code belongs to, if it is valid at `now`.
+fn matching_step(totp: &TOTP, code: &str, now: u64) -> Option<i64> {
+    let code = code.trim();
+    let current = (now / TOTP_STEP_SECS) as i64;
+    (current - TOTP_SKEW_STEPS..=current + TOTP_SKEW_STEPS)
+        .filter(|step| *step >= 0)
+        .find(|step| totp.generate(*step as u64 * TOTP_STEP_SECS) == code)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+pub async fn get_enrollment(
+    db: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<TotpEnrollment>, sqlx::Error> {
+    sqlx::query_as::<_, TotpEnrollment>(
+        "SELECT secret, confirmed_at FROM user_totp WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn is_two_factor_enabled(db: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    Ok(get_enrollment(db, user_id)
+        .await?
+        .is_some_and(|enrollment| enrollment.confirmed_at.is_some()))
+}
+
+/// Stores a fresh unconfirmed secret. Returns `false` when TOTP is already confirmed.
+pub async fn start_enrollment(
+    db: &PgPool,
+    user_id: Uuid,
+    secret: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO user_totp (user_id, secret, created_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = now()
+            WHERE user_totp.confirmed_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(secret)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Checks a code against the stored secret. Each time step is accepted once, so a code seen
+/// over someone's shoulder cannot be replayed. `confirming` also marks the enrollment active.
+pub async fn accept_totp_code(
+    db: &PgPool,
+    user_id: Uuid,
+    account: &str,
+    code: &str,
+    confirming: bool,
+) -> Result<bool, TwoFactorError> {
+    let Some(enrollment) = get_enrollment(db, user_id).await? else {
+        return Ok(false);
+    };
+    if enrollment.confirmed_at.is_some() == confirming {
+        return Ok(false);
+    }
+
+    let totp = build_totp(&enrollment.secret, account)?;
+    let Some(step) = matching_step(&totp, code, unix_now()) else {
+        return Ok(false);
+    };
+
+    let accepted = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        UPDATE user_totp
+        SET last_used_step = $2,
+            confirmed_at = CASE WHEN $3 THEN now() ELSE confirmed_at END
+        WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+        RETURNING user_id
+        "#,
+    )
+    .bind(user_id)
+    .bind(step)
+    .bind(confirming)
+    .fetch_optional(db)
+    .await?;
+    Ok(accepted.is_some())
+}
+
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            OsRng.fill_bytes(&mut bytes);
+            let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("{}-{}", &hex[..5], &hex[5..])
+        })
+        .collect()
+}
+
+/// Replaces all recovery codes of the user with `codes`, stored hashed.
+pub async fn replace_recovery_codes(
+    db: &PgPool,
+    user_id: Uuid,
+    codes: &[String],
+) -> Result<(), TwoFactorError> {
+    let hashes = codes
+        .iter()
+        .map(|code| hash_password(code))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(TwoFactorError::Hash)?;
+
+    let mut tx = db.begin().await?;
+    sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    for hash in hashes {
+        sqlx::query(
+            r#"
+            INSERT INTO user_recovery_codes (id, user_id, code_hash, created_at)
+            VALUES ($1, $2, $3, now())
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(hash)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct RecoveryCodeRow {
+    id: Uuid,
+    code_hash: String,
+}
+
+/// Consumes a matching unused recovery code.
+pub async fn use_recovery_code(
+    db: &PgPool,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    let code = code.trim().to_lowercase();
+    let rows = sqlx::query_as::<_, RecoveryCodeRow>(
+        "SELECT id, code_hash FROM user_recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    let Some(row) = rows
+        .iter()
+        .find(|row| verify_password(&code, &row.code_hash).unwrap_or(false))
+    else {
+        return Ok(false);
+    };
+
+    let used = sqlx::query(
+        "UPDATE user_recovery_codes SET used_at = now() WHERE id = $1 AND used_at IS NULL",
+    )
+    .bind(row.id)
+    .execute(db)
+    .await?;
+    Ok(used.rows_affected() > 0)
+}
+
+/// Short-lived token proving the password step passed; exchanged for real tokens once the
+/// second factor is verified. It is not tracked server-side: on its own it grants nothing, and
+/// guessing codes with it is rate limited by the `two_factor` lockout scope.
+pub fn issue_challenge_token(
+    user_id: Uuid,
+    email: &str,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    sign_single_use_token(
+        &user_id.to_string(),
+        email,
+        CHALLENGE_TOKEN_TYPE,
+        Uuid::new_v4(),
+        CHALLENGE_TTL_SECS,
+    )
+}
+
+pub fn decode_challenge_token(token: &str) -> Option<Uuid> {
+    let claims = decode_jwt(token).ok()?;
+    if claims.token_type != CHALLENGE_TOKEN_TYPE {
+        return None;
+    }
+    Uuid::parse_str(&claims.sub).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_matches_only_within_skew() {
+        let secret = generate_totp_secret();
+        let totp = build_totp(&secret, "admin@example.com").unwrap();
+        let now = 1_800_000_000;
+        let step = (now / TOTP_STEP_SECS) as i64;
+
+        let code = totp.generate(now);
+        assert_eq!(matching_step(&totp, &code, now), Some(step));
+
+        let previous = totp.generate(now - TOTP_STEP_SECS);
+        assert_eq!(matching_step(&totp, &previous, now), Some(step - 1));
+
+        let stale = totp.generate(now - 5 * TOTP_STEP_SECS);
+        assert_eq!(matching_step(&totp, &stale, now), None);
+    }
+
+    #[test]
+    fn test_otpauth_uri_names_issuer_and_account() {
+        let secret = generate_totp_secret();
+        let uri = otpauth_uri(&secret, "admin@example.com").unwrap();
+        assert!(uri.starts_with("otpauth://totp/"));
+        assert!(uri.contains(&format!("secret={}", secret)));
+        assert!(uri.contains("issuer=Healthy%20Diet"));
+    }
+
+    #[test]
+    fn test_recovery_codes_are_unique() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            assert_eq!(code.len(), 11);
+            assert_eq!(codes.iter().filter(|other| *other == code).count(), 1);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use chrono::{NaiveDate, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Write},
+    path::Path,
+};
+use uuid::Uuid;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::utils::diet_items::{MealItem, items_for_records};
+
+pub const DEFAULT_VAL_RATIO: f64 = 0.2;
+pub const MAX_VAL_RATIO: f64 = 0.5;
+pub const DEFAULT_EXPORT_LIMIT: i64 = 500;
+/// Images are packed in memory, so one export stays well below a few hundred MB.
+pub const MAX_EXPORT_LIMIT: i64 = 2000;
+
+/// Which corrected records go into an export.
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetFilter {
+    /// Correction dates, inclusive (UTC).
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Boxes the user left as detected need at least this confidence; corrected boxes always
+    /// count.
+    pub min_confidence: f64,
+    pub val_ratio: f64,
+    /// The most recently corrected records considered.
+    pub limit: i64,
+    /// Class names in class id order.
+    pub classes: Vec<String>,
+}
+
+/// Why a corrected record was left out of an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// A food has no box, so the image would teach the model that it is background.
+    MissingBox,
+    LowConfidence,
+    UnknownClass,
+    MissingImage,
+}
+
+impl SkipReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SkipReason::MissingBox => "missing_box",
+            SkipReason::LowConfidence => "low_confidence",
+            SkipReason::UnknownClass => "unknown_class",
+            SkipReason::MissingImage => "missing_image",
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DatasetSummary {
+    pub train_images: usize,
+    pub val_images: usize,
+    pub boxes: usize,
+    pub skipped: BTreeMap<&'static str, usize>,
+}
+
+impl DatasetSummary {
+    fn skip(&mut self, reason: SkipReason) {
+        *self.skipped.entry(reason.as_str()).or_default() += 1;
+    }
+}
+
+/// A corrected photo with its boxes as `(class id, [x_min, y_min, x_max, y_max])` in pixels.
+#[derive(Debug)]
+pub struct LabeledImage {
+    pub record_id: Uuid,
+    pub image_path: String,
+    pub boxes: Vec<(usize, [f64; 4])>,
+}
+
+/// The labels of one corrected record, or why it cannot be used.
+pub fn label_record(
+    items: &[MealItem],
+    classes: &[String],
+    min_confidence: f64,
+) -> Result<Vec<(usize, [f64; 4])>, SkipReason> {
+    let mut boxes = Vec::with_capacity(items.len());
+    for item in items {
+        let detection = item.detection.as_ref().ok_or(SkipReason::MissingBox)?;
+        if !detection.corrected && detection.confidence < min_confidence {
+            return Err(SkipReason::LowConfidence);
+        }
+        let class_id = classes
+            .iter()
+            .position(|class| *class == item.class)
+            .ok_or(SkipReason::UnknownClass)?;
+        boxes.push((class_id, detection.bbox));
+    }
+    if boxes.is_empty() {
+        return Err(SkipReason::MissingBox);
+    }
+    Ok(boxes)
+}
+
+/// One line of a YOLO label file: class id, then the box centre and size relative to the
+/// image. Boxes are clipped to the image first.
+pub fn yolo_label_line(class_id: usize, bbox: &[f64; 4], width: u32, height: u32) -> String {
+    let (width, height) = (width as f64, height as f64);
+    let x_min = bbox[0].clamp(0.0, width);
+    let y_min = bbox[1].clamp(0.0, height);
+    let x_max = bbox[2].clamp(0.0, width);
+    let y_max = bbox[3].clamp(0.0, height);
+    format!(
+        "{} {:.6} {:.6} {:.6} {:.6}",
+        class_id,
+        (x_min + x_max) / 2.0 / width,
+        (y_min + y_max) / 2.0 / height,
+        (x_max - x_min) / width,
+        (y_max - y_min) / height
+    )
+}
+
+/// The split depends only on the record id, so a record stays on the same side in every
+/// export and validation scores remain comparable between retraining runs.
+pub fn is_validation(record_id: Uuid, val_ratio: f64) -> bool {
+    let digest = Sha256::digest(record_id.as_bytes());
+    let bucket = u16::from_be_bytes([digest[0], digest[1]]) as f64 / 65536.0;
+    bucket < val_ratio
+}
+
+pub fn data_yaml(classes: &[String]) -> String {
+    let mut yaml = String::from("path: .\ntrain: images/train\nval: images/val\nnames:\n");
+    for (class_id, name) in classes.iter().enumerate() {
+        // A JSON string is a valid YAML scalar, whatever the class name contains.
+        yaml.push_str(&format!("  {}: {}\n", class_id, json!(name)));
+    }
+    yaml
+}
+
+#[derive(FromRow)]
+struct CorrectedRecord {
+    id: Uuid,
+    original_image_path: String,
+}
+
+/// Photo records whose labels users corrected, with the boxes that pass the filter. Records
+/// that do not are counted in the summary.
+pub async fn load_labeled_images(
+    db: &PgPool,
+    filter: &DatasetFilter,
+) -> Result<(Vec<LabeledImage>, DatasetSummary), sqlx::Error> {
+    let records = sqlx::query_as::<_, CorrectedRecord>(
+        r#"
+        SELECT id, original_image_path
+        FROM diet_records
+        WHERE labels_corrected_at IS NOT NULL
+          AND original_image_path IS NOT NULL
+          AND ($1::date IS NULL OR labels_corrected_at >= $1::date)
+          AND ($2::date IS NULL OR labels_corrected_at < $2::date + 1)
+        ORDER BY labels_corrected_at DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(filter.limit)
+    .fetch_all(db)
+    .await?;
+
+    let record_ids: Vec<Uuid> = records.iter().map(|record| record.id).collect();
+    let mut items = items_for_records(db, &record_ids).await?;
+
+    let mut summary = DatasetSummary::default();
+    let mut images = Vec::with_capacity(records.len());
+    for record in records {
+        let record_items: Vec<MealItem> = items
+            .remove(&record.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|stored| stored.item)
+            .collect();
+        match label_record(&record_items, &filter.classes, filter.min_confidence) {
+            Ok(boxes) => images.push(LabeledImage {
+                record_id: record.id,
+                image_path: record.original_image_path,
+                boxes,
+            }),
+            Err(reason) => summary.skip(reason),
+        }
+    }
+    Ok((images, summary))
+}
+
+/// Packs the dataset as a ZIP: `images/{train,val}/`, `labels/{train,val}/*.txt` with one
+/// box per line, `data.yaml` for Ultralytics and `manifest.json` with the filter and counts.
+pub fn write_dataset_zip(
+    images: &[LabeledImage],
+    filter: &DatasetFilter,
+    mut summary: DatasetSummary,
+) -> Result<(Vec<u8>, DatasetSummary), String> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+
+    for image in images {
+        let (Ok(bytes), Ok((width, height))) = (
+            std::fs::read(&image.image_path),
+            image::image_dimensions(&image.image_path),
+        ) else {
+            summary.skip(SkipReason::MissingImage);
+            continue;
+        };
+        let split = if is_validation(image.record_id, filter.val_ratio) {
+            summary.val_images += 1;
+            "val"
+        } else {
+            summary.train_images += 1;
+            "train"
+        };
+        let extension = Path::new(&image.image_path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("jpg");
+        let labels: Vec<String> = image
+            .boxes
+            .iter()
+            .map(|(class_id, bbox)| yolo_label_line(*class_id, bbox, width, height))
+            .collect();
+        summary.boxes += labels.len();
+
+        zip.start_file(
+            format!("images/{}/{}.{}", split, image.record_id, extension),
+            options,
+        )
+        .map_err(|e| e.to_string())?;
+        zip.write_all(&bytes).map_err(|e| e.to_string())?;
+        zip.start_file(format!("labels/{}/{}.txt", split, image.record_id), options)
+            .map_err(|e| e.to_string())?;
+        zip.write_all(format!("{}\n", labels.join("\n")).as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+
+    zip.start_file("data.yaml", options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(data_yaml(&filter.classes).as_bytes())
+        .map_err(|e| e.to_string())?;
+    let manifest = json!({
+        "exported_at": Utc::now(),
+        "filter": filter,
+        "summary": summary,
+    });
+    zip.start_file("manifest.json", options)
+        .map_err(|e| e.to_string())?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?)
+        .map_err(|e| e.to_string())?;
+
+    let bytes = zip.finish().map_err(|e| e.to_string())?.into_inner();
+    Ok((bytes, summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{diet_items::ItemDetection, nutrition_engine::NutrientVector};
+
+    fn item(class: &str, confidence: f64, corrected: bool) -> MealItem {
+        MealItem {
+            class: class.to_string(),
+            food_id: None,
+            name: None,
+            source_text: None,
+            weight_g: 100.0,
+            calories: 130.0,
+            nutrients: NutrientVector::default(),
+            detection: Some(ItemDetection {
+                bbox: [100.0, 50.0, 300.0, 250.0],
+                confidence,
+                area_ratio: 0.1,
+                corrected,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_label_record_applies_quality_filter() {
+        let classes = vec!["grain".to_string(), "vegetable".to_string()];
+        let items = [item("grain", 0.9, false), item("vegetable", 0.3, true)];
+        assert_eq!(
+            label_record(&items, &classes, 0.5),
+            Ok(vec![
+                (0, [100.0, 50.0, 300.0, 250.0]),
+                (1, [100.0, 50.0, 300.0, 250.0])
+            ])
+        );
+
+        // An uncorrected box under the threshold drops the whole image.
+        let items = [item("grain", 0.4, false)];
+        assert_eq!(
+            label_record(&items, &classes, 0.5),
+            Err(SkipReason::LowConfidence)
+        );
+        let items = [item("fruit", 0.9, true)];
+        assert_eq!(
+            label_record(&items, &classes, 0.5),
+            Err(SkipReason::UnknownClass)
+        );
+        let items = [MealItem {
+            detection: None,
+            ..item("grain", 1.0, true)
+        }];
+        assert_eq!(
+            label_record(&items, &classes, 0.5),
+            Err(SkipReason::MissingBox)
+        );
+    }
+
+    #[test]
+    fn test_yolo_label_line_and_split() {
+        assert_eq!(
+            yolo_label_line(1, &[100.0, 50.0, 300.0, 250.0], 400, 500),
+            "1 0.500000 0.300000 0.500000 0.400000"
+        );
+        // Boxes reaching past the edge are clipped.
+        assert_eq!(
+            yolo_label_line(0, &[-20.0, 0.0, 200.0, 600.0], 400, 500),
+            "0 0.250000 0.500000 0.500000 1.000000"
+        );
+
+        let record_ids: Vec<Uuid> = (0..1000).map(|_| Uuid::new_v4()).collect();
+        let val = record_ids
+            .iter()
+            .filter(|id| is_validation(**id, DEFAULT_VAL_RATIO))
+            .count();
+        assert!((120..280).contains(&val), "{} of 1000 in val", val);
+        assert!(!record_ids.iter().any(|id| is_validation(*id, 0.0)));
+
+        // Fixed ids pin the split: raising the ratio only moves train records into val.
+        let early = Uuid::from_u128(0x10);
+        let middle = Uuid::from_u128(0x11);
+        let late = Uuid::from_u128(0x05);
+        assert!(is_validation(early, 0.2));
+        assert!(is_validation(early, 0.3));
+        assert!(!is_validation(middle, 0.2));
+        assert!(is_validation(middle, 0.3));
+        assert!(!is_validation(late, 0.2));
+        assert!(!is_validation(late, MAX_VAL_RATIO));
+
+        assert_eq!(
+            data_yaml(&["grain".to_string(), "protein_meat".to_string()]),
+            "path: .\ntrain: images/train\nval: images/val\nnames:\n  0: \"grain\"\n  1: \"protein_meat\"\n"
+        );
+    }
+}